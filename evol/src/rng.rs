@@ -28,6 +28,13 @@ impl RandomNumberGenerator {
         Self { rng: StdRng::from_entropy() }
     }
 
+    /// Creates a generator producing the same sequence of numbers for the same seed,
+    /// e.g. to reproduce an evolution in a test.
+    #[must_use]
+    pub fn from_seed(seed: u64) -> Self {
+        Self { rng: StdRng::seed_from_u64(seed) }
+    }
+
     /// Generates a specified number of random floating-point numbers within the given range.
     ///
     /// # Parameters
@@ -111,6 +118,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_from_seed_is_reproducible() {
+        let mut first = super::RandomNumberGenerator::from_seed(7);
+        let mut second = super::RandomNumberGenerator::from_seed(7);
+        assert_eq!(first.fetch_uniform(0.0, 1.0, 10), second.fetch_uniform(0.0, 1.0, 10));
+    }
+
     #[test]
     fn test_fetch_uniform_with_empty_result() {
         let mut rng = super::RandomNumberGenerator::new();
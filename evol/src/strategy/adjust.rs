@@ -7,6 +7,8 @@
 //! If the increase was significant the number of mutate calls is decreased, otherwise it is increased.
 use super::BreedStrategy;
 use crate::phenotype::Phenotype;
+use crate::rng::RandomNumberGenerator;
+use rand::Rng;
 use std::{fmt::Error, marker::PhantomData};
 
 pub trait Adjust<Pheno: Phenotype> {
@@ -63,39 +65,60 @@ where
         evol_options: &crate::evolution::options::EvolutionOptions,
         rng: &mut crate::rng::RandomNumberGenerator,
     ) -> Result<Vec<Pheno>, Error> {
-        let mut children: Vec<Pheno> = Vec::new();
+        let (first_child, undeveloped_children) =
+            Self::undeveloped_children(parents, evol_options, rng);
+        let mut children = vec![first_child];
+        children.extend(
+            undeveloped_children.into_iter().map(|(child, seed)| Self::develop_child(child, seed)),
+        );
+        Ok(children)
+    }
+}
+
+impl<Pheno> AdjustStrategy<Pheno>
+where
+    Pheno: Phenotype + Adjust<Pheno>,
+{
+    /// Returns the unchanged winner of the previous generation followed by the children
+    /// which still have to be developed with `develop_child`.
+    ///
+    /// The winner is crossed over with every other parent and the offspring is filled up
+    /// with clones of the winner. Every child comes with the seed of its own random number
+    /// generator, so the children can be developed in any order or concurrently and still
+    /// end up the same for the same `rng`.
+    pub fn undeveloped_children(
+        parents: &[Pheno],
+        evol_options: &crate::evolution::options::EvolutionOptions,
+        rng: &mut RandomNumberGenerator,
+    ) -> (Pheno, Vec<(Pheno, u64)>) {
         let winner_previous_generation = parents[0].clone();
         let mut first_child = winner_previous_generation.clone();
         first_child.incr_number_mutates();
-        children.push(first_child);
 
-        parents.iter().skip(1).try_for_each(|parent| -> Result<(), Error> {
+        let mut undeveloped_children = Vec::new();
+        for parent in parents.iter().skip(1) {
             let mut child = winner_previous_generation.clone();
             child.crossover(parent);
-            let mut mutated_child = Self::develop(child, rng);
-            mutated_child.decr_number_mutates();
-            children.push(mutated_child);
-            Ok(())
-        })?;
-
-        (parents.len()..evol_options.get_num_offspring()).try_for_each(
-            |_| -> Result<(), Error> {
-                let child = winner_previous_generation.clone();
-                let mut mutated_child = Self::develop(child, rng);
-                mutated_child.decr_number_mutates();
-                children.push(mutated_child);
-                Ok(())
-            },
-        )?;
+            undeveloped_children.push((child, rng.rng.gen::<u64>()));
+        }
+        for _ in parents.len()..evol_options.get_num_offspring() {
+            undeveloped_children.push((winner_previous_generation.clone(), rng.rng.gen::<u64>()));
+        }
+        (first_child, undeveloped_children)
+    }
 
-        Ok(children)
+    /// Develops a child returned by `undeveloped_children` with a random number generator
+    /// seeded from its seed.
+    pub fn develop_child(
+        child: Pheno,
+        seed: u64,
+    ) -> Pheno {
+        let mut child_rng = RandomNumberGenerator::from_seed(seed);
+        let mut mutated_child = Self::develop(child, &mut child_rng);
+        mutated_child.decr_number_mutates();
+        mutated_child
     }
-}
 
-impl<Pheno> AdjustStrategy<Pheno>
-where
-    Pheno: Phenotype + Adjust<Pheno>,
-{
     /// Develops a phenotype with consideration of the fitness increase of the previous generation
     ///
     /// # Arguments
@@ -114,7 +137,7 @@ where
     /// calculated by the method `calculate_number_of_mutations`.
     fn develop(
         pheno: Pheno,
-        rng: &mut crate::rng::RandomNumberGenerator,
+        rng: &mut RandomNumberGenerator,
     ) -> Pheno {
         let mut phenotype = pheno;
         let number_of_mutations = phenotype.get_number_mutates();
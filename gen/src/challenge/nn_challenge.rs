@@ -1,13 +1,20 @@
 use crate::pheno::nn_pheno::NeuralNetworkPhenotype;
 use evol::evolution::challenge::Challenge;
+use neural::nn::nn_trait::WrappedTrainableNeuralNetwork;
 use neural::training::data_importer::DataImporter;
 use neural::training::training_params::TrainingParams;
 use neural::training::training_session::TrainingSession;
 
+use std::sync::Arc;
+
+/// A fitness function scoring a trained neural network, higher scores are better.
+pub type Fitness = dyn Fn(&mut WrappedTrainableNeuralNetwork) -> f64 + Send + Sync;
+
 #[derive(Clone)]
 pub struct NeuralNetworkChallenge {
     params: TrainingParams,
     data_importer: Box<dyn DataImporter + Send + Sync>,
+    fitness: Option<Arc<Fitness>>,
 }
 
 impl NeuralNetworkChallenge {
@@ -16,7 +23,25 @@ impl NeuralNetworkChallenge {
         params: TrainingParams,
         data_importer: Box<dyn DataImporter + Send + Sync>,
     ) -> Self {
-        Self { params, data_importer }
+        Self { params, data_importer, fitness: None }
+    }
+
+    /// Scores the phenotypes with the given fitness function after training them,
+    /// instead of with the validation accuracy of the training.
+    #[must_use]
+    pub fn with_fitness(
+        self,
+        fitness: Box<Fitness>,
+    ) -> Self {
+        self.with_shared_fitness(Some(Arc::from(fitness)))
+    }
+
+    pub(crate) fn with_shared_fitness(
+        mut self,
+        fitness: Option<Arc<Fitness>>,
+    ) -> Self {
+        self.fitness = fitness;
+        self
     }
 }
 
@@ -32,7 +57,9 @@ impl Challenge<NeuralNetworkPhenotype> for NeuralNetworkChallenge {
         )
         .unwrap();
         let result = training_session.train();
-        phenotype.set_nn(training_session.get_nn());
-        result.unwrap()
+        let mut nn = training_session.get_nn();
+        phenotype.set_nn(nn.clone());
+        let accuracy = result.unwrap();
+        self.fitness.as_ref().map_or(accuracy, |fitness| fitness(&mut nn))
     }
 }
@@ -10,17 +10,21 @@ use evol::evolution::EvolutionOptions;
 use evol::evolution::ParallelEvolutionLauncher;
 use evol::rng::RandomNumberGenerator;
 
-use crate::challenge::nn_challenge::NeuralNetworkChallenge;
+use crate::challenge::nn_challenge::{Fitness, NeuralNetworkChallenge};
 use crate::pheno::nn_pheno::NeuralNetworkPhenotype;
 
 use super::strategy::nn_strategy::NeuralNetworkStrategy;
 
+use std::sync::Arc;
+
 pub struct NeuralNetworkGenerator {
     num_threads: usize,
     params: TrainingParams,
     evolution_params: EvolutionOptions,
     current_winner: WrappedTrainableNeuralNetwork,
     data_importer: Box<dyn DataImporter + Send + Sync>,
+    fitness: Option<Arc<Fitness>>,
+    seed: Option<u64>,
 }
 
 impl NeuralNetworkGenerator {
@@ -40,7 +44,15 @@ impl NeuralNetworkGenerator {
             model_directory,
             utils,
         ));
-        Self { current_winner: nn, params, evolution_params, num_threads, data_importer }
+        Self {
+            current_winner: nn,
+            params,
+            evolution_params,
+            num_threads,
+            data_importer,
+            fitness: None,
+            seed: None,
+        }
     }
 
     #[must_use]
@@ -61,15 +73,40 @@ impl NeuralNetworkGenerator {
             evolution_params,
             num_threads,
             data_importer,
+            fitness: None,
+            seed: None,
         }
     }
 
+    /// Scores the candidates with the given fitness function after training them,
+    /// instead of with the validation accuracy of the training.
+    #[must_use]
+    pub fn with_fitness(
+        mut self,
+        fitness: Box<Fitness>,
+    ) -> Self {
+        self.fitness = Some(Arc::from(fitness));
+        self
+    }
+
+    /// Seeds the random number generator of the evolution, so that the candidates are bred
+    /// the same way in every call to `generate`.
+    #[must_use]
+    pub const fn with_seed(
+        mut self,
+        seed: u64,
+    ) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
     /// Generate a new neural network using a genetic algorithm
     ///
     /// # Panics
     /// Panics if the current winner's shape is not valid or has zero layers.
     pub fn generate(&mut self) {
-        let mut rng = RandomNumberGenerator::new();
+        let mut rng =
+            self.seed.map_or_else(RandomNumberGenerator::new, RandomNumberGenerator::from_seed);
 
         assert!(self.current_winner.shape().is_valid());
         assert!(self.current_winner.shape().num_layers() > 0);
@@ -79,8 +116,10 @@ impl NeuralNetworkGenerator {
         let starting_value = NeuralNetworkPhenotype::new(&self.current_winner);
         let options = self.evolution_params.clone();
         let challenge =
-            NeuralNetworkChallenge::new(self.params.clone(), self.data_importer.clone());
-        let strategy = NeuralNetworkStrategy::new(self.current_winner.get_model_directory().path());
+            NeuralNetworkChallenge::new(self.params.clone(), self.data_importer.clone())
+                .with_shared_fitness(self.fitness.clone());
+        let strategy = NeuralNetworkStrategy::new(self.current_winner.get_model_directory().path())
+            .with_parallel_breeding(self.num_threads > 1);
         let launcher: ParallelEvolutionLauncher<
             NeuralNetworkPhenotype,
             NeuralNetworkStrategy,
@@ -1,3 +1,4 @@
+pub mod mutation_config;
 pub mod nn_mutater;
 pub mod nn_pheno;
 pub mod rng_wrapper;
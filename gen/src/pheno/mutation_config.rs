@@ -0,0 +1,201 @@
+use super::rng_wrapper::RngWrapper;
+
+use num_traits::NumCast;
+
+/// The kind of mutation applied to a neural network phenotype.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutationKind {
+    /// The phenotype is left unchanged.
+    Skip,
+    /// The shape of the neural network is mutated.
+    Classic,
+    /// The neural network is replaced by a retry network with a random number of levels.
+    Levels,
+}
+
+/// Configures how neural network phenotypes are mutated.
+///
+/// Every mutation happens with probability `mutation_rate`, which is multiplied by `decay`
+/// after each generation so that the evolution converges.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MutationConfig {
+    classic_probability: f64,
+    levels_range: (f32, f32),
+    mutation_rate: f64,
+    decay: f64,
+}
+
+impl Default for MutationConfig {
+    fn default() -> Self {
+        Self { classic_probability: 0.5, levels_range: (1.0, 5.0), mutation_rate: 1.0, decay: 1.0 }
+    }
+}
+
+impl MutationConfig {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the probability of a classic shape mutation, the remaining mutations change the levels.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the probability is not within `[0, 1]`.
+    #[must_use]
+    pub fn with_classic_probability(
+        mut self,
+        classic_probability: f64,
+    ) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&classic_probability),
+            "classic_probability must be between 0 and 1"
+        );
+        self.classic_probability = classic_probability;
+        self
+    }
+
+    /// Sets the range the number of levels of a levels mutation is drawn from.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the range is empty or starts below one.
+    #[must_use]
+    pub fn with_levels_range(
+        mut self,
+        min_levels: u32,
+        max_levels: u32,
+    ) -> Self {
+        assert!(1 <= min_levels && min_levels < max_levels, "Invalid levels range");
+        self.levels_range = (
+            NumCast::from(min_levels).expect("Failed to convert min_levels to f32"),
+            NumCast::from(max_levels).expect("Failed to convert max_levels to f32"),
+        );
+        self
+    }
+
+    /// Sets the probability that a mutation changes the phenotype at all.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the rate is not within `[0, 1]`.
+    #[must_use]
+    pub fn with_mutation_rate(
+        mut self,
+        mutation_rate: f64,
+    ) -> Self {
+        assert!((0.0..=1.0).contains(&mutation_rate), "mutation_rate must be between 0 and 1");
+        self.mutation_rate = mutation_rate;
+        self
+    }
+
+    /// Sets the factor the mutation rate is multiplied with after every generation.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the decay is not within `[0, 1]`.
+    #[must_use]
+    pub fn with_decay(
+        mut self,
+        decay: f64,
+    ) -> Self {
+        assert!((0.0..=1.0).contains(&decay), "decay must be between 0 and 1");
+        self.decay = decay;
+        self
+    }
+
+    #[must_use]
+    pub const fn classic_probability(&self) -> f64 {
+        self.classic_probability
+    }
+
+    #[must_use]
+    pub const fn levels_range(&self) -> (f32, f32) {
+        self.levels_range
+    }
+
+    #[must_use]
+    pub const fn mutation_rate(&self) -> f64 {
+        self.mutation_rate
+    }
+
+    #[must_use]
+    pub const fn decay(&self) -> f64 {
+        self.decay
+    }
+
+    /// Returns the configuration with the mutation rate decayed for the given generation.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the generation cannot be converted to `i32`.
+    #[must_use]
+    pub fn at_generation(
+        &self,
+        generation: usize,
+    ) -> Self {
+        let generation: i32 =
+            NumCast::from(generation).expect("Failed to convert generation to i32");
+        Self { mutation_rate: self.mutation_rate * self.decay.powi(generation), ..*self }
+    }
+
+    /// Draws the kind of the next mutation.
+    pub fn select_mutation(
+        &self,
+        rng: &mut dyn RngWrapper,
+    ) -> MutationKind {
+        if self.mutation_rate < 1.0 {
+            let random_number: f64 = rng.fetch_uniform(0.0, 1.0, 1)[0].into();
+            if random_number >= self.mutation_rate {
+                return MutationKind::Skip;
+            }
+        }
+        let random_number: f64 = rng.fetch_uniform(0.0, 10.0, 1)[0].into();
+        let classic = match self.classic_probability {
+            p if p >= 1.0 => true,
+            p if p <= 0.0 => false,
+            p => random_number < 10.0 * p,
+        };
+        if classic {
+            MutationKind::Classic
+        } else {
+            MutationKind::Levels
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pheno::rng_wrapper::RealRng;
+    use evol::rng::RandomNumberGenerator;
+
+    #[test]
+    fn test_full_classic_probability_never_selects_levels() {
+        let config = MutationConfig::new().with_classic_probability(1.0);
+        let mut rng = RandomNumberGenerator::new();
+        let mut rng_wrapper = RealRng::new(&mut rng);
+        for _ in 0..1000 {
+            assert_eq!(config.select_mutation(&mut rng_wrapper), MutationKind::Classic);
+        }
+
+        let config = MutationConfig::new().with_classic_probability(0.0);
+        for _ in 0..1000 {
+            assert_eq!(config.select_mutation(&mut rng_wrapper), MutationKind::Levels);
+        }
+    }
+
+    #[test]
+    fn test_mutation_rate_decays_over_generations() {
+        let config = MutationConfig::new().with_mutation_rate(0.8).with_decay(0.5);
+        assert!((config.at_generation(0).mutation_rate() - 0.8).abs() < 1e-12);
+        assert!((config.at_generation(2).mutation_rate() - 0.2).abs() < 1e-12);
+
+        let mut rng = RandomNumberGenerator::new();
+        let mut rng_wrapper = RealRng::new(&mut rng);
+        let frozen = config.with_mutation_rate(0.0);
+        for _ in 0..100 {
+            assert_eq!(frozen.select_mutation(&mut rng_wrapper), MutationKind::Skip);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use super::mutation_config::{MutationConfig, MutationKind};
 use super::rng_wrapper::RngWrapper;
 use super::{
     nn_mutater::fetch_activation_data, nn_mutater::NeuralNetworkMutater, rng_wrapper::RealRng,
@@ -15,6 +16,7 @@ pub struct NeuralNetworkPhenotype {
     left_half_shape: Option<NeuralNetworkShape>,
     right_half_shape: Option<NeuralNetworkShape>,
     nb_mutates: usize,
+    mutation_config: MutationConfig,
 }
 
 impl Clone for NeuralNetworkPhenotype {
@@ -24,6 +26,7 @@ impl Clone for NeuralNetworkPhenotype {
             left_half_shape: self.left_half_shape.clone(),
             right_half_shape: self.right_half_shape.clone(),
             nb_mutates: self.nb_mutates,
+            mutation_config: self.mutation_config,
         }
     }
 }
@@ -36,6 +39,7 @@ impl NeuralNetworkPhenotype {
             left_half_shape: None,
             right_half_shape: None,
             nb_mutates: 0,
+            mutation_config: MutationConfig::default(),
         }
     }
 
@@ -51,6 +55,19 @@ impl NeuralNetworkPhenotype {
         self.nn = nn;
     }
 
+    #[must_use]
+    pub const fn mutation_config(&self) -> MutationConfig {
+        self.mutation_config
+    }
+
+    /// Sets how the phenotype is mutated.
+    pub const fn set_mutation_config(
+        &mut self,
+        mutation_config: MutationConfig,
+    ) {
+        self.mutation_config = mutation_config;
+    }
+
     fn set_left_half_shape(
         &mut self,
         shape: NeuralNetworkShape,
@@ -80,13 +97,12 @@ impl NeuralNetworkPhenotype {
     ) {
         let left_half_shape = self.left_half_shape.clone();
         let right_half_shape = self.right_half_shape.clone();
-        let previous_shape = if left_half_shape.is_some() && right_half_shape.is_some() {
-            let left_shape = left_half_shape.unwrap();
-            let right_shape = right_half_shape.unwrap();
-            left_shape.merge(right_shape, fetch_activation_data(rng_wrapper))
-        } else {
-            self.get_nn().shape()
-        };
+        let previous_shape =
+            if let (Some(left_shape), Some(right_shape)) = (left_half_shape, right_half_shape) {
+                left_shape.merge(right_shape, fetch_activation_data(rng_wrapper))
+            } else {
+                self.get_nn().shape()
+            };
 
         let mut mutater = NeuralNetworkMutater::new(rng_wrapper);
 
@@ -117,15 +133,15 @@ impl NeuralNetworkPhenotype {
     ) {
         let left_half_shape = self.left_half_shape.clone();
         let right_half_shape = self.right_half_shape.clone();
-        let previous_shape = if left_half_shape.is_some() && right_half_shape.is_some() {
-            let left_shape = left_half_shape.unwrap();
-            let right_shape = right_half_shape.unwrap();
-            left_shape.merge(right_shape, fetch_activation_data(rng_wrapper))
-        } else {
-            self.get_nn().shape()
-        };
-
-        let random_numbers = rng_wrapper.fetch_uniform(1.0, 5.0, 1);
+        let previous_shape =
+            if let (Some(left_shape), Some(right_shape)) = (left_half_shape, right_half_shape) {
+                left_shape.merge(right_shape, fetch_activation_data(rng_wrapper))
+            } else {
+                self.get_nn().shape()
+            };
+
+        let (min_levels, max_levels) = self.mutation_config.levels_range();
+        let random_numbers = rng_wrapper.fetch_uniform(min_levels, max_levels, 1);
         // round do to integer
         let random_number: i32 = random_numbers[0].round() as i32;
         let nn = new_trainable_neural_network(NeuralNetworkCreationArguments::new(
@@ -169,12 +185,10 @@ impl Phenotype for NeuralNetworkPhenotype {
         rng: &mut RandomNumberGenerator,
     ) {
         let mut rng_wrapper = RealRng::new(rng);
-        // fetch a random number between 0 and 1
-        let random_number = rng_wrapper.fetch_uniform(0.0, 10.0, 1);
-        if random_number[0] < 5.0 {
-            self.mutate_classic_nn(&mut rng_wrapper);
-        } else {
-            self.mutate_levels(&mut rng_wrapper);
+        match self.mutation_config.select_mutation(&mut rng_wrapper) {
+            MutationKind::Skip => {},
+            MutationKind::Classic => self.mutate_classic_nn(&mut rng_wrapper),
+            MutationKind::Levels => self.mutate_levels(&mut rng_wrapper),
         }
     }
 }
@@ -1 +1,2 @@
 pub mod nn_strategy;
+pub mod random_search;
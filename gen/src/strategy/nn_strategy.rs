@@ -1,21 +1,185 @@
+use crate::pheno::mutation_config::MutationConfig;
 use crate::pheno::nn_pheno::NeuralNetworkPhenotype;
 use evol::evolution::EvolutionOptions;
+use evol::phenotype::Phenotype;
 use evol::rng::RandomNumberGenerator;
+use evol::strategy::Adjust;
 use evol::strategy::AdjustStrategy;
 use evol::strategy::BreedStrategy;
+use neural::nn::nn_factory::trainable_neural_network_from_disk;
+use neural::utilities::util::WrappedUtils;
+
+use rayon::iter::IntoParallelIterator;
+use rayon::iter::ParallelIterator;
+use rayon::ThreadPool;
+use rayon::ThreadPoolBuilder;
 
 use std::fmt::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The thread pool of parallel breeding together with its number of threads.
+type SizedThreadPool = (usize, Arc<ThreadPool>);
 
 #[derive(Debug, Clone)]
 pub struct NeuralNetworkStrategy {
     model_directory: String,
+    parallel_breeding: bool,
+    mutation_config: MutationConfig,
+    generation: Arc<AtomicUsize>,
+    seed_model: Option<NeuralNetworkPhenotype>,
+    thread_pool: Arc<Mutex<Option<SizedThreadPool>>>,
 }
 
 impl NeuralNetworkStrategy {
     #[must_use]
-    pub const fn new(model_directory: String) -> Self {
-        Self { model_directory }
+    pub fn new(model_directory: String) -> Self {
+        Self {
+            model_directory,
+            parallel_breeding: false,
+            mutation_config: MutationConfig::default(),
+            generation: Arc::new(AtomicUsize::new(0)),
+            seed_model: None,
+            thread_pool: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Creates a strategy whose initial population is derived from the trained model
+    /// saved in `model_directory` instead of random networks.
+    #[must_use]
+    pub fn from_seed_model(
+        model_directory: String,
+        utils: WrappedUtils,
+    ) -> Self {
+        let nn = trainable_neural_network_from_disk(model_directory.clone(), utils);
+        let seed_model = NeuralNetworkPhenotype::new(&nn);
+        Self { seed_model: Some(seed_model), ..Self::new(model_directory) }
+    }
+
+    /// Returns the phenotype loaded by `from_seed_model`, if any.
+    #[must_use]
+    pub const fn seed_model(&self) -> Option<&NeuralNetworkPhenotype> {
+        self.seed_model.as_ref()
+    }
+
+    /// Produces the initial population from the seed model.
+    /// The seed itself is kept unchanged, the remaining offspring are mutations of it.
+    /// Returns an empty population if the strategy was not created with `from_seed_model`.
+    #[must_use]
+    pub fn initial_population(
+        &self,
+        evol_options: &EvolutionOptions,
+        rng: &mut RandomNumberGenerator,
+    ) -> Vec<NeuralNetworkPhenotype> {
+        let Some(seed_model) = &self.seed_model else {
+            return Vec::new();
+        };
+        let mut seed_model = seed_model.clone();
+        seed_model.set_mutation_config(self.mutation_config);
+        let mut population = vec![seed_model.clone()];
+        for _ in 1..evol_options.get_num_offspring() {
+            population.push(develop(seed_model.clone(), rng));
+        }
+        population
+    }
+
+    /// Sets how the children are mutated. The mutation rate of the config decays
+    /// with every call to `breed`.
+    #[must_use]
+    pub const fn with_mutation_config(
+        mut self,
+        mutation_config: MutationConfig,
+    ) -> Self {
+        self.mutation_config = mutation_config;
+        self
+    }
+
+    /// Returns the number of generations bred so far.
+    #[must_use]
+    pub fn generation(&self) -> usize {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Develops the children concurrently with as many threads as the parents' `WrappedUtils`.
+    /// Every child creates its own model directory, so the children do not interfere.
+    /// The children are the same as with sequential breeding for the same random number generator.
+    #[must_use]
+    pub const fn with_parallel_breeding(
+        mut self,
+        parallel_breeding: bool,
+    ) -> Self {
+        self.parallel_breeding = parallel_breeding;
+        self
+    }
+
+    /// Breeds the children like `AdjustStrategy` but develops them concurrently.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the thread pool cannot be built.
+    fn breed_parallel(
+        &self,
+        parents: &[NeuralNetworkPhenotype],
+        evol_options: &EvolutionOptions,
+        rng: &mut RandomNumberGenerator,
+    ) -> Vec<NeuralNetworkPhenotype> {
+        let (first_child, undeveloped_children) =
+            AdjustStrategy::undeveloped_children(parents, evol_options, rng);
+
+        // a dedicated pool is used because developing children allocates and drops networks
+        // which need to lock the utils themselves
+        let thread_pool = self.thread_pool(parents[0].get_nn().get_utils().get_num_threads());
+        let developed_children = thread_pool.install(|| {
+            undeveloped_children
+                .into_par_iter()
+                .map(|(child, seed)| AdjustStrategy::develop_child(child, seed))
+                .collect::<Vec<_>>()
+        });
+
+        let mut children = vec![first_child];
+        children.extend(developed_children);
+        children
+    }
+
+    /// Returns the pool with `num_threads` threads. It is kept for the following generations
+    /// and only rebuilt if they are bred with a different number of threads.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the thread pool cannot be built.
+    fn thread_pool(
+        &self,
+        num_threads: usize,
+    ) -> Arc<ThreadPool> {
+        let mut thread_pool = self.thread_pool.lock().unwrap();
+        match thread_pool.as_ref() {
+            Some((pool_threads, pool)) if *pool_threads == num_threads => Arc::clone(pool),
+            _ => {
+                let pool = Arc::new(
+                    ThreadPoolBuilder::new()
+                        .num_threads(num_threads)
+                        .build()
+                        .expect("Failed to build thread pool"),
+                );
+                *thread_pool = Some((num_threads, Arc::clone(&pool)));
+                pool
+            },
+        }
+    }
+}
+
+/// Mutates the phenotype once plus as often as its number of mutates.
+fn develop(
+    pheno: NeuralNetworkPhenotype,
+    rng: &mut RandomNumberGenerator,
+) -> NeuralNetworkPhenotype {
+    let mut phenotype = pheno;
+    let number_of_mutations = phenotype.get_number_mutates();
+    phenotype.mutate(rng);
+    for _ in 0..number_of_mutations {
+        phenotype.mutate(rng);
     }
+    phenotype
 }
 
 impl BreedStrategy<NeuralNetworkPhenotype> for NeuralNetworkStrategy {
@@ -25,10 +189,42 @@ impl BreedStrategy<NeuralNetworkPhenotype> for NeuralNetworkStrategy {
         evol_options: &EvolutionOptions,
         rng: &mut RandomNumberGenerator,
     ) -> Result<Vec<NeuralNetworkPhenotype>, Error> {
-        let adjust_strategy = AdjustStrategy::default();
         let mut nn = parents[0].get_nn();
         println!("Saving model to: {} with shape: {:?}", self.model_directory, nn.shape());
         let _ = nn.save(self.model_directory.clone());
-        adjust_strategy.breed(parents, evol_options, rng)
+        let mutation_config =
+            self.mutation_config.at_generation(self.generation.fetch_add(1, Ordering::SeqCst));
+        let parents = parents
+            .iter()
+            .map(|parent| {
+                let mut parent = parent.clone();
+                parent.set_mutation_config(mutation_config);
+                parent
+            })
+            .collect::<Vec<_>>();
+        if self.parallel_breeding {
+            return Ok(self.breed_parallel(&parents, evol_options, rng));
+        }
+        let adjust_strategy = AdjustStrategy::default();
+        adjust_strategy.breed(&parents, evol_options, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thread_pool_follows_the_number_of_threads() {
+        let strategy = NeuralNetworkStrategy::new("thread_pool_test_model".to_string());
+        let pool = strategy.thread_pool(4);
+        assert_eq!(pool.current_num_threads(), 4);
+        // the pool is kept across generations and shared with clones of the strategy
+        let cloned_strategy = strategy.clone();
+        assert!(Arc::ptr_eq(&pool, &cloned_strategy.thread_pool(4)));
+
+        let smaller_pool = strategy.thread_pool(2);
+        assert_eq!(smaller_pool.current_num_threads(), 2);
+        assert!(Arc::ptr_eq(&smaller_pool, &strategy.thread_pool(2)));
     }
 }
@@ -0,0 +1,197 @@
+use crate::pheno::rng_wrapper::RngWrapper;
+use neural::nn::directory::Directory;
+use neural::nn::nn_trait::WrappedTrainableNeuralNetwork;
+use neural::nn::shape::{ActivationData, LayerShape, LayerType, NeuralNetworkShape};
+use neural::training::data_importer::DataImporter;
+use neural::training::training_params::TrainingParams;
+use neural::training::training_session::TrainingSession;
+use neural::utilities::util::WrappedUtils;
+
+use num_traits::NumCast;
+
+use std::error::Error;
+
+/// Describes the architectures a `RandomSearchStrategy` samples from.
+#[derive(Debug, Clone)]
+pub struct ShapeSpace {
+    input_size: usize,
+    output_size: usize,
+    min_layers: usize,
+    max_layers: usize,
+    min_units: usize,
+    max_units: usize,
+    activations: Vec<ActivationData>,
+}
+
+impl ShapeSpace {
+    /// Creates a shape space of networks with `min_layers..=max_layers` dense layers whose
+    /// hidden layers have `min_units..=max_units` units. Every layer picks its activation
+    /// from `activations`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a size is zero, a minimum exceeds its maximum or no activation is allowed.
+    #[must_use]
+    pub fn new(
+        input_size: usize,
+        output_size: usize,
+        (min_layers, max_layers): (usize, usize),
+        (min_units, max_units): (usize, usize),
+        activations: Vec<ActivationData>,
+    ) -> Self {
+        assert!(input_size > 0 && output_size > 0, "Input and output size must be positive");
+        assert!(min_layers > 0 && min_layers <= max_layers, "Invalid layer bounds");
+        assert!(min_units > 0 && min_units <= max_units, "Invalid unit bounds");
+        assert!(!activations.is_empty(), "At least one activation must be allowed");
+        Self { input_size, output_size, min_layers, max_layers, min_units, max_units, activations }
+    }
+
+    #[must_use]
+    pub const fn layer_bounds(&self) -> (usize, usize) {
+        (self.min_layers, self.max_layers)
+    }
+
+    #[must_use]
+    pub const fn unit_bounds(&self) -> (usize, usize) {
+        (self.min_units, self.max_units)
+    }
+
+    #[must_use]
+    pub fn activations(&self) -> &[ActivationData] {
+        &self.activations
+    }
+
+    /// Samples a random valid shape from the space. The last layer always outputs
+    /// `output_size` values, the widths of all other layers lie within the unit bounds.
+    pub fn sample(
+        &self,
+        rng: &mut dyn RngWrapper,
+    ) -> NeuralNetworkShape {
+        let num_layers = fetch_in_range(rng, self.min_layers, self.max_layers);
+        let mut layers = Vec::with_capacity(num_layers);
+        let mut input_size = self.input_size;
+        for i in 0..num_layers {
+            let output_size = if i == num_layers - 1 {
+                self.output_size
+            } else {
+                fetch_in_range(rng, self.min_units, self.max_units)
+            };
+            let activation_index = fetch_in_range(rng, 0, self.activations.len() - 1);
+            layers.push(LayerShape {
+                layer_type: LayerType::Dense { input_size, output_size },
+                activation: self.activations[activation_index].clone(),
+            });
+            input_size = output_size;
+        }
+        NeuralNetworkShape::new(layers)
+    }
+}
+
+/// Returns a uniformly distributed number in `min..=max`.
+fn fetch_in_range(
+    rng: &mut dyn RngWrapper,
+    min: usize,
+    max: usize,
+) -> usize {
+    let min_f32: f32 = NumCast::from(min).expect("Failed to convert min to f32");
+    let max_f32: f32 = NumCast::from(max + 1).expect("Failed to convert max to f32");
+    let random_number: usize =
+        NumCast::from(rng.fetch_uniform(min_f32, max_f32, 1).pop_front().unwrap().floor())
+            .expect("Failed to convert random number to usize");
+    random_number.clamp(min, max)
+}
+
+/// A baseline for the evolutionary search: samples random shapes, trains each of them
+/// briefly and keeps the one with the best accuracy.
+#[derive(Clone)]
+pub struct RandomSearchStrategy {
+    shape_space: ShapeSpace,
+    num_candidates: usize,
+    params: TrainingParams,
+    data_importer: Box<dyn DataImporter + Send + Sync>,
+    model_directory: String,
+}
+
+impl RandomSearchStrategy {
+    /// Creates a search over `num_candidates` random shapes. Every candidate is trained with
+    /// `params` in its own internal directory below `model_directory`.
+    #[must_use]
+    pub fn new(
+        shape_space: ShapeSpace,
+        num_candidates: usize,
+        params: TrainingParams,
+        data_importer: Box<dyn DataImporter + Send + Sync>,
+        model_directory: String,
+    ) -> Self {
+        Self { shape_space, num_candidates, params, data_importer, model_directory }
+    }
+
+    #[must_use]
+    pub const fn shape_space(&self) -> &ShapeSpace {
+        &self.shape_space
+    }
+
+    /// Trains the sampled candidates one after another and returns the best network
+    /// together with its accuracy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no candidates are requested or if training a candidate fails.
+    pub fn search(
+        &self,
+        rng: &mut dyn RngWrapper,
+        utils: &WrappedUtils,
+    ) -> Result<(WrappedTrainableNeuralNetwork, f64), Box<dyn Error>> {
+        let mut best: Option<(WrappedTrainableNeuralNetwork, f64)> = None;
+        for i in 0..self.num_candidates {
+            let mut params = self.params.clone();
+            params.set_shape(self.shape_space.sample(rng));
+            let model_directory =
+                Directory::Internal(format!("{}_candidate_{i}", self.model_directory));
+            let mut training_session = TrainingSession::new(
+                params,
+                self.data_importer.clone(),
+                &model_directory,
+                utils.clone(),
+            )?;
+            let accuracy = training_session.train()?;
+            if best.as_ref().map_or(true, |(_, best_accuracy)| accuracy > *best_accuracy) {
+                best = Some((training_session.get_nn(), accuracy));
+            }
+        }
+        best.ok_or_else(|| "Random search needs at least one candidate".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pheno::rng_wrapper::RealRng;
+    use evol::rng::RandomNumberGenerator;
+    use neural::nn::shape::ActivationType;
+
+    #[test]
+    fn test_sampled_shapes_stay_within_bounds() {
+        let activations = vec![
+            ActivationData::new(ActivationType::ReLU),
+            ActivationData::new(ActivationType::Tanh),
+        ];
+        let space = ShapeSpace::new(6, 3, (1, 4), (2, 9), activations.clone());
+        let mut rng = RandomNumberGenerator::new();
+        let mut rng_wrapper = RealRng::new(&mut rng);
+        let mut seen_layers = [false; 5];
+        for _ in 0..500 {
+            let shape = space.sample(&mut rng_wrapper);
+            assert!(shape.is_valid());
+            assert!((1..=4).contains(&shape.num_layers()));
+            seen_layers[shape.num_layers()] = true;
+            assert_eq!(shape.layers[0].input_size(), 6);
+            assert_eq!(shape.layers[shape.num_layers() - 1].output_size(), 3);
+            for layer in &shape.layers[..shape.num_layers() - 1] {
+                assert!((2..=9).contains(&layer.output_size()));
+            }
+            assert!(shape.layers.iter().all(|layer| activations.contains(&layer.activation)));
+        }
+        assert!(seen_layers[1..].iter().all(|seen| *seen));
+    }
+}
@@ -69,3 +69,179 @@ fn test_neural_network_breeding() {
     // Remove model directory
     // std::fs::remove_dir_all(model_directory).expect("Failed to remove model directory");
 }
+
+#[test]
+fn test_neural_network_parallel_breeding() {
+    let nn_shape = NeuralNetworkShape {
+        layers: vec![
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 16, output_size: 8 },
+                activation: ActivationData::new(ActivationType::ReLU),
+            },
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 8, output_size: 4 },
+                activation: ActivationData::new(ActivationType::Sigmoid),
+            },
+        ],
+    };
+
+    let utils = WrappedUtils::new(Utils::new(1000000000, 4));
+    let model_directory = "parallel_breeding_test_model".to_owned();
+
+    let mut nn = new_trainable_neural_network(NeuralNetworkCreationArguments::new(
+        nn_shape.clone(),
+        None,
+        None,
+        format!("{model_directory}/start"),
+        utils,
+    ));
+    let _ = nn.predict(vec![0.0; 16]);
+    let parents = vec![NeuralNetworkPhenotype::new(&nn)];
+
+    let evol_opts = EvolutionOptions::new(1, LogLevel::None, 4, 12);
+
+    let sequential_strategy = NeuralNetworkStrategy::new(format!("{model_directory}/sequential"));
+    let parallel_strategy = NeuralNetworkStrategy::new(format!("{model_directory}/parallel"))
+        .with_parallel_breeding(true);
+
+    let start = std::time::Instant::now();
+    let sequential_children = sequential_strategy
+        .breed(&parents, &evol_opts, &mut RandomNumberGenerator::from_seed(42))
+        .expect("Breed failed");
+    let sequential_duration = start.elapsed();
+
+    let start = std::time::Instant::now();
+    let parallel_children = parallel_strategy
+        .breed(&parents, &evol_opts, &mut RandomNumberGenerator::from_seed(42))
+        .expect("Breed failed");
+    let parallel_duration = start.elapsed();
+
+    assert_eq!(sequential_children.len(), 12);
+    assert_eq!(parallel_children.len(), sequential_children.len());
+    for children in [&sequential_children, &parallel_children] {
+        assert!(children.iter().all(|child| child.get_nn().shape().is_valid()));
+        // the winner of the previous generation is always kept unchanged
+        assert!(children.iter().any(|child| child.get_nn().shape() == nn_shape));
+        // every child keeps the input and output size of the parent
+        assert!(children.iter().all(|child| child.get_nn().input_size() == 16));
+        assert!(children.iter().all(|child| child.get_nn().output_size() == 4));
+    }
+    // the same seed develops the same children in both modes
+    for (sequential_child, parallel_child) in sequential_children.iter().zip(&parallel_children) {
+        assert_eq!(sequential_child.get_nn().shape(), parallel_child.get_nn().shape());
+    }
+    // leave room for timing noise, mutating is dominated by file system access
+    assert!(parallel_duration <= sequential_duration * 2 + std::time::Duration::from_millis(500));
+
+    drop(sequential_children);
+    drop(parallel_children);
+    drop(parents);
+    drop(nn);
+    std::fs::remove_dir_all(model_directory).expect("Failed to remove model directory");
+}
+
+#[test]
+fn test_neural_network_seeded_population() {
+    let seed_shape = NeuralNetworkShape {
+        layers: vec![
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 16, output_size: 8 },
+                activation: ActivationData::new(ActivationType::ReLU),
+            },
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 8, output_size: 4 },
+                activation: ActivationData::new(ActivationType::Sigmoid),
+            },
+        ],
+    };
+
+    let utils = WrappedUtils::new(Utils::new(1000000000, 4));
+    let model_directory = "seeded_population_test_model".to_owned();
+
+    let mut nn = new_trainable_neural_network(NeuralNetworkCreationArguments::new(
+        seed_shape.clone(),
+        None,
+        None,
+        model_directory.clone(),
+        utils.clone(),
+    ));
+    let _ = nn.predict(vec![0.0; 16]);
+    nn.save(model_directory.clone()).expect("Failed to save model");
+    drop(nn);
+
+    let strategy = NeuralNetworkStrategy::from_seed_model(model_directory.clone(), utils);
+    assert_eq!(strategy.seed_model().expect("Seed model missing").get_nn().shape(), seed_shape);
+
+    let evol_opts = EvolutionOptions::new(1, LogLevel::None, 4, 8);
+    let mut rng = RandomNumberGenerator::new();
+    let population = strategy.initial_population(&evol_opts, &mut rng);
+
+    assert_eq!(population.len(), 8);
+    // the seed itself is part of the initial population
+    assert_eq!(population[0].get_nn().shape(), seed_shape);
+    for pheno in &population {
+        let nn = pheno.get_nn();
+        assert!(nn.shape().is_valid());
+        assert_eq!(nn.input_size(), 16);
+        assert_eq!(nn.output_size(), 4);
+    }
+
+    // an unseeded strategy has no initial population of its own
+    let unseeded = NeuralNetworkStrategy::new(model_directory.clone());
+    assert!(unseeded.initial_population(&evol_opts, &mut rng).is_empty());
+
+    drop(population);
+    drop(strategy);
+    std::fs::remove_dir_all(model_directory).expect("Failed to remove model directory");
+}
+
+#[test]
+fn test_neural_network_breeding_is_reproducible_with_a_seed() {
+    let nn_shape = NeuralNetworkShape {
+        layers: vec![
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 6, output_size: 4 },
+                activation: ActivationData::new(ActivationType::ReLU),
+            },
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 4, output_size: 2 },
+                activation: ActivationData::new(ActivationType::Sigmoid),
+            },
+        ],
+    };
+    let utils = WrappedUtils::new(Utils::new(1000000000, 4));
+    let model_directory = "reproducible_breeding_test_model".to_owned();
+    let evol_opts = EvolutionOptions::new(1, LogLevel::None, 3, 6);
+
+    // breeds three generations and returns the shape and size of every child
+    let run = |name: &str| {
+        let nn = new_trainable_neural_network(NeuralNetworkCreationArguments::new(
+            nn_shape.clone(),
+            None,
+            None,
+            format!("{model_directory}/{name}/start"),
+            utils.clone(),
+        ));
+        let strategy = NeuralNetworkStrategy::new(format!("{model_directory}/{name}/winner"));
+        let mut rng = RandomNumberGenerator::from_seed(42);
+        let mut parents = vec![NeuralNetworkPhenotype::new(&nn)];
+        let mut history = Vec::new();
+        for _ in 0..3 {
+            let children = strategy.breed(&parents, &evol_opts, &mut rng).expect("Breed failed");
+            history.push(
+                children
+                    .iter()
+                    .map(|child| (child.get_nn().shape(), child.get_nn().num_parameters()))
+                    .collect::<Vec<_>>(),
+            );
+            parents = children.into_iter().take(3).collect();
+        }
+        history
+    };
+
+    let first = run("first");
+    let second = run("second");
+    assert_eq!(first, second);
+
+    std::fs::remove_dir_all(model_directory).expect("Failed to remove model directory");
+}
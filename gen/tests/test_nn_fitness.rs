@@ -0,0 +1,73 @@
+use evol::evolution::challenge::Challenge;
+use gen::challenge::nn_challenge::NeuralNetworkChallenge;
+use gen::pheno::nn_pheno::NeuralNetworkPhenotype;
+use neural::nn::nn_factory::{new_trainable_neural_network, NeuralNetworkCreationArguments};
+use neural::nn::shape::NeuralNetworkShape;
+use neural::nn::shape::{ActivationData, ActivationType, LayerShape, LayerType};
+use neural::training::data_importer::{DataImporter, SessionData};
+use neural::training::training_params::TrainingParams;
+use neural::training::verbosity::Verbosity;
+use neural::utilities::util::{Utils, WrappedUtils};
+
+use num_traits::NumCast;
+
+#[derive(Clone)]
+struct XorDataImporter;
+
+impl DataImporter for XorDataImporter {
+    fn get_data(&self) -> SessionData {
+        let data = vec![vec![0.0, 0.0], vec![0.0, 1.0], vec![1.0, 0.0], vec![1.0, 1.0]];
+        let labels = vec![vec![0.0], vec![1.0], vec![1.0], vec![0.0]];
+        SessionData { data, labels }
+    }
+}
+
+fn hidden_shape(hidden_size: usize) -> NeuralNetworkShape {
+    NeuralNetworkShape {
+        layers: vec![
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 2, output_size: hidden_size },
+                activation: ActivationData::new(ActivationType::ReLU),
+            },
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: hidden_size, output_size: 1 },
+                activation: ActivationData::new(ActivationType::Sigmoid),
+            },
+        ],
+    }
+}
+
+#[test]
+fn test_custom_fitness_prefers_smaller_networks() {
+    let utils = WrappedUtils::new(Utils::new(1000000000, 4));
+    let params =
+        TrainingParams::new(hidden_shape(4), None, None, 0.75, 0.01, 1, 0.1, 4, false, 1.0)
+            .with_verbosity(Verbosity::Silent);
+    let challenge = NeuralNetworkChallenge::new(params, Box::new(XorDataImporter)).with_fitness(
+        Box::new(|nn| {
+            let num_parameters: f64 = NumCast::from(nn.num_parameters()).unwrap();
+            -num_parameters
+        }),
+    );
+
+    let mut scored = [16, 2, 8]
+        .into_iter()
+        .map(|hidden_size| {
+            let nn = new_trainable_neural_network(NeuralNetworkCreationArguments::new(
+                hidden_shape(hidden_size),
+                None,
+                None,
+                format!("fitness_test_model_{hidden_size}"),
+                utils.clone(),
+            ));
+            let mut phenotype = NeuralNetworkPhenotype::new(&nn);
+            let score = challenge.score(&mut phenotype);
+            (score, phenotype)
+        })
+        .collect::<Vec<_>>();
+    scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+    let best = scored[0].1.get_nn();
+    assert_eq!(best.shape(), hidden_shape(2));
+    assert!((scored[0].0 + 9.0).abs() < f64::EPSILON);
+}
@@ -31,6 +31,22 @@ impl fmt::Display for OutOfRangeError {
 
 impl Error for OutOfRangeError {}
 
+#[derive(Debug)]
+pub struct DimensionMismatchError {
+    message: String,
+}
+
+impl fmt::Display for DimensionMismatchError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for DimensionMismatchError {}
+
 impl<T> Matrix<T>
 where
     T: Default + Clone,
@@ -44,6 +60,111 @@ where
         Self { rows, cols, data: vec![T::default(); rows * cols] }
     }
 
+    /// Create a matrix from its rows
+    ///
+    /// # Errors
+    /// Returns `DimensionMismatchError` if the rows do not all have the same length.
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Result<Self, DimensionMismatchError> {
+        let cols = rows.first().map_or(0, Vec::len);
+        if let Some((i, row)) = rows.iter().enumerate().find(|(_, row)| row.len() != cols) {
+            return Err(DimensionMismatchError {
+                message: format!(
+                    "Matrix::from_rows row {} has {} columns, expected {}",
+                    i,
+                    row.len(),
+                    cols
+                ),
+            });
+        }
+        let num_rows = rows.len();
+        Ok(Self { rows: num_rows, cols, data: rows.into_iter().flatten().collect() })
+    }
+
+    /// Create a matrix from its elements in row-major order
+    ///
+    /// # Errors
+    /// Returns `DimensionMismatchError` if `data` does not have `rows * cols` elements.
+    pub fn from_flat(
+        rows: usize,
+        cols: usize,
+        data: Vec<T>,
+    ) -> Result<Self, DimensionMismatchError> {
+        if data.len() != rows * cols {
+            return Err(DimensionMismatchError {
+                message: format!(
+                    "Matrix::from_flat got {} elements, expected rows: {}, cols: {}",
+                    data.len(),
+                    rows,
+                    cols
+                ),
+            });
+        }
+        Ok(Self { rows, cols, data })
+    }
+
+    /// Return a copy of all elements in row-major order
+    #[must_use]
+    pub fn to_flat(&self) -> Vec<T> {
+        self.data.clone()
+    }
+
+    /// Get the elements of row `i`
+    ///
+    /// # Errors
+    /// Returns `OutOfRangeError` if `i` is out of bounds.
+    pub fn row(
+        &self,
+        i: usize,
+    ) -> Result<&[T], OutOfRangeError> {
+        if i >= self.rows {
+            return Err(OutOfRangeError {
+                message: format!("Matrix::row out of range i: {}, rows: {}", i, self.rows),
+            });
+        }
+        Ok(&self.data[i * self.cols..(i + 1) * self.cols])
+    }
+
+    /// Return a copy of the elements of column `j`
+    ///
+    /// # Errors
+    /// Returns `OutOfRangeError` if `j` is out of bounds.
+    pub fn col(
+        &self,
+        j: usize,
+    ) -> Result<Vec<T>, OutOfRangeError> {
+        if j >= self.cols {
+            return Err(OutOfRangeError {
+                message: format!("Matrix::col out of range j: {}, cols: {}", j, self.cols),
+            });
+        }
+        Ok((0..self.rows).map(|i| self.data[i * self.cols + j].clone()).collect())
+    }
+
+    /// Return a copy of the block of rows `r0..r1` and columns `c0..c1`
+    ///
+    /// # Errors
+    /// Returns `OutOfRangeError` if a range is reversed or exceeds the matrix.
+    pub fn sub_matrix(
+        &self,
+        r0: usize,
+        r1: usize,
+        c0: usize,
+        c1: usize,
+    ) -> Result<Self, OutOfRangeError> {
+        if r0 > r1 || r1 > self.rows || c0 > c1 || c1 > self.cols {
+            return Err(OutOfRangeError {
+                message: format!(
+                    "Matrix::sub_matrix out of range rows: {}..{}, cols: {}..{}, matrix rows: {}, matrix cols: {}",
+                    r0, r1, c0, c1, self.rows, self.cols
+                ),
+            });
+        }
+        let data = (r0..r1)
+            .flat_map(|i| self.data[i * self.cols + c0..i * self.cols + c1].iter().cloned())
+            .collect();
+        Ok(Self { rows: r1 - r0, cols: c1 - c0, data })
+    }
+
     /// Get a mutable reference to an element at (x, y)
     ///
     /// # Errors
@@ -140,12 +261,12 @@ pub struct RowIterMut<'a, T> {
 impl<T> Matrix<T> {
     /// Returns an iterator over the rows of the matrix (immutable)
     #[must_use]
-    pub const fn iter(&self) -> RowIter<T> {
+    pub const fn iter(&self) -> RowIter<'_, T> {
         RowIter { matrix: self, current_row: 0 }
     }
 
     /// Returns an iterator over the rows of the matrix (mutable)
-    pub fn iter_mut(&mut self) -> RowIterMut<T> {
+    pub fn iter_mut(&mut self) -> RowIterMut<'_, T> {
         RowIterMut { matrix: self, current_row: 0 }
     }
 }
@@ -0,0 +1,29 @@
+use matrix::mat::Matrix;
+
+#[test]
+fn test_matrix_from_rows_roundtrip() {
+    let rows: Vec<Vec<f64>> = vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]];
+    let matrix = Matrix::from_rows(rows).unwrap();
+    assert_eq!(matrix.rows(), 2);
+    assert_eq!(matrix.cols(), 3);
+    assert!((matrix.get_unchecked(1, 0) - 4.0).abs() < f64::EPSILON);
+    assert_eq!(matrix.to_flat(), vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+
+    let flat = Matrix::from_flat(3, 2, matrix.to_flat()).unwrap();
+    assert_eq!(flat.rows(), 3);
+    assert_eq!(flat.cols(), 2);
+    assert!((flat.get_unchecked(2, 1) - 6.0).abs() < f64::EPSILON);
+    assert_eq!(flat.to_flat(), matrix.to_flat());
+}
+
+#[test]
+fn test_matrix_from_rows_rejects_ragged_rows() {
+    let rows = vec![vec![1.0, 2.0], vec![3.0]];
+    let error = Matrix::<f64>::from_rows(rows).unwrap_err();
+    assert!(error.to_string().contains("row 1 has 1 columns, expected 2"));
+}
+
+#[test]
+fn test_matrix_from_flat_rejects_wrong_length() {
+    assert!(Matrix::<f64>::from_flat(2, 2, vec![1.0, 2.0, 3.0]).is_err());
+}
@@ -0,0 +1,36 @@
+use matrix::mat::Matrix;
+
+fn sample_matrix() -> Matrix<f64> {
+    Matrix::from_rows(vec![
+        vec![1.0, 2.0, 3.0, 4.0],
+        vec![5.0, 6.0, 7.0, 8.0],
+        vec![9.0, 10.0, 11.0, 12.0],
+    ])
+    .unwrap()
+}
+
+#[test]
+fn test_matrix_row_and_col() {
+    let matrix = sample_matrix();
+    assert_eq!(matrix.row(1).unwrap(), &[5.0, 6.0, 7.0, 8.0]);
+    assert_eq!(matrix.col(2).unwrap(), vec![3.0, 7.0, 11.0]);
+    assert!(matrix.row(3).is_err());
+    assert!(matrix.col(4).is_err());
+}
+
+#[test]
+fn test_matrix_sub_matrix() {
+    let matrix = sample_matrix();
+    let block = matrix.sub_matrix(1, 3, 1, 3).unwrap();
+    assert_eq!(block.rows(), 2);
+    assert_eq!(block.cols(), 2);
+    assert_eq!(block.to_flat(), vec![6.0, 7.0, 10.0, 11.0]);
+
+    let empty = matrix.sub_matrix(2, 2, 0, 4).unwrap();
+    assert_eq!(empty.rows(), 0);
+    assert!(empty.to_flat().is_empty());
+
+    assert!(matrix.sub_matrix(0, 4, 0, 2).is_err());
+    assert!(matrix.sub_matrix(2, 1, 0, 2).is_err());
+    assert!(matrix.sub_matrix(0, 1, 3, 5).is_err());
+}
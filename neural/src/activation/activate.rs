@@ -19,6 +19,16 @@ pub trait ActivationTrait: std::fmt::Debug + DynClone {
         input: &[f64],
     ) -> Vec<f64>;
 
+    /// Applies the activation function to the values in place, e.g. to reuse the buffers of a
+    /// prediction. Nothing is cached for a backward pass.
+    fn forward_in_place(
+        &mut self,
+        values: &mut [f64],
+    ) {
+        let output = self.forward(values);
+        values.copy_from_slice(&output);
+    }
+
     /// Computes the gradient of the activation function for backpropagation.
     ///
     /// # Arguments
@@ -0,0 +1,151 @@
+use super::activate::ActivationTrait;
+use crate::nn::shape::{ActivationData, ActivationSegment};
+
+use std::ops::Range;
+
+/// Applies different activation functions to consecutive ranges of the output units,
+/// e.g. a sigmoid gate followed by linear values.
+#[derive(Debug, Clone)]
+pub struct CompositeActivation {
+    segments: Vec<(Range<usize>, Box<dyn ActivationTrait + Send>)>,
+}
+
+impl CompositeActivation {
+    /// Creates a new `CompositeActivation` applying every activation to its range of units.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the ranges are empty or do not cover the units
+    /// in order without gaps, starting at the first unit.
+    #[must_use]
+    pub fn new(segments: Vec<(Range<usize>, Box<dyn ActivationTrait + Send>)>) -> Self {
+        let mut end = 0;
+        for (range, _) in &segments {
+            assert!(
+                range.start == end && range.end > range.start,
+                "The ranges of a composite activation have to be contiguous and not empty"
+            );
+            end = range.end;
+        }
+        Self { segments }
+    }
+
+    /// Applies `apply` to the slice of every segment and concatenates the results.
+    fn apply_segments(
+        &mut self,
+        values: &[f64],
+        apply: fn(&mut Box<dyn ActivationTrait + Send>, &[f64]) -> Vec<f64>,
+    ) -> Vec<f64> {
+        let units = self.segments.last().map_or(0, |(range, _)| range.end);
+        assert_eq!(
+            values.len(),
+            units,
+            "The composite activation applies to {units} units, got {}",
+            values.len()
+        );
+        let mut output = Vec::with_capacity(values.len());
+        for (range, activation) in &mut self.segments {
+            output.extend(apply(activation, &values[range.clone()]));
+        }
+        output
+    }
+}
+
+impl ActivationTrait for CompositeActivation {
+    fn forward(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<f64> {
+        self.apply_segments(input, |activation, input| activation.forward(input))
+    }
+
+    fn forward_in_place(
+        &mut self,
+        values: &mut [f64],
+    ) {
+        let units = self.segments.last().map_or(0, |(range, _)| range.end);
+        assert_eq!(
+            values.len(),
+            units,
+            "The composite activation applies to {units} units, got {}",
+            values.len()
+        );
+        for (range, activation) in &mut self.segments {
+            activation.forward_in_place(&mut values[range.clone()]);
+        }
+    }
+
+    fn backward(
+        &mut self,
+        grad_output: &[f64],
+    ) -> Vec<f64> {
+        self.apply_segments(grad_output, |activation, grad| activation.backward(grad))
+    }
+
+    fn get_activation_data(&self) -> ActivationData {
+        ActivationData::new_composite(
+            self.segments
+                .iter()
+                .map(|(range, activation)| {
+                    ActivationSegment::new(range.start, range.end, activation.get_activation_data())
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nn::directory::Directory;
+    use crate::nn::neuralnet::TrainableClassicNeuralNetwork;
+    use crate::nn::nn_trait::{NeuralNetwork, TrainableNeuralNetwork};
+    use crate::nn::shape::{ActivationType, LayerShape, LayerType, NeuralNetworkShape};
+    use crate::utilities::util::{Utils, WrappedUtils};
+
+    #[test]
+    fn test_composite_activation() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let activation = ActivationData::new_composite(vec![
+            ActivationSegment::new(0, 2, ActivationData::new(ActivationType::Sigmoid)),
+            ActivationSegment::new(2, 4, ActivationData::new(ActivationType::Identity)),
+        ]);
+        let shape = NeuralNetworkShape::new(vec![LayerShape {
+            layer_type: LayerType::Dense { input_size: 4, output_size: 4 },
+            activation: activation.clone(),
+        }]);
+        assert!(shape.is_valid());
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            shape.clone(),
+            &Directory::Internal("test_model_composite_activation".to_string()),
+            utils,
+        );
+        assert_eq!(nn.shape(), shape);
+
+        // route every input to its own output so the raw outputs are known
+        let mut weights = Vec::new();
+        for row in 0..4 {
+            weights.extend((0..4).map(|col| if row == col { 1.0 } else { 0.0 }));
+        }
+        weights.extend([0.0; 4]);
+        nn.load_weights_flat(&weights).unwrap();
+
+        let input = vec![3.0, -2.0, 5.0, -7.5];
+        let output = nn.predict(input.clone());
+        for (x, y) in input[..2].iter().zip(&output[..2]) {
+            assert!((y - 1.0 / (1.0 + (-x).exp())).abs() < 1e-12);
+        }
+        assert_eq!(output[2..], input[2..]);
+
+        // the mapping is serialized with the activation
+        let yaml = serde_yaml::to_string(&activation).unwrap();
+        assert_eq!(serde_yaml::from_str::<ActivationData>(&yaml).unwrap(), activation);
+
+        // the segments have to cover all output units
+        let uncovered = NeuralNetworkShape::new(vec![LayerShape {
+            layer_type: LayerType::Dense { input_size: 4, output_size: 5 },
+            activation,
+        }]);
+        assert!(!uncovered.is_valid());
+    }
+}
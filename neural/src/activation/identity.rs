@@ -0,0 +1,113 @@
+use crate::nn::shape::ActivationData;
+use crate::nn::shape::ActivationType;
+
+use super::activate::ActivationTrait;
+
+/// Identity (linear) activation function passing its input through unchanged.
+/// It is meant for the output layer of regression networks.
+#[derive(Debug, Clone)]
+pub struct Identity;
+
+impl Identity {
+    /// Creates a new Identity instance.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for Identity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ActivationTrait for Identity {
+    fn forward(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<f64> {
+        input.to_vec()
+    }
+
+    fn forward_in_place(
+        &mut self,
+        _values: &mut [f64],
+    ) {
+    }
+
+    fn backward(
+        &mut self,
+        grad_output: &[f64],
+    ) -> Vec<f64> {
+        grad_output.to_vec()
+    }
+
+    fn get_activation_data(&self) -> ActivationData {
+        ActivationData::new(ActivationType::Identity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nn::directory::Directory;
+    use crate::nn::neuralnet::TrainableClassicNeuralNetwork;
+    use crate::nn::nn_trait::{NeuralNetwork, TrainableNeuralNetwork};
+    use crate::nn::shape::{LayerShape, LayerType, NeuralNetworkShape};
+    use crate::training::training_options::TrainingOptions;
+    use crate::training::verbosity::Verbosity;
+    use crate::utilities::util::{Utils, WrappedUtils};
+
+    #[test]
+    fn test_identity() {
+        let mut identity = Identity::new();
+        let input = vec![-1.5, 0.0, 2.0];
+        assert_eq!(identity.forward(&input), input);
+        assert_eq!(identity.backward(&[0.5, -0.25, 1.0]), vec![0.5, -0.25, 1.0]);
+    }
+
+    /// Trains a single dense layer with the given output activation on partly negative targets
+    /// and returns the mean squared error of the predictions.
+    fn fit_negative_targets(
+        activation: ActivationType,
+        model_directory: &str,
+    ) -> f64 {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape::new(vec![LayerShape {
+            layer_type: LayerType::Dense { input_size: 2, output_size: 1 },
+            activation: ActivationData::new(activation),
+        }]);
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            shape,
+            &Directory::Internal(model_directory.to_string()),
+            utils,
+        );
+        nn.set_training_options(TrainingOptions {
+            verbosity: Verbosity::Silent,
+            ..nn.training_options()
+        });
+
+        let inputs = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![-1.0, 0.0], vec![0.0, -1.0]];
+        let targets: Vec<Vec<f64>> =
+            inputs.iter().map(|input| vec![(-2.0f64).mul_add(input[0], -input[1])]).collect();
+        // train_batch uses every sample in every epoch
+        nn.train_batch(&inputs, &targets, 0.2, 300, 0.1, 1, None);
+
+        inputs
+            .iter()
+            .zip(&targets)
+            .map(|(input, target)| (nn.predict(input.clone())[0] - target[0]).powi(2))
+            .sum::<f64>()
+            / 4.0
+    }
+
+    #[test]
+    fn test_identity_output_fits_negative_targets() {
+        let identity_error = fit_negative_targets(ActivationType::Identity, "test_model_identity");
+        let relu_error = fit_negative_targets(ActivationType::ReLU, "test_model_identity_relu");
+        assert!(identity_error < 1e-2, "identity output did not fit, error {identity_error}");
+        // a ReLU output never goes below zero
+        assert!(relu_error > 1.0, "ReLU output fitted negative targets, error {relu_error}");
+    }
+}
@@ -0,0 +1,87 @@
+use super::activate::ActivationTrait;
+use crate::nn::shape::ActivationData;
+
+/// Applies one activation to all but the last units and a separate activation to the last units.
+///
+/// The retry network appends internal units to every layer which signal whether the
+/// backup network has to be used; this activation lets those units be squashed independently.
+#[derive(Debug, Clone)]
+pub struct InternalUnitActivation {
+    main: Box<dyn ActivationTrait + Send>,
+    internal: Box<dyn ActivationTrait + Send>,
+    internal_units: usize,
+}
+
+impl InternalUnitActivation {
+    /// Creates a new `InternalUnitActivation` from the activation of the main units
+    /// and the activation of the last `internal_units` units.
+    #[must_use]
+    pub fn new(
+        main: Box<dyn ActivationTrait + Send>,
+        internal: Box<dyn ActivationTrait + Send>,
+        internal_units: usize,
+    ) -> Self {
+        Self { main, internal, internal_units }
+    }
+}
+
+impl ActivationTrait for InternalUnitActivation {
+    fn forward(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<f64> {
+        let (main_input, internal_input) =
+            input.split_at(input.len().saturating_sub(self.internal_units));
+        let mut output = self.main.forward(main_input);
+        output.extend(self.internal.forward(internal_input));
+        output
+    }
+
+    fn forward_in_place(
+        &mut self,
+        values: &mut [f64],
+    ) {
+        let num_main = values.len().saturating_sub(self.internal_units);
+        let (main_values, internal_values) = values.split_at_mut(num_main);
+        self.main.forward_in_place(main_values);
+        self.internal.forward_in_place(internal_values);
+    }
+
+    fn backward(
+        &mut self,
+        grad_output: &[f64],
+    ) -> Vec<f64> {
+        let (main_grad, internal_grad) =
+            grad_output.split_at(grad_output.len().saturating_sub(self.internal_units));
+        let mut grad = self.main.backward(main_grad);
+        grad.extend(self.internal.backward(internal_grad));
+        grad
+    }
+
+    fn get_activation_data(&self) -> ActivationData {
+        self.main
+            .get_activation_data()
+            .with_internal_activation(self.internal.get_activation_data())
+            .with_internal_units(self.internal_units)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activation::{relu::ReLU, sigmoid::Sigmoid};
+    use crate::nn::shape::ActivationType;
+
+    #[test]
+    fn test_internal_unit_activation() {
+        let mut activation =
+            InternalUnitActivation::new(Box::new(ReLU::new()), Box::new(Sigmoid), 1);
+        let output = activation.forward(&[-1.0, 2.0, 0.0]);
+        assert_eq!(output, vec![0.0, 2.0, 0.5]);
+        assert_eq!(
+            activation.get_activation_data(),
+            ActivationData::new(ActivationType::ReLU)
+                .with_internal_activation(ActivationData::new(ActivationType::Sigmoid))
+        );
+    }
+}
@@ -1,4 +1,8 @@
 pub mod activate;
+pub mod composite;
+pub mod identity;
+pub mod internal;
+pub mod registry;
 pub mod relu;
 pub mod sigmoid;
 pub mod softmax;
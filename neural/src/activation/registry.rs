@@ -0,0 +1,187 @@
+use super::activate::ActivationTrait;
+use super::composite::CompositeActivation;
+use super::internal::InternalUnitActivation;
+use super::{identity::Identity, relu::ReLU, sigmoid::Sigmoid, softmax::Softmax, tanh::Tanh};
+use crate::nn::shape::{ActivationData, ActivationType};
+
+use utils::safer::safe_lock;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// A closure constructing a fresh instance of an activation function.
+pub type ActivationConstructor = Arc<dyn Fn() -> Box<dyn ActivationTrait + Send> + Send + Sync>;
+
+/// A registry mapping names to constructors of user defined activation functions.
+///
+/// Layers refer to a registered activation with `ActivationData::new_custom(name)`.
+#[derive(Clone, Default)]
+pub struct ActivationRegistry {
+    constructors: HashMap<String, ActivationConstructor>,
+}
+
+impl ActivationRegistry {
+    /// Creates an empty `ActivationRegistry`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the process wide registry consulted when networks are built.
+    pub fn global() -> &'static Mutex<Self> {
+        static REGISTRY: OnceLock<Mutex<ActivationRegistry>> = OnceLock::new();
+        REGISTRY.get_or_init(|| Mutex::new(Self::new()))
+    }
+
+    /// Registers a constructor under the given name, replacing a previous registration.
+    pub fn register<F>(
+        &mut self,
+        name: &str,
+        constructor: F,
+    ) where
+        F: Fn() -> Box<dyn ActivationTrait + Send> + Send + Sync + 'static,
+    {
+        self.constructors.insert(name.to_string(), Arc::new(constructor));
+    }
+
+    /// Returns whether an activation is registered under the given name.
+    #[must_use]
+    pub fn contains(
+        &self,
+        name: &str,
+    ) -> bool {
+        self.constructors.contains_key(name)
+    }
+
+    /// Creates the activation registered under the given name.
+    #[must_use]
+    pub fn create(
+        &self,
+        name: &str,
+    ) -> Option<Box<dyn ActivationTrait + Send>> {
+        self.constructors.get(name).map(|constructor| constructor())
+    }
+}
+
+/// Registers a user defined activation in the global registry.
+pub fn register_activation<F>(
+    name: &str,
+    constructor: F,
+) where
+    F: Fn() -> Box<dyn ActivationTrait + Send> + Send + Sync + 'static,
+{
+    safe_lock(ActivationRegistry::global()).register(name, constructor);
+}
+
+/// Creates the activation function described by the given activation data.
+/// Built-in activations are created directly, custom ones are looked up in the global registry.
+///
+/// # Panics
+///
+/// This function will panic if a custom activation is not registered.
+#[must_use]
+pub fn create_activation(activation: &ActivationData) -> Box<dyn ActivationTrait + Send> {
+    if let Some(internal_activation) = activation.internal_activation() {
+        let mut main_activation = activation.clone();
+        main_activation.clear_internal_activation();
+        return Box::new(InternalUnitActivation::new(
+            create_activation(&main_activation),
+            create_activation(internal_activation),
+            activation.internal_units(),
+        ));
+    }
+    match activation.activation_type() {
+        ActivationType::ReLU => Box::new(ReLU::new()),
+        ActivationType::Sigmoid => Box::new(Sigmoid),
+        ActivationType::Tanh => Box::new(Tanh),
+        ActivationType::Identity => Box::new(Identity),
+        ActivationType::Softmax => {
+            Box::new(Softmax::with_optional_temperature(activation.temperature()))
+        },
+        ActivationType::Composite => Box::new(CompositeActivation::new(
+            activation
+                .segments()
+                .iter()
+                .map(|segment| (segment.start..segment.end, create_activation(&segment.activation)))
+                .collect(),
+        )),
+        ActivationType::Custom => {
+            let name = activation.name().expect("Custom activation without a name");
+            // clone the constructor so the registry is not locked while it runs
+            let constructor =
+                safe_lock(ActivationRegistry::global()).constructors.get(name).cloned();
+            constructor.map_or_else(
+                || panic!("No activation registered under the name {name}"),
+                |constructor| constructor(),
+            )
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nn::directory::Directory;
+    use crate::nn::neuralnet::TrainableClassicNeuralNetwork;
+    use crate::nn::nn_trait::NeuralNetwork;
+    use crate::nn::shape::{LayerShape, LayerType, NeuralNetworkShape};
+    use crate::utilities::util::{Utils, WrappedUtils};
+
+    #[derive(Debug, Clone, Default)]
+    struct Square {
+        input_cache: Vec<f64>,
+    }
+
+    impl ActivationTrait for Square {
+        fn forward(
+            &mut self,
+            input: &[f64],
+        ) -> Vec<f64> {
+            self.input_cache = input.to_vec();
+            input.iter().map(|x| x * x).collect()
+        }
+
+        fn backward(
+            &mut self,
+            grad_output: &[f64],
+        ) -> Vec<f64> {
+            grad_output.iter().zip(&self.input_cache).map(|(g, x)| 2.0 * x * g).collect()
+        }
+
+        fn get_activation_data(&self) -> ActivationData {
+            ActivationData::new_custom("square")
+        }
+    }
+
+    #[test]
+    fn test_custom_activation_network() {
+        register_activation("square", || Box::new(Square::default()));
+        assert!(safe_lock(ActivationRegistry::global()).contains("square"));
+
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape {
+            layers: vec![
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 3 },
+                    activation: ActivationData::new_custom("square"),
+                },
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 2 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                },
+            ],
+        };
+        assert!(shape.is_valid());
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            shape.clone(),
+            &Directory::Internal("test_model_custom_activation".to_string()),
+            utils,
+        );
+        assert_eq!(nn.shape(), shape);
+
+        let activations = nn.forward_with_activations(&[1.0, -0.5, 0.25]);
+        assert_eq!(activations.len(), 2);
+        assert!(activations[0].iter().all(|value| *value >= 0.0));
+        assert_eq!(nn.predict(vec![1.0, -0.5, 0.25]).len(), 2);
+    }
+}
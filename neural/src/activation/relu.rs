@@ -35,6 +35,15 @@ impl ActivationTrait for ReLU {
         input.iter().map(|&x| if x > 0.0 { x } else { 0.0 }).collect()
     }
 
+    fn forward_in_place(
+        &mut self,
+        values: &mut [f64],
+    ) {
+        for x in values {
+            *x = x.max(0.0);
+        }
+    }
+
     fn backward(
         &mut self,
         grad_output: &[f64],
@@ -33,6 +33,15 @@ impl ActivationTrait for Sigmoid {
         Self::sigmoid_vec(input)
     }
 
+    fn forward_in_place(
+        &mut self,
+        values: &mut [f64],
+    ) {
+        for x in values {
+            *x = 1.0 / (1.0 + (-*x).exp());
+        }
+    }
+
     fn backward(
         &mut self,
         grad_output: &[f64],
@@ -1,6 +1,13 @@
 use super::activate::ActivationTrait;
 use crate::nn::shape::ActivationData;
 
+/// The smallest temperature a softmax is created with; lower temperatures are clamped to it
+/// because dividing the logits by a near-zero temperature overflows.
+pub const MIN_TEMPERATURE: f64 = 1e-3;
+
+/// The temperature of a softmax whose temperature is not set.
+pub const DEFAULT_TEMPERATURE: f64 = 1.0;
+
 /// Softmax activation function.
 #[derive(Debug, Clone)]
 pub struct Softmax {
@@ -10,21 +17,27 @@ pub struct Softmax {
 
 impl Softmax {
     /// Creates a new Softmax instance with the specified temperature.
-    ///
-    /// # Panics
-    ///
-    /// Panics if `temperature` is not positive.
+    /// Temperatures below `MIN_TEMPERATURE` (or NaN) are clamped to `MIN_TEMPERATURE`.
     #[must_use]
     pub fn new(temperature: f64) -> Self {
-        assert!(temperature > 0.0, "Temperature must be positive.");
+        let temperature =
+            if temperature >= MIN_TEMPERATURE { temperature } else { MIN_TEMPERATURE };
         Self { temperature, cached_output: None }
     }
 
+    /// Creates a new Softmax instance with the given temperature,
+    /// or with `DEFAULT_TEMPERATURE` if it is not set.
+    #[must_use]
+    pub fn with_optional_temperature(temperature: Option<f64>) -> Self {
+        Self::new(temperature.unwrap_or(DEFAULT_TEMPERATURE))
+    }
+
     /// Applies the softmax function to a vector of inputs.
     fn softmax(
         &self,
         input: &[f64],
     ) -> Vec<f64> {
+        // subtract the maximum logit so that the exponentials cannot overflow for large logits
         let max_input = input.iter().copied().fold(f64::NEG_INFINITY, f64::max);
         let exp_values: Vec<f64> =
             input.iter().map(|&x| ((x - max_input) / self.temperature).exp()).collect();
@@ -43,6 +56,20 @@ impl ActivationTrait for Softmax {
         output
     }
 
+    fn forward_in_place(
+        &mut self,
+        values: &mut [f64],
+    ) {
+        let max_value = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        for x in values.iter_mut() {
+            *x = ((*x - max_value) / self.temperature).exp();
+        }
+        let sum_exp = values.iter().sum::<f64>();
+        for x in values {
+            *x /= sum_exp;
+        }
+    }
+
     fn backward(
         &mut self,
         grad_output: &[f64],
@@ -102,6 +129,52 @@ mod tests {
         assert!(output.iter().all(|&v| v > 0.0), "Softmax probabilities must be positive.");
     }
 
+    #[test]
+    fn test_softmax_forward_in_place() {
+        let mut softmax = Softmax::new(0.5);
+        let mut values = vec![1.0, 2.0, 3.0];
+        let output = softmax.forward(&values);
+        softmax.forward_in_place(&mut values);
+        assert_eq!(values, output);
+    }
+
+    #[test]
+    fn test_softmax_large_logits() {
+        for temperature in [1.0, 0.1] {
+            let mut softmax = Softmax::new(temperature);
+            let output = softmax.forward(&[1000.0, 1001.0]);
+
+            println!("Softmax output for large logits: {output:?}");
+
+            assert!(output.iter().all(|v| v.is_finite()), "Softmax must not overflow.");
+            let sum: f64 = output.iter().sum();
+            assert!((sum - 1.0).abs() < 1e-7, "Softmax outputs should sum to 1.");
+            assert!(output.iter().all(|&v| v >= 0.0), "Softmax probabilities must be positive.");
+            assert!(output[1] > output[0], "The larger logit must have the larger probability.");
+        }
+    }
+
+    #[test]
+    fn test_softmax_clamps_temperature() {
+        let softmaxes = [
+            Softmax::with_optional_temperature(None),
+            Softmax::new(0.0),
+            Softmax::new(-1.0),
+            Softmax::new(f64::NAN),
+            Softmax::new(1e-300),
+        ];
+        assert!((softmaxes[0].temperature - DEFAULT_TEMPERATURE).abs() < f64::EPSILON);
+        for mut softmax in softmaxes {
+            assert!(softmax.temperature >= MIN_TEMPERATURE);
+            let output = softmax.forward(&[1.0, 2.0, 3.0]);
+
+            assert!(output.iter().all(|v| v.is_finite()), "Softmax must not overflow.");
+            let sum: f64 = output.iter().sum();
+            assert!((sum - 1.0).abs() < 1e-7, "Softmax outputs should sum to 1.");
+            assert!(output.iter().all(|&v| v >= 0.0), "Softmax probabilities must be positive.");
+        }
+    }
+
     #[test]
     fn test_softmax_backward() {
         let mut softmax = Softmax::new(1.0);
@@ -33,6 +33,15 @@ impl ActivationTrait for Tanh {
         Self::tanh_vec(input)
     }
 
+    fn forward_in_place(
+        &mut self,
+        values: &mut [f64],
+    ) {
+        for x in values {
+            *x = x.tanh();
+        }
+    }
+
     fn backward(
         &mut self,
         grad_output: &[f64],
@@ -0,0 +1,89 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+use rand_distr::{Distribution, Normal};
+
+/// Adds zero-mean Gaussian noise to every feature of the training inputs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoiseAugmenter {
+    stddev: f64,
+    seed: u64,
+}
+
+impl NoiseAugmenter {
+    /// Creates a new `NoiseAugmenter` with the given standard deviation of the noise.
+    /// The seed is used for the random number generator returned by `rng`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the standard deviation is negative or not finite.
+    #[must_use]
+    pub fn new(
+        stddev: f64,
+        seed: u64,
+    ) -> Self {
+        assert!(stddev.is_finite() && stddev >= 0.0, "stddev must be a non negative number");
+        Self { stddev, seed }
+    }
+
+    #[must_use]
+    pub const fn stddev(&self) -> f64 {
+        self.stddev
+    }
+
+    #[must_use]
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns a random number generator seeded with the seed of the augmenter.
+    #[must_use]
+    pub fn rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.seed)
+    }
+
+    /// Returns a copy of the inputs with Gaussian noise added to each feature.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the normal distribution cannot be created.
+    pub fn augment<R: Rng>(
+        &self,
+        inputs: &[Vec<f64>],
+        rng: &mut R,
+    ) -> Vec<Vec<f64>> {
+        let normal = Normal::new(0.0, self.stddev).expect("Failed to create normal distribution");
+        inputs.iter().map(|input| input.iter().map(|x| x + normal.sample(rng)).collect()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_traits::NumCast;
+
+    #[test]
+    fn test_noise_augmenter() {
+        let noise = NoiseAugmenter::new(0.5, 42);
+        let inputs = vec![vec![1.0, -2.0, 0.5, 3.0]; 2500];
+
+        let augmented = noise.augment(&inputs, &mut noise.rng());
+        assert_eq!(augmented.len(), inputs.len());
+        assert_ne!(augmented, inputs);
+
+        let differences = augmented
+            .iter()
+            .zip(&inputs)
+            .flat_map(|(a, i)| a.iter().zip(i).map(|(x, y)| x - y).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let n: f64 = NumCast::from(differences.len()).unwrap();
+        let mean = differences.iter().sum::<f64>() / n;
+        let variance = differences.iter().map(|d| (d - mean) * (d - mean)).sum::<f64>() / n;
+        assert!(mean.abs() < 0.05);
+        assert!((variance - 0.25).abs() < 0.02);
+
+        // the same seed yields the same noise
+        let augmented_again = noise.augment(&inputs, &mut noise.rng());
+        assert_eq!(augmented, augmented_again);
+    }
+}
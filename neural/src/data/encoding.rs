@@ -0,0 +1,51 @@
+use crate::data::split::class_of;
+
+/// One-hot encodes the class labels into targets of `num_classes` values.
+///
+/// # Errors
+///
+/// Returns an error if a label is not smaller than `num_classes`.
+pub fn one_hot(
+    labels: &[usize],
+    num_classes: usize,
+) -> Result<Vec<Vec<f64>>, Box<dyn std::error::Error>> {
+    labels
+        .iter()
+        .map(|&label| {
+            if label >= num_classes {
+                return Err(
+                    format!("Label {label} is out of range for {num_classes} classes").into()
+                );
+            }
+            let mut target = vec![0.0; num_classes];
+            target[label] = 1.0;
+            Ok(target)
+        })
+        .collect()
+}
+
+/// Decodes one-hot encoded targets into class labels.
+/// The label of a target is the position of its largest value.
+#[must_use]
+pub fn from_one_hot(targets: &[Vec<f64>]) -> Vec<usize> {
+    targets.iter().map(|target| class_of(target)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_hot_round_trip() {
+        let labels = vec![2, 0, 1, 2];
+        let targets = one_hot(&labels, 3).unwrap();
+        assert_eq!(targets[0], vec![0.0, 0.0, 1.0]);
+        assert_eq!(targets[1], vec![1.0, 0.0, 0.0]);
+        assert_eq!(from_one_hot(&targets), labels);
+    }
+
+    #[test]
+    fn test_one_hot_label_out_of_range() {
+        assert!(one_hot(&[0, 3], 3).is_err());
+    }
+}
@@ -0,0 +1,252 @@
+use num_traits::NumCast;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+/// A batch of inputs with their targets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Batch {
+    pub inputs: Vec<Vec<f64>>,
+    pub targets: Vec<Vec<f64>>,
+}
+
+impl Batch {
+    /// Returns the number of samples in the batch.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+}
+
+/// Divides inputs and targets into batches for every epoch of batch training.
+///
+/// Without shuffling the batches follow the order of the samples. With shuffling the samples
+/// are shuffled with a random number generator seeded with the seed and the epoch, so the same
+/// seed yields the same batches in every run.
+#[derive(Debug, Clone, Copy)]
+pub struct DataLoader<'a> {
+    inputs: &'a [Vec<f64>],
+    targets: &'a [Vec<f64>],
+    batch_size: usize,
+    shuffle_seed: Option<u64>,
+    drop_last: bool,
+}
+
+impl<'a> DataLoader<'a> {
+    /// Creates a new `DataLoader` yielding batches of `batch_size` samples in order.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `batch_size` is zero or if the number of inputs and targets differ.
+    #[must_use]
+    pub fn new(
+        inputs: &'a [Vec<f64>],
+        targets: &'a [Vec<f64>],
+        batch_size: usize,
+    ) -> Self {
+        assert!(batch_size > 0, "batch_size must be positive");
+        assert_eq!(inputs.len(), targets.len(), "inputs and targets must have the same length");
+        Self { inputs, targets, batch_size, shuffle_seed: None, drop_last: false }
+    }
+
+    /// Shuffles the samples in every epoch with the given seed.
+    #[must_use]
+    pub const fn with_shuffle(
+        mut self,
+        seed: u64,
+    ) -> Self {
+        self.shuffle_seed = Some(seed);
+        self
+    }
+
+    /// Drops the last batch of every epoch if it has fewer than `batch_size` samples.
+    #[must_use]
+    pub const fn with_drop_last(
+        mut self,
+        drop_last: bool,
+    ) -> Self {
+        self.drop_last = drop_last;
+        self
+    }
+
+    #[must_use]
+    pub const fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    #[must_use]
+    pub const fn shuffle_seed(&self) -> Option<u64> {
+        self.shuffle_seed
+    }
+
+    #[must_use]
+    pub const fn drop_last(&self) -> bool {
+        self.drop_last
+    }
+
+    #[must_use]
+    pub const fn targets(&self) -> &'a [Vec<f64>] {
+        self.targets
+    }
+
+    /// Returns a loader yielding the same batches with the given targets instead,
+    /// e.g. with the part of every target a sub network is trained on.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the number of targets differs from the number of inputs.
+    #[must_use]
+    pub fn with_targets<'b>(
+        &self,
+        targets: &'b [Vec<f64>],
+    ) -> DataLoader<'b>
+    where
+        'a: 'b,
+    {
+        assert_eq!(
+            self.inputs.len(),
+            targets.len(),
+            "inputs and targets must have the same length"
+        );
+        DataLoader { targets, ..*self }
+    }
+
+    /// Returns the number of samples of the loader.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// Returns the number of batches yielded in every epoch.
+    #[must_use]
+    pub const fn num_batches(&self) -> usize {
+        if self.drop_last {
+            self.len() / self.batch_size
+        } else {
+            (self.len() + self.batch_size - 1) / self.batch_size
+        }
+    }
+
+    /// Returns the number of samples yielded in every epoch.
+    #[must_use]
+    pub const fn num_samples_per_epoch(&self) -> usize {
+        if self.drop_last {
+            self.num_batches() * self.batch_size
+        } else {
+            self.len()
+        }
+    }
+
+    /// Returns an iterator over the batches of the given epoch.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the epoch cannot be converted to `u64`.
+    #[must_use]
+    pub fn batches(
+        &self,
+        epoch: usize,
+    ) -> Batches<'a> {
+        let mut indices = (0..self.len()).collect::<Vec<_>>();
+        if let Some(seed) = self.shuffle_seed {
+            let epoch: u64 = NumCast::from(epoch).expect("Failed to convert epoch to u64");
+            indices.shuffle(&mut StdRng::seed_from_u64(seed.wrapping_add(epoch)));
+        }
+        indices.truncate(self.num_samples_per_epoch());
+        Batches {
+            inputs: self.inputs,
+            targets: self.targets,
+            indices,
+            batch_size: self.batch_size,
+            position: 0,
+        }
+    }
+}
+
+/// Iterator over the batches of one epoch of a `DataLoader`.
+#[derive(Debug, Clone)]
+pub struct Batches<'a> {
+    inputs: &'a [Vec<f64>],
+    targets: &'a [Vec<f64>],
+    indices: Vec<usize>,
+    batch_size: usize,
+    position: usize,
+}
+
+impl Iterator for Batches<'_> {
+    type Item = Batch;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.indices.len() {
+            return None;
+        }
+        let end = (self.position + self.batch_size).min(self.indices.len());
+        let indices = &self.indices[self.position..end];
+        self.position = end;
+        Some(Batch {
+            inputs: indices.iter().map(|&i| self.inputs[i].clone()).collect(),
+            targets: indices.iter().map(|&i| self.targets[i].clone()).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(n: usize) -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+        let inputs = (0..n)
+            .map(|i| vec![NumCast::from(i).expect("Failed to convert index to f64")])
+            .collect::<Vec<Vec<f64>>>();
+        let targets = inputs.iter().map(|input| vec![input[0] * 2.0]).collect();
+        (inputs, targets)
+    }
+
+    #[test]
+    fn test_batch_counts() {
+        let (inputs, targets) = samples(10);
+        let loader = DataLoader::new(&inputs, &targets, 3);
+        let batches = loader.batches(0).collect::<Vec<_>>();
+        assert_eq!(loader.num_batches(), 4);
+        assert_eq!(batches.iter().map(Batch::len).collect::<Vec<_>>(), vec![3, 3, 3, 1]);
+        assert_eq!(batches[0].inputs, vec![vec![0.0], vec![1.0], vec![2.0]]);
+        assert_eq!(batches[3].targets, vec![vec![18.0]]);
+
+        let loader = loader.with_drop_last(true);
+        let batches = loader.batches(0).collect::<Vec<_>>();
+        assert_eq!(loader.num_batches(), 3);
+        assert_eq!(batches.iter().map(Batch::len).collect::<Vec<_>>(), vec![3, 3, 3]);
+    }
+
+    #[test]
+    fn test_shuffle_is_deterministic() {
+        let (inputs, targets) = samples(20);
+        let loader = DataLoader::new(&inputs, &targets, 4).with_shuffle(7);
+        let order = |loader: &DataLoader, epoch| {
+            loader.batches(epoch).flat_map(|batch| batch.inputs).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            order(&loader, 0),
+            order(&DataLoader::new(&inputs, &targets, 4).with_shuffle(7), 0)
+        );
+        assert_ne!(order(&loader, 0), order(&loader, 1));
+        assert_ne!(order(&loader, 0), inputs);
+
+        // inputs and targets stay paired
+        for batch in loader.batches(3) {
+            for (input, target) in batch.inputs.iter().zip(&batch.targets) {
+                assert!(input[0].mul_add(2.0, -target[0]).abs() < f64::EPSILON);
+            }
+        }
+    }
+}
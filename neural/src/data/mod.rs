@@ -0,0 +1,5 @@
+pub mod augment;
+pub mod encoding;
+pub mod loader;
+pub mod scaler;
+pub mod split;
@@ -0,0 +1,89 @@
+use num_traits::NumCast;
+use serde::{Deserialize, Serialize};
+
+/// Standardizes every feature to zero mean and unit variance.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scaler {
+    means: Vec<f64>,
+    stddevs: Vec<f64>,
+}
+
+impl Scaler {
+    /// Fits a `Scaler` to the mean and standard deviation of every feature of the inputs.
+    /// Features without variance are only shifted.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if there are no inputs.
+    #[must_use]
+    pub fn fit(inputs: &[Vec<f64>]) -> Self {
+        assert!(!inputs.is_empty(), "Cannot fit a scaler without inputs");
+        let num_features = inputs[0].len();
+        let n: f64 = NumCast::from(inputs.len()).expect("Failed to convert inputs.len() to f64");
+        let means = (0..num_features)
+            .map(|j| inputs.iter().map(|input| input[j]).sum::<f64>() / n)
+            .collect::<Vec<_>>();
+        let stddevs = means
+            .iter()
+            .enumerate()
+            .map(|(j, mean)| {
+                let variance =
+                    inputs.iter().map(|input| (input[j] - mean).powi(2)).sum::<f64>() / n;
+                if variance > 0.0 {
+                    variance.sqrt()
+                } else {
+                    1.0
+                }
+            })
+            .collect();
+        Self { means, stddevs }
+    }
+
+    #[must_use]
+    pub fn means(&self) -> &[f64] {
+        &self.means
+    }
+
+    #[must_use]
+    pub fn stddevs(&self) -> &[f64] {
+        &self.stddevs
+    }
+
+    /// Returns the standardized input.
+    #[must_use]
+    pub fn transform(
+        &self,
+        input: &[f64],
+    ) -> Vec<f64> {
+        input
+            .iter()
+            .zip(self.means.iter().zip(&self.stddevs))
+            .map(|(x, (mean, stddev))| (x - mean) / stddev)
+            .collect()
+    }
+
+    /// Returns all standardized inputs.
+    #[must_use]
+    pub fn transform_all(
+        &self,
+        inputs: &[Vec<f64>],
+    ) -> Vec<Vec<f64>> {
+        inputs.iter().map(|input| self.transform(input)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scaler() {
+        let inputs = vec![vec![1.0, 5.0], vec![3.0, 5.0], vec![5.0, 5.0]];
+        let scaler = Scaler::fit(&inputs);
+        assert_eq!(scaler.means(), &[3.0, 5.0]);
+        let standardized = scaler.transform_all(&inputs);
+        assert!((standardized[0][0] + standardized[2][0]).abs() < 1e-12);
+        assert!((standardized[2][0] - 1.5_f64.sqrt()).abs() < 1e-12);
+        assert!(standardized.iter().all(|input| input[1] == 0.0));
+    }
+}
@@ -0,0 +1,211 @@
+use num_traits::NumCast;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+/// How the samples are divided into a training and a validation subset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SplitMode {
+    /// The leading samples are used for training, the trailing ones for validation.
+    Positional,
+    /// The samples are shuffled before they are split.
+    #[default]
+    Random,
+    /// Each class is split on its own so both subsets keep the class proportions.
+    /// The class of a sample is the position of the largest value of its one-hot target.
+    Stratified,
+}
+
+impl SplitMode {
+    /// Splits the indices of the targets into training and validation indices,
+    /// putting `train_fraction` of the samples into the training subset.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `train_fraction` is not within `[0, 1]`.
+    pub fn split_indices<R: Rng>(
+        &self,
+        targets: &[Vec<f64>],
+        train_fraction: f64,
+        rng: &mut R,
+    ) -> (Vec<usize>, Vec<usize>) {
+        match self {
+            Self::Positional => {
+                let split_index = train_size(targets.len(), train_fraction);
+                ((0..split_index).collect(), (split_index..targets.len()).collect())
+            },
+            Self::Random => random_split(targets.len(), train_fraction, rng),
+            Self::Stratified => StratifiedSplit::new(train_fraction).split_indices(targets, rng),
+        }
+    }
+}
+
+/// Returns shuffled training and validation indices of `n` samples, putting `train_fraction`
+/// of them into the training subset.
+fn random_split<R: Rng>(
+    n: usize,
+    train_fraction: f64,
+    rng: &mut R,
+) -> (Vec<usize>, Vec<usize>) {
+    let split_index = train_size(n, train_fraction);
+    let mut indices = (0..n).collect::<Vec<_>>();
+    indices.shuffle(rng);
+    let validation_indices = indices.split_off(split_index);
+    (indices, validation_indices)
+}
+
+/// Returns the training and validation indices of `n` samples shuffled with the given seed.
+///
+/// The same split can be applied to parallel arrays such as inputs, targets and sample ids.
+/// Like the `validation_split` of `train`, `validation_split` is the fraction of the samples
+/// put into the training subset.
+///
+/// # Panics
+///
+/// This function will panic if `validation_split` is not within `[0, 1]`.
+#[must_use]
+pub fn train_val_indices(
+    n: usize,
+    validation_split: f64,
+    seed: u64,
+) -> (Vec<usize>, Vec<usize>) {
+    random_split(n, validation_split, &mut StdRng::seed_from_u64(seed))
+}
+
+/// Splits one-hot encoded samples while preserving the class proportions in both subsets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StratifiedSplit {
+    train_fraction: f64,
+}
+
+impl StratifiedSplit {
+    /// Creates a new `StratifiedSplit` putting `train_fraction` of every class into training.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `train_fraction` is not within `[0, 1]`.
+    #[must_use]
+    pub fn new(train_fraction: f64) -> Self {
+        assert!((0.0..=1.0).contains(&train_fraction), "train_fraction must be between 0 and 1");
+        Self { train_fraction }
+    }
+
+    #[must_use]
+    pub const fn train_fraction(&self) -> f64 {
+        self.train_fraction
+    }
+
+    /// Returns the shuffled training and validation indices of the targets.
+    pub fn split_indices<R: Rng>(
+        &self,
+        targets: &[Vec<f64>],
+        rng: &mut R,
+    ) -> (Vec<usize>, Vec<usize>) {
+        let num_classes = targets.iter().map(Vec::len).max().unwrap_or(0);
+        let mut classes = vec![Vec::new(); num_classes.max(1)];
+        for (i, target) in targets.iter().enumerate() {
+            classes[class_of(target)].push(i);
+        }
+
+        let mut train_indices = Vec::new();
+        let mut validation_indices = Vec::new();
+        for mut class_indices in classes {
+            class_indices.shuffle(rng);
+            let split_index = train_size(class_indices.len(), self.train_fraction);
+            validation_indices.extend_from_slice(&class_indices[split_index..]);
+            train_indices.extend_from_slice(&class_indices[..split_index]);
+        }
+        train_indices.shuffle(rng);
+        validation_indices.shuffle(rng);
+        (train_indices, validation_indices)
+    }
+}
+
+/// Returns the class of a one-hot target, the position of its largest value.
+/// Ties are broken by the lower class, so equal outputs always yield the same class.
+#[must_use]
+pub fn class_of(target: &[f64]) -> usize {
+    target
+        .iter()
+        .enumerate()
+        .fold(
+            (0, f64::NEG_INFINITY),
+            |best, (class, &value)| {
+                if value > best.1 {
+                    (class, value)
+                } else {
+                    best
+                }
+            },
+        )
+        .0
+}
+
+/// Returns the number of training samples out of `len` for the given fraction.
+fn train_size(
+    len: usize,
+    train_fraction: f64,
+) -> usize {
+    assert!((0.0..=1.0).contains(&train_fraction), "train_fraction must be between 0 and 1");
+    let len_f64: f64 = NumCast::from(len).expect("Failed to convert len to f64");
+    NumCast::from((len_f64 * train_fraction).round())
+        .expect("Failed to convert split index to usize")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stratified_split_keeps_class_proportions() {
+        // 80 samples of class 0, 15 of class 1 and 5 of class 2
+        let targets = (0..100)
+            .map(|i| match i {
+                0..80 => vec![1.0, 0.0, 0.0],
+                80..95 => vec![0.0, 1.0, 0.0],
+                _ => vec![0.0, 0.0, 1.0],
+            })
+            .collect::<Vec<_>>();
+        let mut rng = StdRng::seed_from_u64(42);
+        let (train, validation) = StratifiedSplit::new(0.8).split_indices(&targets, &mut rng);
+        assert_eq!(train.len() + validation.len(), targets.len());
+
+        let count = |indices: &[usize], class: usize| {
+            indices.iter().filter(|i| class_of(&targets[**i]) == class).count()
+        };
+        assert_eq!([count(&train, 0), count(&train, 1), count(&train, 2)], [64, 12, 4]);
+        assert_eq!(
+            [count(&validation, 0), count(&validation, 1), count(&validation, 2)],
+            [16, 3, 1]
+        );
+
+        // the positional split puts the whole minority classes into validation
+        let (train, _) = SplitMode::Positional.split_indices(&targets, 0.8, &mut rng);
+        assert_eq!(count(&train, 2), 0);
+    }
+
+    #[test]
+    fn test_train_val_indices_are_reproducible() {
+        let (train, validation) = train_val_indices(50, 0.8, 7);
+        assert_eq!((train.len(), validation.len()), (40, 10));
+        assert_eq!(train_val_indices(50, 0.8, 7), (train.clone(), validation.clone()));
+        assert_ne!(train_val_indices(50, 0.8, 8).0, train);
+
+        // the partitions are disjoint and cover every index
+        let mut all = train.iter().chain(&validation).copied().collect::<Vec<_>>();
+        all.sort_unstable();
+        assert_eq!(all, (0..50).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_class_of_breaks_ties_by_lowest_index() {
+        for _ in 0..100 {
+            assert_eq!(class_of(&[0.2, 0.4, 0.1, 0.4, 0.4]), 1);
+            assert_eq!(class_of(&[0.5, 0.5]), 0);
+        }
+        assert_eq!(class_of(&[-1.0, -1.0, -2.0]), 0);
+        assert_eq!(class_of(&[]), 0);
+    }
+}
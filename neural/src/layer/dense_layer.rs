@@ -1,6 +1,11 @@
+use super::init::BiasInit;
+use super::init::FillStrategy;
+use super::init::InitScheme;
 use super::layer_trait::Layer;
 use super::layer_trait::TrainableLayer;
 use super::layer_trait::WrappedTrainableLayer;
+use super::layer_trait::{LayerGradients, LayerMoments};
+use super::quantized::QuantizedDenseLayer;
 use super::AllocatableLayer;
 use super::TrainableAllocatableLayer;
 use crate::nn::directory::Directory;
@@ -10,17 +15,24 @@ use alloc::allocatable::Allocatable;
 pub use matrix::mat::Matrix;
 use matrix::mat::WrappedMatrix;
 
-use rayon::iter::IntoParallelIterator;
 use rayon::iter::ParallelIterator;
+use rayon::slice::ParallelSlice;
 
 use num_traits::cast::NumCast;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
 
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use fs2::FileExt;
-use rand::Rng;
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
+use std::io::BufWriter;
 use std::io::Write;
 use std::path::Path;
 
@@ -43,12 +55,11 @@ impl DenseLayer {
         position_in_nn: usize,
     ) -> Self {
         // create a Directory type which has the path model_directory/layers/layer_{position_in_nn}.txt
+        // or its gzip-compressed counterpart
         let layer_path = match model_directory {
-            Directory::User(path) => {
-                Directory::User(format!("{path}/layers/layer_{position_in_nn}.txt"))
-            },
+            Directory::User(path) => Directory::User(layer_file_path(&path, position_in_nn)),
             Directory::Internal(path) => {
-                Directory::Internal(format!("{path}/layers/layer_{position_in_nn}.txt"))
+                Directory::Internal(layer_file_path(&path, position_in_nn))
             },
         };
         Self {
@@ -60,6 +71,79 @@ impl DenseLayer {
             layer_path,
         }
     }
+
+    /// Returns the largest singular value of the weight matrix estimated with
+    /// `iterations` steps of power iteration.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the layer is not allocated.
+    #[must_use]
+    pub fn spectral_norm(
+        &self,
+        iterations: usize,
+    ) -> f64 {
+        assert!(self.is_allocated(), "Layer not allocated");
+        spectral_norm(self.weights.as_ref().unwrap(), iterations)
+    }
+
+    /// Returns a copy of the layer with its weights quantized to int8.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the layer is not allocated.
+    #[must_use]
+    pub fn quantize_int8(&self) -> QuantizedDenseLayer {
+        assert!(self.is_allocated(), "Layer not allocated");
+        QuantizedDenseLayer::new(self.weights.as_ref().unwrap(), self.biases.as_ref().unwrap())
+    }
+}
+
+/// Seed of the start vector of the power iteration, fixed so that the estimate is deterministic.
+const SPECTRAL_NORM_SEED: u64 = 0x5eed;
+
+/// How many inputs the batch cache of a trainable dense layer holds at most by default.
+pub const DEFAULT_BATCH_CACHE_LIMIT: usize = 1024;
+
+/// Returns the largest singular value of the matrix estimated with `iterations` steps of
+/// power iteration on `W^T W`, starting from a random vector. At least one step is made.
+#[must_use]
+pub fn spectral_norm(
+    weights: &WrappedMatrix<f64>,
+    iterations: usize,
+) -> f64 {
+    let (rows, cols) = (weights.rows(), weights.cols());
+    let mut rng = StdRng::seed_from_u64(SPECTRAL_NORM_SEED);
+    let mut v: Vec<f64> = (0..cols).map(|_| rng.gen_range(-1.0..1.0)).collect();
+    let mut sigma = 0.0;
+    for _ in 0..iterations.max(1) {
+        let mut u = (0..rows)
+            .map(|i| (0..cols).map(|j| weights.get_unchecked(i, j) * v[j]).sum::<f64>())
+            .collect::<Vec<_>>();
+        if normalize(&mut u) == 0.0 {
+            return 0.0;
+        }
+        v = (0..cols)
+            .map(|j| (0..rows).map(|i| weights.get_unchecked(i, j) * u[i]).sum::<f64>())
+            .collect();
+        // |W^T u| for the unit vector u converges to the largest singular value
+        sigma = normalize(&mut v);
+        if sigma == 0.0 {
+            return 0.0;
+        }
+    }
+    sigma
+}
+
+/// Scales the vector to unit length unless it is zero and returns its previous length.
+fn normalize(vector: &mut [f64]) -> f64 {
+    let norm = vector.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm > 0.0 {
+        for x in vector.iter_mut() {
+            *x /= norm;
+        }
+    }
+    norm
 }
 
 impl Drop for DenseLayer {
@@ -179,6 +263,23 @@ impl Layer for DenseLayer {
         })
     }
 
+    fn forward_into(
+        &mut self,
+        input: &[f64],
+        out: &mut Vec<f64>,
+        _utils: WrappedUtils,
+    ) {
+        assert!(self.is_allocated(), "Layer not allocated");
+        let weights = self.weights.as_ref().unwrap().mat();
+        let weights = weights.lock().unwrap();
+        out.clear();
+        out.extend((&*weights).into_iter().zip(self.biases.as_ref().unwrap()).map(
+            |(weights_row, bias)| {
+                weights_row.iter().zip(input).map(|(&w, &x)| w * x).sum::<f64>() + bias
+            },
+        ));
+    }
+
     fn forward_batch(
         &mut self,
         _input: &[f64],
@@ -279,15 +380,15 @@ impl AllocatableLayer for DenseLayer {
 }
 
 #[derive(Default, Debug, Clone, Copy)]
-struct Weight {
-    value: f64,
-    grad: f64,
-    m: f64,
-    v: f64,
+pub(crate) struct Weight {
+    pub(crate) value: f64,
+    pub(crate) grad: f64,
+    pub(crate) m: f64,
+    pub(crate) v: f64,
 }
 
 #[derive(Default, Debug, Clone, Copy)]
-struct Bias {
+pub(crate) struct Bias {
     value: f64,
     grad: f64,
     m: f64,
@@ -302,9 +403,47 @@ pub struct TrainableDenseLayer {
     weights: Option<WrappedMatrix<Weight>>, // Weight matrix (output_size x input_size)
     biases: Option<Vec<Bias>>,              // Bias vector (output_size)
     input_cache: Option<Vec<f64>>,          // Cache input for use in backward pass
-    input_batch_cache: Option<Vec<Vec<f64>>>, // Cache batch input for use in backward pass
+    input_batch_cache: Option<VecDeque<Vec<f64>>>, // Cache batch input for use in backward pass
+    batch_cache_limit: usize,
+    // number of inputs dropped from the full batch cache whose backward passes are still due
+    evicted_batch_inputs: usize,
     in_use: bool,
     layer_path: Directory,
+    bias_init: BiasInit,
+    init_scheme: InitScheme,
+    spectral_norm: Option<SpectralNormState>,
+    // column-major copy of the weight values read by the backward pass, `None` once the weights
+    // changed until the next backward pass rebuilds it
+    column_major_weights: Option<Vec<f64>>,
+}
+
+/// The state of the spectral normalization of a dense layer, kept across steps so that
+/// a single power iteration step per forward pass suffices.
+#[derive(Debug, Clone)]
+struct SpectralNormState {
+    // estimate of the left singular vector of the largest singular value
+    u: Vec<f64>,
+    // estimate of the largest singular value the weights are divided by
+    sigma: f64,
+}
+
+impl SpectralNormState {
+    fn new(rows: usize) -> Self {
+        let mut rng = StdRng::seed_from_u64(SPECTRAL_NORM_SEED);
+        let mut u: Vec<f64> = (0..rows).map(|_| rng.gen_range(-1.0..1.0)).collect();
+        normalize(&mut u);
+        Self { u, sigma: 1.0 }
+    }
+
+    /// Returns the factor the weighted sums are scaled with.
+    fn scale(&self) -> f64 {
+        // a zero matrix stays zero, there is nothing to normalize
+        if self.sigma > f64::EPSILON {
+            1.0 / self.sigma
+        } else {
+            1.0
+        }
+    }
 }
 
 impl TrainableDenseLayer {
@@ -317,12 +456,11 @@ impl TrainableDenseLayer {
         position_in_nn: usize,
     ) -> Self {
         // create a Directory type which has the path model_directory/layers/layer_{position_in_nn}.txt
+        // or its gzip-compressed counterpart
         let layer_path = match model_directory {
-            Directory::User(path) => {
-                Directory::User(format!("{path}/layers/layer_{position_in_nn}.txt"))
-            },
+            Directory::User(path) => Directory::User(layer_file_path(&path, position_in_nn)),
             Directory::Internal(path) => {
-                Directory::Internal(format!("{path}/layers/layer_{position_in_nn}.txt"))
+                Directory::Internal(layer_file_path(&path, position_in_nn))
             },
         };
         Self {
@@ -332,22 +470,116 @@ impl TrainableDenseLayer {
             biases: None,
             input_cache: None,
             input_batch_cache: None,
+            batch_cache_limit: DEFAULT_BATCH_CACHE_LIMIT,
+            evicted_batch_inputs: 0,
             in_use: false,
             layer_path,
+            bias_init: BiasInit::default(),
+            init_scheme: InitScheme::default(),
+            spectral_norm: None,
+            column_major_weights: None,
         }
     }
 
-    /// Initialize the weights with random values in the range [-0.5, 0.5]
-    fn initialize_weights(&self) {
-        let mut rng = rand::thread_rng();
-        // initialize weights from -0.5 to 0.5
+    /// Returns the largest singular value of the weight matrix estimated with
+    /// `iterations` steps of power iteration.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the layer is not allocated.
+    #[must_use]
+    pub fn spectral_norm(
+        &self,
+        iterations: usize,
+    ) -> f64 {
+        assert!(self.is_allocated(), "Layer not allocated");
+        spectral_norm(&self.get_weights(), iterations)
+    }
+
+    /// Makes one power iteration step from the cached singular vector estimate and updates
+    /// the estimate of the largest singular value. Returns the factor the weighted sums are
+    /// scaled with, which is one if spectral normalization is disabled.
+    fn spectral_norm_step(&mut self) -> f64 {
+        let Some(state) = self.spectral_norm.as_mut() else {
+            return 1.0;
+        };
+        let weights = self.weights.as_ref().unwrap().mat();
+        let weights = weights.lock().unwrap();
+        let mut v = vec![0.0; self.cols];
+        for (row, u) in weights.iter().zip(&state.u) {
+            for (v, weight) in v.iter_mut().zip(row) {
+                *v = weight.value.mul_add(*u, *v);
+            }
+        }
+        normalize(&mut v);
+        state.u = weights
+            .iter()
+            .map(|row| row.iter().zip(&v).map(|(weight, v)| weight.value * v).sum::<f64>())
+            .collect();
+        drop(weights);
+        state.sigma = normalize(&mut state.u);
+        state.scale()
+    }
+
+    /// Returns the weight values in column-major order.
+    fn build_column_major_weights(&self) -> Vec<f64> {
+        let (rows, cols) = (self.rows, self.cols);
+        let mut column_major = vec![0.0; rows * cols];
+        for (i, row) in self.weights.as_ref().unwrap().mat().lock().unwrap().iter().enumerate() {
+            for (j, weight) in row.iter().enumerate() {
+                column_major[j * rows + i] = weight.value;
+            }
+        }
+        column_major
+    }
+
+    /// Sets how the biases are initialized when the layer is allocated without a file on disk.
+    #[must_use]
+    pub const fn with_bias_init(
+        mut self,
+        bias_init: BiasInit,
+    ) -> Self {
+        self.bias_init = bias_init;
+        self
+    }
+
+    /// Sets how the weights are initialized when the layer is allocated without a file on disk.
+    #[must_use]
+    pub const fn with_init_scheme(
+        mut self,
+        init_scheme: InitScheme,
+    ) -> Self {
+        self.init_scheme = init_scheme;
+        self
+    }
+
+    /// Sets how many inputs the batch cache holds at most. The cache is emptied by the
+    /// backward passes of a batch and by `clear_batch_cache`.
+    pub const fn set_batch_cache_limit(
+        &mut self,
+        batch_cache_limit: usize,
+    ) {
+        self.batch_cache_limit = batch_cache_limit;
+    }
+
+    /// Initialize the weights according to the weight initialization scheme
+    /// and the biases according to the bias initialization scheme.
+    fn initialize_weights(
+        &mut self,
+        rng: &mut impl Rng,
+    ) {
+        let init_scheme = self.init_scheme;
         for i in 0..self.weights.as_ref().unwrap().rows() {
             for j in 0..self.weights.as_ref().unwrap().cols() {
-                let value = rng.gen_range(-0.5..0.5);
+                let value = init_scheme.sample(self.cols, self.rows, rng);
                 let w = Weight { value, grad: 0.0, m: 0.0, v: 0.0 };
                 self.weights.as_ref().unwrap().set_mut_unchecked(i, j, w);
             }
         }
+        let bias_init = self.bias_init;
+        for bias in self.biases.as_mut().unwrap() {
+            *bias = Bias { value: bias_init.sample(rng), grad: 0.0, m: 0.0, v: 0.0 };
+        }
     }
 }
 
@@ -368,6 +600,7 @@ impl Allocatable for TrainableDenseLayer {
         if self.is_allocated() {
             return;
         }
+        self.column_major_weights = None;
         // if the layer_path does not exist, create a new matrix and store it
         if self.layer_path.exists() {
             // if the layer_path exists, read the matrix and store it
@@ -381,7 +614,7 @@ impl Allocatable for TrainableDenseLayer {
             } else {
                 self.weights = Some(WrappedMatrix::new(self.rows, self.cols));
                 self.biases = Some(vec![Bias::default(); self.rows]);
-                self.initialize_weights();
+                self.initialize_weights(&mut rand::thread_rng());
                 save_weight(
                     self.layer_path.path(),
                     self.weights.as_ref().unwrap(),
@@ -392,7 +625,7 @@ impl Allocatable for TrainableDenseLayer {
         } else {
             self.weights = Some(WrappedMatrix::new(self.rows, self.cols));
             self.biases = Some(vec![Bias::default(); self.rows]);
-            self.initialize_weights();
+            self.initialize_weights(&mut rand::thread_rng());
             save_weight(
                 self.layer_path.path(),
                 self.weights.as_ref().unwrap(),
@@ -401,7 +634,8 @@ impl Allocatable for TrainableDenseLayer {
             .expect("Failed to save layer weights and biases");
         }
         self.input_cache = Some(Vec::new());
-        self.input_batch_cache = Some(Vec::new());
+        self.input_batch_cache = Some(VecDeque::new());
+        self.evicted_batch_inputs = 0;
     }
 
     fn deallocate(&mut self) {
@@ -417,6 +651,8 @@ impl Allocatable for TrainableDenseLayer {
         self.biases = None;
         self.input_cache = None;
         self.input_batch_cache = None;
+        self.evicted_batch_inputs = 0;
+        self.column_major_weights = None;
     }
 
     fn is_allocated(&self) -> bool {
@@ -448,6 +684,7 @@ impl Layer for TrainableDenseLayer {
     ) -> Vec<f64> {
         assert!(self.is_allocated(), "Layer not allocated");
         self.input_cache = Some(input.to_vec()); // Cache the input for backpropagation
+        let scale = self.spectral_norm_step();
         let weights = self.weights.as_ref().unwrap().clone();
         let biases = self.biases.as_ref().unwrap().clone();
         let inputs = input.to_vec();
@@ -458,19 +695,80 @@ impl Layer for TrainableDenseLayer {
                 .unwrap()
                 .par_indexed_iter()
                 .map(|(row_idx, weights_row)| {
-                    weights_row.iter().zip(inputs.iter()).map(|(&w, &x)| w.value * x).sum::<f64>()
-                        + biases[row_idx].value // Use the bias corresponding to the row index
+                    weights_row
+                        .iter()
+                        .zip(inputs.iter())
+                        .map(|(&w, &x)| w.value * x)
+                        .sum::<f64>()
+                        .mul_add(scale, biases[row_idx].value) // Use the bias corresponding to the row index
                 })
                 .collect()
         })
     }
 
-    #[allow(clippy::needless_range_loop)]
+    /// Uses the current spectral norm estimate without another power iteration step.
+    fn forward_into(
+        &mut self,
+        input: &[f64],
+        out: &mut Vec<f64>,
+        _utils: WrappedUtils,
+    ) {
+        assert!(self.is_allocated(), "Layer not allocated");
+        let scale = self.spectral_norm.as_ref().map_or(1.0, SpectralNormState::scale);
+        let weights = self.weights.as_ref().unwrap().mat();
+        let weights = weights.lock().unwrap();
+        out.clear();
+        out.extend((&*weights).into_iter().zip(self.biases.as_ref().unwrap()).map(
+            |(weights_row, bias)| {
+                weights_row
+                    .iter()
+                    .zip(input)
+                    .map(|(w, &x)| w.value * x)
+                    .sum::<f64>()
+                    .mul_add(scale, bias.value)
+            },
+        ));
+    }
+
+    /// Caches the input until the backward pass of the batch consumes it.
+    ///
+    /// If the batch cache already holds `batch_cache_limit` inputs, the oldest one is dropped
+    /// and its backward pass contributes no gradient. `try_forward_batch` reports a full cache
+    /// as an error instead.
     fn forward_batch(
         &mut self,
-        _input: &[f64],
+        input: &[f64],
     ) -> Vec<f64> {
-        unimplemented!()
+        assert!(self.is_allocated(), "Layer not allocated");
+        let full = self.batch_cache_is_full();
+        let cache = self.input_batch_cache.get_or_insert_with(VecDeque::new);
+        if full && cache.pop_front().is_some() {
+            self.evicted_batch_inputs += 1;
+        }
+        cache.push_back(input.to_vec());
+        let scale = self.spectral_norm_step();
+        let weights = self.weights.as_ref().unwrap().mat();
+        let weights = weights.lock().unwrap();
+        (&*weights)
+            .into_iter()
+            .zip(self.biases.as_ref().unwrap())
+            .map(|(weights_row, bias)| {
+                weights_row
+                    .iter()
+                    .zip(input)
+                    .map(|(w, &x)| w.value * x)
+                    .sum::<f64>()
+                    .mul_add(scale, bias.value)
+            })
+            .collect()
+    }
+
+    fn batch_cache_is_full(&self) -> bool {
+        self.batch_cache_len() >= self.batch_cache_limit
+    }
+
+    fn batch_cache_len(&self) -> usize {
+        self.input_batch_cache.as_ref().map_or(0, VecDeque::len)
     }
 
     fn input_size(&self) -> usize {
@@ -524,6 +822,7 @@ impl Layer for TrainableDenseLayer {
         &mut self,
         path: String,
     ) -> Result<(), Box<dyn Error>> {
+        self.column_major_weights = None;
         // Read weights and biases from a file at the specified path
         let (weights, biases) = read(path)?;
         self.rows = weights.rows();
@@ -591,7 +890,10 @@ impl TrainableLayer for TrainableDenseLayer {
     ) -> Vec<f64> {
         let weights = self.weights.as_ref().unwrap().clone();
         let input_cache = self.input_cache.as_ref().unwrap().clone();
-        let d_out_vec = d_out.to_vec();
+        // the normalized weights are the weights divided by sigma, which is treated as a constant
+        let scale = self.spectral_norm.as_ref().map_or(1.0, SpectralNormState::scale);
+        let d_out = d_out.iter().map(|d| d * scale).collect::<Vec<_>>();
+        let d_out_vec = d_out.clone();
         // Calculate weight gradients
         let _ = utils.execute(move || {
             weights.mat().lock().unwrap().par_indexed_iter_mut().for_each(|(i, row_grad)| {
@@ -602,25 +904,21 @@ impl TrainableLayer for TrainableDenseLayer {
             0
         });
 
-        let weights_sec = self.weights.as_ref().unwrap().clone();
-        let input_cache_sec = self.input_cache.as_ref().unwrap().clone();
-        let d_out_vec_sec = d_out.to_vec();
-        // Calculate input gradients
-        utils.execute(move || {
-            (0..input_cache_sec.len())
-                .into_par_iter()
-                .map(|j| {
-                    weights_sec
-                        .mat()
-                        .lock()
-                        .unwrap()
-                        .iter()
-                        .enumerate()
-                        .map(|(i, row)| row[j].value * d_out_vec_sec[i])
-                        .sum::<f64>()
-                })
-                .collect::<Vec<f64>>()
-        })
+        // Calculate input gradients from a column-major copy of the weights, so that the
+        // gradient of every input is a sum over contiguous values. The copy is only rebuilt
+        // after the weights changed.
+        let rows = self.rows;
+        let column_major =
+            self.column_major_weights.take().unwrap_or_else(|| self.build_column_major_weights());
+        let (grad_input, column_major) = utils.execute(move || {
+            let grad_input = column_major
+                .par_chunks(rows.max(1))
+                .map(|column| column.iter().zip(&d_out).map(|(w, d)| w * d).sum::<f64>())
+                .collect::<Vec<f64>>();
+            (grad_input, column_major)
+        });
+        self.column_major_weights = Some(column_major);
+        grad_input
     }
 
     /// Update weights and biases using their respective gradients
@@ -632,6 +930,7 @@ impl TrainableLayer for TrainableDenseLayer {
         utils: WrappedUtils,
     ) {
         assert!(self.is_allocated(), "Layer not allocated");
+        self.column_major_weights = None;
         let weights = self.weights.as_ref().unwrap().clone();
         // Update weight
         let _ = utils.execute(move || {
@@ -649,22 +948,66 @@ impl TrainableLayer for TrainableDenseLayer {
         }
     }
 
-    #[allow(clippy::needless_range_loop)]
+    /// Consumes the oldest cached input of the batch, the backward passes follow the order
+    /// of the forward passes. The gradients of the batch are accumulated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no input is cached.
     fn backward_batch(
         &mut self,
-        _grad_output: &[f64],
+        grad_output: &[f64],
     ) -> Vec<f64> {
-        unimplemented!()
+        assert!(self.is_allocated(), "Layer not allocated");
+        if self.evicted_batch_inputs > 0 {
+            self.evicted_batch_inputs -= 1;
+            return vec![0.0; self.cols];
+        }
+        let input = self
+            .input_batch_cache
+            .as_mut()
+            .and_then(VecDeque::pop_front)
+            .expect("No cached input for the batch backward pass");
+        let scale = self.spectral_norm.as_ref().map_or(1.0, SpectralNormState::scale);
+        let mut grad_input = vec![0.0; self.cols];
+        let weights = self.weights.as_ref().unwrap().mat();
+        for ((weights_row, bias), d) in
+            weights.lock().unwrap().iter_mut().zip(self.biases.as_mut().unwrap()).zip(grad_output)
+        {
+            let d = d * scale;
+            for ((weight, x), grad) in weights_row.iter_mut().zip(&input).zip(&mut grad_input) {
+                weight.grad += d * x;
+                *grad += weight.value * d;
+            }
+            bias.grad += d;
+        }
+        grad_input
+    }
+
+    fn clear_batch_cache(&mut self) {
+        if let Some(cache) = self.input_batch_cache.as_mut() {
+            cache.clear();
+        }
+        self.evicted_batch_inputs = 0;
     }
 
     fn assign_weights(
         &mut self,
         other: WrappedTrainableLayer,
+        carry_moments: bool,
     ) {
-        let weights = other.get_weights();
-
-        let biases = other.get_biases();
+        self.assign_weights_and_biases(&other.get_weights(), &other.get_biases());
+        if carry_moments {
+            self.assign_moments(&other.moments());
+        }
+    }
 
+    fn assign_weights_and_biases(
+        &mut self,
+        weights: &WrappedMatrix<f64>,
+        biases: &[f64],
+    ) {
+        self.column_major_weights = None;
         for i in 0..self.weights.as_ref().unwrap().rows() {
             for j in 0..self.weights.as_ref().unwrap().cols() {
                 if i < weights.rows() && j < weights.cols() {
@@ -688,6 +1031,7 @@ impl TrainableLayer for TrainableDenseLayer {
         epsilon: f64,
         utils: WrappedUtils,
     ) {
+        self.column_major_weights = None;
         let t_f: f64 = NumCast::from(t).expect("Failed to convert time step to f64");
         let beta1_pow_t = beta1.powf(t_f);
         let beta2_pow_t = beta2.powf(t_f);
@@ -737,6 +1081,205 @@ impl TrainableLayer for TrainableDenseLayer {
         }
     }
 
+    fn grad_norm_sq(&self) -> f64 {
+        assert!(self.is_allocated(), "Layer not allocated");
+        let weights_norm_sq = self
+            .weights
+            .as_ref()
+            .unwrap()
+            .mat()
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|row| row.iter().map(|weight| weight.grad.powi(2)).sum::<f64>())
+            .sum::<f64>();
+        weights_norm_sq
+            + self.biases.as_ref().unwrap().iter().map(|bias| bias.grad.powi(2)).sum::<f64>()
+    }
+
+    fn gradients(&self) -> LayerGradients {
+        assert!(self.is_allocated(), "Layer not allocated");
+        let mut gradients = LayerGradients::zeros(self.rows, self.cols, 0);
+        let weights = self.weights.as_ref().unwrap().mat();
+        for (i, row) in weights.lock().unwrap().iter().enumerate() {
+            for (j, weight) in row.iter().enumerate() {
+                gradients.weights.set_mut_unchecked(i, j, weight.grad);
+            }
+        }
+        gradients.biases = self.biases.as_ref().unwrap().iter().map(|bias| bias.grad).collect();
+        gradients
+    }
+
+    fn set_gradients(
+        &mut self,
+        gradients: &LayerGradients,
+    ) {
+        assert!(self.is_allocated(), "Layer not allocated");
+        let weights = self.weights.as_ref().unwrap().mat();
+        for (i, row) in weights.lock().unwrap().iter_mut().enumerate() {
+            for (j, weight) in row.iter_mut().enumerate() {
+                weight.grad = *gradients.weights.get_unchecked(i, j);
+            }
+        }
+        for (bias, grad) in self.biases.as_mut().unwrap().iter_mut().zip(&gradients.biases) {
+            bias.grad = *grad;
+        }
+    }
+
+    /// Resizes the weight matrix and the biases. Entries of the overlapping region keep their
+    /// values and optimizer moments, the new ones start with zero gradients and moments.
+    fn resize_with(
+        &mut self,
+        input_size: usize,
+        output_size: usize,
+        fill: FillStrategy,
+    ) -> Result<(), Box<dyn Error>> {
+        assert!(self.is_allocated(), "Layer not allocated");
+        self.column_major_weights = None;
+        if input_size == 0 || output_size == 0 {
+            return Err(format!(
+                "Cannot resize dense layer to input size {input_size}, output size {output_size}"
+            )
+            .into());
+        }
+        let (old_rows, old_cols) = (self.rows, self.cols);
+        let old_weights = self.weights.take().unwrap();
+        let old_biases = self.biases.take().unwrap();
+        let mut rng = rand::thread_rng();
+        let weights = WrappedMatrix::new(output_size, input_size);
+        let mut biases = Vec::with_capacity(output_size);
+        for i in 0..output_size {
+            for j in 0..input_size {
+                let weight = if i < old_rows && j < old_cols {
+                    old_weights.get_unchecked(i, j)
+                } else {
+                    let value = match fill {
+                        FillStrategy::Zero => 0.0,
+                        FillStrategy::Random => {
+                            self.init_scheme.sample(input_size, output_size, &mut rng)
+                        },
+                        FillStrategy::Repeat => {
+                            old_weights.get_unchecked(i % old_rows, j % old_cols).value
+                        },
+                    };
+                    Weight { value, grad: 0.0, m: 0.0, v: 0.0 }
+                };
+                weights.set_mut_unchecked(i, j, weight);
+            }
+            let bias = if i < old_rows {
+                old_biases[i]
+            } else {
+                let value = match fill {
+                    FillStrategy::Zero => 0.0,
+                    FillStrategy::Random => self.bias_init.sample(&mut rng),
+                    FillStrategy::Repeat => old_biases[i % old_rows].value,
+                };
+                Bias { value, grad: 0.0, m: 0.0, v: 0.0 }
+            };
+            biases.push(bias);
+        }
+        self.rows = output_size;
+        self.cols = input_size;
+        self.weights = Some(weights);
+        self.biases = Some(biases);
+        self.input_cache = None;
+        self.input_batch_cache = None;
+        self.evicted_batch_inputs = 0;
+        if self.spectral_norm.is_some() {
+            self.spectral_norm = Some(SpectralNormState::new(output_size));
+        }
+        Ok(())
+    }
+
+    fn set_spectral_norm(
+        &mut self,
+        enabled: bool,
+    ) {
+        self.column_major_weights = None;
+        match (&self.spectral_norm, enabled) {
+            (None, true) => self.spectral_norm = Some(SpectralNormState::new(self.rows)),
+            (Some(state), false) => {
+                // fold the normalization into the weights so the layer computes the same outputs
+                let scale = state.scale();
+                assert!(self.is_allocated(), "Layer not allocated");
+                let weights = self.weights.as_ref().unwrap().mat();
+                for row in weights.lock().unwrap().iter_mut() {
+                    for weight in row.iter_mut() {
+                        weight.value *= scale;
+                    }
+                }
+                self.spectral_norm = None;
+            },
+            _ => {},
+        }
+    }
+
+    fn constrain_max_norm(
+        &mut self,
+        max_norm: f64,
+    ) {
+        assert!(self.is_allocated(), "Layer not allocated");
+        self.column_major_weights = None;
+        let weights = self.weights.as_ref().unwrap().mat();
+        for row in weights.lock().unwrap().iter_mut() {
+            let norm = row.iter().map(|weight| weight.value.powi(2)).sum::<f64>().sqrt();
+            if norm > max_norm {
+                let scale = max_norm / norm;
+                for weight in row.iter_mut() {
+                    weight.value *= scale;
+                }
+            }
+        }
+    }
+
+    fn reinitialize(
+        &mut self,
+        rng: &mut StdRng,
+    ) {
+        assert!(self.is_allocated(), "Layer not allocated");
+        self.column_major_weights = None;
+        self.initialize_weights(rng);
+        if self.spectral_norm.is_some() {
+            self.spectral_norm = Some(SpectralNormState::new(self.rows));
+        }
+    }
+
+    fn moments(&self) -> LayerMoments {
+        assert!(self.is_allocated(), "Layer not allocated");
+        let mut moments = LayerMoments::zeros(self.rows, self.cols, 0);
+        let weights = self.weights.as_ref().unwrap().mat();
+        for (i, row) in weights.lock().unwrap().iter().enumerate() {
+            for (j, weight) in row.iter().enumerate() {
+                moments.first.weights.set_mut_unchecked(i, j, weight.m);
+                moments.second.weights.set_mut_unchecked(i, j, weight.v);
+            }
+        }
+        moments.first.biases = self.biases.as_ref().unwrap().iter().map(|bias| bias.m).collect();
+        moments.second.biases = self.biases.as_ref().unwrap().iter().map(|bias| bias.v).collect();
+        moments
+    }
+
+    fn assign_moments(
+        &mut self,
+        moments: &LayerMoments,
+    ) {
+        assert!(self.is_allocated(), "Layer not allocated");
+        let rows = self.rows.min(moments.first.weights.rows());
+        let cols = self.cols.min(moments.first.weights.cols());
+        let weights = self.weights.as_ref().unwrap().mat();
+        for (i, row) in weights.lock().unwrap().iter_mut().enumerate().take(rows) {
+            for (j, weight) in row.iter_mut().enumerate().take(cols) {
+                weight.m = *moments.first.weights.get_unchecked(i, j);
+                weight.v = *moments.second.weights.get_unchecked(i, j);
+            }
+        }
+        let biases = self.biases.as_mut().unwrap().iter_mut();
+        for ((bias, m), v) in biases.zip(&moments.first.biases).zip(&moments.second.biases) {
+            bias.m = *m;
+            bias.v = *v;
+        }
+    }
+
     fn save_weight(
         &self,
         path: String,
@@ -776,6 +1319,7 @@ impl TrainableLayer for TrainableDenseLayer {
         &mut self,
         path: String,
     ) -> Result<(), Box<dyn Error>> {
+        self.column_major_weights = None;
         // Read weights and biases from a file at the specified path
         let (weights, biases) = read_weight(path)?;
         self.rows = weights.rows();
@@ -807,12 +1351,14 @@ impl TrainableAllocatableLayer for TrainableDenseLayer {
         position_in_nn: usize,
     ) -> Box<dyn TrainableAllocatableLayer + Send> {
         self.deallocate();
-        let new_layer = Box::new(Self::new(
+        let mut new_layer = Self::new(
             self.input_size(),
             self.output_size(),
             Directory::Internal(model_directory),
             position_in_nn,
-        )) as Box<dyn TrainableAllocatableLayer + Send>;
+        );
+        new_layer.spectral_norm.clone_from(&self.spectral_norm);
+        let new_layer = Box::new(new_layer) as Box<dyn TrainableAllocatableLayer + Send>;
         new_layer.copy_on_filesystem(self.layer_path.path());
         new_layer
     }
@@ -839,9 +1385,93 @@ impl TrainableAllocatableLayer for TrainableDenseLayer {
             std::fs::copy(original_path, new_layer_path.path()).expect("Failed to copy layer file");
         }
     }
+
+    fn as_trainable_layer_mut(&mut self) -> &mut dyn TrainableLayer {
+        self
+    }
+}
+
+/// Version of the layer file format written by `save` and `save_weight`.
+const LAYER_FILE_VERSION: u32 = 1;
+/// Prefix of the version header line. Legacy files start directly with the dimensions.
+const LAYER_FILE_VERSION_PREFIX: &str = "version";
+
+/// Extension of layer files which are written gzip-compressed.
+pub const GZIP_EXTENSION: &str = ".gz";
+/// The first bytes of every gzip-compressed file.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Returns the path of the file of the layer at the given position in the model directory.
+/// A gzip-compressed layer file is used if there is no plain one.
+pub(crate) fn layer_file_path(
+    model_directory: &str,
+    position_in_nn: usize,
+) -> String {
+    let path = format!("{model_directory}/layers/layer_{position_in_nn}.txt");
+    let gzip_path = format!("{path}{GZIP_EXTENSION}");
+    if !Path::new(&path).exists() && Path::new(&gzip_path).exists() {
+        gzip_path
+    } else {
+        path
+    }
 }
 
-fn save(
+/// Creates the layer file at the given path and writes it with `write`,
+/// gzip-compressed if the path ends in `GZIP_EXTENSION`.
+fn write_layer_file(
+    path: String,
+    write: impl FnOnce(&mut dyn Write) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let compressed = path.ends_with(GZIP_EXTENSION);
+    let file = BufWriter::new(File::create(path)?);
+    if compressed {
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        write(&mut encoder)?;
+        encoder.finish()?.flush()?;
+    } else {
+        let mut file = file;
+        write(&mut file)?;
+        file.flush()?;
+    }
+    Ok(())
+}
+
+/// Opens the layer file at the given path for reading. Gzip-compressed files are recognized
+/// by their content, so a compressed layer can be read whatever its extension.
+fn open_layer_file(path: String) -> Result<Box<dyn BufRead>, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        Ok(Box::new(BufReader::new(GzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Reads the dimensions of the weight matrix, skipping the version header if present.
+fn read_dimensions<B: BufRead>(
+    lines: &mut std::io::Lines<B>
+) -> Result<Option<(usize, usize)>, Box<dyn Error>> {
+    let Some(Ok(mut line)) = lines.next() else {
+        return Ok(None);
+    };
+    if let Some(version) = line.strip_prefix(LAYER_FILE_VERSION_PREFIX) {
+        let version = version.trim().parse::<u32>()?;
+        if version > LAYER_FILE_VERSION {
+            return Err(format!("Unsupported layer file version: {version}").into());
+        }
+        // a versioned file always has a dimension line, without it the file is truncated
+        line = lines.next().ok_or("Missing dimension line after the version header")??;
+    }
+    let mut parts = line.split_whitespace();
+    let rows = parts.next().ok_or("Missing number of rows")?.parse::<usize>()?;
+    let cols = parts.next().ok_or("Missing number of cols")?.parse::<usize>()?;
+    if parts.next().is_some() {
+        return Err(format!("Invalid dimension line: {line}").into());
+    }
+    Ok(Some((rows, cols)))
+}
+
+pub(crate) fn save(
     path: String,
     weights: &WrappedMatrix<f64>,
     biases: &[f64],
@@ -856,22 +1486,24 @@ fn save(
     let lock_file = File::create(&lock_file_path)?;
     lock_file.lock_exclusive()?;
     // Save weights and biases to a file at the specified path
-    let mut file = File::create(path)?;
-    writeln!(file, "{} {}", weights.rows(), weights.cols())?;
-    for i in 0..weights.rows() {
-        for j in 0..weights.cols() {
-            write!(file, "{};", weights.get_unchecked(i, j))?;
+    write_layer_file(path, |file| {
+        writeln!(file, "{LAYER_FILE_VERSION_PREFIX} {LAYER_FILE_VERSION}")?;
+        writeln!(file, "{} {}", weights.rows(), weights.cols())?;
+        for i in 0..weights.rows() {
+            for j in 0..weights.cols() {
+                write!(file, "{};", weights.get_unchecked(i, j))?;
+            }
+            writeln!(file)?;
+        }
+        for bias in biases {
+            write!(file, "{bias}; ")?;
         }
         writeln!(file)?;
-    }
-    for bias in biases {
-        write!(file, "{bias}; ")?;
-    }
-    writeln!(file)?;
-    Ok(())
+        Ok(())
+    })
 }
 
-fn save_weight(
+pub(crate) fn save_weight(
     path: String,
     weights: &WrappedMatrix<Weight>,
     biases: &[Bias],
@@ -888,37 +1520,34 @@ fn save_weight(
     lock_file.lock_exclusive()?;
 
     // Save weights and biases to a file at the specified path
-    let mut file = File::create(path)?;
-    writeln!(file, "{} {}", weights.rows(), weights.cols())?;
-    for i in 0..weights.rows() {
-        for j in 0..weights.cols() {
-            let weight = weights.get_unchecked(i, j);
-            write!(file, "{} {} {} {};", weight.value, weight.grad, weight.m, weight.v)?;
+    write_layer_file(path, |file| {
+        writeln!(file, "{LAYER_FILE_VERSION_PREFIX} {LAYER_FILE_VERSION}")?;
+        writeln!(file, "{} {}", weights.rows(), weights.cols())?;
+        for i in 0..weights.rows() {
+            for j in 0..weights.cols() {
+                let weight = weights.get_unchecked(i, j);
+                write!(file, "{} {} {} {};", weight.value, weight.grad, weight.m, weight.v)?;
+            }
+            writeln!(file)?;
+        }
+        for bias in biases {
+            write!(file, "{} {} {} {};", bias.value, bias.grad, bias.m, bias.v)?;
         }
         writeln!(file)?;
-    }
-    for bias in biases {
-        write!(file, "{} {} {} {};", bias.value, bias.grad, bias.m, bias.v)?;
-    }
-    writeln!(file)?;
-    Ok(())
+        Ok(())
+    })
 }
 
-fn read(path: String) -> Result<(WrappedMatrix<f64>, Vec<f64>), Box<dyn Error>> {
+pub(crate) fn read(path: String) -> Result<(WrappedMatrix<f64>, Vec<f64>), Box<dyn Error>> {
     // create a lock file which acts as a lock
     let lock_file_path = format!("{path}.lock");
     let lock_file = File::create(&lock_file_path)?;
     lock_file.lock_exclusive()?;
 
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let mut lines = reader.lines();
+    let mut lines = open_layer_file(path)?.lines();
     let mut weights = WrappedMatrix::new(1, 1);
     let mut biases = vec![0.0; 1];
-    if let Some(Ok(line)) = lines.next() {
-        let mut parts = line.split_whitespace();
-        let rows = parts.next().unwrap().parse::<usize>()?;
-        let cols = parts.next().unwrap().parse::<usize>()?;
+    if let Some((rows, cols)) = read_dimensions(&mut lines)? {
         weights = WrappedMatrix::new(rows, cols);
         for i in 0..rows {
             if let Some(Ok(line)) = lines.next() {
@@ -943,6 +1572,10 @@ fn read(path: String) -> Result<(WrappedMatrix<f64>, Vec<f64>), Box<dyn Error>>
                         }
                     }
                 }
+            } else {
+                return Err(
+                    format!("Unexpected end of file, missing weight row {i} of {rows}").into()
+                );
             }
         }
     }
@@ -969,25 +1602,24 @@ fn read(path: String) -> Result<(WrappedMatrix<f64>, Vec<f64>), Box<dyn Error>>
                 }
             }
         }
+    } else {
+        return Err("Unexpected end of file, missing biases".into());
     }
     Ok((weights, biases))
 }
 
-fn read_weight(path: String) -> Result<(WrappedMatrix<Weight>, Vec<Bias>), Box<dyn Error>> {
+pub(crate) fn read_weight(
+    path: String
+) -> Result<(WrappedMatrix<Weight>, Vec<Bias>), Box<dyn Error>> {
     // create a lock file which acts as a lock
     let lock_file_path = format!("{path}.lock");
     let lock_file = File::create(&lock_file_path)?;
     lock_file.lock_exclusive()?;
 
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let mut lines = reader.lines();
+    let mut lines = open_layer_file(path)?.lines();
     let mut weights = WrappedMatrix::new(1, 1);
     let mut biases = vec![Bias::default(); 1];
-    if let Some(Ok(line)) = lines.next() {
-        let mut dim_parts = line.split_whitespace();
-        let rows = dim_parts.next().unwrap().parse::<usize>()?;
-        let cols = dim_parts.next().unwrap().parse::<usize>()?;
+    if let Some((rows, cols)) = read_dimensions(&mut lines)? {
         weights = WrappedMatrix::new(rows, cols);
         for i in 0..rows {
             if let Some(Ok(line)) = lines.next() {
@@ -1032,6 +1664,10 @@ fn read_weight(path: String) -> Result<(WrappedMatrix<Weight>, Vec<Bias>), Box<d
                         }
                     }
                 }
+            } else {
+                return Err(
+                    format!("Unexpected end of file, missing weight row {i} of {rows}").into()
+                );
             }
         }
     }
@@ -1066,6 +1702,8 @@ fn read_weight(path: String) -> Result<(WrappedMatrix<Weight>, Vec<Bias>), Box<d
                 }
             }
         }
+    } else {
+        return Err("Unexpected end of file, missing biases".into());
     }
     Ok((weights, biases))
 }
@@ -1099,4 +1737,379 @@ mod tests {
 
         std::fs::remove_dir_all("test_model_unit").unwrap();
     }
+
+    #[test]
+    fn test_backward_on_large_layer_matches_row_major_sums() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut layer = TrainableDenseLayer::new(
+            512,
+            512,
+            Directory::Internal("test_model_backward_512".to_string()),
+            0,
+        );
+        layer.allocate();
+        let mut rng = StdRng::seed_from_u64(42);
+        let input = (0..512).map(|_| rng.gen_range(-1.0..1.0)).collect::<Vec<f64>>();
+        let d_out = (0..512).map(|_| rng.gen_range(-1.0..1.0)).collect::<Vec<f64>>();
+        let _ = layer.forward(&input, utils.clone());
+
+        // the input gradients as computed by iterating the columns of the row-major weights
+        let weights = layer.get_weights();
+        let expected = (0..512)
+            .map(|j| (0..512).map(|i| weights.get_unchecked(i, j) * d_out[i]).sum::<f64>())
+            .collect::<Vec<f64>>();
+        // the second pass reuses the column-major copy of the first one
+        for _ in 0..2 {
+            assert_eq!(layer.backward(&d_out, utils.clone()), expected);
+            assert!(layer.column_major_weights.is_some());
+        }
+
+        // updating the weights invalidates the copy, the next pass sees the new weights
+        layer.update_weights(0.1, utils.clone());
+        assert!(layer.column_major_weights.is_none());
+        let weights = layer.get_weights();
+        let expected = (0..512)
+            .map(|j| (0..512).map(|i| weights.get_unchecked(i, j) * d_out[i]).sum::<f64>())
+            .collect::<Vec<f64>>();
+        assert_eq!(layer.backward(&d_out, utils), expected);
+
+        drop(layer);
+        let _ = std::fs::remove_dir_all("test_model_backward_512");
+    }
+
+    #[test]
+    fn test_resize_with_repeat_tiles_existing_rows() {
+        let mut layer =
+            TrainableDenseLayer::new(2, 2, Directory::Internal("test_model_resize".to_string()), 0);
+        layer.allocate();
+        let weights = WrappedMatrix::new(2, 2);
+        for (k, value) in [1.0, 2.0, 3.0, 4.0].into_iter().enumerate() {
+            weights.set_mut_unchecked(k / 2, k % 2, value);
+        }
+        layer.assign_weights_and_biases(&weights, &[0.5, -0.5]);
+
+        layer.resize_with(3, 5, FillStrategy::Repeat).unwrap();
+        assert_eq!(layer.input_size(), 3);
+        assert_eq!(layer.output_size(), 5);
+        let resized = layer.get_weights();
+        for i in 0..5 {
+            for j in 0..3 {
+                let expected = weights.get_unchecked(i % 2, j % 2);
+                assert!((resized.get_unchecked(i, j) - expected).abs() < f64::EPSILON);
+            }
+        }
+        assert_eq!(layer.get_biases(), vec![0.5, -0.5, 0.5, -0.5, 0.5]);
+
+        layer.resize_with(4, 6, FillStrategy::Zero).unwrap();
+        assert!(layer.get_weights().get_unchecked(5, 3).abs() < f64::EPSILON);
+        assert!((layer.get_weights().get_unchecked(4, 2) - 1.0).abs() < f64::EPSILON);
+        assert!(layer.get_biases()[5].abs() < f64::EPSILON);
+
+        layer.resize_with(1, 1, FillStrategy::Random).unwrap();
+        assert!((layer.get_weights().get_unchecked(0, 0) - 1.0).abs() < f64::EPSILON);
+
+        drop(layer);
+        let _ = std::fs::remove_dir_all("test_model_resize");
+    }
+
+    #[test]
+    fn test_spectral_norm_of_diagonal_weights() {
+        let mut layer =
+            DenseLayer::new(3, 3, Directory::Internal("test_model_spectral_norm".to_string()), 0);
+        layer.allocate();
+        let weights = layer.weights.as_ref().unwrap();
+        for (i, value) in [2.0, -5.0, 3.0].into_iter().enumerate() {
+            weights.set_mut_unchecked(i, i, value);
+        }
+        assert!((layer.spectral_norm(100) - 5.0).abs() < 1e-9);
+
+        let mut zero_layer = DenseLayer::new(
+            2,
+            2,
+            Directory::Internal("test_model_spectral_norm_zero".to_string()),
+            0,
+        );
+        zero_layer.allocate();
+        assert!(zero_layer.spectral_norm(10).abs() < f64::EPSILON);
+
+        drop(layer);
+        drop(zero_layer);
+        let _ = std::fs::remove_dir_all("test_model_spectral_norm");
+        let _ = std::fs::remove_dir_all("test_model_spectral_norm_zero");
+    }
+
+    #[test]
+    fn test_gzip_layer_file() {
+        let source_directory = "test_model_gzip_layer_source";
+        let model_directory = "test_model_gzip_layer";
+        let mut layer =
+            DenseLayer::new(40, 30, Directory::Internal(source_directory.to_string()), 0);
+        layer.allocate();
+        let weights = layer.weights.as_ref().unwrap();
+        for i in 0..30 {
+            for j in 0..40 {
+                let value: f64 = NumCast::from(i * j % 7).unwrap();
+                weights.set_mut_unchecked(i, j, value * 0.25);
+            }
+        }
+        let plain_path = format!("{source_directory}/layers/layer_0.txt");
+        let gzip_path = format!("{model_directory}/layers/layer_0.txt{GZIP_EXTENSION}");
+        layer.save(plain_path.clone()).unwrap();
+        layer.save(gzip_path.clone()).unwrap();
+        let size = |path: &str| std::fs::metadata(path).unwrap().len();
+        let flat = |weights: &WrappedMatrix<f64>| {
+            (0..weights.rows())
+                .flat_map(|i| (0..weights.cols()).map(move |j| weights.get_unchecked(i, j)))
+                .collect::<Vec<_>>()
+        };
+        assert!(size(&gzip_path) < size(&plain_path));
+
+        let (read_weights, read_biases) = read(gzip_path).unwrap();
+        assert_eq!(flat(&read_weights), flat(&layer.get_weights()));
+        assert_eq!(read_biases, layer.get_biases());
+
+        // a layer of the model directory without a plain file uses the compressed one
+        let mut reloaded =
+            DenseLayer::new(40, 30, Directory::Internal(model_directory.to_string()), 0);
+        reloaded.allocate();
+        assert_eq!(flat(&reloaded.get_weights()), flat(&layer.get_weights()));
+
+        drop(layer);
+        drop(reloaded);
+        let _ = std::fs::remove_dir_all(source_directory);
+        let _ = std::fs::remove_dir_all(model_directory);
+    }
+
+    #[test]
+    fn test_try_forward_reports_size_mismatch() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut layer = TrainableDenseLayer::new(
+            3,
+            2,
+            Directory::Internal("test_model_try_forward".to_string()),
+            0,
+        );
+        layer.allocate();
+        layer.mark_for_use();
+        let error = layer.try_forward(&[1.0, 2.0], utils.clone()).unwrap_err();
+        assert_eq!(error.to_string(), "Layer input size mismatch: expected 3, got 2");
+        let error = layer.try_forward_batch(&[1.0, 2.0, 3.0, 4.0]).unwrap_err();
+        assert_eq!(error.to_string(), "Layer input size mismatch: expected 3, got 4");
+        assert_eq!(layer.try_forward(&[1.0, 2.0, 3.0], utils).unwrap().len(), 2);
+        layer.free_from_use();
+
+        std::fs::remove_dir_all("test_model_try_forward").unwrap();
+    }
+
+    #[test]
+    fn test_batch_cache_stays_bounded() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut layer = TrainableDenseLayer::new(
+            3,
+            2,
+            Directory::Internal("test_model_batch_cache".to_string()),
+            0,
+        );
+        layer.allocate();
+        layer.mark_for_use();
+        layer.set_batch_cache_limit(4);
+        let input = [1.0, -2.0, 0.5];
+        assert_eq!(layer.forward_batch(&input), layer.forward(&input, utils));
+        layer.clear_batch_cache();
+
+        for batch in 0..100 {
+            for _ in 0..4 {
+                layer.forward_batch(&input);
+            }
+            // every other batch only runs a part of its backward passes
+            let backward_passes = if batch % 2 == 0 { 5 } else { 2 };
+            for _ in 0..backward_passes.min(layer.batch_cache_len()) {
+                layer.backward_batch(&[0.5, -0.5]);
+            }
+            assert!(layer.batch_cache_len() <= 4);
+            layer.clear_batch_cache();
+            assert_eq!(layer.batch_cache_len(), 0);
+        }
+
+        for _ in 0..4 {
+            layer.try_forward_batch(&input).unwrap();
+        }
+        assert!(layer.batch_cache_is_full());
+        assert!(layer.try_forward_batch(&input).is_err());
+        assert_eq!(layer.batch_cache_len(), 4);
+        layer.backward_batch(&[0.5, -0.5]);
+        assert!(layer.try_forward_batch(&input).is_ok());
+
+        // a full cache drops its oldest input, whose backward pass adds no gradient
+        layer.forward_batch(&input);
+        assert_eq!(layer.batch_cache_len(), 4);
+        layer.set_gradients(&LayerGradients::zeros(2, 3, 2));
+        assert_eq!(layer.backward_batch(&[0.5, -0.5]), vec![0.0; 3]);
+        assert!(layer.grad_norm_sq().abs() < f64::EPSILON);
+        assert_ne!(layer.backward_batch(&[0.5, -0.5]), vec![0.0; 3]);
+        layer.free_from_use();
+
+        std::fs::remove_dir_all("test_model_batch_cache").unwrap();
+    }
+
+    #[test]
+    fn test_dense_layer_constant_bias_init() {
+        let mut layer = TrainableDenseLayer::new(
+            3,
+            4,
+            Directory::Internal("test_model_bias_init".to_string()),
+            0,
+        )
+        .with_bias_init(BiasInit::Constant(0.1));
+        layer.allocate();
+
+        let biases = layer.get_biases();
+        assert_eq!(biases.len(), 4);
+        for bias in biases {
+            assert!((bias - 0.1).abs() < f64::EPSILON);
+        }
+
+        std::fs::remove_dir_all("test_model_bias_init").unwrap();
+    }
+
+    #[test]
+    fn test_dense_layer_normal_init_schemes() {
+        for init_scheme in [InitScheme::GlorotNormal, InitScheme::LecunNormal] {
+            let mut layer = TrainableDenseLayer::new(
+                200,
+                300,
+                Directory::Internal("test_model_normal_init".to_string()),
+                0,
+            )
+            .with_init_scheme(init_scheme);
+            layer.allocate();
+
+            let weights = layer.get_weights();
+            let values = (0..weights.rows())
+                .flat_map(|i| (0..weights.cols()).map(move |j| (i, j)))
+                .map(|(i, j)| weights.get_unchecked(i, j))
+                .collect::<Vec<_>>();
+            let count: f64 = NumCast::from(values.len()).unwrap();
+            let mean = values.iter().sum::<f64>() / count;
+            let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count;
+            let expected = init_scheme.variance(200, 300);
+            assert!(mean.abs() < 0.01);
+            assert!((variance - expected).abs() < 0.05 * expected);
+
+            std::fs::remove_dir_all("test_model_normal_init").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_dense_layer_reads_legacy_format() {
+        let directory = "test_model_legacy_format";
+        let path = format!("{directory}/layers/layer_0.txt");
+        std::fs::create_dir_all(format!("{directory}/layers")).unwrap();
+        // legacy files have no version header
+        std::fs::write(&path, "2 3\n0.1;0.2;0.3;\n0.4;0.5;0.6;\n0.7; 0.8; \n").unwrap();
+
+        let (weights, biases) = read(path.clone()).unwrap();
+        assert_eq!(weights.rows(), 2);
+        assert_eq!(weights.cols(), 3);
+        assert!((weights.get_unchecked(1, 2) - 0.6).abs() < f64::EPSILON);
+        assert_eq!(biases, vec![0.7, 0.8]);
+
+        let mut layer =
+            TrainableDenseLayer::new(3, 2, Directory::Internal(directory.to_string()), 0);
+        layer.allocate();
+        assert!((layer.get_weights().get_unchecked(0, 1) - 0.2).abs() < f64::EPSILON);
+        assert_eq!(layer.get_biases(), vec![0.7, 0.8]);
+
+        // saving migrates the file to the current format
+        layer.deallocate();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content.lines().next().unwrap(),
+            format!("{LAYER_FILE_VERSION_PREFIX} {LAYER_FILE_VERSION}")
+        );
+        let (weights, biases) = read_weight(path).unwrap();
+        assert!((weights.get_unchecked(1, 0).value - 0.4).abs() < f64::EPSILON);
+        assert!((biases[1].value - 0.8).abs() < f64::EPSILON);
+
+        drop(layer);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_dense_layer_rejects_missing_or_malformed_dimensions() {
+        let directory = "test_model_missing_dimensions";
+        let path = format!("{directory}/layers/layer_0.txt");
+        std::fs::create_dir_all(format!("{directory}/layers")).unwrap();
+        let header = format!("{LAYER_FILE_VERSION_PREFIX} {LAYER_FILE_VERSION}");
+
+        std::fs::write(&path, format!("{header}\n")).unwrap();
+        assert!(read(path.clone()).is_err());
+        std::fs::write(&path, format!("{header}\n2\n")).unwrap();
+        assert!(read(path.clone()).is_err());
+        std::fs::write(&path, format!("{header}\n2 x\n")).unwrap();
+        assert!(read(path.clone()).is_err());
+        std::fs::write(&path, format!("{header}\n2 3 4\n")).unwrap();
+        assert!(read(path).is_err());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_assign_weights_carries_moments() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut source = TrainableDenseLayer::new(
+            3,
+            2,
+            Directory::Internal("test_model_moments_source".to_string()),
+            0,
+        );
+        source.allocate();
+        source.mark_for_use();
+        source.forward(&[1.0, 2.0, 3.0], utils.clone());
+        source.backward(&[0.1, 0.2], utils.clone());
+        let mut gradients = source.gradients();
+        gradients.biases = vec![0.1, 0.2];
+        source.set_gradients(&gradients);
+        source.adjust_adam(1, 0.01, 0.9, 0.999, 1e-8, utils);
+        source.free_from_use();
+        let source_moments = source.moments();
+        let source = WrappedTrainableLayer::new(Box::new(source));
+
+        let mut target = TrainableDenseLayer::new(
+            4,
+            3,
+            Directory::Internal("test_model_moments_target".to_string()),
+            0,
+        );
+        target.allocate();
+        target.assign_weights(source.clone(), false);
+        let moments = target.moments();
+        assert!(moments.first.biases.iter().all(|m| *m == 0.0));
+
+        target.assign_weights(source, true);
+        let moments = target.moments();
+        let (rows, cols) =
+            (source_moments.first.weights.rows(), source_moments.first.weights.cols());
+        for i in 0..moments.first.weights.rows() {
+            for j in 0..moments.first.weights.cols() {
+                let (m, v) = if i < rows && j < cols {
+                    (
+                        *source_moments.first.weights.get_unchecked(i, j),
+                        *source_moments.second.weights.get_unchecked(i, j),
+                    )
+                } else {
+                    (0.0, 0.0)
+                };
+                assert!((moments.first.weights.get_unchecked(i, j) - m).abs() < f64::EPSILON);
+                assert!((moments.second.weights.get_unchecked(i, j) - v).abs() < f64::EPSILON);
+            }
+        }
+        assert_eq!(moments.first.biases[..2], source_moments.first.biases[..]);
+        assert_eq!(moments.second.biases[..2], source_moments.second.biases[..]);
+        assert!(source_moments.first.biases.iter().all(|m| *m != 0.0));
+        assert!((moments.first.biases[2]).abs() < f64::EPSILON);
+
+        std::fs::remove_dir_all("test_model_moments_source").unwrap();
+        std::fs::remove_dir_all("test_model_moments_target").unwrap();
+    }
 }
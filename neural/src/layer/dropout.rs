@@ -0,0 +1,83 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+
+/// Inverted dropout zeroing each value with probability `rate`.
+/// The surviving values are scaled by `1 / (1 - rate)` so the expected value is unchanged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dropout {
+    rate: f64,
+    seed: u64,
+}
+
+impl Dropout {
+    /// Creates a new `Dropout` with the given drop probability.
+    /// The seed is used for the random number generator returned by `rng`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the rate is not within `[0, 1)`.
+    #[must_use]
+    pub fn new(
+        rate: f64,
+        seed: u64,
+    ) -> Self {
+        assert!((0.0..1.0).contains(&rate), "dropout rate must be within [0, 1)");
+        Self { rate, seed }
+    }
+
+    #[must_use]
+    pub const fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    #[must_use]
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns a random number generator seeded with the seed of the dropout.
+    #[must_use]
+    pub fn rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.seed)
+    }
+
+    /// Returns a copy of the values with the dropped values set to zero.
+    pub fn apply<R: Rng>(
+        &self,
+        values: &[f64],
+        rng: &mut R,
+    ) -> Vec<f64> {
+        let mask = self.mask(values.len(), rng);
+        values.iter().zip(mask).map(|(x, factor)| x * factor).collect()
+    }
+
+    /// Returns the factor of every value, zero for the dropped values and `1 / (1 - rate)`
+    /// otherwise. The gradients of the values are scaled by the same factors.
+    pub fn mask<R: Rng>(
+        &self,
+        len: usize,
+        rng: &mut R,
+    ) -> Vec<f64> {
+        let scale = 1.0 / (1.0 - self.rate);
+        (0..len).map(|_| if rng.gen::<f64>() < self.rate { 0.0 } else { scale }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dropout() {
+        let values = vec![1.0; 1000];
+        let dropout = Dropout::new(0.5, 7);
+        let dropped = dropout.apply(&values, &mut dropout.rng());
+        assert!(dropped.iter().all(|x| *x == 0.0 || (*x - 2.0).abs() < 1e-12));
+        let zeros = dropped.iter().filter(|x| **x == 0.0).count();
+        assert!(zeros > 400 && zeros < 600);
+
+        let no_dropout = Dropout::new(0.0, 7);
+        assert_eq!(no_dropout.apply(&values, &mut no_dropout.rng()), values);
+    }
+}
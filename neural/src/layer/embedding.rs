@@ -0,0 +1,741 @@
+use super::dense_layer::{
+    layer_file_path, read_weight, save, save_weight, Bias, Weight, DEFAULT_BATCH_CACHE_LIMIT,
+};
+use super::init::FillStrategy;
+use super::layer_trait::Layer;
+use super::layer_trait::TrainableLayer;
+use super::layer_trait::WrappedTrainableLayer;
+use super::layer_trait::{LayerGradients, LayerMoments};
+use super::AllocatableLayer;
+use super::TrainableAllocatableLayer;
+use crate::nn::directory::Directory;
+use crate::utilities::util::WrappedUtils;
+use alloc::allocatable::Allocatable;
+
+use matrix::mat::WrappedMatrix;
+
+use num_traits::cast::NumCast;
+
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::path::Path;
+
+/// An embedding lookup table mapping integer indices to learned dense vectors.
+///
+/// The input of the layer is a single index passed as `f64`, the output is the row of the
+/// table at that index. The table is stored like the weights of a dense layer with
+/// `num_embeddings` rows and `dim` columns. The biases in the file are unused and zero.
+#[derive(Debug, Clone)]
+pub struct EmbeddingLayer {
+    num_embeddings: usize,
+    dim: usize,
+    weights: Option<WrappedMatrix<Weight>>, // Embedding table (num_embeddings x dim)
+    index_cache: Option<usize>,             // Cache the looked up index for the backward pass
+    index_batch_cache: VecDeque<usize>,     // Cache the batch indices for the batch backward pass
+    evicted_batch_indices: usize,           // Dropped batch indices whose backward passes are due
+    touched_rows: Vec<usize>,               // Rows with accumulated gradients
+    in_use: bool,
+    layer_path: Directory,
+}
+
+impl EmbeddingLayer {
+    /// Creates a new `EmbeddingLayer` with the given number of embeddings and embedding dimension.
+    #[must_use]
+    pub fn new(
+        num_embeddings: usize,
+        dim: usize,
+        model_directory: Directory,
+        position_in_nn: usize,
+    ) -> Self {
+        // create a Directory type which has the path model_directory/layers/layer_{position_in_nn}.txt
+        // or its gzip-compressed counterpart
+        let layer_path = match model_directory {
+            Directory::User(path) => Directory::User(layer_file_path(&path, position_in_nn)),
+            Directory::Internal(path) => {
+                Directory::Internal(layer_file_path(&path, position_in_nn))
+            },
+        };
+        Self {
+            num_embeddings,
+            dim,
+            weights: None,
+            index_cache: None,
+            index_batch_cache: VecDeque::new(),
+            evicted_batch_indices: 0,
+            touched_rows: Vec::new(),
+            in_use: false,
+            layer_path,
+        }
+    }
+
+    /// Returns the number of rows of the embedding table.
+    #[must_use]
+    pub const fn num_embeddings(&self) -> usize {
+        self.num_embeddings
+    }
+
+    /// Converts the input into an index of the embedding table.
+    fn index(
+        &self,
+        input: &[f64],
+    ) -> usize {
+        assert_eq!(input.len(), 1, "Embedding layer expects a single index as input");
+        let index: usize =
+            NumCast::from(input[0].round()).expect("Failed to convert input to an index");
+        assert!(index < self.num_embeddings, "Embedding index {index} out of range");
+        index
+    }
+
+    /// Initialize the embedding table with random values in the range [-0.5, 0.5]
+    fn initialize_weights(
+        &self,
+        rng: &mut impl Rng,
+    ) {
+        for i in 0..self.num_embeddings {
+            for j in 0..self.dim {
+                let value = rng.gen_range(-0.5..0.5);
+                let w = Weight { value, grad: 0.0, m: 0.0, v: 0.0 };
+                self.weights.as_ref().unwrap().set_mut_unchecked(i, j, w);
+            }
+        }
+    }
+
+    /// Returns the unused biases stored alongside the embedding table in the layer file.
+    fn zero_biases(&self) -> Vec<Bias> {
+        vec![Bias::default(); self.num_embeddings]
+    }
+
+    /// Returns the row of the embedding table at the given index.
+    fn row(
+        &self,
+        index: usize,
+    ) -> Vec<f64> {
+        let weights = self.weights.as_ref().unwrap();
+        (0..self.dim).map(|j| weights.get_unchecked(index, j).value).collect()
+    }
+
+    /// Adds the output gradient to the gradients of the row at the given index.
+    fn accumulate_row_gradient(
+        &mut self,
+        index: usize,
+        d_out: &[f64],
+    ) {
+        let weights = self.weights.as_ref().unwrap();
+        for (j, &grad) in d_out.iter().enumerate().take(self.dim) {
+            let mut weight = weights.get_unchecked(index, j);
+            weight.grad += grad;
+            weights.set_mut_unchecked(index, j, weight);
+        }
+        if !self.touched_rows.contains(&index) {
+            self.touched_rows.push(index);
+        }
+    }
+
+    /// Sets the gradients of the rows touched since the last update back to zero.
+    fn reset_gradients(&mut self) {
+        let weights = self.weights.as_ref().unwrap();
+        for &row in &self.touched_rows {
+            for j in 0..self.dim {
+                let mut weight = weights.get_unchecked(row, j);
+                weight.grad = 0.0;
+                weights.set_mut_unchecked(row, j, weight);
+            }
+        }
+        self.touched_rows.clear();
+    }
+}
+
+impl Drop for EmbeddingLayer {
+    fn drop(&mut self) {
+        // Save the model to ensure that everything is on disk if it is a user_model_directory
+        if let Directory::User(dir) = &self.layer_path {
+            if std::fs::metadata(dir).is_ok() {
+                // Save the model to disk
+                self.deallocate();
+            }
+        }
+    }
+}
+
+impl Allocatable for EmbeddingLayer {
+    fn allocate(&mut self) {
+        if self.is_allocated() {
+            return;
+        }
+        if self.layer_path.exists() {
+            let (weights, _) = read_weight(self.layer_path.path())
+                .expect("Failed to read embedding layer weights");
+            if self.num_embeddings == weights.rows() && self.dim == weights.cols() {
+                self.weights = Some(weights);
+                return;
+            }
+        }
+        self.weights = Some(WrappedMatrix::new(self.num_embeddings, self.dim));
+        self.initialize_weights(&mut rand::thread_rng());
+        save_weight(self.layer_path.path(), self.weights.as_ref().unwrap(), &self.zero_biases())
+            .expect("Failed to save embedding layer weights");
+    }
+
+    fn deallocate(&mut self) {
+        if self.is_allocated() {
+            save_weight(
+                self.layer_path.path(),
+                self.weights.as_ref().unwrap(),
+                &self.zero_biases(),
+            )
+            .expect("Failed to save embedding layer weights");
+        }
+        self.weights = None;
+        self.index_cache = None;
+        self.index_batch_cache.clear();
+        self.evicted_batch_indices = 0;
+        self.touched_rows.clear();
+    }
+
+    fn is_allocated(&self) -> bool {
+        self.weights.is_some()
+    }
+
+    fn get_size(&self) -> usize {
+        self.num_embeddings * self.dim * std::mem::size_of::<Weight>()
+    }
+
+    fn mark_for_use(&mut self) {
+        self.in_use = true;
+    }
+
+    fn free_from_use(&mut self) {
+        self.in_use = false;
+    }
+
+    fn is_in_use(&self) -> bool {
+        self.in_use
+    }
+}
+
+impl Layer for EmbeddingLayer {
+    fn forward(
+        &mut self,
+        input: &[f64],
+        _utils: WrappedUtils,
+    ) -> Vec<f64> {
+        assert!(self.is_allocated(), "Layer not allocated");
+        let index = self.index(input);
+        self.index_cache = Some(index);
+        self.row(index)
+    }
+
+    fn forward_into(
+        &mut self,
+        input: &[f64],
+        out: &mut Vec<f64>,
+        _utils: WrappedUtils,
+    ) {
+        assert!(self.is_allocated(), "Layer not allocated");
+        let index = self.index(input);
+        let weights = self.weights.as_ref().unwrap();
+        out.clear();
+        out.extend((0..self.dim).map(|j| weights.get_unchecked(index, j).value));
+    }
+
+    /// Caches the looked up index until the backward pass of the batch consumes it.
+    ///
+    /// If the batch cache is full, the oldest index is dropped and its backward pass contributes
+    /// no gradient. `try_forward_batch` reports a full cache as an error instead.
+    fn forward_batch(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<f64> {
+        assert!(self.is_allocated(), "Layer not allocated");
+        if self.batch_cache_is_full() && self.index_batch_cache.pop_front().is_some() {
+            self.evicted_batch_indices += 1;
+        }
+        let index = self.index(input);
+        self.index_batch_cache.push_back(index);
+        self.row(index)
+    }
+
+    fn batch_cache_is_full(&self) -> bool {
+        self.index_batch_cache.len() >= DEFAULT_BATCH_CACHE_LIMIT
+    }
+
+    fn batch_cache_len(&self) -> usize {
+        self.index_batch_cache.len()
+    }
+
+    fn input_size(&self) -> usize {
+        1
+    }
+
+    fn output_size(&self) -> usize {
+        self.dim
+    }
+
+    fn save(
+        &self,
+        path: String,
+    ) -> Result<(), Box<dyn Error>> {
+        if !self.is_allocated() {
+            // just copy the files
+            let original_path = self.layer_path.path();
+            if std::fs::metadata(original_path.clone()).is_err() {
+                return Ok(());
+            }
+            if original_path != path {
+                std::fs::copy(original_path, path).expect("Failed to copy file in save layer");
+            }
+            return Ok(());
+        }
+        save(path, &self.get_weights(), &vec![0.0; self.num_embeddings])
+    }
+
+    fn read(
+        &mut self,
+        path: String,
+    ) -> Result<(), Box<dyn Error>> {
+        self.read_weight(path)
+    }
+
+    fn get_weights(&self) -> WrappedMatrix<f64> {
+        let table = self.weights.as_ref().unwrap();
+        let weights = WrappedMatrix::new(table.rows(), table.cols());
+        for i in 0..table.rows() {
+            for j in 0..table.cols() {
+                weights.set_mut_unchecked(i, j, table.get_unchecked(i, j).value);
+            }
+        }
+        weights
+    }
+
+    fn get_biases(&self) -> Vec<f64> {
+        Vec::new()
+    }
+
+    fn cleanup(&self) {
+        // Remove the internal model directory from disk
+        if let Directory::Internal(dir) = &self.layer_path {
+            let path = Path::new(dir);
+            if path.is_file() {
+                std::fs::remove_file(dir).expect("Failed to remove file");
+            }
+        }
+    }
+}
+
+impl AllocatableLayer for EmbeddingLayer {
+    fn duplicate(
+        &mut self,
+        model_directory: String,
+        position_in_nn: usize,
+    ) -> Box<dyn AllocatableLayer + Send> {
+        self.deallocate();
+        let new_layer = Box::new(Self::new(
+            self.num_embeddings,
+            self.dim,
+            Directory::Internal(model_directory),
+            position_in_nn,
+        )) as Box<dyn AllocatableLayer + Send>;
+        new_layer.copy_on_filesystem(self.layer_path.path());
+        new_layer
+    }
+
+    fn copy_on_filesystem(
+        &self,
+        layer_path: String,
+    ) {
+        TrainableAllocatableLayer::copy_on_filesystem(self, layer_path);
+    }
+}
+
+impl TrainableLayer for EmbeddingLayer {
+    /// Backward pass for the embedding layer
+    ///
+    /// The gradient is accumulated only in the row that was looked up in the forward pass.
+    /// The index is not differentiable, so the returned input gradient is zero.
+    fn backward(
+        &mut self,
+        d_out: &[f64],
+        _utils: WrappedUtils,
+    ) -> Vec<f64> {
+        let index = self.index_cache.expect("Embedding forward must be called before backward.");
+        self.accumulate_row_gradient(index, d_out);
+        vec![0.0; 1]
+    }
+
+    /// Scatters the gradient into the row of the oldest cached index of the batch, the
+    /// backward passes follow the order of the forward passes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no index is cached.
+    fn backward_batch(
+        &mut self,
+        grad_output: &[f64],
+    ) -> Vec<f64> {
+        assert!(self.is_allocated(), "Layer not allocated");
+        if self.evicted_batch_indices > 0 {
+            self.evicted_batch_indices -= 1;
+            return vec![0.0; 1];
+        }
+        let index = self
+            .index_batch_cache
+            .pop_front()
+            .expect("No cached index for the batch backward pass");
+        self.accumulate_row_gradient(index, grad_output);
+        vec![0.0; 1]
+    }
+
+    fn clear_batch_cache(&mut self) {
+        self.index_batch_cache.clear();
+        self.evicted_batch_indices = 0;
+    }
+
+    /// Updates the looked up rows using their accumulated gradients
+    fn update_weights(
+        &mut self,
+        learning_rate: f64,
+        _utils: WrappedUtils,
+    ) {
+        assert!(self.is_allocated(), "Layer not allocated");
+        let weights = self.weights.as_ref().unwrap();
+        for &row in &self.touched_rows {
+            for j in 0..self.dim {
+                let mut weight = weights.get_unchecked(row, j);
+                weight.value -= learning_rate * weight.grad;
+                weights.set_mut_unchecked(row, j, weight);
+            }
+        }
+        self.reset_gradients();
+    }
+
+    fn assign_weights(
+        &mut self,
+        other: WrappedTrainableLayer,
+        carry_moments: bool,
+    ) {
+        self.assign_weights_and_biases(&other.get_weights(), &other.get_biases());
+        if carry_moments {
+            self.assign_moments(&other.moments());
+        }
+    }
+
+    /// Assigns the embedding table, the biases are ignored as the layer has none.
+    fn assign_weights_and_biases(
+        &mut self,
+        weights: &WrappedMatrix<f64>,
+        _biases: &[f64],
+    ) {
+        for i in 0..self.num_embeddings.min(weights.rows()) {
+            for j in 0..self.dim.min(weights.cols()) {
+                let w = Weight { value: weights.get_unchecked(i, j), grad: 0.0, m: 0.0, v: 0.0 };
+                self.weights.as_ref().unwrap().set_mut_unchecked(i, j, w);
+            }
+        }
+    }
+
+    /// Updates the looked up rows according to the Adam optimizer
+    fn adjust_adam(
+        &mut self,
+        t: usize,
+        learning_rate: f64,
+        beta1: f64,
+        beta2: f64,
+        epsilon: f64,
+        _utils: WrappedUtils,
+    ) {
+        let t_f: f64 = NumCast::from(t).expect("Failed to convert time step to f64");
+        let beta1_pow_t = beta1.powf(t_f);
+        let beta2_pow_t = beta2.powf(t_f);
+        let weights = self.weights.as_ref().unwrap();
+        for &row in &self.touched_rows {
+            for j in 0..self.dim {
+                let mut weight = weights.get_unchecked(row, j);
+                let grad = weight.grad;
+
+                // Update first and second moments
+                weight.m = beta1.mul_add(weight.m, (1.0 - beta1) * grad);
+                weight.v = beta2.mul_add(weight.v, (1.0 - beta2) * grad.powi(2));
+
+                // Bias correction
+                let m_hat = weight.m / (1.0 - beta1_pow_t);
+                let v_hat = weight.v / (1.0 - beta2_pow_t);
+
+                // Adjusted learning rate and update
+                let adjusted_learning_rate = learning_rate / (v_hat.sqrt() + epsilon);
+                weight.value -= adjusted_learning_rate * m_hat;
+                weights.set_mut_unchecked(row, j, weight);
+            }
+        }
+        self.reset_gradients();
+    }
+
+    fn grad_norm_sq(&self) -> f64 {
+        assert!(self.is_allocated(), "Layer not allocated");
+        let weights = self.weights.as_ref().unwrap();
+        self.touched_rows
+            .iter()
+            .map(|&row| {
+                (0..self.dim).map(|j| weights.get_unchecked(row, j).grad.powi(2)).sum::<f64>()
+            })
+            .sum()
+    }
+
+    /// The embedding table has no biases, so the bias gradients are empty.
+    fn gradients(&self) -> LayerGradients {
+        assert!(self.is_allocated(), "Layer not allocated");
+        let weights = self.weights.as_ref().unwrap();
+        let mut gradients = LayerGradients::zeros(self.num_embeddings, self.dim, 0);
+        for &row in &self.touched_rows {
+            for j in 0..self.dim {
+                gradients.weights.set_mut_unchecked(row, j, weights.get_unchecked(row, j).grad);
+            }
+        }
+        gradients
+    }
+
+    fn set_gradients(
+        &mut self,
+        gradients: &LayerGradients,
+    ) {
+        assert!(self.is_allocated(), "Layer not allocated");
+        self.reset_gradients();
+        let weights = self.weights.as_ref().unwrap();
+        for row in 0..self.num_embeddings {
+            let row_grads: Vec<f64> =
+                (0..self.dim).map(|j| *gradients.weights.get_unchecked(row, j)).collect();
+            if row_grads.iter().all(|grad| *grad == 0.0) {
+                continue;
+            }
+            for (j, grad) in row_grads.into_iter().enumerate() {
+                let mut weight = weights.get_unchecked(row, j);
+                weight.grad = grad;
+                weights.set_mut_unchecked(row, j, weight);
+            }
+            self.touched_rows.push(row);
+        }
+    }
+
+    /// An embedding looks up rows instead of multiplying its input with the table,
+    /// so there is nothing to normalize.
+    fn set_spectral_norm(
+        &mut self,
+        _enabled: bool,
+    ) {
+    }
+
+    /// The rows of the table are looked up, not weighted sums of the inputs.
+    fn constrain_max_norm(
+        &mut self,
+        _max_norm: f64,
+    ) {
+    }
+
+    fn reinitialize(
+        &mut self,
+        rng: &mut StdRng,
+    ) {
+        assert!(self.is_allocated(), "Layer not allocated");
+        self.initialize_weights(rng);
+        self.touched_rows.clear();
+    }
+
+    fn moments(&self) -> LayerMoments {
+        assert!(self.is_allocated(), "Layer not allocated");
+        let weights = self.weights.as_ref().unwrap();
+        let mut moments = LayerMoments::zeros(self.num_embeddings, self.dim, 0);
+        for i in 0..self.num_embeddings {
+            for j in 0..self.dim {
+                let weight = weights.get_unchecked(i, j);
+                moments.first.weights.set_mut_unchecked(i, j, weight.m);
+                moments.second.weights.set_mut_unchecked(i, j, weight.v);
+            }
+        }
+        moments
+    }
+
+    /// Assigns the moments of the embedding table, the biases are ignored as the layer has none.
+    fn assign_moments(
+        &mut self,
+        moments: &LayerMoments,
+    ) {
+        assert!(self.is_allocated(), "Layer not allocated");
+        let weights = self.weights.as_ref().unwrap();
+        for i in 0..self.num_embeddings.min(moments.first.weights.rows()) {
+            for j in 0..self.dim.min(moments.first.weights.cols()) {
+                let mut weight = weights.get_unchecked(i, j);
+                weight.m = *moments.first.weights.get_unchecked(i, j);
+                weight.v = *moments.second.weights.get_unchecked(i, j);
+                weights.set_mut_unchecked(i, j, weight);
+            }
+        }
+    }
+
+    /// The number of embeddings is fixed by the vocabulary, so the table cannot be resized.
+    fn resize_with(
+        &mut self,
+        _input_size: usize,
+        _output_size: usize,
+        _fill: FillStrategy,
+    ) -> Result<(), Box<dyn Error>> {
+        Err("Resizing is not supported for embedding layers".into())
+    }
+
+    fn save_weight(
+        &self,
+        path: String,
+    ) -> Result<(), Box<dyn Error>> {
+        if !self.is_allocated() {
+            // just copy the files
+            let original_path = self.layer_path.path();
+            if std::fs::metadata(original_path.clone()).is_err() {
+                return Ok(());
+            }
+            if original_path != path {
+                std::fs::copy(original_path, path)
+                    .expect("Failed to copy file in save layer weight");
+            }
+            return Ok(());
+        }
+        save_weight(path, self.weights.as_ref().unwrap(), &self.zero_biases())
+    }
+
+    fn read_weight(
+        &mut self,
+        path: String,
+    ) -> Result<(), Box<dyn Error>> {
+        let (weights, _) = read_weight(path)?;
+        self.num_embeddings = weights.rows();
+        self.dim = weights.cols();
+        self.weights = Some(weights);
+        self.touched_rows.clear();
+        Ok(())
+    }
+}
+
+impl TrainableAllocatableLayer for EmbeddingLayer {
+    fn duplicate(
+        &mut self,
+        model_directory: String,
+        position_in_nn: usize,
+    ) -> Box<dyn TrainableAllocatableLayer + Send> {
+        self.deallocate();
+        let new_layer = Box::new(Self::new(
+            self.num_embeddings,
+            self.dim,
+            Directory::Internal(model_directory),
+            position_in_nn,
+        )) as Box<dyn TrainableAllocatableLayer + Send>;
+        TrainableAllocatableLayer::copy_on_filesystem(new_layer.as_ref(), self.layer_path.path());
+        new_layer
+    }
+
+    fn copy_on_filesystem(
+        &self,
+        layer_path: String,
+    ) {
+        // Copy the layer to the new directory
+        let new_layer_path = self.layer_path.path();
+        if !self.layer_path.exists() {
+            // create the parent directory if it does not exist
+            let parent_dir = Path::new(&new_layer_path).parent().unwrap();
+            std::fs::create_dir_all(parent_dir).expect("Failed to create directory");
+        }
+        // Copy the file of the original path to the new path on the filesystem
+        if Path::new(&layer_path).is_file() {
+            std::fs::copy(layer_path, new_layer_path).expect("Failed to copy layer file");
+        }
+    }
+
+    fn as_trainable_layer_mut(&mut self) -> &mut dyn TrainableLayer {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utilities::util::Utils;
+
+    use super::*;
+
+    #[test]
+    fn test_embedding_layer() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut layer =
+            EmbeddingLayer::new(5, 3, Directory::Internal("test_model_embedding".to_string()), 0);
+        layer.allocate();
+        let table_before = layer.get_weights();
+
+        let output = layer.forward(&[2.0], utils.clone());
+        assert_eq!(output.len(), 3);
+        for (j, value) in output.iter().enumerate() {
+            assert!((value - table_before.get_unchecked(2, j)).abs() < f64::EPSILON);
+        }
+
+        let grad_input = layer.backward(&[1.0, -1.0, 0.5], utils.clone());
+        assert_eq!(grad_input, vec![0.0]);
+        layer.update_weights(0.1, utils);
+
+        let table_after = layer.get_weights();
+        for i in 0..5 {
+            for j in 0..3 {
+                let difference = table_after.get_unchecked(i, j) - table_before.get_unchecked(i, j);
+                if i == 2 {
+                    let expected = -0.1 * [1.0, -1.0, 0.5][j];
+                    assert!((difference - expected).abs() < 1e-12);
+                } else {
+                    assert!(difference.abs() < f64::EPSILON);
+                }
+            }
+        }
+
+        // the table survives a save and read round trip
+        layer.deallocate();
+        layer.allocate();
+        let table_read = layer.get_weights();
+        assert!((table_read.get_unchecked(2, 0) - table_after.get_unchecked(2, 0)).abs() < 1e-12);
+
+        drop(layer);
+        std::fs::remove_dir_all("test_model_embedding").unwrap();
+    }
+
+    #[test]
+    fn test_embedding_layer_batch() {
+        let mut layer = EmbeddingLayer::new(
+            4,
+            2,
+            Directory::Internal("test_model_embedding_batch".to_string()),
+            0,
+        );
+        layer.allocate();
+        let table = layer.get_weights();
+
+        assert!(layer.try_forward_batch(&[1.0, 2.0]).is_err());
+        for index in [1.0, 3.0, 1.0] {
+            let output = layer.try_forward_batch(&[index]).unwrap();
+            assert_eq!(output, layer.row(NumCast::from(index).unwrap()));
+        }
+        for grad in [[1.0, 0.0], [0.5, 0.5], [0.0, 2.0]] {
+            assert_eq!(layer.backward_batch(&grad), vec![0.0]);
+        }
+        let gradients = layer.gradients();
+        assert_eq!(
+            (0..4).map(|i| *gradients.weights.get_unchecked(i, 1)).collect::<Vec<_>>(),
+            vec![0.0, 2.0, 0.0, 0.5]
+        );
+        assert!((gradients.weights.get_unchecked(1, 0) - 1.0).abs() < f64::EPSILON);
+
+        layer.update_weights(0.1, WrappedUtils::new(Utils::new(1_000_000_000, 4)));
+        let updated = layer.get_weights();
+        assert!((table.get_unchecked(0, 0) - updated.get_unchecked(0, 0)).abs() < f64::EPSILON);
+        assert!((table.get_unchecked(3, 1) - 0.05 - updated.get_unchecked(3, 1)).abs() < 1e-12);
+
+        layer.forward_batch(&[2.0]);
+        layer.clear_batch_cache();
+        assert!(layer.index_batch_cache.is_empty());
+
+        drop(layer);
+        std::fs::remove_dir_all("test_model_embedding_batch").unwrap();
+    }
+}
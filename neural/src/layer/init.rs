@@ -0,0 +1,104 @@
+use num_traits::NumCast;
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+
+/// How the biases of a layer are initialized.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BiasInit {
+    /// All biases start at zero.
+    #[default]
+    Zeros,
+    /// All biases start at the given constant.
+    Constant(f64),
+    /// Biases are drawn uniformly from `[-bound, bound]`.
+    /// Use `1 / sqrt(fan_in)` as bound for Kaiming-uniform bias initialization.
+    Uniform(f64),
+}
+
+impl BiasInit {
+    /// Samples a single bias value according to the initialization scheme.
+    pub fn sample<R: Rng>(
+        &self,
+        rng: &mut R,
+    ) -> f64 {
+        match *self {
+            Self::Constant(value) => value,
+            Self::Uniform(bound) if bound > 0.0 => rng.gen_range(-bound..bound),
+            Self::Zeros | Self::Uniform(_) => 0.0,
+        }
+    }
+}
+
+/// How the weights of a layer are initialized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InitScheme {
+    /// Weights are drawn uniformly from `[-bound, bound)`.
+    Uniform(f64),
+    /// Weights are drawn from a normal distribution with variance `2 / (fan_in + fan_out)`.
+    GlorotNormal,
+    /// Weights are drawn from a normal distribution with variance `1 / fan_in`.
+    LecunNormal,
+}
+
+impl Default for InitScheme {
+    fn default() -> Self {
+        Self::Uniform(0.5)
+    }
+}
+
+impl InitScheme {
+    /// Returns the variance of the weights drawn by the initialization scheme.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the fan in or fan out cannot be converted to `f64`.
+    #[must_use]
+    pub fn variance(
+        &self,
+        fan_in: usize,
+        fan_out: usize,
+    ) -> f64 {
+        let fan_in_f64: f64 = NumCast::from(fan_in).expect("Failed to convert fan_in to f64");
+        let fan_out_f64: f64 = NumCast::from(fan_out).expect("Failed to convert fan_out to f64");
+        match *self {
+            Self::Uniform(bound) => bound * bound / 3.0,
+            Self::GlorotNormal => 2.0 / (fan_in_f64 + fan_out_f64),
+            Self::LecunNormal => 1.0 / fan_in_f64,
+        }
+    }
+
+    /// Samples a single weight value for a layer with the given fan in and fan out.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the normal distribution cannot be created.
+    pub fn sample<R: Rng>(
+        &self,
+        fan_in: usize,
+        fan_out: usize,
+        rng: &mut R,
+    ) -> f64 {
+        match *self {
+            Self::Uniform(bound) if bound > 0.0 => rng.gen_range(-bound..bound),
+            Self::Uniform(_) => 0.0,
+            Self::GlorotNormal | Self::LecunNormal => {
+                Normal::new(0.0, self.variance(fan_in, fan_out).sqrt())
+                    .expect("Failed to create normal distribution")
+                    .sample(rng)
+            },
+        }
+    }
+}
+
+/// How the new weights and biases are filled when a layer is grown with `resize_with`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FillStrategy {
+    /// New weights and biases start at zero.
+    #[default]
+    Zero,
+    /// New weights and biases are drawn with the initialization schemes of the layer.
+    Random,
+    /// New rows and columns tile the existing ones, e.g. the new row `i` copies the old
+    /// row `i % old_rows`.
+    Repeat,
+}
@@ -1,11 +1,26 @@
+use super::init::FillStrategy;
+use crate::training::optimizer::Optimizer;
 use crate::utilities::util::WrappedUtils;
 use alloc::allocatable::{Allocatable, WrappedAllocatableTrait};
-use matrix::mat::WrappedMatrix;
+use matrix::mat::{Matrix, WrappedMatrix};
 use utils::safer::safe_lock;
 
 use dyn_clone::DynClone;
+use rand::rngs::StdRng;
 use std::error::Error;
 use std::sync::{Arc, Mutex};
+/// Returns an error if the input does not have the expected number of values.
+fn check_layer_input_size(
+    expected: usize,
+    input: &[f64],
+) -> Result<(), Box<dyn Error>> {
+    if input.len() == expected {
+        Ok(())
+    } else {
+        Err(format!("Layer input size mismatch: expected {expected}, got {}", input.len()).into())
+    }
+}
+
 // A trait representing a layer in a neural network.
 /// Provides methods for the forward pass, backward pass, weight updates, and layer size information.
 pub trait Layer: std::fmt::Debug + DynClone + Allocatable {
@@ -24,12 +39,67 @@ pub trait Layer: std::fmt::Debug + DynClone + Allocatable {
         utils: WrappedUtils,
     ) -> Vec<f64>;
 
+    /// Performs the forward pass of the layer into `out`, which is cleared first.
+    /// Reusing `out` avoids allocating an output vector, nothing is cached for the backward pass.
+    fn forward_into(
+        &mut self,
+        input: &[f64],
+        out: &mut Vec<f64>,
+        utils: WrappedUtils,
+    ) {
+        let output = self.forward(input, utils);
+        out.clear();
+        out.extend_from_slice(&output);
+    }
+
     /// Performs the forward pass of the layer for inputs doing batch caching.
     fn forward_batch(
         &mut self,
         input: &[f64],
     ) -> Vec<f64>;
 
+    /// Performs the forward pass after checking the input against the input size of the layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the expected and actual sizes if the input size does not match.
+    fn try_forward(
+        &mut self,
+        input: &[f64],
+        utils: WrappedUtils,
+    ) -> Result<Vec<f64>, Box<dyn Error>> {
+        check_layer_input_size(self.input_size(), input)?;
+        Ok(self.forward(input, utils))
+    }
+
+    /// Performs the batch forward pass after checking the input against the input size of the layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the expected and actual sizes if the input size does not match,
+    /// or an error if the batch cache of the layer is full.
+    fn try_forward_batch(
+        &mut self,
+        input: &[f64],
+    ) -> Result<Vec<f64>, Box<dyn Error>> {
+        check_layer_input_size(self.input_size(), input)?;
+        if self.batch_cache_is_full() {
+            return Err("The batch cache of the layer is full, clear it after each batch".into());
+        }
+        Ok(self.forward_batch(input))
+    }
+
+    /// Returns whether `forward_batch` cannot cache another input until the cached inputs are
+    /// consumed by their backward passes or cleared. Layers without a batch cache are never full.
+    fn batch_cache_is_full(&self) -> bool {
+        false
+    }
+
+    /// Returns the number of cached inputs still waiting for their batch backward pass.
+    fn batch_cache_len(&self) -> usize {
+        0
+    }
+
     /// Returns the input size of the layer.
     ///
     /// # Returns
@@ -124,6 +194,15 @@ impl WrappedLayer {
         safe_lock(&self.layer).forward(input, utils)
     }
 
+    pub fn forward_into(
+        &mut self,
+        input: &[f64],
+        out: &mut Vec<f64>,
+        utils: WrappedUtils,
+    ) {
+        safe_lock(&self.layer).forward_into(input, out, utils);
+    }
+
     pub fn forward_batch(
         &mut self,
         input: &[f64],
@@ -131,6 +210,31 @@ impl WrappedLayer {
         safe_lock(&self.layer).forward_batch(input)
     }
 
+    /// Performs the forward pass after checking the input size.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input size does not match the input size of the layer.
+    pub fn try_forward(
+        &mut self,
+        input: &[f64],
+        utils: WrappedUtils,
+    ) -> Result<Vec<f64>, Box<dyn Error>> {
+        safe_lock(&self.layer).try_forward(input, utils)
+    }
+
+    /// Performs the batch forward pass after checking the input size.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input size does not match the input size of the layer.
+    pub fn try_forward_batch(
+        &mut self,
+        input: &[f64],
+    ) -> Result<Vec<f64>, Box<dyn Error>> {
+        safe_lock(&self.layer).try_forward_batch(input)
+    }
+
     #[must_use]
     pub fn input_size(&self) -> usize {
         safe_lock(&self.layer).input_size()
@@ -206,6 +310,81 @@ impl WrappedAllocatableTrait for WrappedLayer {
     }
 }
 
+/// The gradients of the weights and biases of a layer.
+#[derive(Debug, Clone)]
+pub struct LayerGradients {
+    /// The weight gradients, with the dimensions of the weight matrix.
+    pub weights: Matrix<f64>,
+    /// The bias gradients, one per output unit.
+    pub biases: Vec<f64>,
+}
+
+impl LayerGradients {
+    /// Creates zero gradients of the given dimensions.
+    #[must_use]
+    pub fn zeros(
+        rows: usize,
+        cols: usize,
+        num_biases: usize,
+    ) -> Self {
+        Self { weights: Matrix::new(rows, cols), biases: vec![0.0; num_biases] }
+    }
+
+    /// Adds the given gradients, which must have the same dimensions.
+    pub fn accumulate(
+        &mut self,
+        other: &Self,
+    ) {
+        for i in 0..self.weights.rows() {
+            for j in 0..self.weights.cols() {
+                *self.weights.get_mut_unchecked(i, j) += other.weights.get_unchecked(i, j);
+            }
+        }
+        for (bias, other_bias) in self.biases.iter_mut().zip(&other.biases) {
+            *bias += other_bias;
+        }
+    }
+
+    /// Multiplies all gradients with `factor`.
+    pub fn scale(
+        &mut self,
+        factor: f64,
+    ) {
+        for i in 0..self.weights.rows() {
+            for j in 0..self.weights.cols() {
+                *self.weights.get_mut_unchecked(i, j) *= factor;
+            }
+        }
+        for bias in &mut self.biases {
+            *bias *= factor;
+        }
+    }
+}
+
+/// The Adam optimizer moments of the weights and biases of a layer.
+#[derive(Debug, Clone)]
+pub struct LayerMoments {
+    /// The first moments, with the dimensions of the weights and biases.
+    pub first: LayerGradients,
+    /// The second moments, with the dimensions of the weights and biases.
+    pub second: LayerGradients,
+}
+
+impl LayerMoments {
+    /// Creates zero moments of the given dimensions.
+    #[must_use]
+    pub fn zeros(
+        rows: usize,
+        cols: usize,
+        num_biases: usize,
+    ) -> Self {
+        Self {
+            first: LayerGradients::zeros(rows, cols, num_biases),
+            second: LayerGradients::zeros(rows, cols, num_biases),
+        }
+    }
+}
+
 pub trait TrainableLayer: Layer {
     /// Performs the backward pass of the layer, computing the gradient based on the output gradient.
     ///
@@ -229,6 +408,10 @@ pub trait TrainableLayer: Layer {
         grad_output: &[f64],
     ) -> Vec<f64>;
 
+    /// Drops the inputs cached by `forward_batch`, to be called after the backward pass
+    /// of every batch so that the cache does not grow across batches.
+    fn clear_batch_cache(&mut self);
+
     /// Updates the weights of the layer based on the specified learning rate.
     ///
     /// # Arguments
@@ -240,10 +423,21 @@ pub trait TrainableLayer: Layer {
         utils: WrappedUtils,
     );
 
-    /// Assigns the weight of the input other layer
+    /// Assigns the weight of the input other layer.
+    /// With `carry_moments` the Adam moments of the overlapping region are carried over as well,
+    /// otherwise the optimizer starts from scratch.
     fn assign_weights(
         &mut self,
         other: WrappedTrainableLayer,
+        carry_moments: bool,
+    );
+
+    /// Assigns the given weight values and biases, resetting gradients and optimizer moments.
+    /// Values outside of the dimensions of the layer are ignored.
+    fn assign_weights_and_biases(
+        &mut self,
+        weights: &WrappedMatrix<f64>,
+        biases: &[f64],
     );
 
     /// Adjusts the weights according to the Adam optimizer.
@@ -257,6 +451,66 @@ pub trait TrainableLayer: Layer {
         utils: WrappedUtils,
     );
 
+    /// Returns the squared L2 norm of the current weight and bias gradients.
+    fn grad_norm_sq(&self) -> f64;
+
+    /// Returns a copy of the current weight and bias gradients.
+    fn gradients(&self) -> LayerGradients;
+
+    /// Returns a copy of the current Adam moments of the weights and biases.
+    fn moments(&self) -> LayerMoments;
+
+    /// Overwrites the Adam moments of the weights and biases.
+    /// Values outside of the dimensions of the layer are ignored.
+    fn assign_moments(
+        &mut self,
+        moments: &LayerMoments,
+    );
+
+    /// Overwrites the current weight and bias gradients, e.g. before calling `update_weights`.
+    fn set_gradients(
+        &mut self,
+        gradients: &LayerGradients,
+    );
+
+    /// Enables or disables spectral normalization, which divides the weights by a power
+    /// iteration estimate of their largest singular value in every forward pass.
+    /// Disabling it folds the current normalization into the weights.
+    /// Layers without a weight matrix applied to their inputs ignore it.
+    fn set_spectral_norm(
+        &mut self,
+        enabled: bool,
+    );
+
+    /// Rescales the incoming weights of every output neuron whose L2 norm exceeds
+    /// `max_norm`, so that it equals `max_norm` (max-norm regularization).
+    /// Layers without a weight matrix applied to their inputs ignore it.
+    fn constrain_max_norm(
+        &mut self,
+        max_norm: f64,
+    );
+
+    /// Re-runs the initialization of the weights and biases with the given random number
+    /// generator, which also zeros their gradients and optimizer moments.
+    fn reinitialize(
+        &mut self,
+        rng: &mut StdRng,
+    );
+
+    /// Resizes the layer to the given input and output sizes. Weights and biases of the
+    /// overlapping region are kept with their optimizer moments, the new ones are filled
+    /// according to `fill`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the layer cannot take the given sizes.
+    fn resize_with(
+        &mut self,
+        input_size: usize,
+        output_size: usize,
+        fill: FillStrategy,
+    ) -> Result<(), Box<dyn Error>>;
+
     /// Saves the layer to a file at the specified path.
     ///
     /// # Errors
@@ -293,6 +547,9 @@ pub trait TrainableAllocatableLayer: Allocatable + TrainableLayer {
         &self,
         layer_path: String,
     );
+
+    /// Returns the layer as a trainable layer, e.g. to pass it to an `Optimizer`.
+    fn as_trainable_layer_mut(&mut self) -> &mut dyn TrainableLayer;
 }
 
 #[derive(Debug, Clone)]
@@ -328,6 +585,15 @@ impl WrappedTrainableLayer {
         safe_lock(&self.layer).forward(input, utils)
     }
 
+    pub fn forward_into(
+        &mut self,
+        input: &[f64],
+        out: &mut Vec<f64>,
+        utils: WrappedUtils,
+    ) {
+        safe_lock(&self.layer).forward_into(input, out, utils);
+    }
+
     pub fn forward_batch(
         &mut self,
         input: &[f64],
@@ -335,6 +601,31 @@ impl WrappedTrainableLayer {
         safe_lock(&self.layer).forward_batch(input)
     }
 
+    /// Performs the forward pass after checking the input size.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input size does not match the input size of the layer.
+    pub fn try_forward(
+        &mut self,
+        input: &[f64],
+        utils: WrappedUtils,
+    ) -> Result<Vec<f64>, Box<dyn Error>> {
+        safe_lock(&self.layer).try_forward(input, utils)
+    }
+
+    /// Performs the batch forward pass after checking the input size.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input size does not match the input size of the layer.
+    pub fn try_forward_batch(
+        &mut self,
+        input: &[f64],
+    ) -> Result<Vec<f64>, Box<dyn Error>> {
+        safe_lock(&self.layer).try_forward_batch(input)
+    }
+
     #[must_use]
     pub fn input_size(&self) -> usize {
         safe_lock(&self.layer).input_size()
@@ -345,6 +636,18 @@ impl WrappedTrainableLayer {
         safe_lock(&self.layer).output_size()
     }
 
+    /// Saves the layer to a file at the specified path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the layer could not be saved to the specified path.
+    pub fn save(
+        &self,
+        path: String,
+    ) -> Result<(), Box<dyn Error>> {
+        safe_lock(&self.layer).save(path)
+    }
+
     /// Reads the layer from a file at the specified path.
     ///
     /// # Errors
@@ -367,6 +670,13 @@ impl WrappedTrainableLayer {
         safe_lock(&self.layer).get_biases()
     }
 
+    pub fn copy_on_filesystem(
+        &self,
+        layer_path: String,
+    ) {
+        safe_lock(&self.layer).copy_on_filesystem(layer_path);
+    }
+
     pub fn backward(
         &mut self,
         grad_output: &[f64],
@@ -382,6 +692,20 @@ impl WrappedTrainableLayer {
         safe_lock(&self.layer).backward_batch(grad_output)
     }
 
+    pub fn clear_batch_cache(&mut self) {
+        safe_lock(&self.layer).clear_batch_cache();
+    }
+
+    #[must_use]
+    pub fn batch_cache_is_full(&self) -> bool {
+        safe_lock(&self.layer).batch_cache_is_full()
+    }
+
+    #[must_use]
+    pub fn batch_cache_len(&self) -> usize {
+        safe_lock(&self.layer).batch_cache_len()
+    }
+
     pub fn update_weights(
         &mut self,
         learning_rate: f64,
@@ -393,8 +717,17 @@ impl WrappedTrainableLayer {
     pub fn assign_weights(
         &mut self,
         other: Self,
+        carry_moments: bool,
+    ) {
+        safe_lock(&self.layer).assign_weights(other, carry_moments);
+    }
+
+    pub fn assign_weights_and_biases(
+        &mut self,
+        weights: &WrappedMatrix<f64>,
+        biases: &[f64],
     ) {
-        safe_lock(&self.layer).assign_weights(other);
+        safe_lock(&self.layer).assign_weights_and_biases(weights, biases);
     }
 
     pub fn adjust_adam(
@@ -409,6 +742,16 @@ impl WrappedTrainableLayer {
         safe_lock(&self.layer).adjust_adam(t, learning_rate, beta1, beta2, epsilon, utils);
     }
 
+    /// Updates the weights of the layer with the given optimizer.
+    pub fn optimize(
+        &mut self,
+        optimizer: &mut dyn Optimizer,
+        learning_rate: f64,
+        utils: WrappedUtils,
+    ) {
+        optimizer.step(safe_lock(&self.layer).as_trainable_layer_mut(), learning_rate, utils);
+    }
+
     /// Saves the layer weights to a file at the specified path.
     ///
     /// # Errors
@@ -432,6 +775,70 @@ impl WrappedTrainableLayer {
     ) -> Result<(), Box<dyn Error>> {
         safe_lock(&self.layer).read_weight(path)
     }
+
+    #[must_use]
+    pub fn grad_norm_sq(&self) -> f64 {
+        safe_lock(&self.layer).grad_norm_sq()
+    }
+
+    #[must_use]
+    pub fn gradients(&self) -> LayerGradients {
+        safe_lock(&self.layer).gradients()
+    }
+
+    pub fn set_gradients(
+        &mut self,
+        gradients: &LayerGradients,
+    ) {
+        safe_lock(&self.layer).set_gradients(gradients);
+    }
+
+    pub fn set_spectral_norm(
+        &mut self,
+        enabled: bool,
+    ) {
+        safe_lock(&self.layer).set_spectral_norm(enabled);
+    }
+
+    pub fn constrain_max_norm(
+        &mut self,
+        max_norm: f64,
+    ) {
+        safe_lock(&self.layer).constrain_max_norm(max_norm);
+    }
+
+    pub fn reinitialize(
+        &mut self,
+        rng: &mut StdRng,
+    ) {
+        safe_lock(&self.layer).reinitialize(rng);
+    }
+
+    /// Resizes the layer, filling new weights and biases according to `fill`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the layer cannot take the given sizes.
+    pub fn resize_with(
+        &mut self,
+        input_size: usize,
+        output_size: usize,
+        fill: FillStrategy,
+    ) -> Result<(), Box<dyn Error>> {
+        safe_lock(&self.layer).resize_with(input_size, output_size, fill)
+    }
+
+    #[must_use]
+    pub fn moments(&self) -> LayerMoments {
+        safe_lock(&self.layer).moments()
+    }
+
+    pub fn assign_moments(
+        &mut self,
+        moments: &LayerMoments,
+    ) {
+        safe_lock(&self.layer).assign_moments(moments);
+    }
 }
 
 impl WrappedAllocatableTrait for WrappedTrainableLayer {
@@ -1,5 +1,11 @@
 pub mod dense_layer;
+pub mod dropout;
+pub mod embedding;
+pub mod init;
 pub mod layer_trait;
+pub mod prelu;
+pub mod quantized;
+pub mod residual;
 
 pub use layer_trait::AllocatableLayer;
 pub use layer_trait::TrainableAllocatableLayer;
@@ -0,0 +1,637 @@
+use super::dense_layer::{read_weight, save, save_weight, Bias, Weight, DEFAULT_BATCH_CACHE_LIMIT};
+use super::init::FillStrategy;
+use super::layer_trait::Layer;
+use super::layer_trait::TrainableLayer;
+use super::layer_trait::WrappedTrainableLayer;
+use super::layer_trait::{LayerGradients, LayerMoments};
+use super::AllocatableLayer;
+use super::TrainableAllocatableLayer;
+use crate::nn::directory::Directory;
+use crate::utilities::util::WrappedUtils;
+use alloc::allocatable::Allocatable;
+
+use matrix::mat::WrappedMatrix;
+
+use num_traits::cast::NumCast;
+use rand::rngs::StdRng;
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::path::Path;
+
+/// The slope a new `PReLULayer` starts with.
+pub const DEFAULT_PRELU_SLOPE: f64 = 0.25;
+
+/// A parametric `ReLU` whose negative slope is learned by backpropagation.
+///
+/// The output is `x` for positive inputs and `slope * x` otherwise, with one slope shared by
+/// all values. The slope is stored like the weights of a dense layer as a `1 x 1` matrix,
+/// the single bias in the file is unused and zero.
+#[derive(Debug, Clone)]
+pub struct PReLULayer {
+    size: usize,
+    initial_slope: f64,
+    slope: Option<Weight>,                 // The learnable negative slope
+    input_cache: Option<Vec<f64>>,         // Cache input for use in backward pass
+    input_batch_cache: VecDeque<Vec<f64>>, // Cache the batch inputs for the batch backward pass
+    evicted_batch_inputs: usize,           // Dropped batch inputs whose backward passes are due
+    in_use: bool,
+    layer_path: Directory,
+}
+
+impl PReLULayer {
+    /// Creates a new `PReLULayer` for `size` values starting with `DEFAULT_PRELU_SLOPE`.
+    #[must_use]
+    pub fn new(
+        size: usize,
+        model_directory: Directory,
+        position_in_nn: usize,
+    ) -> Self {
+        // create a Directory type which has the path model_directory/layers/layer_{position_in_nn}.txt
+        let layer_path = match model_directory {
+            Directory::User(path) => {
+                Directory::User(format!("{path}/layers/layer_{position_in_nn}.txt"))
+            },
+            Directory::Internal(path) => {
+                Directory::Internal(format!("{path}/layers/layer_{position_in_nn}.txt"))
+            },
+        };
+        Self {
+            size,
+            initial_slope: DEFAULT_PRELU_SLOPE,
+            slope: None,
+            input_cache: None,
+            input_batch_cache: VecDeque::new(),
+            evicted_batch_inputs: 0,
+            in_use: false,
+            layer_path,
+        }
+    }
+
+    /// Sets the slope used when the layer is allocated without a saved slope on disk.
+    #[must_use]
+    pub const fn with_initial_slope(
+        mut self,
+        initial_slope: f64,
+    ) -> Self {
+        self.initial_slope = initial_slope;
+        self
+    }
+
+    /// Returns the current negative slope.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the layer is not allocated.
+    #[must_use]
+    pub fn slope(&self) -> f64 {
+        self.slope.expect("Layer not allocated").value
+    }
+
+    /// Returns the slope as the `1 x 1` weight matrix stored in the layer file.
+    fn slope_matrix(&self) -> WrappedMatrix<Weight> {
+        let matrix = WrappedMatrix::new(1, 1);
+        matrix.set_mut_unchecked(0, 0, self.slope.expect("Layer not allocated"));
+        matrix
+    }
+
+    /// Returns the unused bias stored alongside the slope in the layer file.
+    fn zero_biases() -> Vec<Bias> {
+        vec![Bias::default(); 1]
+    }
+
+    /// Applies the slope to the non positive inputs.
+    fn activate(
+        &self,
+        input: &[f64],
+    ) -> Vec<f64> {
+        let slope = self.slope();
+        input.iter().map(|&x| if x > 0.0 { x } else { slope * x }).collect()
+    }
+
+    /// Accumulates the slope gradient for the given input and returns the input gradient.
+    fn accumulate_gradient(
+        &mut self,
+        d_out: &[f64],
+        input: &[f64],
+    ) -> Vec<f64> {
+        let slope = self.slope.as_mut().expect("Layer not allocated");
+        let mut grad_input = Vec::with_capacity(d_out.len());
+        for (&grad, &x) in d_out.iter().zip(input) {
+            if x > 0.0 {
+                grad_input.push(grad);
+            } else {
+                slope.grad += grad * x;
+                grad_input.push(slope.value * grad);
+            }
+        }
+        grad_input
+    }
+
+    fn reset_gradient(&mut self) {
+        if let Some(slope) = self.slope.as_mut() {
+            slope.grad = 0.0;
+        }
+    }
+}
+
+impl Drop for PReLULayer {
+    fn drop(&mut self) {
+        // Save the model to ensure that everything is on disk if it is a user_model_directory
+        if let Directory::User(dir) = &self.layer_path {
+            if std::fs::metadata(dir).is_ok() {
+                // Save the model to disk
+                self.deallocate();
+            }
+        }
+    }
+}
+
+impl Allocatable for PReLULayer {
+    fn allocate(&mut self) {
+        if self.is_allocated() {
+            return;
+        }
+        if self.layer_path.exists() {
+            let (weights, _) =
+                read_weight(self.layer_path.path()).expect("Failed to read PReLU layer slope");
+            if weights.rows() == 1 && weights.cols() == 1 {
+                self.slope = Some(weights.get_unchecked(0, 0));
+                return;
+            }
+        }
+        self.slope = Some(Weight { value: self.initial_slope, grad: 0.0, m: 0.0, v: 0.0 });
+        save_weight(self.layer_path.path(), &self.slope_matrix(), &Self::zero_biases())
+            .expect("Failed to save PReLU layer slope");
+    }
+
+    fn deallocate(&mut self) {
+        if self.is_allocated() {
+            save_weight(self.layer_path.path(), &self.slope_matrix(), &Self::zero_biases())
+                .expect("Failed to save PReLU layer slope");
+        }
+        self.slope = None;
+        self.input_cache = None;
+        self.input_batch_cache.clear();
+        self.evicted_batch_inputs = 0;
+    }
+
+    fn is_allocated(&self) -> bool {
+        self.slope.is_some()
+    }
+
+    fn get_size(&self) -> usize {
+        std::mem::size_of::<Weight>()
+    }
+
+    fn mark_for_use(&mut self) {
+        self.in_use = true;
+    }
+
+    fn free_from_use(&mut self) {
+        self.in_use = false;
+    }
+
+    fn is_in_use(&self) -> bool {
+        self.in_use
+    }
+}
+
+impl Layer for PReLULayer {
+    fn forward(
+        &mut self,
+        input: &[f64],
+        _utils: WrappedUtils,
+    ) -> Vec<f64> {
+        self.input_cache = Some(input.to_vec());
+        self.activate(input)
+    }
+
+    fn forward_into(
+        &mut self,
+        input: &[f64],
+        out: &mut Vec<f64>,
+        _utils: WrappedUtils,
+    ) {
+        let slope = self.slope();
+        out.clear();
+        out.extend(input.iter().map(|&x| if x > 0.0 { x } else { slope * x }));
+    }
+
+    /// Caches the input until the backward pass of the batch consumes it.
+    ///
+    /// If the batch cache is full, the oldest input is dropped and its backward pass contributes
+    /// no gradient. `try_forward_batch` reports a full cache as an error instead.
+    fn forward_batch(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<f64> {
+        if self.batch_cache_is_full() && self.input_batch_cache.pop_front().is_some() {
+            self.evicted_batch_inputs += 1;
+        }
+        let output = self.activate(input);
+        self.input_batch_cache.push_back(input.to_vec());
+        output
+    }
+
+    fn batch_cache_is_full(&self) -> bool {
+        self.input_batch_cache.len() >= DEFAULT_BATCH_CACHE_LIMIT
+    }
+
+    fn batch_cache_len(&self) -> usize {
+        self.input_batch_cache.len()
+    }
+
+    fn input_size(&self) -> usize {
+        self.size
+    }
+
+    fn output_size(&self) -> usize {
+        self.size
+    }
+
+    fn save(
+        &self,
+        path: String,
+    ) -> Result<(), Box<dyn Error>> {
+        if !self.is_allocated() {
+            // just copy the files
+            let original_path = self.layer_path.path();
+            if std::fs::metadata(original_path.clone()).is_err() {
+                return Ok(());
+            }
+            if original_path != path {
+                std::fs::copy(original_path, path).expect("Failed to copy file in save layer");
+            }
+            return Ok(());
+        }
+        save(path, &self.get_weights(), &self.get_biases())
+    }
+
+    fn read(
+        &mut self,
+        path: String,
+    ) -> Result<(), Box<dyn Error>> {
+        self.read_weight(path)
+    }
+
+    fn get_weights(&self) -> WrappedMatrix<f64> {
+        let weights = WrappedMatrix::new(1, 1);
+        weights.set_mut_unchecked(0, 0, self.slope());
+        weights
+    }
+
+    fn get_biases(&self) -> Vec<f64> {
+        vec![0.0; 1]
+    }
+
+    fn cleanup(&self) {
+        // Remove the internal model directory from disk
+        if let Directory::Internal(dir) = &self.layer_path {
+            let path = Path::new(dir);
+            if path.is_file() {
+                std::fs::remove_file(dir).expect("Failed to remove file");
+            }
+        }
+    }
+}
+
+impl AllocatableLayer for PReLULayer {
+    fn duplicate(
+        &mut self,
+        model_directory: String,
+        position_in_nn: usize,
+    ) -> Box<dyn AllocatableLayer + Send> {
+        self.deallocate();
+        let new_layer = Box::new(
+            Self::new(self.size, Directory::Internal(model_directory), position_in_nn)
+                .with_initial_slope(self.initial_slope),
+        ) as Box<dyn AllocatableLayer + Send>;
+        new_layer.copy_on_filesystem(self.layer_path.path());
+        new_layer
+    }
+
+    fn copy_on_filesystem(
+        &self,
+        layer_path: String,
+    ) {
+        TrainableAllocatableLayer::copy_on_filesystem(self, layer_path);
+    }
+}
+
+impl TrainableLayer for PReLULayer {
+    /// Backward pass for the `PReLU` layer
+    ///
+    /// The slope gradient accumulates `grad * x` over all non positive inputs,
+    /// the input gradient is scaled by the slope for those inputs.
+    fn backward(
+        &mut self,
+        d_out: &[f64],
+        _utils: WrappedUtils,
+    ) -> Vec<f64> {
+        let input =
+            self.input_cache.clone().expect("PReLU forward must be called before backward.");
+        self.accumulate_gradient(d_out, &input)
+    }
+
+    /// Accumulates the slope gradient for the oldest cached input of the batch, the
+    /// backward passes follow the order of the forward passes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no input is cached.
+    fn backward_batch(
+        &mut self,
+        grad_output: &[f64],
+    ) -> Vec<f64> {
+        if self.evicted_batch_inputs > 0 {
+            self.evicted_batch_inputs -= 1;
+            return vec![0.0; grad_output.len()];
+        }
+        let input = self
+            .input_batch_cache
+            .pop_front()
+            .expect("No cached input for the batch backward pass");
+        self.accumulate_gradient(grad_output, &input)
+    }
+
+    fn clear_batch_cache(&mut self) {
+        self.input_batch_cache.clear();
+        self.evicted_batch_inputs = 0;
+    }
+
+    /// Updates the slope using its accumulated gradient
+    fn update_weights(
+        &mut self,
+        learning_rate: f64,
+        _utils: WrappedUtils,
+    ) {
+        let slope = self.slope.as_mut().expect("Layer not allocated");
+        slope.value -= learning_rate * slope.grad;
+        self.reset_gradient();
+    }
+
+    fn assign_weights(
+        &mut self,
+        other: WrappedTrainableLayer,
+        carry_moments: bool,
+    ) {
+        self.assign_weights_and_biases(&other.get_weights(), &other.get_biases());
+        if carry_moments {
+            self.assign_moments(&other.moments());
+        }
+    }
+
+    /// Assigns the slope from the first entry of the weights, the biases are ignored.
+    fn assign_weights_and_biases(
+        &mut self,
+        weights: &WrappedMatrix<f64>,
+        _biases: &[f64],
+    ) {
+        if weights.rows() > 0 && weights.cols() > 0 {
+            self.slope =
+                Some(Weight { value: weights.get_unchecked(0, 0), grad: 0.0, m: 0.0, v: 0.0 });
+        }
+    }
+
+    /// Updates the slope according to the Adam optimizer
+    fn adjust_adam(
+        &mut self,
+        t: usize,
+        learning_rate: f64,
+        beta1: f64,
+        beta2: f64,
+        epsilon: f64,
+        _utils: WrappedUtils,
+    ) {
+        let t_f: f64 = NumCast::from(t).expect("Failed to convert time step to f64");
+        let slope = self.slope.as_mut().expect("Layer not allocated");
+        let grad = slope.grad;
+
+        // Update first and second moments
+        slope.m = beta1.mul_add(slope.m, (1.0 - beta1) * grad);
+        slope.v = beta2.mul_add(slope.v, (1.0 - beta2) * grad.powi(2));
+
+        // Bias correction
+        let m_hat = slope.m / (1.0 - beta1.powf(t_f));
+        let v_hat = slope.v / (1.0 - beta2.powf(t_f));
+
+        // Adjusted learning rate and update
+        let adjusted_learning_rate = learning_rate / (v_hat.sqrt() + epsilon);
+        slope.value -= adjusted_learning_rate * m_hat;
+        self.reset_gradient();
+    }
+
+    fn grad_norm_sq(&self) -> f64 {
+        self.slope.expect("Layer not allocated").grad.powi(2)
+    }
+
+    /// The slope gradient is the single weight gradient, the bias gradient is always zero.
+    fn gradients(&self) -> LayerGradients {
+        let mut gradients = LayerGradients::zeros(1, 1, 1);
+        gradients.weights.set_mut_unchecked(0, 0, self.slope.expect("Layer not allocated").grad);
+        gradients
+    }
+
+    fn set_gradients(
+        &mut self,
+        gradients: &LayerGradients,
+    ) {
+        let slope = self.slope.as_mut().expect("Layer not allocated");
+        slope.grad = *gradients.weights.get_unchecked(0, 0);
+    }
+
+    /// The slope is not a weight matrix, so there is nothing to normalize.
+    fn set_spectral_norm(
+        &mut self,
+        _enabled: bool,
+    ) {
+    }
+
+    /// The slope is not a weight matrix, so there is nothing to constrain.
+    fn constrain_max_norm(
+        &mut self,
+        _max_norm: f64,
+    ) {
+    }
+
+    /// The slope is not random, it starts again from the initial slope.
+    fn reinitialize(
+        &mut self,
+        _rng: &mut StdRng,
+    ) {
+        assert!(self.is_allocated(), "Layer not allocated");
+        self.slope = Some(Weight { value: self.initial_slope, grad: 0.0, m: 0.0, v: 0.0 });
+    }
+
+    fn moments(&self) -> LayerMoments {
+        let slope = self.slope.expect("Layer not allocated");
+        let mut moments = LayerMoments::zeros(1, 1, 1);
+        moments.first.weights.set_mut_unchecked(0, 0, slope.m);
+        moments.second.weights.set_mut_unchecked(0, 0, slope.v);
+        moments
+    }
+
+    /// Assigns the moments of the slope from the first entry of the weights.
+    fn assign_moments(
+        &mut self,
+        moments: &LayerMoments,
+    ) {
+        let slope = self.slope.as_mut().expect("Layer not allocated");
+        if moments.first.weights.rows() > 0 && moments.first.weights.cols() > 0 {
+            slope.m = *moments.first.weights.get_unchecked(0, 0);
+            slope.v = *moments.second.weights.get_unchecked(0, 0);
+        }
+    }
+
+    /// The slope is shared by all values, so only the size changes and there is nothing to fill.
+    fn resize_with(
+        &mut self,
+        input_size: usize,
+        output_size: usize,
+        _fill: FillStrategy,
+    ) -> Result<(), Box<dyn Error>> {
+        if input_size != output_size {
+            return Err(format!(
+                "PReLU layer needs matching sizes: input size {input_size}, output size {output_size}"
+            )
+            .into());
+        }
+        self.size = input_size;
+        self.input_cache = None;
+        self.input_batch_cache.clear();
+        self.evicted_batch_inputs = 0;
+        Ok(())
+    }
+
+    fn save_weight(
+        &self,
+        path: String,
+    ) -> Result<(), Box<dyn Error>> {
+        if !self.is_allocated() {
+            // just copy the files
+            let original_path = self.layer_path.path();
+            if std::fs::metadata(original_path.clone()).is_err() {
+                return Ok(());
+            }
+            if original_path != path {
+                std::fs::copy(original_path, path)
+                    .expect("Failed to copy file in save layer weight");
+            }
+            return Ok(());
+        }
+        save_weight(path, &self.slope_matrix(), &Self::zero_biases())
+    }
+
+    fn read_weight(
+        &mut self,
+        path: String,
+    ) -> Result<(), Box<dyn Error>> {
+        let (weights, _) = read_weight(path)?;
+        if weights.rows() != 1 || weights.cols() != 1 {
+            return Err(format!(
+                "Invalid PReLU slope format: expected 1 x 1, found {} x {}",
+                weights.rows(),
+                weights.cols()
+            )
+            .into());
+        }
+        self.slope = Some(weights.get_unchecked(0, 0));
+        Ok(())
+    }
+}
+
+impl TrainableAllocatableLayer for PReLULayer {
+    fn duplicate(
+        &mut self,
+        model_directory: String,
+        position_in_nn: usize,
+    ) -> Box<dyn TrainableAllocatableLayer + Send> {
+        self.deallocate();
+        let new_layer = Box::new(
+            Self::new(self.size, Directory::Internal(model_directory), position_in_nn)
+                .with_initial_slope(self.initial_slope),
+        ) as Box<dyn TrainableAllocatableLayer + Send>;
+        TrainableAllocatableLayer::copy_on_filesystem(new_layer.as_ref(), self.layer_path.path());
+        new_layer
+    }
+
+    fn copy_on_filesystem(
+        &self,
+        layer_path: String,
+    ) {
+        // Copy the layer to the new directory
+        let new_layer_path = self.layer_path.path();
+        if !self.layer_path.exists() {
+            // create the parent directory if it does not exist
+            let parent_dir = Path::new(&new_layer_path).parent().unwrap();
+            std::fs::create_dir_all(parent_dir).expect("Failed to create directory");
+        }
+        // Copy the file of the original path to the new path on the filesystem
+        if Path::new(&layer_path).is_file() {
+            std::fs::copy(layer_path, new_layer_path).expect("Failed to copy layer file");
+        }
+    }
+
+    fn as_trainable_layer_mut(&mut self) -> &mut dyn TrainableLayer {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utilities::util::Utils;
+
+    use super::*;
+
+    #[test]
+    fn test_prelu_layer_learns_slope() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut layer = PReLULayer::new(3, Directory::Internal("test_model_prelu".to_string()), 0);
+        layer.allocate();
+        assert!((layer.slope() - DEFAULT_PRELU_SLOPE).abs() < f64::EPSILON);
+
+        let input = vec![-2.0, 1.0, -0.5];
+        let output = layer.forward(&input, utils.clone());
+        assert_eq!(output, vec![-0.5, 1.0, -0.125]);
+
+        // learn a slope of 0.5 from the negative inputs with a squared error
+        let target: Vec<f64> = input.iter().map(|&x| if x > 0.0 { x } else { 0.5 * x }).collect();
+        for _ in 0..200 {
+            let output = layer.forward(&input, utils.clone());
+            let grad: Vec<f64> = output.iter().zip(&target).map(|(o, t)| 2.0 * (o - t)).collect();
+            let grad_input = layer.backward(&grad, utils.clone());
+            assert_eq!(grad_input.len(), 3);
+            layer.update_weights(0.05, utils.clone());
+        }
+        assert!((layer.slope() - 0.5).abs() < 1e-3);
+
+        // the slope survives a save and read round trip
+        layer.deallocate();
+        layer.allocate();
+        assert!((layer.slope() - 0.5).abs() < 1e-3);
+
+        drop(layer);
+        std::fs::remove_dir_all("test_model_prelu").unwrap();
+    }
+    #[test]
+    fn test_prelu_layer_batch() {
+        let mut layer =
+            PReLULayer::new(2, Directory::Internal("test_model_prelu_batch".to_string()), 0);
+        layer.allocate();
+
+        assert_eq!(layer.try_forward_batch(&[-2.0, 1.0]).unwrap(), vec![-0.5, 1.0]);
+        assert_eq!(layer.try_forward_batch(&[3.0, -1.0]).unwrap(), vec![3.0, -0.25]);
+        // the gradients are matched with the inputs in the order of the forward passes
+        assert_eq!(layer.backward_batch(&[1.0, 1.0]), vec![0.25, 1.0]);
+        assert_eq!(layer.backward_batch(&[2.0, 2.0]), vec![2.0, 0.5]);
+        assert!((layer.gradients().weights.get_unchecked(0, 0) + 4.0).abs() < f64::EPSILON);
+
+        layer.forward_batch(&[1.0, 1.0]);
+        layer.clear_batch_cache();
+        assert!(layer.input_batch_cache.is_empty());
+
+        drop(layer);
+        std::fs::remove_dir_all("test_model_prelu_batch").unwrap();
+    }
+}
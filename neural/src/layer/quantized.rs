@@ -0,0 +1,163 @@
+use matrix::mat::WrappedMatrix;
+
+/// Smallest and largest value of a quantized weight.
+const INT8_MIN: f64 = -128.0;
+const INT8_MAX: f64 = 127.0;
+
+/// A dense layer whose weights are quantized to `i8` with a per tensor scale and zero point.
+///
+/// A weight `w` is stored as `q = round(w / scale) + zero_point` and restored as
+/// `(q - zero_point) * scale`. The range of the weights is widened to include zero, which
+/// keeps zero exact, and every restored weight is within `scale / 2` of the original one.
+/// The biases are kept as `f64`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantizedDenseLayer {
+    rows: usize,
+    cols: usize,
+    weights: Vec<i8>,
+    scale: f64,
+    zero_point: i8,
+    biases: Vec<f64>,
+}
+
+impl QuantizedDenseLayer {
+    /// Quantizes the given weights, one row per output unit, and keeps the biases.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the number of biases differs from the number of rows.
+    #[must_use]
+    pub fn new(
+        weights: &WrappedMatrix<f64>,
+        biases: &[f64],
+    ) -> Self {
+        let (rows, cols) = (weights.rows(), weights.cols());
+        assert_eq!(biases.len(), rows, "every output unit needs a bias");
+        let values = (0..rows)
+            .flat_map(|i| (0..cols).map(move |j| weights.get_unchecked(i, j)))
+            .collect::<Vec<f64>>();
+        let min = values.iter().copied().fold(0.0, f64::min);
+        let max = values.iter().copied().fold(0.0, f64::max);
+        let scale = if max > min { (max - min) / (INT8_MAX - INT8_MIN) } else { 1.0 };
+        let zero_point = to_i8((INT8_MIN - min / scale).round());
+        let quantized = values
+            .iter()
+            .map(|value| to_i8((value / scale).round() + f64::from(zero_point)))
+            .collect();
+        Self { rows, cols, weights: quantized, scale, zero_point, biases: biases.to_vec() }
+    }
+
+    #[must_use]
+    pub const fn input_size(&self) -> usize {
+        self.cols
+    }
+
+    #[must_use]
+    pub const fn output_size(&self) -> usize {
+        self.rows
+    }
+
+    #[must_use]
+    pub const fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    #[must_use]
+    pub const fn zero_point(&self) -> i8 {
+        self.zero_point
+    }
+
+    /// Returns the quantized weights row by row.
+    #[must_use]
+    pub fn quantized_weights(&self) -> &[i8] {
+        &self.weights
+    }
+
+    #[must_use]
+    pub fn biases(&self) -> &[f64] {
+        &self.biases
+    }
+
+    /// Returns the restored `f64` weights.
+    #[must_use]
+    pub fn dequantized_weights(&self) -> WrappedMatrix<f64> {
+        let weights = WrappedMatrix::new(self.rows, self.cols);
+        for i in 0..self.rows {
+            for j in 0..self.cols {
+                weights.set_mut_unchecked(i, j, self.dequantize(self.weights[i * self.cols + j]));
+            }
+        }
+        weights
+    }
+
+    /// Computes the output of the layer, dequantizing the weights on the fly.
+    /// Every output is within `scale / 2 * sum(|input|)` of the output of the `f64` weights.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the input size does not match the input size of the layer.
+    #[must_use]
+    pub fn forward(
+        &self,
+        input: &[f64],
+    ) -> Vec<f64> {
+        assert_eq!(input.len(), self.cols, "Layer input size mismatch");
+        let zero_point = i32::from(self.zero_point);
+        (0..self.rows)
+            .zip(&self.biases)
+            .map(|(i, bias)| {
+                let sum = self.weights[i * self.cols..(i + 1) * self.cols]
+                    .iter()
+                    .zip(input)
+                    .map(|(&q, x)| f64::from(i32::from(q) - zero_point) * x)
+                    .sum::<f64>();
+                sum.mul_add(self.scale, *bias)
+            })
+            .collect()
+    }
+
+    fn dequantize(
+        &self,
+        value: i8,
+    ) -> f64 {
+        f64::from(i32::from(value) - i32::from(self.zero_point)) * self.scale
+    }
+}
+
+/// Converts the rounded value to `i8`, clamping it to the range of `i8`.
+fn to_i8(value: f64) -> i8 {
+    num_traits::cast(value.clamp(INT8_MIN, INT8_MAX)).expect("Failed to convert value to i8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantization_error_is_bounded_by_half_the_scale() {
+        let weights = WrappedMatrix::new(2, 3);
+        for (k, value) in [0.5, -0.25, 0.0, 1.5, -0.75, 0.1].into_iter().enumerate() {
+            weights.set_mut_unchecked(k / 3, k % 3, value);
+        }
+        let layer = QuantizedDenseLayer::new(&weights, &[0.1, -0.2]);
+        assert!((layer.scale() - 2.25 / 255.0).abs() < 1e-15);
+
+        let restored = layer.dequantized_weights();
+        for i in 0..2 {
+            for j in 0..3 {
+                let error = (restored.get_unchecked(i, j) - weights.get_unchecked(i, j)).abs();
+                assert!(error <= layer.scale() / 2.0 + 1e-15);
+            }
+        }
+        // zero stays exact
+        assert!(restored.get_unchecked(0, 2).abs() < f64::EPSILON);
+
+        let input = [1.0, 2.0, -1.0];
+        let output = layer.forward(&input);
+        for (i, value) in output.iter().enumerate() {
+            let expected = (0..3).map(|j| restored.get_unchecked(i, j) * input[j]).sum::<f64>()
+                + layer.biases()[i];
+            assert!((value - expected).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,357 @@
+use super::init::FillStrategy;
+use super::layer_trait::Layer;
+use super::layer_trait::TrainableLayer;
+use super::layer_trait::WrappedTrainableLayer;
+use super::layer_trait::{LayerGradients, LayerMoments};
+use super::TrainableAllocatableLayer;
+use crate::utilities::util::WrappedUtils;
+use alloc::allocatable::{Allocatable, WrappedAllocatableTrait};
+
+use matrix::mat::WrappedMatrix;
+use rand::rngs::StdRng;
+
+use std::error::Error;
+
+/// A residual block adding a skip connection around an inner layer.
+///
+/// The output is `inner.forward(x) + x`, so the gradient with respect to the input is the
+/// gradient of the inner layer plus the output gradient flowing through the skip path.
+#[derive(Debug, Clone)]
+pub struct ResidualBlock {
+    inner: WrappedTrainableLayer,
+}
+
+impl ResidualBlock {
+    /// Creates a new `ResidualBlock` around the given inner layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input size of the inner layer differs from its output size.
+    pub fn new(inner: WrappedTrainableLayer) -> Result<Self, Box<dyn Error>> {
+        if inner.input_size() != inner.output_size() {
+            return Err(format!(
+                "Residual block needs matching sizes: input size {}, output size {}",
+                inner.input_size(),
+                inner.output_size()
+            )
+            .into());
+        }
+        Ok(Self { inner })
+    }
+
+    /// Returns the wrapped inner layer.
+    #[must_use]
+    pub fn inner(&self) -> WrappedTrainableLayer {
+        self.inner.clone()
+    }
+}
+
+impl Allocatable for ResidualBlock {
+    fn allocate(&mut self) {
+        self.inner.allocate();
+    }
+
+    fn deallocate(&mut self) {
+        self.inner.deallocate();
+    }
+
+    fn is_allocated(&self) -> bool {
+        self.inner.is_allocated()
+    }
+
+    fn get_size(&self) -> usize {
+        self.inner.get_size()
+    }
+
+    fn mark_for_use(&mut self) {
+        self.inner.mark_for_use();
+    }
+
+    fn free_from_use(&mut self) {
+        self.inner.free_from_use();
+    }
+
+    fn is_in_use(&self) -> bool {
+        self.inner.is_in_use()
+    }
+}
+
+impl Layer for ResidualBlock {
+    fn forward(
+        &mut self,
+        input: &[f64],
+        utils: WrappedUtils,
+    ) -> Vec<f64> {
+        let output = self.inner.forward(input, utils);
+        output.iter().zip(input).map(|(o, x)| o + x).collect()
+    }
+
+    fn forward_into(
+        &mut self,
+        input: &[f64],
+        out: &mut Vec<f64>,
+        utils: WrappedUtils,
+    ) {
+        self.inner.forward_into(input, out, utils);
+        for (o, x) in out.iter_mut().zip(input) {
+            *o += x;
+        }
+    }
+
+    fn forward_batch(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<f64> {
+        let output = self.inner.forward_batch(input);
+        output.iter().zip(input).map(|(o, x)| o + x).collect()
+    }
+
+    fn batch_cache_is_full(&self) -> bool {
+        self.inner.batch_cache_is_full()
+    }
+
+    fn batch_cache_len(&self) -> usize {
+        self.inner.batch_cache_len()
+    }
+
+    fn input_size(&self) -> usize {
+        self.inner.input_size()
+    }
+
+    fn output_size(&self) -> usize {
+        self.inner.output_size()
+    }
+
+    fn save(
+        &self,
+        path: String,
+    ) -> Result<(), Box<dyn Error>> {
+        self.inner.save(path)
+    }
+
+    fn read(
+        &mut self,
+        path: String,
+    ) -> Result<(), Box<dyn Error>> {
+        self.inner.read(path)
+    }
+
+    fn get_weights(&self) -> WrappedMatrix<f64> {
+        self.inner.get_weights()
+    }
+
+    fn get_biases(&self) -> Vec<f64> {
+        self.inner.get_biases()
+    }
+
+    fn cleanup(&self) {
+        self.inner.cleanup();
+    }
+}
+
+impl TrainableLayer for ResidualBlock {
+    fn backward(
+        &mut self,
+        grad_output: &[f64],
+        utils: WrappedUtils,
+    ) -> Vec<f64> {
+        let grad_input = self.inner.backward(grad_output, utils);
+        grad_input.iter().zip(grad_output).map(|(g, skip)| g + skip).collect()
+    }
+
+    fn backward_batch(
+        &mut self,
+        grad_output: &[f64],
+    ) -> Vec<f64> {
+        let grad_input = self.inner.backward_batch(grad_output);
+        grad_input.iter().zip(grad_output).map(|(g, skip)| g + skip).collect()
+    }
+
+    fn clear_batch_cache(&mut self) {
+        self.inner.clear_batch_cache();
+    }
+
+    fn update_weights(
+        &mut self,
+        learning_rate: f64,
+        utils: WrappedUtils,
+    ) {
+        self.inner.update_weights(learning_rate, utils);
+    }
+
+    fn assign_weights(
+        &mut self,
+        other: WrappedTrainableLayer,
+        carry_moments: bool,
+    ) {
+        self.inner.assign_weights(other, carry_moments);
+    }
+
+    fn assign_weights_and_biases(
+        &mut self,
+        weights: &WrappedMatrix<f64>,
+        biases: &[f64],
+    ) {
+        self.inner.assign_weights_and_biases(weights, biases);
+    }
+
+    fn adjust_adam(
+        &mut self,
+        t: usize,
+        learning_rate: f64,
+        beta1: f64,
+        beta2: f64,
+        epsilon: f64,
+        utils: WrappedUtils,
+    ) {
+        self.inner.adjust_adam(t, learning_rate, beta1, beta2, epsilon, utils);
+    }
+
+    fn grad_norm_sq(&self) -> f64 {
+        self.inner.grad_norm_sq()
+    }
+
+    fn gradients(&self) -> LayerGradients {
+        self.inner.gradients()
+    }
+
+    fn set_gradients(
+        &mut self,
+        gradients: &LayerGradients,
+    ) {
+        self.inner.set_gradients(gradients);
+    }
+
+    fn set_spectral_norm(
+        &mut self,
+        enabled: bool,
+    ) {
+        self.inner.set_spectral_norm(enabled);
+    }
+
+    fn constrain_max_norm(
+        &mut self,
+        max_norm: f64,
+    ) {
+        self.inner.constrain_max_norm(max_norm);
+    }
+
+    fn reinitialize(
+        &mut self,
+        rng: &mut StdRng,
+    ) {
+        self.inner.reinitialize(rng);
+    }
+
+    fn moments(&self) -> LayerMoments {
+        self.inner.moments()
+    }
+
+    fn assign_moments(
+        &mut self,
+        moments: &LayerMoments,
+    ) {
+        self.inner.assign_moments(moments);
+    }
+
+    /// Resizes the inner layer, which has to keep matching input and output sizes.
+    fn resize_with(
+        &mut self,
+        input_size: usize,
+        output_size: usize,
+        fill: FillStrategy,
+    ) -> Result<(), Box<dyn Error>> {
+        if input_size != output_size {
+            return Err(format!(
+                "Residual block needs matching sizes: input size {input_size}, output size {output_size}"
+            )
+            .into());
+        }
+        self.inner.resize_with(input_size, output_size, fill)
+    }
+
+    fn save_weight(
+        &self,
+        path: String,
+    ) -> Result<(), Box<dyn Error>> {
+        self.inner.save_weights(path)
+    }
+
+    fn read_weight(
+        &mut self,
+        path: String,
+    ) -> Result<(), Box<dyn Error>> {
+        self.inner.read_weight(path)
+    }
+}
+
+impl TrainableAllocatableLayer for ResidualBlock {
+    fn duplicate(
+        &mut self,
+        model_directory: String,
+        position_in_nn: usize,
+    ) -> Box<dyn TrainableAllocatableLayer + Send> {
+        Box::new(Self { inner: self.inner.duplicate(model_directory, position_in_nn) })
+    }
+
+    fn copy_on_filesystem(
+        &self,
+        layer_path: String,
+    ) {
+        self.inner.copy_on_filesystem(layer_path);
+    }
+
+    fn as_trainable_layer_mut(&mut self) -> &mut dyn TrainableLayer {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::dense_layer::TrainableDenseLayer;
+    use crate::nn::directory::Directory;
+    use crate::utilities::util::Utils;
+
+    #[test]
+    fn test_residual_block() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut inner = WrappedTrainableLayer::new(Box::new(TrainableDenseLayer::new(
+            3,
+            3,
+            Directory::Internal("test_model_residual".to_string()),
+            0,
+        )));
+        let mut block = ResidualBlock::new(inner.clone()).unwrap();
+        block.allocate();
+
+        let input = vec![1.0, -2.0, 0.5];
+        let inner_output = inner.forward(&input, utils.clone());
+        let output = block.forward(&input, utils.clone());
+        for ((o, i), x) in output.iter().zip(&inner_output).zip(&input) {
+            assert!((o - (i + x)).abs() < 1e-12);
+        }
+
+        let grad_output = vec![0.1, 0.2, -0.3];
+        let inner_grad = inner.backward(&grad_output, utils.clone());
+        let grad_input = block.backward(&grad_output, utils);
+        for ((g, i), skip) in grad_input.iter().zip(&inner_grad).zip(&grad_output) {
+            assert!((g - (i + skip)).abs() < 1e-12);
+        }
+
+        drop(block);
+        drop(inner);
+        std::fs::remove_dir_all("test_model_residual").unwrap();
+    }
+
+    #[test]
+    fn test_residual_block_rejects_mismatched_sizes() {
+        let inner = WrappedTrainableLayer::new(Box::new(TrainableDenseLayer::new(
+            3,
+            2,
+            Directory::Internal("test_model_residual_mismatch".to_string()),
+            0,
+        )));
+        assert!(ResidualBlock::new(inner).is_err());
+    }
+}
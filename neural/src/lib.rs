@@ -17,6 +17,7 @@
 //! The library is organized into several modules:
 //!
 //! - [`activation`]: Activation functions (`ReLU`, `Sigmoid`, `Tanh`, etc.)
+//! - [`data`]: Dataset preparation such as augmentation
 //! - [`layer`]: Neural network layer implementations
 //! - [`nn`]: Complete neural network structures and builders
 //! - [`training`]: Training algorithms and data management
@@ -49,6 +50,7 @@
 #![allow(clippy::multiple_crate_versions)]
 
 pub mod activation;
+pub mod data;
 pub mod layer;
 pub mod nn;
 pub mod training;
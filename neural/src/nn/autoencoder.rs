@@ -0,0 +1,220 @@
+use crate::nn::directory::Directory;
+use crate::nn::joint::mean_loss;
+use crate::nn::neuralnet::TrainableClassicNeuralNetwork;
+use crate::nn::nn_trait::{NeuralNetwork, TrainableNeuralNetwork};
+use crate::nn::shape::{ActivationData, ActivationType, LayerShape, LayerType, NeuralNetworkShape};
+use crate::training::adam::AdamParams;
+use crate::training::loss::LossFunction;
+use crate::training::training_options::TrainingOptions;
+use crate::utilities::util::WrappedUtils;
+
+/// An autoencoder made of an encoder compressing its input into a bottleneck and a
+/// decoder mirroring the encoder which reconstructs the input from the bottleneck.
+///
+/// Both halves are the layers of one classic neural network, which is trained with its
+/// regular training on the inputs as targets, i.e. with its loss function, its optimizer
+/// and its Adam parameters.
+#[derive(Debug)]
+pub struct Autoencoder {
+    network: TrainableClassicNeuralNetwork,
+    num_encoder_layers: usize,
+}
+
+impl Autoencoder {
+    /// Creates a new `Autoencoder` from the layer sizes of the encoder.
+    ///
+    /// `layer_sizes` starts with the input size and ends with the bottleneck size, the decoder
+    /// uses the same sizes in reverse order. All layers use `activation` except for the last
+    /// layer of the decoder, which uses the identity so that any input can be reconstructed.
+    /// The network is stored in `model_directory`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if less than two layer sizes are given or if a size is zero.
+    #[must_use]
+    pub fn new(
+        layer_sizes: &[usize],
+        activation: ActivationType,
+        model_directory: &Directory,
+        utils: WrappedUtils,
+    ) -> Self {
+        assert!(layer_sizes.len() >= 2, "An autoencoder needs an input and a bottleneck size");
+        assert!(layer_sizes.iter().all(|&size| size > 0), "Layer sizes must be positive");
+        let dense = |input_size, output_size, activation_type| LayerShape {
+            layer_type: LayerType::Dense { input_size, output_size },
+            activation: ActivationData::new(activation_type),
+        };
+        let num_encoder_layers = layer_sizes.len() - 1;
+        let encoder_layers =
+            layer_sizes.windows(2).map(|sizes| dense(sizes[0], sizes[1], activation));
+        let decoder_layers = layer_sizes.windows(2).rev().enumerate().map(|(i, sizes)| {
+            let activation_type =
+                if i + 1 == num_encoder_layers { ActivationType::Identity } else { activation };
+            dense(sizes[1], sizes[0], activation_type)
+        });
+        let shape = NeuralNetworkShape { layers: encoder_layers.chain(decoder_layers).collect() };
+
+        Self {
+            network: TrainableClassicNeuralNetwork::new(shape, model_directory, utils),
+            num_encoder_layers,
+        }
+    }
+
+    /// Returns the size of the bottleneck, i.e. of the encoded vectors.
+    #[must_use]
+    pub fn bottleneck_size(&self) -> usize {
+        self.encoder_shape().layers.last().map_or(0, LayerShape::output_size)
+    }
+
+    /// Returns the shape of the encoder.
+    #[must_use]
+    pub fn encoder_shape(&self) -> NeuralNetworkShape {
+        let mut shape = self.network.shape();
+        shape.layers.truncate(self.num_encoder_layers);
+        shape
+    }
+
+    /// Returns the shape of the decoder.
+    #[must_use]
+    pub fn decoder_shape(&self) -> NeuralNetworkShape {
+        let mut shape = self.network.shape();
+        shape.layers.drain(..self.num_encoder_layers);
+        shape
+    }
+
+    /// Returns the utilities of the network.
+    #[must_use]
+    pub fn get_utils(&self) -> WrappedUtils {
+        self.network.get_utils()
+    }
+
+    /// Returns the network holding the layers of the encoder followed by those of the
+    /// decoder, e.g. to configure its training.
+    pub const fn network_mut(&mut self) -> &mut TrainableClassicNeuralNetwork {
+        &mut self.network
+    }
+
+    /// Encodes the input into a vector of the bottleneck size.
+    pub fn encode(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<f64> {
+        self.network.forward_with_activations(input).swap_remove(self.num_encoder_layers - 1)
+    }
+
+    /// Encodes the input and decodes it again.
+    pub fn reconstruct(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<f64> {
+        self.network.predict(input.to_vec())
+    }
+
+    /// Sets the Adam hyperparameters used to train the encoder and the decoder.
+    pub fn set_adam_params(
+        &mut self,
+        adam_params: AdamParams,
+    ) {
+        let options = self.network.training_options();
+        self.network.set_training_options(TrainingOptions { adam_params, ..options });
+    }
+
+    /// Sets the loss between the reconstruction and the input the autoencoder is trained with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the loss function does not fit the output of the decoder.
+    pub fn set_loss_function(
+        &mut self,
+        loss_function: LossFunction,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.network.set_loss_function(loss_function)
+    }
+
+    /// Trains the autoencoder to reconstruct the given inputs.
+    ///
+    /// All inputs are used for training. Returns the average loss of the last epoch,
+    /// zero if there are no inputs.
+    pub fn train(
+        &mut self,
+        inputs: &[Vec<f64>],
+        learning_rate: f64,
+        epochs: usize,
+        use_adam: bool,
+    ) -> f64 {
+        if inputs.is_empty() {
+            return 0.0;
+        }
+        self.network.train(
+            inputs,
+            inputs,
+            learning_rate,
+            epochs,
+            0.0,
+            use_adam,
+            None,
+            1.0,
+            1.0,
+            None,
+        );
+        self.network.training_history().epochs.last().map_or(0.0, |metrics| metrics.train_loss)
+    }
+
+    /// Computes the average squared error between the inputs and their reconstructions,
+    /// zero if there are no inputs.
+    pub fn reconstruction_error(
+        &mut self,
+        inputs: &[Vec<f64>],
+    ) -> f64 {
+        let error = inputs
+            .iter()
+            .map(|input| {
+                let reconstruction = self.reconstruct(input);
+                reconstruction.iter().zip(input).map(|(o, t)| (o - t) * (o - t)).sum::<f64>()
+            })
+            .sum::<f64>();
+        mean_loss(error, inputs.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utilities::util::Utils;
+
+    #[test]
+    fn test_autoencoder_train() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut autoencoder = Autoencoder::new(
+            &[4, 3, 2],
+            ActivationType::Tanh,
+            &Directory::Internal("test_autoencoder".to_string()),
+            utils,
+        );
+        assert_eq!(autoencoder.bottleneck_size(), 2);
+        assert_eq!(autoencoder.encoder_shape().layers.len(), 2);
+        let decoder_shape = autoencoder.decoder_shape();
+        assert_eq!(decoder_shape.layers.len(), 2);
+        assert_eq!(decoder_shape.layers[0].input_size(), 2);
+        assert_eq!(decoder_shape.layers[1].output_size(), 4);
+
+        // the samples only vary along two directions and fit through the bottleneck
+        let inputs = vec![
+            vec![0.5, 0.5, 0.0, 0.0],
+            vec![0.0, 0.0, 0.5, 0.5],
+            vec![0.5, 0.5, 0.5, 0.5],
+            vec![0.25, 0.25, 0.0, 0.0],
+            vec![0.0, 0.0, 0.25, 0.25],
+        ];
+        assert_eq!(autoencoder.encode(&inputs[0]).len(), 2);
+        assert_eq!(autoencoder.reconstruct(&inputs[0]).len(), 4);
+
+        let error_before = autoencoder.reconstruction_error(&inputs);
+        let loss = autoencoder.train(&inputs, 0.01, 100, true);
+        let error_after = autoencoder.reconstruction_error(&inputs);
+        assert!(error_after < error_before);
+        assert!(loss > 0.0);
+        assert_eq!(autoencoder.network_mut().training_history().len(), 100);
+        assert!(autoencoder.train(&[], 0.01, 300, true).abs() < f64::EPSILON);
+    }
+}
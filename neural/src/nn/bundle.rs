@@ -0,0 +1,164 @@
+use crate::data::scaler::Scaler;
+use crate::nn::nn_factory::trainable_neural_network_from_disk;
+use crate::nn::nn_trait::WrappedTrainableNeuralNetwork;
+use crate::training::training_params::TrainingParams;
+use crate::utilities::util::WrappedUtils;
+
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+
+/// A neural network stored together with its input scaler and training parameters,
+/// so inference can be reproduced exactly after reloading.
+///
+/// The bundle directory contains the network in `model`, the parameters in
+/// `training_params.yaml` and the scaler, if any, in `scaler.yaml`.
+#[derive(Debug, Clone)]
+pub struct ModelBundle {
+    nn: WrappedTrainableNeuralNetwork,
+    scaler: Option<Scaler>,
+    params: TrainingParams,
+}
+
+impl ModelBundle {
+    #[must_use]
+    pub const fn new(
+        nn: WrappedTrainableNeuralNetwork,
+        scaler: Option<Scaler>,
+        params: TrainingParams,
+    ) -> Self {
+        Self { nn, scaler, params }
+    }
+
+    /// Loads a bundle saved with `save` from the given directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory contains no model or if the parameters or the scaler
+    /// cannot be read.
+    pub fn load(
+        bundle_directory: &str,
+        utils: WrappedUtils,
+    ) -> Result<Self, Box<dyn Error>> {
+        let model_directory = format!("{bundle_directory}/model");
+        if !Path::new(&model_directory).exists() {
+            return Err(format!("No model found in bundle directory {bundle_directory}").into());
+        }
+        let params: TrainingParams = serde_yaml::from_reader(File::open(format!(
+            "{bundle_directory}/training_params.yaml"
+        ))?)?;
+        let scaler_path = format!("{bundle_directory}/scaler.yaml");
+        let scaler = if Path::new(&scaler_path).exists() {
+            Some(serde_yaml::from_reader(File::open(scaler_path)?)?)
+        } else {
+            None
+        };
+        let nn = trainable_neural_network_from_disk(model_directory, utils);
+        Ok(Self { nn, scaler, params })
+    }
+
+    /// Saves the network, the scaler and the training parameters into the given directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any part of the bundle cannot be written.
+    pub fn save(
+        &mut self,
+        bundle_directory: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        std::fs::create_dir_all(bundle_directory)?;
+        self.nn.save(format!("{bundle_directory}/model"))?;
+        serde_yaml::to_writer(
+            File::create(format!("{bundle_directory}/training_params.yaml"))?,
+            &self.params,
+        )?;
+        let scaler_path = format!("{bundle_directory}/scaler.yaml");
+        match &self.scaler {
+            Some(scaler) => serde_yaml::to_writer(File::create(scaler_path)?, scaler)?,
+            None if Path::new(&scaler_path).exists() => std::fs::remove_file(scaler_path)?,
+            None => {},
+        }
+        Ok(())
+    }
+
+    /// Scales the input with the scaler of the bundle, if any, and predicts the output.
+    pub fn predict(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<f64> {
+        let input = self.scaler.as_ref().map_or_else(|| input.to_vec(), |s| s.transform(input));
+        self.nn.predict(input)
+    }
+
+    #[must_use]
+    pub fn nn(&self) -> WrappedTrainableNeuralNetwork {
+        self.nn.clone()
+    }
+
+    #[must_use]
+    pub const fn scaler(&self) -> Option<&Scaler> {
+        self.scaler.as_ref()
+    }
+
+    #[must_use]
+    pub const fn params(&self) -> &TrainingParams {
+        &self.params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nn::nn_factory::{new_trainable_neural_network, NeuralNetworkCreationArguments};
+    use crate::nn::shape::{
+        ActivationData, ActivationType, LayerShape, LayerType, NeuralNetworkShape,
+    };
+    use crate::training::verbosity::Verbosity;
+    use crate::utilities::util::Utils;
+
+    #[test]
+    fn test_model_bundle_roundtrip() {
+        let bundle_directory = "test_model_bundle";
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape {
+            layers: vec![
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 2, output_size: 3 },
+                    activation: ActivationData::new(ActivationType::ReLU),
+                },
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 1 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                },
+            ],
+        };
+        let mut nn = new_trainable_neural_network(NeuralNetworkCreationArguments::new(
+            shape.clone(),
+            None,
+            None,
+            "test_model_bundle_source".to_string(),
+            utils.clone(),
+        ));
+        let inputs = vec![vec![10.0, -3.0], vec![20.0, 1.0], vec![30.0, 5.0]];
+        let scaler = Scaler::fit(&inputs);
+        let params = TrainingParams::new(shape, None, None, 0.8, 0.01, 5, 0.1, 1, false, 1.0)
+            .with_verbosity(Verbosity::Silent);
+
+        // the original pipeline, predicting also allocates the layers so they are saved
+        let expected =
+            inputs.iter().map(|input| nn.predict(scaler.transform(input))).collect::<Vec<_>>();
+
+        let mut bundle = ModelBundle::new(nn, Some(scaler.clone()), params.clone());
+        bundle.save(bundle_directory).unwrap();
+        let mut loaded = ModelBundle::load(bundle_directory, utils).unwrap();
+        assert_eq!(loaded.params(), &params);
+        assert_eq!(loaded.scaler(), Some(&scaler));
+        for (input, expected) in inputs.iter().zip(&expected) {
+            assert_eq!(&loaded.predict(input), expected);
+        }
+
+        drop(loaded);
+        drop(bundle);
+        std::fs::remove_dir_all(bundle_directory).unwrap();
+    }
+}
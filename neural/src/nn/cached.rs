@@ -0,0 +1,223 @@
+use crate::nn::directory::Directory;
+use crate::nn::nn_trait::{NeuralNetwork, WrappedNeuralNetwork};
+use crate::nn::shape::NeuralNetworkShape;
+use crate::utilities::util::WrappedUtils;
+
+use std::collections::{HashMap, VecDeque};
+
+/// A neural network memoizing the results of `predict` for repeated identical inputs.
+///
+/// The cache holds at most `capacity` predictions and evicts the least recently used one.
+/// Inputs are keyed by the bit patterns of their values, so only bitwise identical inputs hit.
+/// Every method that may change the wrapped network clears the cache. Changes made through
+/// other handles of the wrapped network are not noticed, call `clear_cache` after those.
+#[derive(Debug)]
+pub struct CachedNeuralNetwork {
+    nn: WrappedNeuralNetwork,
+    capacity: usize,
+    cache: HashMap<Vec<u64>, Vec<f64>>,
+    // keys from least to most recently used
+    usage: VecDeque<Vec<u64>>,
+}
+
+impl CachedNeuralNetwork {
+    /// Creates a new `CachedNeuralNetwork` caching at most `capacity` predictions of `nn`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the capacity is zero.
+    #[must_use]
+    pub fn new(
+        nn: WrappedNeuralNetwork,
+        capacity: usize,
+    ) -> Self {
+        assert!(capacity > 0, "The cache capacity must be greater than zero");
+        Self { nn, capacity, cache: HashMap::new(), usage: VecDeque::new() }
+    }
+
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of cached predictions.
+    #[must_use]
+    pub fn cache_len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Removes all cached predictions.
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+        self.usage.clear();
+    }
+
+    /// Returns the wrapped network for modification. The cache is cleared.
+    pub fn inner_mut(&mut self) -> &mut WrappedNeuralNetwork {
+        self.clear_cache();
+        &mut self.nn
+    }
+
+    /// Moves the key to the most recently used position.
+    fn touch(
+        &mut self,
+        key: &[u64],
+    ) {
+        if let Some(position) = self.usage.iter().position(|used| used.as_slice() == key) {
+            if let Some(used) = self.usage.remove(position) {
+                self.usage.push_back(used);
+            }
+        }
+    }
+}
+
+impl NeuralNetwork for CachedNeuralNetwork {
+    fn predict(
+        &mut self,
+        input: Vec<f64>,
+    ) -> Vec<f64> {
+        let key = input.iter().map(|value| value.to_bits()).collect::<Vec<_>>();
+        if let Some(output) = self.cache.get(&key).cloned() {
+            self.touch(&key);
+            return output;
+        }
+        let output = self.nn.predict(input);
+        if self.cache.len() >= self.capacity {
+            if let Some(least_recently_used) = self.usage.pop_front() {
+                self.cache.remove(&least_recently_used);
+            }
+        }
+        self.cache.insert(key.clone(), output.clone());
+        self.usage.push_back(key);
+        output
+    }
+
+    fn shape(&self) -> NeuralNetworkShape {
+        self.nn.shape()
+    }
+
+    fn save(
+        &mut self,
+        user_model_directory: String,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.clear_cache();
+        self.nn.save(user_model_directory)
+    }
+
+    fn get_model_directory(&self) -> Directory {
+        self.nn.get_model_directory()
+    }
+
+    fn allocate(&mut self) {
+        self.nn.allocate();
+    }
+
+    fn deallocate(&mut self) {
+        self.nn.deallocate();
+    }
+
+    fn set_internal(&mut self) {
+        self.clear_cache();
+        self.nn.set_internal();
+    }
+
+    fn persist_on_drop(&mut self) {
+        self.nn.persist_on_drop();
+    }
+
+    fn duplicate(&self) -> WrappedNeuralNetwork {
+        WrappedNeuralNetwork::new(Box::new(Self::new(self.nn.duplicate(), self.capacity)))
+    }
+
+    fn get_utils(&self) -> WrappedUtils {
+        self.nn.get_utils()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utilities::util::Utils;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// A network doubling its input and counting how often it is asked to predict.
+    #[derive(Debug, Clone)]
+    struct CountingNetwork {
+        calls: Arc<AtomicUsize>,
+        utils: WrappedUtils,
+    }
+
+    impl NeuralNetwork for CountingNetwork {
+        fn predict(
+            &mut self,
+            input: Vec<f64>,
+        ) -> Vec<f64> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            input.iter().map(|x| 2.0 * x).collect()
+        }
+
+        fn shape(&self) -> NeuralNetworkShape {
+            NeuralNetworkShape::default()
+        }
+
+        fn save(
+            &mut self,
+            _user_model_directory: String,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            Ok(())
+        }
+
+        fn get_model_directory(&self) -> Directory {
+            Directory::Internal("test_model_cached".to_string())
+        }
+
+        fn allocate(&mut self) {}
+
+        fn deallocate(&mut self) {}
+
+        fn set_internal(&mut self) {}
+
+        fn persist_on_drop(&mut self) {}
+
+        fn duplicate(&self) -> WrappedNeuralNetwork {
+            WrappedNeuralNetwork::new(Box::new(self.clone()))
+        }
+
+        fn get_utils(&self) -> WrappedUtils {
+            self.utils.clone()
+        }
+    }
+
+    #[test]
+    fn test_cached_neural_network() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counting = CountingNetwork {
+            calls: calls.clone(),
+            utils: WrappedUtils::new(Utils::new(1_000_000_000, 4)),
+        };
+        let mut nn = CachedNeuralNetwork::new(WrappedNeuralNetwork::new(Box::new(counting)), 2);
+
+        assert_eq!(nn.predict(vec![1.0, 2.0]), vec![2.0, 4.0]);
+        assert_eq!(nn.predict(vec![1.0, 2.0]), vec![2.0, 4.0]);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // [1, 2] is used more recently than [3, 4], so [3, 4] is evicted for [5, 6]
+        nn.predict(vec![3.0, 4.0]);
+        nn.predict(vec![1.0, 2.0]);
+        nn.predict(vec![5.0, 6.0]);
+        assert_eq!(nn.cache_len(), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        nn.predict(vec![1.0, 2.0]);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        nn.predict(vec![3.0, 4.0]);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        // mutable access to the wrapped network invalidates the cache
+        nn.inner_mut();
+        assert_eq!(nn.cache_len(), 0);
+        nn.predict(vec![1.0, 2.0]);
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+}
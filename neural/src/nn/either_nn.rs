@@ -1,20 +1,27 @@
 use std::path::Path;
+use std::time::Duration;
 
 use super::nn_factory::copy_dir_recursive;
 use super::nn_factory::neural_network_from_disk;
 use super::nn_factory::new_trainable_neural_network;
 use super::nn_factory::trainable_neural_network_from_disk;
 use super::nn_factory::NeuralNetworkCreationArguments;
+use super::nn_trait::load_weights_flat_sequentially;
 use super::nn_trait::WrappedNeuralNetwork;
 use super::nn_trait::WrappedTrainableNeuralNetwork;
-use super::shape::NeuralNetworkShape;
+use super::shape::{LayerShape, NeuralNetworkShape};
 
+use crate::data::loader::DataLoader;
+use crate::layer::layer_trait::LayerGradients;
 use crate::nn::directory::Directory;
 use crate::nn::neuralnet::ClassicNeuralNetwork;
 use crate::nn::neuralnet::TrainableClassicNeuralNetwork;
-use crate::nn::nn_factory::get_first_free_model_directory;
 use crate::nn::nn_trait::NeuralNetwork;
 use crate::nn::nn_trait::TrainableNeuralNetwork;
+use crate::training::loss::LossFunction;
+use crate::training::metrics::EpochMetrics;
+use crate::training::optimizer::Optimizer;
+use crate::training::training_options::TrainingOptions;
 use crate::utilities::util::WrappedUtils;
 use num_traits::cast::NumCast;
 
@@ -26,6 +33,7 @@ pub struct EitherNeuralNetwork {
     shape: NeuralNetworkShape,
     model_directory: Directory,
     past_internal_model_directories: Vec<String>,
+    persist_on_drop: bool,
     utils: WrappedUtils,
 }
 
@@ -66,6 +74,7 @@ impl EitherNeuralNetwork {
                 shape,
                 model_directory: Directory::User(model_directory),
                 past_internal_model_directories: vec![],
+                persist_on_drop: false,
                 utils,
             }))
         } else {
@@ -120,13 +129,14 @@ impl NeuralNetwork for EitherNeuralNetwork {
     }
 
     fn shape(&self) -> NeuralNetworkShape {
-        if self.left_nn.is_some() {
-            self.left_nn.as_ref().unwrap().shape()
-        } else if self.right_nn.is_some() {
-            self.right_nn.as_ref().unwrap().shape()
-        } else {
-            self.shape.clone()
-        }
+        self.left_nn.as_ref().map_or_else(
+            || {
+                self.right_nn
+                    .as_ref()
+                    .map_or_else(|| self.shape.clone(), WrappedNeuralNetwork::shape)
+            },
+            WrappedNeuralNetwork::shape,
+        )
     }
 
     fn save(
@@ -186,8 +196,19 @@ impl NeuralNetwork for EitherNeuralNetwork {
         }
     }
 
+    fn persist_on_drop(&mut self) {
+        self.persist_on_drop = true;
+        self.pre_nn.persist_on_drop();
+        if let Some(ref mut left_nn) = self.left_nn {
+            left_nn.persist_on_drop();
+        }
+        if let Some(ref mut right_nn) = self.right_nn {
+            right_nn.persist_on_drop();
+        }
+    }
+
     fn duplicate(&self) -> WrappedNeuralNetwork {
-        let new_model_directory = get_first_free_model_directory(&self.model_directory);
+        let new_model_directory = self.utils.first_free_model_directory(&self.model_directory);
         copy_dir_recursive(
             Path::new(&self.model_directory.path()),
             Path::new(&new_model_directory),
@@ -213,6 +234,9 @@ impl Drop for EitherNeuralNetwork {
             }
             self.deallocate();
         }
+        if self.persist_on_drop {
+            return;
+        }
         // Remove the internal model directory from disk
         if let Directory::Internal(dir) = &self.model_directory {
             if std::fs::metadata(dir).is_ok() {
@@ -239,7 +263,9 @@ pub struct TrainableEitherNeuralNetwork {
     max_levels: i32,
     model_directory: Directory,
     past_internal_model_directories: Vec<String>,
+    persist_on_drop: bool,
     utils: WrappedUtils,
+    options: TrainingOptions,
 }
 
 impl TrainableEitherNeuralNetwork {
@@ -266,7 +292,9 @@ impl TrainableEitherNeuralNetwork {
             max_levels,
             model_directory: Directory::Internal(internal_model_directory),
             past_internal_model_directories: vec![],
+            persist_on_drop: false,
             utils,
+            options: TrainingOptions::default(),
         }
     }
 
@@ -309,7 +337,9 @@ impl TrainableEitherNeuralNetwork {
                 max_levels: 2, // allow extension of maximum two levels when retraining
                 model_directory: Directory::User(model_directory),
                 past_internal_model_directories: vec![],
+                persist_on_drop: false,
                 utils,
+                options: TrainingOptions::default(),
             }))
         } else {
             WrappedTrainableNeuralNetwork::new(Box::new(
@@ -356,8 +386,10 @@ impl TrainableEitherNeuralNetwork {
         epochs: usize,
         tolerance: f64,
         use_adam: bool,
+        optimizer: Option<Box<dyn Optimizer>>,
         validation_split: f64,
         sample_match_percentage: f64,
+        on_epoch_end: Option<&mut dyn FnMut(EpochMetrics)>,
     ) -> (WrappedTrainableNeuralNetwork, f64) {
         let mut temp_nn = new_trainable_neural_network(NeuralNetworkCreationArguments::new(
             self.shape.clone(),
@@ -366,6 +398,11 @@ impl TrainableEitherNeuralNetwork {
             append_dir(self.model_directory.path(), "temp"),
             self.utils.clone(),
         ));
+        // the temporary network only splits the samples, so it keeps no checkpoint
+        temp_nn.set_training_options(TrainingOptions {
+            checkpoint_best: None,
+            ..self.options.clone()
+        });
 
         let acc = temp_nn.train(
             inputs,
@@ -374,8 +411,10 @@ impl TrainableEitherNeuralNetwork {
             epochs,
             tolerance,
             use_adam,
+            optimizer,
             validation_split,
             sample_match_percentage,
+            on_epoch_end,
         );
 
         (temp_nn, acc)
@@ -475,6 +514,20 @@ impl TrainableEitherNeuralNetwork {
         (pre_inputs, pre_targets)
     }
 
+    /// Returns the options of the inner network stored in `dir_name` with the given shape.
+    fn sub_network_options(
+        &self,
+        dir_name: &str,
+        shape: &NeuralNetworkShape,
+    ) -> TrainingOptions {
+        let mut options = self.options.for_subdirectory(dir_name);
+        // the pre network has its own shape, so the layer multipliers do not apply to it
+        if *shape != self.shape {
+            options.lr_multipliers = None;
+        }
+        options
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn train_and_save_network(
         &self,
@@ -486,10 +539,12 @@ impl TrainableEitherNeuralNetwork {
         epochs: usize,
         tolerance: f64,
         use_adam: bool,
+        optimizer: Option<Box<dyn Optimizer>>,
         validation_split: f64,
         sample_match_percentage: f64,
     ) -> (WrappedTrainableNeuralNetwork, f64) {
         let model_dir = append_dir(self.model_directory.path(), dir_name);
+        let options = self.sub_network_options(dir_name, &shape);
         let mut nn = new_trainable_neural_network(NeuralNetworkCreationArguments::new(
             shape,
             Some(self.max_levels - 1),
@@ -497,6 +552,7 @@ impl TrainableEitherNeuralNetwork {
             model_dir.clone(),
             self.utils.clone(),
         ));
+        nn.set_training_options(options);
 
         let acc = nn.train(
             inputs,
@@ -505,8 +561,10 @@ impl TrainableEitherNeuralNetwork {
             epochs,
             tolerance,
             use_adam,
+            optimizer,
             validation_split,
             sample_match_percentage,
+            None,
         );
 
         let error_message = format!("Failed to save {dir_name} neural network");
@@ -586,6 +644,17 @@ impl NeuralNetwork for TrainableEitherNeuralNetwork {
         }
     }
 
+    fn persist_on_drop(&mut self) {
+        self.persist_on_drop = true;
+        self.pre_nn.persist_on_drop();
+        if let Some(ref mut left_nn) = self.left_nn {
+            left_nn.persist_on_drop();
+        }
+        if let Some(ref mut right_nn) = self.right_nn {
+            right_nn.persist_on_drop();
+        }
+    }
+
     fn duplicate(&self) -> WrappedNeuralNetwork {
         unimplemented!()
     }
@@ -604,13 +673,16 @@ impl TrainableNeuralNetwork for TrainableEitherNeuralNetwork {
         epochs: usize,
         tolerance: f64,
         use_adam: bool,
+        optimizer: Option<Box<dyn Optimizer>>,
         validation_split: f64,
         sample_match_percentage: f64,
+        on_epoch_end: Option<&mut dyn FnMut(EpochMetrics)>,
     ) -> f64 {
         if Self::not_enough_samples(inputs) {
             return 0.0;
         }
 
+        // only the training on the full data set reports the epoch metrics
         let (mut temp_nn, temp_accuracy) = self.train_temp_network(
             inputs,
             targets,
@@ -618,8 +690,10 @@ impl TrainableNeuralNetwork for TrainableEitherNeuralNetwork {
             epochs,
             tolerance,
             use_adam,
+            optimizer.clone(),
             validation_split,
             sample_match_percentage,
+            on_epoch_end,
         );
 
         if self.no_more_levels() {
@@ -653,6 +727,7 @@ impl TrainableNeuralNetwork for TrainableEitherNeuralNetwork {
             epochs,
             tolerance,
             use_adam,
+            optimizer.clone(),
             validation_split,
             sample_match_percentage,
         );
@@ -667,6 +742,7 @@ impl TrainableNeuralNetwork for TrainableEitherNeuralNetwork {
             epochs,
             tolerance,
             use_adam,
+            optimizer.clone(),
             validation_split,
             sample_match_percentage,
         );
@@ -680,6 +756,7 @@ impl TrainableNeuralNetwork for TrainableEitherNeuralNetwork {
             epochs,
             tolerance,
             use_adam,
+            optimizer,
             validation_split,
             sample_match_percentage,
         );
@@ -695,8 +772,87 @@ impl TrainableNeuralNetwork for TrainableEitherNeuralNetwork {
         epochs: usize,
         tolerance: f64,
         batch_size: usize,
+        optimizer: Option<Box<dyn Optimizer>>,
+    ) {
+        self.pre_nn.train_batch(
+            inputs,
+            targets,
+            learning_rate,
+            epochs,
+            tolerance,
+            batch_size,
+            optimizer,
+        );
+    }
+
+    /// Only the pre network is trained, as the inner networks are trained on splits
+    /// of the training samples.
+    #[allow(clippy::too_many_arguments)]
+    fn train_with_validation(
+        &mut self,
+        train_inputs: &[Vec<f64>],
+        train_targets: &[Vec<f64>],
+        validation_inputs: &[Vec<f64>],
+        validation_targets: &[Vec<f64>],
+        learning_rate: f64,
+        epochs: usize,
+        tolerance: f64,
+        use_adam: bool,
+        optimizer: Option<Box<dyn Optimizer>>,
+        sample_match_percentage: f64,
+        on_epoch_end: Option<&mut dyn FnMut(EpochMetrics)>,
+    ) -> f64 {
+        self.pre_nn.train_with_validation(
+            train_inputs,
+            train_targets,
+            validation_inputs,
+            validation_targets,
+            learning_rate,
+            epochs,
+            tolerance,
+            use_adam,
+            optimizer,
+            sample_match_percentage,
+            on_epoch_end,
+        )
+    }
+
+    fn train_loader(
+        &mut self,
+        loader: &DataLoader,
+        learning_rate: f64,
+        epochs: usize,
+        tolerance: f64,
+        optimizer: Option<Box<dyn Optimizer>>,
     ) {
-        self.pre_nn.train_batch(inputs, targets, learning_rate, epochs, tolerance, batch_size);
+        self.pre_nn.train_loader(loader, learning_rate, epochs, tolerance, optimizer);
+    }
+
+    fn compute_batch_gradients(
+        &mut self,
+        _inputs: &[Vec<f64>],
+        _targets: &[Vec<f64>],
+        _loss_function: &LossFunction,
+    ) -> Result<Vec<LayerGradients>, Box<dyn std::error::Error>> {
+        Err("Batch gradients are only supported for classic neural networks".into())
+    }
+
+    fn partial_fit(
+        &mut self,
+        _input: &[f64],
+        _target: &[f64],
+        _learning_rate: f64,
+        _loss_function: &LossFunction,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        Err("Online training is only supported for classic neural networks".into())
+    }
+
+    fn saliency(
+        &mut self,
+        _input: &[f64],
+        _output_index: usize,
+    ) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        Err("Saliency maps are only supported for classic neural networks".into())
     }
 
     fn input_size(&self) -> usize {
@@ -707,8 +863,220 @@ impl TrainableNeuralNetwork for TrainableEitherNeuralNetwork {
         self.shape.layers[self.shape.layers.len() - 1].output_size()
     }
 
+    fn profile_forward(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<Duration> {
+        // every input passes through the pre network, so its layers are the ones profiled
+        self.pre_nn.profile_forward(input)
+    }
+
+    fn dead_neuron_report(
+        &mut self,
+        inputs: &[Vec<f64>],
+    ) -> Vec<usize> {
+        // every input passes through the pre network, so its units are the ones reported
+        self.pre_nn.dead_neuron_report(inputs)
+    }
+
+    fn training_options(&self) -> TrainingOptions {
+        self.options.clone()
+    }
+
+    fn set_training_options(
+        &mut self,
+        options: TrainingOptions,
+    ) {
+        self.options = options;
+        let pre_options = self.sub_network_options("pre", &self.pre_nn.shape());
+        self.pre_nn.set_training_options(pre_options);
+        if let Some(nn) = self.left_nn.as_mut() {
+            nn.set_training_options(self.options.for_subdirectory("left"));
+        }
+        if let Some(nn) = self.right_nn.as_mut() {
+            nn.set_training_options(self.options.for_subdirectory("right"));
+        }
+    }
+
+    fn set_loss_function(
+        &mut self,
+        loss_function: LossFunction,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // the inner networks are trained on intermediate targets
+        if loss_function == LossFunction::MeanSquaredError {
+            Ok(())
+        } else {
+            Err("Custom loss functions are only supported for classic neural networks".into())
+        }
+    }
+
+    fn set_logits_loss(
+        &mut self,
+        enabled: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // the inner networks are trained on intermediate targets
+        if enabled {
+            Err("Computing the loss on the logits is only supported for classic neural networks"
+                .into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn reinitialize(
+        &mut self,
+        seed: u64,
+    ) {
+        self.pre_nn.reinitialize(seed);
+        if let Some(left_nn) = self.left_nn.as_mut() {
+            left_nn.reinitialize(seed.wrapping_add(1));
+        }
+        if let Some(right_nn) = self.right_nn.as_mut() {
+            right_nn.reinitialize(seed.wrapping_add(2));
+        }
+    }
+
+    fn swa_weights(&self) -> Option<Vec<f64>> {
+        let mut flat = self.pre_nn.swa_weights()?;
+        for nn in self.left_nn.iter().chain(self.right_nn.iter()) {
+            flat.extend(nn.swa_weights()?);
+        }
+        Some(flat)
+    }
+
+    fn set_spectral_norm(
+        &mut self,
+        enabled: bool,
+    ) {
+        self.pre_nn.set_spectral_norm(enabled);
+        for nn in self.left_nn.iter_mut().chain(self.right_nn.iter_mut()) {
+            nn.set_spectral_norm(enabled);
+        }
+    }
+
+    /// Returns the flat weights of the pre network followed by those of the left
+    /// and right networks if they exist.
+    fn weights_flat(&self) -> Vec<f64> {
+        let mut flat = self.pre_nn.weights_flat();
+        for nn in self.left_nn.iter().chain(self.right_nn.iter()) {
+            flat.extend(nn.weights_flat());
+        }
+        flat
+    }
+
+    fn num_parameters(&self) -> usize {
+        std::iter::once(&self.pre_nn)
+            .chain(self.left_nn.iter())
+            .chain(self.right_nn.iter())
+            .map(WrappedTrainableNeuralNetwork::num_parameters)
+            .sum()
+    }
+
+    fn load_weights_flat(
+        &mut self,
+        weights: &[f64],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let networks = std::iter::once(&mut self.pre_nn)
+            .chain(self.left_nn.iter_mut())
+            .chain(self.right_nn.iter_mut())
+            .collect();
+        load_weights_flat_sequentially(networks, weights)
+    }
+
+    fn get_weight(
+        &self,
+        _layer_index: usize,
+        _row: usize,
+        _col: usize,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        Err("Accessing single weights is only supported for classic neural networks".into())
+    }
+
+    fn set_weight(
+        &mut self,
+        _layer_index: usize,
+        _row: usize,
+        _col: usize,
+        _value: f64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Err("Accessing single weights is only supported for classic neural networks".into())
+    }
+
+    fn get_bias(
+        &self,
+        _layer_index: usize,
+        _row: usize,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        Err("Accessing single biases is only supported for classic neural networks".into())
+    }
+
+    fn set_bias(
+        &mut self,
+        _layer_index: usize,
+        _row: usize,
+        _value: f64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Err("Accessing single biases is only supported for classic neural networks".into())
+    }
+
+    fn spectral_norms(
+        &self,
+        _iterations: usize,
+    ) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        Err("Spectral norms are only supported for classic neural networks".into())
+    }
+
+    fn prune_neurons(
+        &mut self,
+        _layer_index: usize,
+        _keep_indices: &[usize],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // the layers of the exposed shape are spread over the inner networks
+        Err("Pruning neurons is only supported for classic neural networks".into())
+    }
+
+    fn add_identity_layer(
+        &mut self,
+        _after_index: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // the layers of the exposed shape are spread over the inner networks
+        Err("Inserting layers is only supported for classic neural networks".into())
+    }
+
+    fn push_layer(
+        &mut self,
+        _layer_shape: LayerShape,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // the layers of the exposed shape are spread over the inner networks
+        Err("Appending layers is only supported for classic neural networks".into())
+    }
+
+    fn widen_layer(
+        &mut self,
+        _layer_index: usize,
+        _new_width: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // the layers of the exposed shape are spread over the inner networks
+        Err("Widening layers is only supported for classic neural networks".into())
+    }
+
+    fn to_inference(&self) -> WrappedNeuralNetwork {
+        WrappedNeuralNetwork::new(Box::new(EitherNeuralNetwork {
+            pre_nn: self.pre_nn.to_inference(),
+            left_nn: self.left_nn.as_ref().map(WrappedTrainableNeuralNetwork::to_inference),
+            right_nn: self.right_nn.as_ref().map(WrappedTrainableNeuralNetwork::to_inference),
+            shape: self.shape.clone(),
+            model_directory: Directory::Internal(
+                self.utils.first_free_model_directory(&self.model_directory),
+            ),
+            past_internal_model_directories: vec![],
+            persist_on_drop: false,
+            utils: self.utils.clone(),
+        }))
+    }
+
     fn duplicate_trainable(&self) -> WrappedTrainableNeuralNetwork {
-        let new_model_directory = get_first_free_model_directory(&self.model_directory);
+        let new_model_directory = self.utils.first_free_model_directory(&self.model_directory);
         copy_dir_recursive(
             Path::new(&self.model_directory.path()),
             Path::new(&new_model_directory),
@@ -731,6 +1099,9 @@ impl Drop for TrainableEitherNeuralNetwork {
             }
             self.deallocate();
         }
+        if self.persist_on_drop {
+            return;
+        }
         // Remove the internal model directory from disk
         if let Directory::Internal(dir) = &self.model_directory {
             if std::fs::metadata(dir).is_ok() {
@@ -787,7 +1158,7 @@ mod tests {
         let target = vec![0.0, 0.0, 0.0];
         let targets = vec![target; 500];
 
-        nn.train(&inputs, &targets, 0.01, 5, 0.1, true, 0.7, 1.0);
+        nn.train(&inputs, &targets, 0.01, 5, 0.1, true, None, 0.7, 1.0, None);
 
         let prediction = nn.predict(inputs[0].clone());
         // print targets[0]
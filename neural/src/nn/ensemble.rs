@@ -0,0 +1,245 @@
+use crate::data::split::class_of;
+use crate::nn::nn_trait::WrappedNeuralNetwork;
+
+use std::error::Error;
+
+/// Several neural networks with the same input and output sizes predicting together.
+///
+/// The members can be weighted, e.g. by their validation accuracy, otherwise every member
+/// counts the same.
+#[derive(Debug, Clone)]
+pub struct Ensemble {
+    members: Vec<WrappedNeuralNetwork>,
+    weights: Option<Vec<f64>>,
+}
+
+impl Ensemble {
+    /// Creates a new `Ensemble` whose members are weighted equally.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if no members are given.
+    #[must_use]
+    pub fn new(members: Vec<WrappedNeuralNetwork>) -> Self {
+        assert!(!members.is_empty(), "An ensemble needs at least one member");
+        Self { members, weights: None }
+    }
+
+    /// Creates a new `Ensemble` whose members are weighted by the given weights.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are no members, if the number of weights does not match the
+    /// number of members, if a weight is negative or not finite, or if all weights are zero.
+    pub fn with_weights(
+        members: Vec<WrappedNeuralNetwork>,
+        weights: Vec<f64>,
+    ) -> Result<Self, Box<dyn Error>> {
+        if members.is_empty() {
+            return Err("An ensemble needs at least one member".into());
+        }
+        if weights.len() != members.len() {
+            return Err(format!(
+                "Expected {} ensemble weights, found {}",
+                members.len(),
+                weights.len()
+            )
+            .into());
+        }
+        if weights.iter().any(|weight| !weight.is_finite() || *weight < 0.0) {
+            return Err("Ensemble weights must be finite and non-negative".into());
+        }
+        if weights.iter().sum::<f64>() <= 0.0 {
+            return Err("At least one ensemble weight must be positive".into());
+        }
+        Ok(Self { members, weights: Some(weights) })
+    }
+
+    #[must_use]
+    pub fn num_members(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Returns the weight of the member with the given index.
+    fn weight(
+        &self,
+        index: usize,
+    ) -> f64 {
+        self.weights.as_ref().map_or(1.0, |weights| weights[index])
+    }
+
+    /// Predicts the weighted average of the outputs of all members.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the output sizes of the members differ.
+    pub fn predict(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<f64> {
+        let mut sum: Vec<f64> = Vec::new();
+        let mut total_weight = 0.0;
+        for i in 0..self.members.len() {
+            let weight = self.weight(i);
+            let output = self.members[i].predict(input.to_vec());
+            if sum.is_empty() {
+                sum = vec![0.0; output.len()];
+            }
+            assert_eq!(
+                sum.len(),
+                output.len(),
+                "All ensemble members must have the same output size"
+            );
+            for (s, o) in sum.iter_mut().zip(&output) {
+                *s = weight.mul_add(*o, *s);
+            }
+            total_weight += weight;
+        }
+        sum.into_iter().map(|s| s / total_weight).collect()
+    }
+
+    /// Predicts the class voted for by the (weighted) majority of the members.
+    /// Every member votes for the position of its largest output.
+    /// Ties are broken by the lower class.
+    pub fn predict_vote(
+        &mut self,
+        input: &[f64],
+    ) -> usize {
+        let mut votes: Vec<f64> = Vec::new();
+        for i in 0..self.members.len() {
+            let weight = self.weight(i);
+            let output = self.members[i].predict(input.to_vec());
+            let class = class_of(&output);
+            if votes.len() <= class {
+                votes.resize(class + 1, 0.0);
+            }
+            votes[class] += weight;
+        }
+        class_of(&votes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nn::directory::Directory;
+    use crate::nn::neuralnet::ClassicNeuralNetwork;
+    use crate::nn::nn_factory::neural_network_from_disk;
+    use crate::nn::nn_trait::NeuralNetwork;
+    use crate::nn::shape::{
+        ActivationData, ActivationType, LayerShape, LayerType, NeuralNetworkShape,
+    };
+    use crate::utilities::util::{Utils, WrappedUtils};
+
+    /// A network always predicting the same output.
+    #[derive(Debug, Clone)]
+    struct ConstantNetwork {
+        output: Vec<f64>,
+        utils: WrappedUtils,
+    }
+
+    impl NeuralNetwork for ConstantNetwork {
+        fn predict(
+            &mut self,
+            _input: Vec<f64>,
+        ) -> Vec<f64> {
+            self.output.clone()
+        }
+
+        fn shape(&self) -> NeuralNetworkShape {
+            NeuralNetworkShape::default()
+        }
+
+        fn save(
+            &mut self,
+            _user_model_directory: String,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            Ok(())
+        }
+
+        fn get_model_directory(&self) -> Directory {
+            Directory::Internal("test_model_ensemble".to_string())
+        }
+
+        fn allocate(&mut self) {}
+
+        fn deallocate(&mut self) {}
+
+        fn set_internal(&mut self) {}
+
+        fn persist_on_drop(&mut self) {}
+
+        fn duplicate(&self) -> WrappedNeuralNetwork {
+            WrappedNeuralNetwork::new(Box::new(self.clone()))
+        }
+
+        fn get_utils(&self) -> WrappedUtils {
+            self.utils.clone()
+        }
+    }
+
+    fn constant(
+        output: Vec<f64>,
+        utils: &WrappedUtils,
+    ) -> WrappedNeuralNetwork {
+        WrappedNeuralNetwork::new(Box::new(ConstantNetwork { output, utils: utils.clone() }))
+    }
+
+    #[test]
+    fn test_ensemble_of_identical_models() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape {
+            layers: vec![LayerShape {
+                layer_type: LayerType::Dense { input_size: 3, output_size: 2 },
+                activation: ActivationData::new(ActivationType::Sigmoid),
+            }],
+        };
+        let mut model = WrappedNeuralNetwork::new(Box::new(ClassicNeuralNetwork::new(
+            shape,
+            "test_model_ensemble_identical".to_string(),
+            utils.clone(),
+        )));
+        let input = vec![0.5, -1.0, 2.0];
+        // predicting allocates the layers so their weights are written on save
+        let expected = model.predict(input.clone());
+        let saved_directory = "test_model_ensemble_identical_saved".to_string();
+        model.save(saved_directory.clone()).unwrap();
+        let members = (0..3)
+            .map(|_| neural_network_from_disk(saved_directory.clone(), utils.clone()))
+            .collect();
+        let mut ensemble = Ensemble::new(members);
+        assert_eq!(ensemble.num_members(), 3);
+
+        let output = ensemble.predict(&input);
+        assert_eq!(output.len(), expected.len());
+        for (o, e) in output.iter().zip(&expected) {
+            assert!((o - e).abs() < 1e-12);
+        }
+        drop(ensemble);
+        drop(model);
+        std::fs::remove_dir_all(saved_directory).unwrap();
+    }
+
+    #[test]
+    fn test_ensemble_vote_and_weights() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let members = vec![
+            constant(vec![0.9, 0.1, 0.0], &utils),
+            constant(vec![0.2, 0.7, 0.1], &utils),
+            constant(vec![0.1, 0.6, 0.3], &utils),
+        ];
+        let mut ensemble = Ensemble::new(members.clone());
+        assert_eq!(ensemble.predict_vote(&[0.0]), 1);
+        let average = ensemble.predict(&[0.0]);
+        assert!((average[0] - 0.4).abs() < 1e-12);
+
+        let mut weighted = Ensemble::with_weights(members.clone(), vec![3.0, 1.0, 1.0]).unwrap();
+        assert_eq!(weighted.predict_vote(&[0.0]), 0);
+        let average = weighted.predict(&[0.0]);
+        assert!((average[0] - 0.6).abs() < 1e-12);
+
+        assert!(Ensemble::with_weights(members.clone(), vec![1.0, 1.0]).is_err());
+        assert!(Ensemble::with_weights(members.clone(), vec![1.0, -1.0, 1.0]).is_err());
+        assert!(Ensemble::with_weights(members, vec![0.0, 0.0, 0.0]).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use crate::activation::{activate::ActivationTrait, registry::create_activation};
+use crate::layer::dense_layer::save;
+use crate::nn::directory::Directory;
+use crate::nn::neuralnet::check_input_size;
+use crate::nn::nn_factory::save_atomically;
+use crate::nn::nn_trait::{NeuralNetwork, WrappedNeuralNetwork};
+use crate::nn::shape::{LayerType, NeuralNetworkShape};
+use crate::utilities::util::WrappedUtils;
+
+use matrix::mat::WrappedMatrix;
+
+use std::error::Error;
+
+/// An inference-only neural network of dense layers whose weights are held in memory.
+/// Unlike the classic network it never creates a model directory, the disk is only
+/// touched when it is saved explicitly.
+#[derive(Debug, Clone)]
+pub struct InMemoryNeuralNetwork {
+    layers: Vec<(WrappedMatrix<f64>, Vec<f64>)>,
+    activations: Vec<Box<dyn ActivationTrait + Send>>,
+    shape: NeuralNetworkShape,
+    model_directory: Directory,
+    utils: WrappedUtils,
+}
+
+impl InMemoryNeuralNetwork {
+    /// Creates a new `InMemoryNeuralNetwork` from weights and biases in the order produced by
+    /// `weights_flat`: for every layer the weights row by row, followed by its biases.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the shape has a layer which is not dense or if the number of
+    /// weights does not match the shape.
+    pub fn new(
+        shape: NeuralNetworkShape,
+        weights: &[f64],
+        utils: WrappedUtils,
+    ) -> Result<Self, Box<dyn Error>> {
+        if let Some(i) = shape
+            .layers
+            .iter()
+            .position(|layer_shape| !matches!(layer_shape.layer_type(), LayerType::Dense { .. }))
+        {
+            return Err(format!("Layer {i} is not dense and cannot be held in memory").into());
+        }
+        let expected: usize = shape
+            .layers
+            .iter()
+            .map(|layer_shape| (layer_shape.input_size() + 1) * layer_shape.output_size())
+            .sum();
+        if weights.len() != expected {
+            return Err(format!(
+                "Expected {expected} weights and biases for the shape, found {}",
+                weights.len()
+            )
+            .into());
+        }
+
+        let mut remaining = weights;
+        let mut layers = Vec::with_capacity(shape.layers.len());
+        for layer_shape in &shape.layers {
+            let (rows, cols) = (layer_shape.output_size(), layer_shape.input_size());
+            let layer_weights = WrappedMatrix::new(rows, cols);
+            for (k, &value) in remaining[..rows * cols].iter().enumerate() {
+                layer_weights.set_mut_unchecked(k / cols, k % cols, value);
+            }
+            let biases = remaining[rows * cols..rows * cols + rows].to_vec();
+            remaining = &remaining[rows * cols + rows..];
+            layers.push((layer_weights, biases));
+        }
+        let activations = shape
+            .layers
+            .iter()
+            .map(|layer_shape| create_activation(&layer_shape.activation))
+            .collect();
+        Ok(Self { layers, activations, shape, model_directory: Directory::default(), utils })
+    }
+}
+
+impl NeuralNetwork for InMemoryNeuralNetwork {
+    fn predict(
+        &mut self,
+        input: Vec<f64>,
+    ) -> Vec<f64> {
+        check_input_size(&self.shape, &input);
+        let mut output = input;
+        for ((weights, biases), activation) in self.layers.iter().zip(&mut self.activations) {
+            let weighted_sums = weights
+                .mat()
+                .lock()
+                .unwrap()
+                .iter()
+                .zip(biases)
+                .map(|(row, bias)| {
+                    row.iter().zip(&output).map(|(&w, &x)| w * x).sum::<f64>() + bias
+                })
+                .collect::<Vec<f64>>();
+            output = activation.forward(&weighted_sums);
+        }
+        output
+    }
+
+    fn shape(&self) -> NeuralNetworkShape {
+        self.shape.clone()
+    }
+
+    /// Writes the network in the layout of a classic neural network, so that it can be
+    /// loaded with `neural_network_from_disk`.
+    fn save(
+        &mut self,
+        user_model_directory: String,
+    ) -> Result<(), Box<dyn Error>> {
+        save_atomically(&user_model_directory, |model_directory| {
+            self.shape.to_yaml(model_directory);
+            for (i, (weights, biases)) in self.layers.iter().enumerate() {
+                save(format!("{model_directory}/layers/layer_{i}.txt"), weights, biases)?;
+            }
+            Ok(())
+        })?;
+        self.model_directory = Directory::User(user_model_directory);
+        Ok(())
+    }
+
+    fn get_model_directory(&self) -> Directory {
+        self.model_directory.clone()
+    }
+
+    /// The weights are always in memory.
+    fn allocate(&mut self) {}
+
+    /// The weights are always in memory.
+    fn deallocate(&mut self) {}
+
+    fn set_internal(&mut self) {
+        self.model_directory = Directory::Internal(self.model_directory.path());
+    }
+
+    /// There is no internal model directory which would be removed.
+    fn persist_on_drop(&mut self) {}
+
+    fn duplicate(&self) -> WrappedNeuralNetwork {
+        WrappedNeuralNetwork::new(Box::new(self.clone()))
+    }
+
+    fn get_utils(&self) -> WrappedUtils {
+        self.utils.clone()
+    }
+}
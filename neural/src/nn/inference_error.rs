@@ -0,0 +1,66 @@
+use std::error::Error;
+use std::fmt;
+
+/// An error raised by a checked prediction whose activations are not finite.
+/// It names the layer and the unit of the first NaN or infinite value and the value itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InferenceError {
+    layer: usize,
+    unit: usize,
+    value: f64,
+}
+
+impl InferenceError {
+    #[must_use]
+    pub const fn new(
+        layer: usize,
+        unit: usize,
+        value: f64,
+    ) -> Self {
+        Self { layer, unit, value }
+    }
+
+    /// Returns the index of the layer which produced the value.
+    #[must_use]
+    pub const fn layer(&self) -> usize {
+        self.layer
+    }
+
+    /// Returns the index of the output unit of the layer which produced the value.
+    #[must_use]
+    pub const fn unit(&self) -> usize {
+        self.unit
+    }
+
+    /// Returns the NaN or infinite value.
+    #[must_use]
+    pub const fn value(&self) -> f64 {
+        self.value
+    }
+}
+
+impl fmt::Display for InferenceError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        write!(
+            f,
+            "Non finite activation {} at unit {} of layer {}",
+            self.value, self.unit, self.layer
+        )
+    }
+}
+
+impl Error for InferenceError {}
+
+/// Returns an error for the first value of the layer output which is NaN or infinite.
+pub(crate) fn check_finite(
+    values: &[f64],
+    layer: usize,
+) -> Result<(), InferenceError> {
+    values
+        .iter()
+        .position(|value| !value.is_finite())
+        .map_or(Ok(()), |unit| Err(InferenceError::new(layer, unit, values[unit])))
+}
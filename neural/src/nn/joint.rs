@@ -0,0 +1,81 @@
+use num_traits::NumCast;
+
+use crate::nn::neuralnet::TrainableClassicNeuralNetwork;
+use crate::training::adam::AdamParams;
+
+/// A network made of classic neural networks whose weights are updated together
+/// after every sample, e.g. the trunk and the heads of a `MultiHeadNetwork`.
+pub trait JointNetwork {
+    /// Returns the classic neural networks making up the network.
+    fn networks_mut(&mut self) -> Vec<&mut TrainableClassicNeuralNetwork>;
+
+    /// Returns the hyperparameters used when the networks are trained with Adam.
+    fn adam_params(&self) -> AdamParams;
+}
+
+/// Returns the squared error of the output and its gradient with respect to the output.
+pub fn squared_error(
+    output: &[f64],
+    target: &[f64],
+) -> (f64, Vec<f64>) {
+    let mut loss = 0.0;
+    let gradient = output
+        .iter()
+        .zip(target)
+        .map(|(o, t)| {
+            let error = o - t;
+            loss += error * error;
+            2.0 * error
+        })
+        .collect();
+    (loss, gradient)
+}
+
+/// Returns the loss summed over the samples divided by their number, zero if there are none.
+///
+/// # Panics
+///
+/// This function will panic if the number of samples cannot be converted to `f64`.
+pub fn mean_loss(
+    loss: f64,
+    num_samples: usize,
+) -> f64 {
+    if num_samples == 0 {
+        return 0.0;
+    }
+    let num_samples: f64 =
+        NumCast::from(num_samples).expect("Failed to convert the number of samples to f64");
+    loss / num_samples
+}
+
+/// Trains the network on the samples for the given number of epochs.
+///
+/// For every sample `train_sample` runs the forward and the backward pass and returns the loss
+/// of the sample, afterwards the weights of all networks are updated with the Adam parameters
+/// of the network if `use_adam` is set. Returns the average loss of the last epoch.
+pub fn train_jointly<N: JointNetwork, S>(
+    network: &mut N,
+    samples: &[S],
+    learning_rate: f64,
+    epochs: usize,
+    use_adam: bool,
+    mut train_sample: impl FnMut(&mut N, &S) -> f64,
+) -> f64 {
+    let AdamParams { beta1, beta2, epsilon } = network.adam_params();
+    let mut loss = 0.0;
+    for _ in 0..epochs {
+        loss = 0.0;
+        for (j, sample) in samples.iter().enumerate() {
+            loss += train_sample(network, sample);
+            for nn in network.networks_mut() {
+                if use_adam {
+                    nn.adjust_adam(j + 1, learning_rate, beta1, beta2, epsilon);
+                } else {
+                    nn.update_weights(learning_rate);
+                }
+            }
+        }
+        loss = mean_loss(loss, samples.len());
+    }
+    loss
+}
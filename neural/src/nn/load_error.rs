@@ -0,0 +1,43 @@
+use std::error::Error;
+use std::fmt;
+
+/// An error raised while loading a model from disk.
+/// It names the file which could not be loaded and the reason.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelLoadError {
+    path: String,
+    reason: String,
+}
+
+impl ModelLoadError {
+    #[must_use]
+    pub fn new(
+        path: impl Into<String>,
+        reason: impl Into<String>,
+    ) -> Self {
+        Self { path: path.into(), reason: reason.into() }
+    }
+
+    /// Returns the path of the file which could not be loaded.
+    #[must_use]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns why the file could not be loaded.
+    #[must_use]
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl fmt::Display for ModelLoadError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        write!(f, "Failed to load {}: {}", self.path, self.reason)
+    }
+}
+
+impl Error for ModelLoadError {}
@@ -1,7 +1,18 @@
+pub mod autoencoder;
+pub mod bundle;
+pub mod cached;
 pub mod directory;
 pub mod either_nn;
+pub mod ensemble;
+pub mod in_memory;
+pub mod inference_error;
+pub(crate) mod joint;
+pub mod load_error;
+pub mod multihead;
 pub mod neuralnet;
 pub mod nn_factory;
 pub mod nn_trait;
+pub mod parallel;
+pub mod quantized;
 pub mod retry_nn;
 pub mod shape;
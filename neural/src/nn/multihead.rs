@@ -0,0 +1,267 @@
+use crate::nn::directory::Directory;
+use crate::nn::joint::{mean_loss, squared_error, train_jointly, JointNetwork};
+use crate::nn::neuralnet::TrainableClassicNeuralNetwork;
+use crate::nn::nn_trait::{NeuralNetwork, TrainableNeuralNetwork};
+use crate::nn::shape::{LayerShape, NeuralNetworkShape};
+use crate::training::adam::AdamParams;
+use crate::utilities::util::WrappedUtils;
+
+/// A neural network with a shared trunk feeding several independent output heads.
+///
+/// Every head receives the output of the trunk as its input. During training the
+/// losses of all heads are summed, so the gradients flowing back from every head
+/// accumulate in the shared trunk.
+#[derive(Debug)]
+pub struct MultiHeadNetwork {
+    trunk: TrainableClassicNeuralNetwork,
+    heads: Vec<TrainableClassicNeuralNetwork>,
+    utils: WrappedUtils,
+    adam_params: AdamParams,
+}
+
+impl MultiHeadNetwork {
+    /// Creates a new `MultiHeadNetwork` from the shape of the trunk and the shapes of the heads.
+    ///
+    /// The trunk is stored in `{model_directory}/trunk` and head `i` in `{model_directory}/head_{i}`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if no heads are given or if the input size of a head
+    /// does not match the output size of the trunk.
+    #[must_use]
+    pub fn new(
+        trunk_shape: NeuralNetworkShape,
+        head_shapes: Vec<NeuralNetworkShape>,
+        model_directory: &Directory,
+        utils: WrappedUtils,
+    ) -> Self {
+        assert!(!head_shapes.is_empty(), "A multi head network needs at least one head");
+        let trunk_output_size = trunk_shape.layers.last().map_or(0, LayerShape::output_size);
+        for head_shape in &head_shapes {
+            let head_input_size = head_shape.layers.first().map_or(0, LayerShape::input_size);
+            assert_eq!(
+                head_input_size, trunk_output_size,
+                "The input size of every head must match the output size of the trunk"
+            );
+        }
+
+        let trunk = TrainableClassicNeuralNetwork::new(
+            trunk_shape,
+            &Directory::Internal(format!("{}/trunk", model_directory.path())),
+            utils.clone(),
+        );
+        let heads = head_shapes
+            .into_iter()
+            .enumerate()
+            .map(|(i, head_shape)| {
+                TrainableClassicNeuralNetwork::new(
+                    head_shape,
+                    &Directory::Internal(format!("{}/head_{i}", model_directory.path())),
+                    utils.clone(),
+                )
+            })
+            .collect();
+
+        Self { trunk, heads, utils, adam_params: AdamParams::default() }
+    }
+
+    /// Returns the number of output heads.
+    #[must_use]
+    pub fn num_heads(&self) -> usize {
+        self.heads.len()
+    }
+
+    /// Returns the shape of the shared trunk.
+    #[must_use]
+    pub fn trunk_shape(&self) -> NeuralNetworkShape {
+        self.trunk.shape()
+    }
+
+    /// Returns the shapes of the output heads.
+    #[must_use]
+    pub fn head_shapes(&self) -> Vec<NeuralNetworkShape> {
+        self.heads.iter().map(NeuralNetwork::shape).collect()
+    }
+
+    /// Returns the utilities shared by the trunk and all heads.
+    #[must_use]
+    pub fn get_utils(&self) -> WrappedUtils {
+        self.utils.clone()
+    }
+
+    /// Performs a forward pass through the trunk and all heads.
+    /// Returns one output vector per head.
+    pub fn forward(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<Vec<f64>> {
+        let features = self.trunk.forward(input);
+        self.heads.iter_mut().map(|head| head.forward(&features)).collect()
+    }
+
+    /// Performs a backward pass through all heads and the trunk.
+    /// The gradients of all heads with respect to the trunk output are summed
+    /// before they are propagated into the trunk. Returns that summed gradient.
+    fn backward(
+        &mut self,
+        head_grads: Vec<Vec<f64>>,
+    ) -> Vec<f64> {
+        let mut trunk_grad = vec![0.0; self.trunk.output_size()];
+        for (head, grad) in self.heads.iter_mut().zip(head_grads) {
+            let head_input_grad = head.backward(grad);
+            for (acc, g) in trunk_grad.iter_mut().zip(head_input_grad) {
+                *acc += g;
+            }
+        }
+        self.trunk.backward(trunk_grad.clone());
+        trunk_grad
+    }
+
+    /// Sets the Adam hyperparameters used to train the trunk and all heads.
+    pub const fn set_adam_params(
+        &mut self,
+        adam_params: AdamParams,
+    ) {
+        self.adam_params = adam_params;
+    }
+
+    /// Trains the network on the given inputs and per head targets.
+    ///
+    /// `targets[j][h]` is the target of head `h` for sample `j`. The loss of a sample is the
+    /// sum of the mean squared errors of all heads.
+    /// Returns the average loss of the last epoch.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the number of inputs and targets differ or if a sample
+    /// does not provide a target for every head.
+    pub fn train(
+        &mut self,
+        inputs: &[Vec<f64>],
+        targets: &[Vec<Vec<f64>>],
+        learning_rate: f64,
+        epochs: usize,
+        use_adam: bool,
+    ) -> f64 {
+        assert_eq!(inputs.len(), targets.len(), "Number of inputs and targets must match");
+        let samples = inputs.iter().zip(targets).collect::<Vec<_>>();
+        train_jointly(self, &samples, learning_rate, epochs, use_adam, |nn, (input, target)| {
+            assert_eq!(target.len(), nn.heads.len(), "Every head needs a target");
+            let outputs = nn.forward(input);
+            let mut loss = 0.0;
+            let head_grads = outputs
+                .iter()
+                .zip(target.iter())
+                .map(|(output, head_target)| {
+                    let (head_loss, head_grad) = squared_error(output, head_target);
+                    loss += head_loss;
+                    head_grad
+                })
+                .collect();
+            nn.backward(head_grads);
+            loss
+        })
+    }
+
+    /// Computes the mean squared error of every head on the given data.
+    pub fn head_losses(
+        &mut self,
+        inputs: &[Vec<f64>],
+        targets: &[Vec<Vec<f64>>],
+    ) -> Vec<f64> {
+        let mut losses = vec![0.0; self.heads.len()];
+        for (input, target) in inputs.iter().zip(targets) {
+            let outputs = self.forward(input);
+            for ((loss, output), head_target) in losses.iter_mut().zip(&outputs).zip(target) {
+                *loss +=
+                    output.iter().zip(head_target).map(|(o, t)| (o - t) * (o - t)).sum::<f64>();
+            }
+        }
+        losses.iter().map(|loss| mean_loss(*loss, inputs.len())).collect()
+    }
+}
+
+impl JointNetwork for MultiHeadNetwork {
+    fn networks_mut(&mut self) -> Vec<&mut TrainableClassicNeuralNetwork> {
+        std::iter::once(&mut self.trunk).chain(self.heads.iter_mut()).collect()
+    }
+
+    fn adam_params(&self) -> AdamParams {
+        self.adam_params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        nn::shape::{ActivationData, ActivationType, LayerType},
+        utilities::util::Utils,
+    };
+
+    fn dense(
+        input_size: usize,
+        output_size: usize,
+    ) -> LayerShape {
+        LayerShape {
+            layer_type: LayerType::Dense { input_size, output_size },
+            activation: ActivationData::new(ActivationType::Sigmoid),
+        }
+    }
+
+    #[test]
+    fn test_multi_head_network_train() {
+        let model_directory = "test_multi_head_network".to_string();
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut nn = MultiHeadNetwork::new(
+            NeuralNetworkShape { layers: vec![dense(3, 4)] },
+            vec![
+                NeuralNetworkShape { layers: vec![dense(4, 2)] },
+                NeuralNetworkShape { layers: vec![dense(4, 3)] },
+            ],
+            &Directory::Internal(model_directory.clone()),
+            utils,
+        );
+        assert_eq!(nn.num_heads(), 2);
+
+        let inputs = vec![vec![1.0, 0.5, -0.5], vec![-1.0, 0.2, 0.7]];
+        let targets = vec![
+            vec![vec![0.9, 0.1], vec![0.2, 0.8, 0.5]],
+            vec![vec![0.1, 0.9], vec![0.8, 0.2, 0.5]],
+        ];
+
+        let outputs = nn.forward(&inputs[0]);
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(outputs[0].len(), 2);
+        assert_eq!(outputs[1].len(), 3);
+
+        // the trunk gradient is the sum of the gradients coming from both heads
+        let grad_first = nn.backward(vec![vec![1.0, -1.0], vec![0.0; 3]]);
+        let grad_second = nn.backward(vec![vec![0.0; 2], vec![0.5, 1.0, -0.5]]);
+        let grad_both = nn.backward(vec![vec![1.0, -1.0], vec![0.5, 1.0, -0.5]]);
+        assert!(grad_first.iter().any(|g| g.abs() > 0.0));
+        assert!(grad_second.iter().any(|g| g.abs() > 0.0));
+        for ((first, second), both) in grad_first.iter().zip(&grad_second).zip(&grad_both) {
+            assert!((first + second - both).abs() < 1e-12);
+        }
+
+        let losses_before = nn.head_losses(&inputs, &targets);
+        nn.train(&inputs, &targets, 0.5, 200, false);
+        let losses_after = nn.head_losses(&inputs, &targets);
+        for (before, after) in losses_before.iter().zip(&losses_after) {
+            assert!(after < before);
+        }
+
+        // training with Adam uses the Adam parameters of the network
+        nn.set_adam_params(AdamParams::new(0.8, 0.99, 1e-6));
+        assert_eq!(nn.adam_params(), AdamParams::new(0.8, 0.99, 1e-6));
+        nn.train(&inputs, &targets, 0.01, 50, true);
+        let losses_adam = nn.head_losses(&inputs, &targets);
+        for (before, after) in losses_after.iter().zip(&losses_adam) {
+            assert!(after < before);
+        }
+
+        drop(nn);
+        std::fs::remove_dir_all(model_directory).unwrap();
+    }
+}
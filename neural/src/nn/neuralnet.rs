@@ -1,27 +1,98 @@
-use crate::activation::{
-    activate::ActivationTrait, relu::ReLU, sigmoid::Sigmoid, softmax::Softmax, tanh::Tanh,
-};
+use crate::activation::{activate::ActivationTrait, registry::create_activation, softmax::Softmax};
+use crate::data::augment::NoiseAugmenter;
+use crate::data::loader::DataLoader;
+use crate::data::split::SplitMode;
+use crate::layer::dense_layer::layer_file_path;
+use crate::layer::dense_layer::read;
+use crate::layer::dense_layer::save;
+use crate::layer::dense_layer::spectral_norm;
 use crate::layer::dense_layer::DenseLayer;
 use crate::layer::dense_layer::TrainableDenseLayer;
+use crate::layer::dropout::Dropout;
+use crate::layer::embedding::EmbeddingLayer;
+use crate::layer::layer_trait::LayerGradients;
 use crate::layer::layer_trait::WrappedLayer;
 use crate::layer::layer_trait::WrappedTrainableLayer;
+use crate::layer::prelu::PReLULayer;
+use crate::layer::quantized::QuantizedDenseLayer;
+use crate::nn::inference_error::{check_finite, InferenceError};
 use crate::nn::nn_trait::{NeuralNetwork, TrainableNeuralNetwork};
-use crate::nn::shape::{ActivationType, LayerType, NeuralNetworkShape};
+use crate::nn::quantized::{check_quantizable, QuantizedNeuralNetwork};
+use crate::nn::shape::{ActivationData, ActivationType, LayerShape, LayerType, NeuralNetworkShape};
+use crate::training::adam::AdamParams;
+use crate::training::diagnostics::{TrainingDiagnostics, TrainingWarning};
+use crate::training::grad_noise::GradientNoise;
+use crate::training::history::TrainingHistory;
+use crate::training::loss::LossFunction;
+use crate::training::metrics::EpochMetrics;
+use crate::training::optimizer::Optimizer;
+use crate::training::swa::WeightAverage;
+use crate::training::training_options::TrainingOptions;
+use crate::training::verbosity::Verbosity;
 use crate::utilities::util::WrappedUtils;
 use alloc::allocatable::WrappedAllocatableTrait;
+use matrix::mat::{Matrix, WrappedMatrix};
+use utils::safer::safe_lock;
 
-use indicatif::ProgressDrawTarget;
 use indicatif::{ProgressBar, ProgressStyle};
 use num_traits::NumCast;
 use rand::prelude::SliceRandom;
-
-use std::path::Path;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 
 use std::boxed::Box;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use super::directory::Directory;
-use super::nn_factory::{copy_dir_recursive, get_first_free_model_directory};
+use super::load_error::ModelLoadError;
+use super::nn_factory::{save_atomically, save_in_place};
 use super::nn_trait::{WrappedNeuralNetwork, WrappedTrainableNeuralNetwork};
+use super::parallel::ParallelNetwork;
+
+/// Reads the shape of the model saved in `model_directory` and checks that every saved
+/// layer file can be parsed.
+fn read_saved_shape(model_directory: &str) -> Result<NeuralNetworkShape, ModelLoadError> {
+    let shape_path = format!("{model_directory}/shape.yaml");
+    let yaml = std::fs::read_to_string(&shape_path)
+        .map_err(|e| ModelLoadError::new(&shape_path, e.to_string()))?;
+    let shape = NeuralNetworkShape::from_yaml(&yaml)
+        .map_err(|e| ModelLoadError::new(&shape_path, e.to_string()))?;
+    for i in 0..shape.layers.len() {
+        let layer_path = layer_file_path(model_directory, i);
+        // layers which were never allocated have no file yet
+        if Path::new(&layer_path).is_file() {
+            read(layer_path.clone()).map_err(|e| ModelLoadError::new(layer_path, e.to_string()))?;
+        }
+    }
+    Ok(shape)
+}
+
+/// Panics with a descriptive message if the input does not match the input size of the shape.
+pub(crate) fn check_input_size(
+    shape: &NeuralNetworkShape,
+    input: &[f64],
+) {
+    let input_size = shape.layers.first().map_or(0, LayerShape::input_size);
+    assert!(
+        input.len() == input_size,
+        "Input has {} values but the neural network expects {input_size} inputs",
+        input.len()
+    );
+}
+
+/// Returns an error if `index` is not smaller than `size`.
+fn check_parameter_index(
+    name: &str,
+    index: usize,
+    size: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if index >= size {
+        return Err(format!("{name} index {index} is out of range for size {size}").into());
+    }
+    Ok(())
+}
 
 /// A neural network.
 #[derive(Debug)]
@@ -31,15 +102,14 @@ pub struct ClassicNeuralNetwork {
     shape: NeuralNetworkShape,
     model_directory: Directory,
     past_internal_directory: Vec<String>,
+    persist_on_drop: bool,
     utils: WrappedUtils,
+    // holds the input of every layer of predict_into, so that it does not allocate
+    prediction_buffer: Vec<f64>,
 }
 
 impl ClassicNeuralNetwork {
     /// Creates a new `NeuralNetwork` from the given shape.
-    ///
-    /// # Panics
-    ///
-    /// This function will panic if the activation type is Softmax and its temperature is not set.
     #[must_use]
     pub fn new(
         shape: NeuralNetworkShape,
@@ -53,28 +123,39 @@ impl ClassicNeuralNetwork {
             shape,
             model_directory: Directory::Internal(internal_model_directory),
             past_internal_directory: Vec::new(),
+            persist_on_drop: false,
             utils,
+            prediction_buffer: Vec::new(),
         };
 
         // Initialize layers and activations based on the provided shape.
         for (i, layer_shape) in shape_clone.layers.iter().enumerate() {
             // Here you would instantiate the appropriate Layer and Activation objects.
-            let dense_layer = DenseLayer::new(
-                layer_shape.input_size(),
-                layer_shape.output_size(),
-                network.model_directory.clone(),
-                i,
-            );
-            let layer = WrappedLayer::new(Box::new(dense_layer));
-            let activation = match layer_shape.activation.activation_type() {
-                ActivationType::ReLU => Box::new(ReLU::new()) as Box<dyn ActivationTrait + Send>,
-                ActivationType::Sigmoid => Box::new(Sigmoid) as Box<dyn ActivationTrait + Send>,
-                ActivationType::Tanh => Box::new(Tanh) as Box<dyn ActivationTrait + Send>,
-                ActivationType::Softmax => {
-                    Box::new(Softmax::new(layer_shape.activation.temperature().unwrap()))
-                        as Box<dyn ActivationTrait + Send>
+            let layer = match layer_shape.layer_type() {
+                LayerType::Dense { input_size, output_size } => {
+                    let dense_layer = DenseLayer::new(
+                        input_size,
+                        output_size,
+                        network.model_directory.clone(),
+                        i,
+                    );
+                    WrappedLayer::new(Box::new(dense_layer))
+                },
+                LayerType::Embedding { num_embeddings, dim } => {
+                    let embedding_layer = EmbeddingLayer::new(
+                        num_embeddings,
+                        dim,
+                        network.model_directory.clone(),
+                        i,
+                    );
+                    WrappedLayer::new(Box::new(embedding_layer))
+                },
+                LayerType::PReLU { size } => {
+                    let prelu_layer = PReLULayer::new(size, network.model_directory.clone(), i);
+                    WrappedLayer::new(Box::new(prelu_layer))
                 },
             };
+            let activation = create_activation(&layer_shape.activation);
 
             network.add_activation_and_layer(activation, layer);
         }
@@ -83,28 +164,35 @@ impl ClassicNeuralNetwork {
     }
 
     /// Creates a new `NeuralNetwork` from the given model directory.
-    ///
-    /// # Panics
-    ///
-    /// This function will panic if the model directory is valid but the shape cannot be unwrapped.
-    #[allow(clippy::question_mark)]
+    /// Returns `None` if the model cannot be loaded, `try_from_disk` reports the reason.
     #[must_use]
     pub fn from_disk(
         model_directory: String,
         utils: WrappedUtils,
     ) -> Option<Self> {
-        let shape = NeuralNetworkShape::from_disk(&model_directory);
-        if shape.is_none() {
-            return None;
-        }
-        let sh = shape.unwrap();
+        Self::try_from_disk(model_directory, utils).ok()
+    }
+
+    /// Creates a new `NeuralNetwork` from the given model directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the offending file if the shape is missing or malformed
+    /// or if a saved layer file cannot be parsed.
+    pub fn try_from_disk(
+        model_directory: String,
+        utils: WrappedUtils,
+    ) -> Result<Self, ModelLoadError> {
+        let sh = read_saved_shape(&model_directory)?;
         let mut network = Self {
             layers: Vec::new(),
             activations: Vec::new(),
             shape: sh.clone(),
             model_directory: Directory::User(model_directory),
             past_internal_directory: Vec::new(),
+            persist_on_drop: false,
             utils,
+            prediction_buffer: Vec::new(),
         };
 
         for i in 0..sh.layers.len() {
@@ -118,54 +206,98 @@ impl ClassicNeuralNetwork {
                     );
                     WrappedLayer::new(Box::new(layer))
                 },
-            };
-            let activation = match sh.layers[i].activation.activation_type() {
-                ActivationType::ReLU => Box::new(ReLU::new()) as Box<dyn ActivationTrait + Send>,
-                ActivationType::Sigmoid => Box::new(Sigmoid) as Box<dyn ActivationTrait + Send>,
-                ActivationType::Tanh => Box::new(Tanh) as Box<dyn ActivationTrait + Send>,
-                ActivationType::Softmax => {
-                    Box::new(Softmax::new(sh.layers[i].activation.temperature().unwrap()))
-                        as Box<dyn ActivationTrait + Send>
+                LayerType::Embedding { num_embeddings, dim } => {
+                    let layer = EmbeddingLayer::new(
+                        *num_embeddings,
+                        *dim,
+                        network.model_directory.clone(),
+                        i,
+                    );
+                    WrappedLayer::new(Box::new(layer))
+                },
+                LayerType::PReLU { size } => {
+                    let layer = PReLULayer::new(*size, network.model_directory.clone(), i);
+                    WrappedLayer::new(Box::new(layer))
                 },
             };
+            let activation = create_activation(&sh.layers[i].activation);
 
             network.add_activation_and_layer(activation, layer);
         }
 
-        Some(network)
+        Ok(network)
+    }
+
+    /// Creates a new `NeuralNetwork` from the dequantized weights of the quantized network,
+    /// saving them to the given internal model directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the layers cannot be saved.
+    pub fn from_quantized(
+        quantized: &QuantizedNeuralNetwork,
+        internal_model_directory: String,
+        utils: WrappedUtils,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        for (i, layer) in quantized.layers().iter().enumerate() {
+            save(
+                format!("{internal_model_directory}/layers/layer_{i}.txt"),
+                &layer.dequantized_weights(),
+                layer.biases(),
+            )?;
+        }
+        Ok(Self::new(quantized.shape().clone(), internal_model_directory, utils))
+    }
+
+    /// Saves the neural network to the given user model directory, atomically or in place.
+    fn save_to(
+        &mut self,
+        user_model_directory: String,
+        atomic: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // Check if we're in test mode - if so, force save to Internal directory
+        if self.utils.is_test_mode() {
+            let workspace = self.utils.get_workspace();
+            let internal_path = if workspace.is_empty() {
+                user_model_directory
+            } else {
+                format!("{workspace}/{user_model_directory}")
+            };
+            // Don't add current directory to past_internal when in test mode to avoid cleanup
+            // since we want to keep the saved model for testing
+            self.model_directory = Directory::Internal(internal_path);
+            let model_directory = self.model_directory.path();
+            return self.save_internal(&model_directory, atomic);
+        }
+
+        if let Directory::Internal(_) = self.model_directory {
+            self.past_internal_directory.push(self.model_directory.path());
+        }
+        self.model_directory = Directory::User(user_model_directory);
+        let model_directory = self.model_directory.path();
+        self.save_internal(&model_directory, atomic)
     }
 
-    /// Saves the neural network to disk with the internal logic.
+    /// Saves the neural network to disk with the internal logic, atomically or in place.
     fn save_internal(
         &self,
         model_directory: &str,
+        atomic: bool,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        // remove the directory if it exists
-        let backup_directory = format!("{model_directory}_backup");
-        if std::fs::metadata(model_directory).is_ok() {
-            // copy the directory to a backup
-            copy_dir_recursive(Path::new(&model_directory), Path::new(&backup_directory))?;
-            std::fs::create_dir_all(model_directory)?;
+        let write = |directory: &str| {
+            self.shape().to_yaml(directory);
+            self.save_layers(directory)
+        };
+        if atomic {
+            save_atomically(model_directory, write)
         } else {
-            // create directory if it doesn't exist
-            std::fs::create_dir_all(model_directory)?;
-        }
-
-        let shape = self.shape();
-        shape.to_yaml(model_directory);
-        self.save_layers(model_directory)?;
-
-        // if backup directory exists, remove it
-        if std::fs::metadata(&backup_directory).is_ok() {
-            std::fs::remove_dir_all(&backup_directory)?;
+            save_in_place(model_directory, write)
         }
-
-        Ok(())
     }
 
     /// Retrieves the first free model directory.
     fn get_first_free_model_directory(&self) -> String {
-        get_first_free_model_directory(&self.model_directory)
+        self.utils.first_free_model_directory(&self.model_directory)
     }
 
     /// Adds an activation and a layer to the neural network.
@@ -202,6 +334,7 @@ impl ClassicNeuralNetwork {
         &mut self,
         input: &[f64],
     ) -> Vec<f64> {
+        check_input_size(&self.shape, input);
         let mut output = input.to_vec();
         for (layer, activation) in self.layers.iter_mut().zip(&mut self.activations) {
             layer.mark_for_use();
@@ -224,6 +357,50 @@ impl NeuralNetwork for ClassicNeuralNetwork {
         self.forward(input.as_slice())
     }
 
+    fn predict_checked(
+        &mut self,
+        input: Vec<f64>,
+    ) -> Result<Vec<f64>, InferenceError> {
+        check_input_size(&self.shape, &input);
+        let mut output = input;
+        for (i, (layer, activation)) in
+            self.layers.iter_mut().zip(&mut self.activations).enumerate()
+        {
+            layer.mark_for_use();
+            self.utils.allocate(layer);
+            output = layer.forward(&output, self.utils.clone());
+            layer.free_from_use();
+            check_finite(&output, i)?;
+            output = activation.forward(&output);
+            check_finite(&output, i)?;
+        }
+        Ok(output)
+    }
+
+    /// Runs the layers alternately between `out` and a buffer kept by the network,
+    /// so that no vector is allocated once both buffers have grown to the layer sizes.
+    fn predict_into(
+        &mut self,
+        input: &[f64],
+        out: &mut Vec<f64>,
+    ) {
+        check_input_size(&self.shape, input);
+        let mut layer_input = std::mem::take(&mut self.prediction_buffer);
+        layer_input.clear();
+        layer_input.extend_from_slice(input);
+        for (layer, activation) in self.layers.iter_mut().zip(&mut self.activations) {
+            layer.mark_for_use();
+            self.utils.allocate(layer);
+            layer.forward_into(&layer_input, out, self.utils.clone());
+            layer.free_from_use();
+            activation.forward_in_place(out);
+            std::mem::swap(&mut layer_input, out);
+        }
+        // the output of the last layer is in layer_input
+        std::mem::swap(&mut layer_input, out);
+        self.prediction_buffer = layer_input;
+    }
+
     fn shape(&self) -> NeuralNetworkShape {
         self.shape.clone()
     }
@@ -232,27 +409,14 @@ impl NeuralNetwork for ClassicNeuralNetwork {
         &mut self,
         user_model_directory: String,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        // Check if we're in test mode - if so, force save to Internal directory
-        if self.utils.is_test_mode() {
-            let workspace = self.utils.get_workspace();
-            let internal_path = if workspace.is_empty() {
-                user_model_directory
-            } else {
-                format!("{workspace}/{user_model_directory}")
-            };
-            // Don't add current directory to past_internal when in test mode to avoid cleanup
-            // since we want to keep the saved model for testing
-            self.model_directory = Directory::Internal(internal_path);
-            let model_directory = self.model_directory.path();
-            return self.save_internal(&model_directory);
-        }
+        self.save_to(user_model_directory, true)
+    }
 
-        if let Directory::Internal(_) = self.model_directory {
-            self.past_internal_directory.push(self.model_directory.path());
-        }
-        self.model_directory = Directory::User(user_model_directory);
-        let model_directory = self.model_directory.path();
-        self.save_internal(&model_directory)
+    fn save_fast(
+        &mut self,
+        user_model_directory: String,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.save_to(user_model_directory, false)
     }
 
     fn get_model_directory(&self) -> Directory {
@@ -278,16 +442,19 @@ impl NeuralNetwork for ClassicNeuralNetwork {
         self.model_directory = Directory::Internal(self.model_directory.path());
     }
 
+    fn persist_on_drop(&mut self) {
+        self.persist_on_drop = true;
+    }
+
     fn duplicate(&self) -> WrappedNeuralNetwork {
         // create a sibling directory with the postfix _clone appendended to model_direcotory path
         let model_directory = self.get_first_free_model_directory();
         // Save the model to the new directory
-        self.save_internal(&model_directory).unwrap();
+        self.save_internal(&model_directory, true).unwrap();
         // Clone the neural network by cloning its layers and activations
         let mut new_layers = Vec::new();
         for (i, layer) in self.layers.iter().enumerate() {
             new_layers.push(layer.duplicate(model_directory.clone(), i));
-            layer.cleanup();
         }
         WrappedNeuralNetwork::new(Box::new(Self {
             layers: new_layers,
@@ -295,13 +462,27 @@ impl NeuralNetwork for ClassicNeuralNetwork {
             shape: self.shape.clone(),
             model_directory: Directory::Internal(model_directory),
             past_internal_directory: Vec::new(),
+            persist_on_drop: false,
             utils: self.utils.clone(),
+            prediction_buffer: Vec::new(),
         }))
     }
 
     fn get_utils(&self) -> WrappedUtils {
         self.utils.clone()
     }
+
+    fn to_quantized(&mut self) -> Result<QuantizedNeuralNetwork, Box<dyn std::error::Error>> {
+        check_quantizable(&self.shape)?;
+        let mut layers = Vec::with_capacity(self.layers.len());
+        for layer in &mut self.layers {
+            layer.mark_for_use();
+            self.utils.allocate(layer);
+            layers.push(QuantizedDenseLayer::new(&layer.get_weights(), &layer.get_biases()));
+            layer.free_from_use();
+        }
+        QuantizedNeuralNetwork::new(self.shape.clone(), layers)
+    }
 }
 
 impl Drop for ClassicNeuralNetwork {
@@ -315,6 +496,12 @@ impl Drop for ClassicNeuralNetwork {
             self.save_layout();
             self.deallocate();
         }
+        if self.persist_on_drop {
+            // write the layers so that the kept directory holds the current weights
+            self.save_layout();
+            self.deallocate();
+            return;
+        }
         // Interne Verzeichnisse immer entfernen, unabhängig vom Testmodus
         if let Directory::Internal(dir) = &self.model_directory {
             if std::fs::metadata(dir).is_ok() {
@@ -331,21 +518,59 @@ impl Drop for ClassicNeuralNetwork {
 
 /// A neural network.
 #[derive(Debug)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct TrainableClassicNeuralNetwork {
     layers: Vec<WrappedTrainableLayer>,
     activations: Vec<Box<dyn ActivationTrait + Send>>,
     shape: NeuralNetworkShape,
     model_directory: Directory,
     past_internal_model_directory: Vec<String>,
+    persist_on_drop: bool,
     utils: WrappedUtils,
+    noise_augmenter: Option<NoiseAugmenter>,
+    dropout: Option<Dropout>,
+    dropout_rng: Option<StdRng>,
+    dropout_masks: Vec<Vec<f64>>,
+    record_grad_norms: bool,
+    history: TrainingHistory,
+    diagnose: bool,
+    diagnostics: Option<TrainingDiagnostics>,
+    warnings: Vec<TrainingWarning>,
+    options: TrainingOptions,
+    // running average of the weights collected by the last train
+    swa_average: WeightAverage,
+    loss_function: LossFunction,
+    // the loss is computed on the logits of the final softmax while training
+    logits_loss: bool,
+    // number of Adam updates made by partial_fit
+    partial_fit_steps: usize,
+    // holds the input of every layer of predict_into, so that it does not allocate
+    prediction_buffer: Vec<f64>,
+}
+
+/// Creates the untrained layer of the given shape at the given position of a network.
+fn trainable_layer(
+    layer_shape: &LayerShape,
+    model_directory: &Directory,
+    layer_index: usize,
+) -> WrappedTrainableLayer {
+    match layer_shape.layer_type() {
+        LayerType::Dense { input_size, output_size } => WrappedTrainableLayer::new(Box::new(
+            TrainableDenseLayer::new(input_size, output_size, model_directory.clone(), layer_index),
+        )),
+        LayerType::Embedding { num_embeddings, dim } => WrappedTrainableLayer::new(Box::new(
+            EmbeddingLayer::new(num_embeddings, dim, model_directory.clone(), layer_index),
+        )),
+        LayerType::PReLU { size } => WrappedTrainableLayer::new(Box::new(PReLULayer::new(
+            size,
+            model_directory.clone(),
+            layer_index,
+        ))),
+    }
 }
 
 impl TrainableClassicNeuralNetwork {
     /// Creates a new `NeuralNetwork` from the given shape.
-    ///
-    /// # Panics
-    ///
-    /// This function will panic if the activation type is Softmax and its temperature is not set.
     #[must_use]
     pub fn new(
         shape: NeuralNetworkShape,
@@ -357,29 +582,32 @@ impl TrainableClassicNeuralNetwork {
             layers: Vec::new(),
             activations: Vec::new(),
             shape,
-            model_directory: Directory::Internal(get_first_free_model_directory(model_directory)),
+            model_directory: Directory::Internal(utils.first_free_model_directory(model_directory)),
             past_internal_model_directory: Vec::new(),
+            persist_on_drop: false,
             utils,
+            prediction_buffer: Vec::new(),
+            noise_augmenter: None,
+            dropout: None,
+            dropout_rng: None,
+            dropout_masks: Vec::new(),
+            record_grad_norms: false,
+            history: TrainingHistory::new(),
+            diagnose: false,
+            diagnostics: None,
+            warnings: Vec::new(),
+            options: TrainingOptions::default(),
+            swa_average: WeightAverage::new(),
+            loss_function: LossFunction::MeanSquaredError,
+            logits_loss: false,
+            partial_fit_steps: 0,
         };
 
         // Initialize layers and activations based on the provided shape.
         for (i, layer_shape) in shape_clone.layers.iter().enumerate() {
             // Here you would instantiate the appropriate Layer and Activation objects.
-            let layer = WrappedTrainableLayer::new(Box::new(TrainableDenseLayer::new(
-                layer_shape.input_size(),
-                layer_shape.output_size(),
-                network.model_directory.clone(),
-                i,
-            )));
-            let activation = match layer_shape.activation.activation_type() {
-                ActivationType::ReLU => Box::new(ReLU::new()) as Box<dyn ActivationTrait + Send>,
-                ActivationType::Sigmoid => Box::new(Sigmoid) as Box<dyn ActivationTrait + Send>,
-                ActivationType::Tanh => Box::new(Tanh) as Box<dyn ActivationTrait + Send>,
-                ActivationType::Softmax => {
-                    Box::new(Softmax::new(layer_shape.activation.temperature().unwrap()))
-                        as Box<dyn ActivationTrait + Send>
-                },
-            };
+            let layer = trainable_layer(layer_shape, &network.model_directory, i);
+            let activation = create_activation(&layer_shape.activation);
 
             network.add_activation_and_trainable_layer(activation, layer);
         }
@@ -389,6 +617,49 @@ impl TrainableClassicNeuralNetwork {
         network
     }
 
+    /// Creates a new `NeuralNetwork` from the given shape using the given weights and biases
+    /// instead of random ones. There has to be one `(weights, biases)` pair per layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the number of layers or the dimensions of any layer's weights or
+    /// biases do not match the shape.
+    pub fn from_shape_and_weights(
+        shape: NeuralNetworkShape,
+        weights: Vec<(Matrix<f64>, Vec<f64>)>,
+        model_directory: &Directory,
+        utils: WrappedUtils,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        if weights.len() != shape.layers.len() {
+            return Err(format!(
+                "Expected weights for {} layers, found {}",
+                shape.layers.len(),
+                weights.len()
+            )
+            .into());
+        }
+        let network = Self::new(shape, model_directory, utils);
+        for (i, (layer_weights, biases)) in weights.into_iter().enumerate() {
+            let mut layer = network.allocated_layer(i);
+            let current_weights = layer.get_weights();
+            let expected =
+                (current_weights.rows(), current_weights.cols(), layer.get_biases().len());
+            let found = (layer_weights.rows(), layer_weights.cols(), biases.len());
+            if expected != found {
+                layer.free_from_use();
+                return Err(format!(
+                    "Layer {i} expects {}x{} weights and {} biases, found {}x{} weights and {} biases",
+                    expected.0, expected.1, expected.2, found.0, found.1, found.2
+                )
+                .into());
+            }
+            let layer_weights = WrappedMatrix { mat: Arc::new(Mutex::new(layer_weights)) };
+            layer.assign_weights_and_biases(&layer_weights, &biases);
+            layer.free_from_use();
+        }
+        Ok(network)
+    }
+
     #[must_use]
     pub fn new_dir(
         model_directory: &Directory,
@@ -398,9 +669,25 @@ impl TrainableClassicNeuralNetwork {
             layers: Vec::new(),
             activations: Vec::new(),
             shape: NeuralNetworkShape::default(),
-            model_directory: Directory::Internal(get_first_free_model_directory(model_directory)),
+            model_directory: Directory::Internal(utils.first_free_model_directory(model_directory)),
             past_internal_model_directory: Vec::new(),
+            persist_on_drop: false,
             utils,
+            prediction_buffer: Vec::new(),
+            noise_augmenter: None,
+            dropout: None,
+            dropout_rng: None,
+            dropout_masks: Vec::new(),
+            record_grad_norms: false,
+            history: TrainingHistory::new(),
+            diagnose: false,
+            diagnostics: None,
+            warnings: Vec::new(),
+            options: TrainingOptions::default(),
+            swa_average: WeightAverage::new(),
+            loss_function: LossFunction::MeanSquaredError,
+            logits_loss: false,
+            partial_fit_steps: 0,
         };
 
         network.save_layout();
@@ -408,167 +695,654 @@ impl TrainableClassicNeuralNetwork {
         network
     }
 
+    /// Saves the neural network to the given user model directory, atomically or in place.
+    fn save_to(
+        &mut self,
+        user_model_directory: String,
+        atomic: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // Check if we're in test mode - if so, force save to Internal directory
+        if self.utils.is_test_mode() {
+            let workspace = self.utils.get_workspace();
+            let internal_path = if workspace.is_empty() {
+                user_model_directory
+            } else {
+                format!("{workspace}/{user_model_directory}")
+            };
+            // Don't add current directory to past_internal when in test mode to avoid cleanup
+            // since we want to keep the saved model for testing
+            self.model_directory = Directory::Internal(internal_path);
+            let model_directory = self.model_directory.path();
+            return self.save_internal(&model_directory, atomic);
+        }
+
+        if self.model_directory.path() != user_model_directory {
+            self.past_internal_model_directory.push(self.model_directory.path());
+        }
+        self.model_directory = Directory::User(user_model_directory);
+        let model_directory = self.model_directory.path();
+        self.save_internal(&model_directory, atomic)
+    }
+
     fn save_internal(
         &self,
         model_directory: &str,
+        atomic: bool,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        // remove the directory if it exists
-        let backup_directory = format!("{model_directory}_backup");
-        if std::fs::metadata(model_directory).is_ok() {
-            // copy the directory to a backup not move
-            copy_dir_recursive(Path::new(&model_directory), Path::new(&backup_directory))?;
-            std::fs::create_dir_all(model_directory)?;
+        let write = |directory: &str| {
+            self.shape().to_yaml(directory);
+            self.save_layers(directory)
+        };
+        if atomic {
+            save_atomically(model_directory, write)
         } else {
-            // create directory if it doesn't exist
-            std::fs::create_dir_all(model_directory)?;
+            save_in_place(model_directory, write)
         }
+    }
 
-        let shape = self.shape();
-        shape.to_yaml(model_directory);
-        self.save_layers(model_directory)?;
+    /// Sets a noise augmenter which adds fresh Gaussian noise to the training inputs
+    /// in every epoch of `train`. Passing `None` disables the augmentation.
+    pub const fn set_noise_augmenter(
+        &mut self,
+        noise_augmenter: Option<NoiseAugmenter>,
+    ) {
+        self.noise_augmenter = noise_augmenter;
+    }
 
-        // if backup directory exists, remove it
-        if std::fs::metadata(&backup_directory).is_ok() {
-            std::fs::remove_dir_all(&backup_directory)?;
-        }
+    /// Sets the dropout applied to the hidden activations during training and by `predict_mc`.
+    /// Passing `None` disables the dropout. The random number generator is seeded once here
+    /// and advances with every forward pass.
+    pub fn set_dropout(
+        &mut self,
+        dropout: Option<Dropout>,
+    ) {
+        self.dropout = dropout;
+        self.dropout_rng = dropout.map(|dropout| dropout.rng());
+        self.dropout_masks.clear();
+    }
 
-        Ok(())
+    /// Enables recording the mean L2 norm of the gradients of every layer per epoch
+    /// into the training history.
+    pub const fn set_record_grad_norms(
+        &mut self,
+        record_grad_norms: bool,
+    ) {
+        self.record_grad_norms = record_grad_norms;
     }
 
-    /// Adjusts the weights of the neural network using the Adam optimizer.
-    fn adjust_adam(
+    /// Returns the history of the last call to `train`.
+    #[must_use]
+    pub fn training_history(&self) -> TrainingHistory {
+        self.history.clone()
+    }
+
+    /// Enables checking the gradients and the loss during the first epoch of `train`.
+    pub const fn set_diagnose(
         &mut self,
-        t: usize,
-        learning_rate: f64,
-        beta1: f64,
-        beta2: f64,
-        epsilon: f64,
+        diagnose: bool,
     ) {
-        for layer in &mut self.layers {
-            layer.adjust_adam(t, learning_rate, beta1, beta2, epsilon, self.utils.clone());
-        }
+        self.diagnose = diagnose;
     }
 
-    /// Saves the layers of the neural network to disk.
-    fn save_layers(
+    /// Returns the diagnostics of the first epoch of the last call to `train`.
+    /// Only available if diagnosing is enabled.
+    #[must_use]
+    pub const fn training_diagnostics(&self) -> Option<TrainingDiagnostics> {
+        self.diagnostics
+    }
+
+    /// Returns the warnings about the training setup raised by the last call to `train`.
+    #[must_use]
+    pub fn training_warnings(&self) -> &[TrainingWarning] {
+        &self.warnings
+    }
+
+    /// Returns the L2 norm of the current gradients of all layers.
+    fn grad_norm(&self) -> f64 {
+        (0..self.layers.len())
+            .map(|i| {
+                let mut layer = self.allocated_layer(i);
+                let grad_norm_sq = layer.grad_norm_sq();
+                layer.free_from_use();
+                grad_norm_sq
+            })
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    /// Adds the L2 norm of the current gradients of every layer to the given sums.
+    fn accumulate_grad_norms(
         &self,
-        model_directory: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        // make a layers subdirectory
-        if std::fs::metadata(format!("{model_directory}/layers")).is_err() {
-            std::fs::create_dir_all(format!("{model_directory}/layers"))?;
-        }
-        for (i, layer) in self.layers.iter().enumerate() {
-            layer.save_weights(format!("{model_directory}/layers/layer_{i}.txt"))?;
+        grad_norm_sums: &mut [f64],
+    ) {
+        for (i, sum) in grad_norm_sums.iter_mut().enumerate() {
+            let mut layer = self.allocated_layer(i);
+            *sum += layer.grad_norm_sq().sqrt();
+            layer.free_from_use();
         }
-        Ok(())
     }
 
-    /// Saves the layout of the neural network to disk.
-    fn save_layout(&self) {
-        let shape = self.shape();
-        // ensure the directory exists
-        if std::fs::metadata(self.model_directory.path()).is_err() {
-            std::fs::create_dir_all(self.model_directory.path()).unwrap();
+    /// Adds the noise of the given epoch to the current gradients of every layer.
+    fn add_grad_noise(
+        &self,
+        grad_noise: &GradientNoise,
+        epoch: usize,
+        rng: &mut StdRng,
+    ) {
+        for i in 0..self.layers.len() {
+            let mut layer = self.allocated_layer(i);
+            let mut gradients = layer.gradients();
+            grad_noise.apply(&mut gradients, epoch, rng);
+            layer.set_gradients(&gradients);
+            layer.free_from_use();
         }
-        shape.to_yaml(&self.model_directory.path());
     }
 
-    /// Adds an activation and a layer to the neural network.
-    fn add_activation_and_trainable_layer(
-        &mut self,
-        activation: Box<dyn ActivationTrait + Send>,
-        layer: WrappedTrainableLayer,
-    ) {
-        self.activations.push(activation);
-        self.layers.push(layer);
+    /// Rescales the incoming weights of every neuron to at most the configured max norm.
+    fn apply_max_weight_norm(&self) {
+        let Some(max_norm) = self.options.max_weight_norm else {
+            return;
+        };
+        for i in 0..self.layers.len() {
+            let mut layer = self.allocated_layer(i);
+            layer.constrain_max_norm(max_norm);
+            layer.free_from_use();
+        }
     }
 
-    /// Performs a forward pass through the network with the given input.
-    fn forward(
+    /// Monte-Carlo dropout prediction for uncertainty estimates.
+    /// Performs `samples` forward passes with the dropout active and returns
+    /// the mean and the variance of every output.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `samples` is zero or if the input size does not match.
+    pub fn predict_mc(
         &mut self,
         input: &[f64],
-    ) -> Vec<f64> {
+        samples: usize,
+    ) -> (Vec<f64>, Vec<f64>) {
+        assert!(samples > 0, "predict_mc needs at least one sample");
+        let outputs =
+            (0..samples).map(|_| self.forward_with_dropout(input, true)).collect::<Vec<_>>();
+
+        let samples_f64: f64 = NumCast::from(samples).expect("Failed to convert samples to f64");
+        let output_size = outputs[0].len();
+        let mean = (0..output_size)
+            .map(|j| outputs.iter().map(|output| output[j]).sum::<f64>() / samples_f64)
+            .collect::<Vec<_>>();
+        let variance = mean
+            .iter()
+            .enumerate()
+            .map(|(j, m)| {
+                outputs.iter().map(|output| (output[j] - m) * (output[j] - m)).sum::<f64>()
+                    / samples_f64
+            })
+            .collect();
+        (mean, variance)
+    }
+
+    /// Returns the layer at the given position marked for use and allocated.
+    fn allocated_layer(
+        &self,
+        layer_index: usize,
+    ) -> WrappedTrainableLayer {
+        let mut layer = self.layers[layer_index].clone();
+        layer.mark_for_use();
+        self.utils.clone().allocate_trainable(&layer);
+        layer
+    }
+
+    /// Returns a network running copies of this network and `other` side by side on the same
+    /// input, concatenating their outputs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input sizes of both networks differ.
+    pub fn parallel(
+        &self,
+        other: &Self,
+    ) -> Result<ParallelNetwork, Box<dyn std::error::Error>> {
+        ParallelNetwork::new(self.copy()?, other.copy()?)
+    }
+
+    /// Returns an independent copy of the network holding the same weights and biases.
+    fn copy(&self) -> Result<Self, Box<dyn std::error::Error>> {
+        let weights = (0..self.layers.len())
+            .map(|i| {
+                self.layer_parameters(i)
+                    .map(|(weights, biases)| (safe_lock(&weights.mat).clone(), biases))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_shape_and_weights(
+            self.shape.clone(),
+            weights,
+            &self.model_directory,
+            self.utils.clone(),
+        )
+    }
+
+    /// Loads the weights and biases of the network saved in the given directory.
+    fn load_checkpoint(
+        &mut self,
+        checkpoint_directory: String,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let checkpoint = Self::try_from_disk(checkpoint_directory, self.utils.clone())?;
+        self.load_weights_flat(&checkpoint.weights_flat())
+    }
+
+    /// Returns a copy of the weights and biases of the layer at the given position.
+    fn layer_parameters(
+        &self,
+        layer_index: usize,
+    ) -> Result<(WrappedMatrix<f64>, Vec<f64>), Box<dyn std::error::Error>> {
+        check_parameter_index("Layer", layer_index, self.layers.len())?;
+        let mut layer = self.allocated_layer(layer_index);
+        let parameters = (layer.get_weights(), layer.get_biases());
+        layer.free_from_use();
+        Ok(parameters)
+    }
+
+    /// Returns the number of weight rows, weight columns and biases of every layer.
+    fn layer_sizes(&self) -> Vec<(usize, usize, usize)> {
+        (0..self.layers.len())
+            .map(|i| {
+                let mut layer = self.allocated_layer(i);
+                let layer_weights = layer.get_weights();
+                let size = (layer_weights.rows(), layer_weights.cols(), layer.get_biases().len());
+                layer.free_from_use();
+                size
+            })
+            .collect()
+    }
+
+    /// Applies `edit` to the weights and biases of the layer at the given position and assigns
+    /// them back if it succeeds.
+    fn edit_layer_parameters(
+        &self,
+        layer_index: usize,
+        edit: impl FnOnce(&WrappedMatrix<f64>, &mut Vec<f64>) -> Result<(), Box<dyn std::error::Error>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (weights, mut biases) = self.layer_parameters(layer_index)?;
+        edit(&weights, &mut biases)?;
+        let mut layer = self.allocated_layer(layer_index);
+        layer.assign_weights_and_biases(&weights, &biases);
+        layer.free_from_use();
+        Ok(())
+    }
+
+    /// Replaces the dense layer at the given position by one holding the given weights and biases.
+    fn replace_dense_layer(
+        &mut self,
+        layer_index: usize,
+        weights: &WrappedMatrix<f64>,
+        biases: &[f64],
+    ) {
+        // write back and release the old layer before its file is reused by the new one
+        self.utils.clone().deallocate_trainable(&self.layers[layer_index]);
+        self.layers[layer_index] = WrappedTrainableLayer::new(Box::new(TrainableDenseLayer::new(
+            weights.cols(),
+            weights.rows(),
+            self.model_directory.clone(),
+            layer_index,
+        )));
+        let mut layer = self.allocated_layer(layer_index);
+        layer.assign_weights_and_biases(weights, biases);
+        layer.free_from_use();
+    }
+
+    /// Returns the learning rate multiplier of the layer at the given position.
+    fn lr_multiplier(
+        &self,
+        layer_index: usize,
+    ) -> f64 {
+        self.options.lr_multipliers.as_ref().map_or(1.0, |multipliers| multipliers[layer_index])
+    }
+
+    /// Adjusts the weights of the neural network using the Adam optimizer.
+    pub(crate) fn adjust_adam(
+        &mut self,
+        t: usize,
+        learning_rate: f64,
+        beta1: f64,
+        beta2: f64,
+        epsilon: f64,
+    ) {
+        for i in 0..self.layers.len() {
+            let layer_learning_rate = learning_rate * self.lr_multiplier(i);
+            self.layers[i].adjust_adam(
+                t,
+                layer_learning_rate,
+                beta1,
+                beta2,
+                epsilon,
+                self.utils.clone(),
+            );
+        }
+    }
+
+    /// Saves the layers of the neural network to disk.
+    fn save_layers(
+        &self,
+        model_directory: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // make a layers subdirectory
+        if std::fs::metadata(format!("{model_directory}/layers")).is_err() {
+            std::fs::create_dir_all(format!("{model_directory}/layers"))?;
+        }
+        for (i, layer) in self.layers.iter().enumerate() {
+            layer.save_weights(format!("{model_directory}/layers/layer_{i}.txt"))?;
+        }
+        Ok(())
+    }
+
+    /// Saves the layout of the neural network to disk.
+    fn save_layout(&self) {
+        let shape = self.shape();
+        // ensure the directory exists
+        if std::fs::metadata(self.model_directory.path()).is_err() {
+            std::fs::create_dir_all(self.model_directory.path()).unwrap();
+        }
+        shape.to_yaml(&self.model_directory.path());
+    }
+
+    /// Adds an activation and a layer to the neural network.
+    fn add_activation_and_trainable_layer(
+        &mut self,
+        activation: Box<dyn ActivationTrait + Send>,
+        layer: WrappedTrainableLayer,
+    ) {
+        self.activations.push(activation);
+        self.layers.push(layer);
+    }
+
+    /// Performs a forward pass through the network with the given input.
+    pub(crate) fn forward(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<f64> {
+        self.forward_with_dropout(input, false)
+    }
+
+    /// Performs a forward pass through the network with the given input, dropping hidden
+    /// activations with the dropout of the network if `training` is set.
+    fn forward_with_dropout(
+        &mut self,
+        input: &[f64],
+        training: bool,
+    ) -> Vec<f64> {
+        let logits = self.forward_logits(input, training);
+        match self.activations.last_mut() {
+            Some(activation) => activation.forward(&logits),
+            None => logits,
+        }
+    }
+
+    /// Performs a forward pass through the network with the given input,
+    /// leaving out the activation of the last layer.
+    ///
+    /// If `training` is set, the dropout of the network is applied to the hidden activations
+    /// and its masks are kept for the backward pass.
+    fn forward_logits(
+        &mut self,
+        input: &[f64],
+        training: bool,
+    ) -> Vec<f64> {
+        check_input_size(&self.shape, input);
+        self.dropout_masks.clear();
+        let num_layers = self.layers.len();
         let mut output = input.to_vec();
-        for (layer, activation) in self.layers.iter_mut().zip(&mut self.activations) {
+        for (i, (layer, activation)) in
+            self.layers.iter_mut().zip(&mut self.activations).enumerate()
+        {
             layer.mark_for_use();
             self.utils.allocate_trainable(layer);
             output = layer.forward(&output, self.utils.clone());
             layer.free_from_use();
-            // this operation should not change the dimension of output
-            output = activation.forward(&output);
+            if i + 1 < num_layers {
+                // this operation should not change the dimension of output
+                output = activation.forward(&output);
+                if let (true, Some(dropout), Some(rng)) =
+                    (training, &self.dropout, &mut self.dropout_rng)
+                {
+                    let mask = dropout.mask(output.len(), rng);
+                    output = output.iter().zip(&mask).map(|(x, factor)| x * factor).collect();
+                    self.dropout_masks.push(mask);
+                }
+            }
         }
         output
     }
 
-    /// Performs a forward pass through the network with the given input doing batch caching.
-    fn forward_batch(
+    /// Performs a forward pass for training and returns the outputs together with the loss
+    /// of the sample and its gradient. If the loss is computed on the logits, the gradient is
+    /// taken with respect to the logits instead of the outputs, see `backward_loss`.
+    /// The dropout is only applied if `training` is set, i.e. not for validation samples.
+    fn forward_loss(
         &mut self,
         input: &[f64],
-    ) -> Vec<f64> {
+        target: &[f64],
+        loss_function: &LossFunction,
+        training: bool,
+    ) -> (Vec<f64>, f64, Vec<f64>) {
+        if !self.logits_loss {
+            let output = self.forward_with_dropout(input, training);
+            let loss = loss_function.value(&output, target);
+            let gradient = loss_function.gradient(&output, target);
+            return (output, loss, gradient);
+        }
+        let logits = self.forward_logits(input, training);
+        let output = self
+            .activations
+            .last_mut()
+            .map_or_else(|| logits.clone(), |activation| activation.forward(&logits));
+        // the softmax divides the logits by its temperature
+        let temperature = self.shape.layers.last().and_then(|layer| layer.activation.temperature());
+        let temperature = Softmax::with_optional_temperature(temperature).temperature;
+        let scaled_logits = logits.iter().map(|z| z / temperature).collect::<Vec<_>>();
+        let loss = loss_function.value_from_logits(&scaled_logits, target);
+        let gradient = loss_function
+            .gradient_from_logits(&scaled_logits, target)
+            .into_iter()
+            .map(|g| g / temperature)
+            .collect();
+        (output, loss, gradient)
+    }
+
+    /// Performs a forward pass through the network with the given input.
+    /// Returns the activated output of every layer.
+    pub(crate) fn forward_with_activations(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<Vec<f64>> {
+        check_input_size(&self.shape, input);
+        let mut activations = Vec::with_capacity(self.layers.len());
         let mut output = input.to_vec();
         for (layer, activation) in self.layers.iter_mut().zip(&mut self.activations) {
-            output = layer.forward_batch(&output);
+            layer.mark_for_use();
+            self.utils.allocate_trainable(layer);
+            output = layer.forward(&output, self.utils.clone());
+            layer.free_from_use();
             output = activation.forward(&output);
+            activations.push(output.clone());
         }
-        output
+        activations
     }
 
     /// Performs a backward pass through the network with the given output gradient.
-    fn backward(
+    /// Returns the gradient with respect to the input of the network.
+    pub(crate) fn backward(
         &mut self,
         grad_output: Vec<f64>,
-    ) {
-        let mut grad = grad_output;
-        for (layer, activation) in
-            self.layers.iter_mut().rev().zip(self.activations.iter_mut().rev())
+    ) -> Vec<f64> {
+        let grad = match self.activations.last_mut() {
+            Some(activation) => activation.backward(&grad_output),
+            None => grad_output,
+        };
+        self.backward_logits(grad)
+    }
+
+    /// Performs a backward pass through the network with the gradient with respect to the
+    /// logits of the last layer, leaving out the backward pass of its activation.
+    fn backward_logits(
+        &mut self,
+        grad_logits: Vec<f64>,
+    ) -> Vec<f64> {
+        let num_layers = self.layers.len();
+        let mut grad = grad_logits;
+        for (i, (layer, activation)) in
+            self.layers.iter_mut().zip(self.activations.iter_mut()).enumerate().rev()
         {
-            grad = activation.backward(&grad);
+            if i + 1 < num_layers {
+                // the dropout masks are only kept by a training forward pass
+                if let Some(mask) = self.dropout_masks.get(i) {
+                    grad = grad.iter().zip(mask).map(|(g, factor)| g * factor).collect();
+                }
+                grad = activation.backward(&grad);
+            }
             layer.mark_for_use();
             self.utils.allocate_trainable(layer);
             grad = layer.backward(&grad, self.utils.clone());
             layer.free_from_use();
         }
+        grad
     }
 
-    /// Performs a backward pass through the network with the given output gradient doing batch caching.
-    fn backward_batch(
+    /// Performs the backward pass for the loss gradient returned by `forward_loss`.
+    fn backward_loss(
         &mut self,
-        grad_output: Vec<f64>,
+        gradient: Vec<f64>,
+    ) -> Vec<f64> {
+        if self.logits_loss {
+            self.backward_logits(gradient)
+        } else {
+            self.backward(gradient)
+        }
+    }
+
+    /// Updates the weights of all layers with plain gradient descent.
+    pub(crate) fn update_weights(
+        &mut self,
+        learning_rate: f64,
     ) {
-        let mut grad = grad_output;
-        for (layer, activation) in
-            self.layers.iter_mut().rev().zip(self.activations.iter_mut().rev())
-        {
-            grad = activation.backward(&grad);
-            grad = layer.backward_batch(&grad);
+        for i in 0..self.layers.len() {
+            let layer_learning_rate = learning_rate * self.lr_multiplier(i);
+            self.layers[i].update_weights(layer_learning_rate, self.utils.clone());
+        }
+    }
+
+    /// Updates the weights of every layer with the given optimizer.
+    fn optimize(
+        &mut self,
+        optimizer: &mut dyn Optimizer,
+        learning_rate: f64,
+    ) {
+        optimizer.begin_step();
+        for i in 0..self.layers.len() {
+            let layer_learning_rate = learning_rate * self.lr_multiplier(i);
+            self.layers[i].optimize(optimizer, layer_learning_rate, self.utils.clone());
+        }
+    }
+
+    /// Runs the forward and backward pass for every sample of the batch and leaves the
+    /// averaged gradients in the layers. Returns the outputs and the losses of the samples
+    /// and the averaged gradients of every layer.
+    fn backward_averaged(
+        &mut self,
+        inputs: &[Vec<f64>],
+        targets: &[Vec<f64>],
+        loss_function: &LossFunction,
+    ) -> (Vec<Vec<f64>>, Vec<f64>, Vec<LayerGradients>) {
+        let mut outputs = Vec::with_capacity(inputs.len());
+        let mut losses = Vec::with_capacity(inputs.len());
+        let mut sums: Vec<Option<LayerGradients>> = vec![None; self.layers.len()];
+        for (input, target) in inputs.iter().zip(targets) {
+            let (output, loss, gradient) = self.forward_loss(input, target, loss_function, true);
+            self.backward_loss(gradient);
+            for (i, sum) in sums.iter_mut().enumerate() {
+                let mut layer = self.allocated_layer(i);
+                let gradients = layer.gradients();
+                // embedding layers accumulate their gradients, so every sample starts from zero
+                layer.set_gradients(&LayerGradients::zeros(
+                    gradients.weights.rows(),
+                    gradients.weights.cols(),
+                    gradients.biases.len(),
+                ));
+                layer.free_from_use();
+                match sum {
+                    Some(sum) => sum.accumulate(&gradients),
+                    None => *sum = Some(gradients),
+                }
+            }
+            outputs.push(output);
+            losses.push(loss);
+        }
+        let batch_len: f64 =
+            NumCast::from(inputs.len()).expect("Failed to convert inputs.len() to f64");
+        let gradients: Vec<LayerGradients> = sums
+            .into_iter()
+            .flatten()
+            .map(|mut gradients| {
+                gradients.scale(1.0 / batch_len);
+                gradients
+            })
+            .collect();
+        for (i, layer_gradients) in gradients.iter().enumerate() {
+            let mut layer = self.allocated_layer(i);
+            layer.set_gradients(layer_gradients);
+            layer.free_from_use();
+        }
+        // the backward pass of the batch is done, so no layer needs its cached batch inputs
+        for layer in &mut self.layers {
+            layer.clear_batch_cache();
         }
+        (outputs, losses, gradients)
     }
 
     /// Creates a new `NeuralNetwork` from the given model directory.
-    ///
-    /// # Panics
-    ///
-    /// This function will panic if the model directory is invalid or if the YAML
-    /// deserialization fails.
-    #[allow(clippy::question_mark)]
+    /// Returns `None` if the model cannot be loaded, `try_from_disk` reports the reason.
     #[must_use]
     pub fn from_disk(
         model_directory: String,
         utils: WrappedUtils,
     ) -> Option<Self> {
-        let shape = NeuralNetworkShape::from_disk(&model_directory);
-        if shape.is_none() {
-            return None;
-        }
-        let sh = shape.unwrap();
+        Self::try_from_disk(model_directory, utils).ok()
+    }
+
+    /// Creates a new `NeuralNetwork` from the given model directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the offending file if the shape is missing or malformed
+    /// or if a saved layer file cannot be parsed.
+    pub fn try_from_disk(
+        model_directory: String,
+        utils: WrappedUtils,
+    ) -> Result<Self, ModelLoadError> {
+        let sh = read_saved_shape(&model_directory)?;
         let mut network = Self {
             layers: Vec::new(),
             activations: Vec::new(),
             shape: sh.clone(),
             model_directory: Directory::User(model_directory),
             past_internal_model_directory: Vec::new(),
+            persist_on_drop: false,
             utils,
+            prediction_buffer: Vec::new(),
+            noise_augmenter: None,
+            dropout: None,
+            dropout_rng: None,
+            dropout_masks: Vec::new(),
+            record_grad_norms: false,
+            history: TrainingHistory::new(),
+            diagnose: false,
+            diagnostics: None,
+            warnings: Vec::new(),
+            options: TrainingOptions::default(),
+            swa_average: WeightAverage::new(),
+            loss_function: LossFunction::MeanSquaredError,
+            logits_loss: false,
+            partial_fit_steps: 0,
         };
 
         for i in 0..sh.layers.len() {
@@ -582,203 +1356,156 @@ impl TrainableClassicNeuralNetwork {
                     );
                     WrappedTrainableLayer::new(Box::new(layer))
                 },
-            };
-            let activation = match sh.layers[i].activation.activation_type() {
-                ActivationType::ReLU => Box::new(ReLU::new()) as Box<dyn ActivationTrait + Send>,
-                ActivationType::Sigmoid => Box::new(Sigmoid) as Box<dyn ActivationTrait + Send>,
-                ActivationType::Tanh => Box::new(Tanh) as Box<dyn ActivationTrait + Send>,
-                ActivationType::Softmax => {
-                    Box::new(Softmax::new(sh.layers[i].activation.temperature().unwrap()))
-                        as Box<dyn ActivationTrait + Send>
+                LayerType::Embedding { num_embeddings, dim } => {
+                    let layer = EmbeddingLayer::new(
+                        *num_embeddings,
+                        *dim,
+                        network.model_directory.clone(),
+                        i,
+                    );
+                    WrappedTrainableLayer::new(Box::new(layer))
+                },
+                LayerType::PReLU { size } => {
+                    let layer = PReLULayer::new(*size, network.model_directory.clone(), i);
+                    WrappedTrainableLayer::new(Box::new(layer))
                 },
             };
+            let activation = create_activation(&sh.layers[i].activation);
 
             network.add_activation_and_trainable_layer(activation, layer);
         }
 
-        Some(network)
+        Ok(network)
     }
 
     /// Retrieves the first free model directory.
     fn get_first_free_model_directory(&self) -> String {
-        get_first_free_model_directory(&self.model_directory)
+        self.utils.first_free_model_directory(&self.model_directory)
     }
 
-    fn transform(
+    /// Splits the samples into training inputs and targets followed by validation inputs and
+    /// targets according to the split mode. Only the training subset of a positional or
+    /// stratified split is repeated and shuffled by `transform`.
+    #[allow(clippy::type_complexity)]
+    fn split_samples(
+        &self,
         inputs: &[Vec<f64>],
         targets: &[Vec<f64>],
-    ) -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
-        let repeat_n_times = 1000 / inputs.len();
-        // if factor is greater than 1, then repeat the inputs and targets
-        let (transformed_inputs, transformed_targets) = if repeat_n_times > 1 {
-            let mut transformed_inputs = Vec::new();
-            let mut transformed_targets = Vec::new();
-            for (i, input) in inputs.iter().cycle().enumerate() {
-                if i >= repeat_n_times * inputs.len() {
-                    break;
-                }
-                // repeat the input n times
-                transformed_inputs.push(input.clone());
-            }
-            for (i, target) in targets.iter().cycle().enumerate() {
-                if i >= repeat_n_times * targets.len() {
-                    break;
-                }
-                // repeat the target n times
-                transformed_targets.push(target.clone());
-            }
-            (transformed_inputs, transformed_targets)
+        validation_split: f64,
+    ) -> (Vec<Vec<f64>>, Vec<Vec<f64>>, Vec<Vec<f64>>, Vec<Vec<f64>>) {
+        if self.options.split_mode == SplitMode::Random {
+            // all samples are repeated and shuffled, then the first `validation_split` share of the
+            // original sample count is trained on and the rest is held out
+            let (mut train_inputs, mut train_targets) = Self::transform(inputs, targets);
+            let inputs_len: f64 =
+                NumCast::from(inputs.len()).expect("Failed to convert inputs.len() to f64");
+            let split_index: usize = NumCast::from((inputs_len * validation_split).round())
+                .expect("Failed to convert split index to usize");
+            let validation_inputs = train_inputs.split_off(split_index);
+            let validation_targets = train_targets.split_off(split_index);
+            return (train_inputs, train_targets, validation_inputs, validation_targets);
+        }
+        let (train_indices, validation_indices) = self.options.split_mode.split_indices(
+            targets,
+            validation_split,
+            &mut rand::thread_rng(),
+        );
+        let gather = |samples: &[Vec<f64>], indices: &[usize]| {
+            indices.iter().map(|i| samples[*i].clone()).collect::<Vec<_>>()
+        };
+        let (train_inputs, train_targets) = if train_indices.is_empty() {
+            (Vec::new(), Vec::new())
         } else {
-            // if factor is 1, then just return the inputs and targets
-            let transformed_inputs = inputs.to_vec();
-            let transformed_targets = targets.to_vec();
-            (transformed_inputs, transformed_targets)
+            Self::transform(&gather(inputs, &train_indices), &gather(targets, &train_indices))
         };
-
-        // zip the transformed inputs and targets
-        let mut zipped = transformed_inputs
-            .iter()
-            .zip(transformed_targets.iter())
-            .map(|(input, target)| {
-                let new_input = input.clone();
-                let new_target = target.clone();
-                (new_input, new_target)
-            })
-            .collect::<Vec<_>>();
-
-        // shuffle the zipped inputs and targets
-        let mut thread_rng = rand::thread_rng();
-        zipped.shuffle(&mut thread_rng);
-
-        // unzip the zipped inputs and targets
-        let (transformed_inputs, transformed_targets): (Vec<_>, Vec<_>) =
-            zipped.into_iter().unzip();
-        (transformed_inputs, transformed_targets)
+        (
+            train_inputs,
+            train_targets,
+            gather(inputs, &validation_indices),
+            gather(targets, &validation_indices),
+        )
     }
-}
 
-impl NeuralNetwork for TrainableClassicNeuralNetwork {
-    /// Makes a prediction based on a single input by performing a forward pass.
-    fn predict(
+    /// Trains the neural network on the training samples, monitoring the loss on the
+    /// validation samples.
+    #[allow(clippy::too_many_arguments)]
+    #[allow(clippy::too_many_lines)]
+    fn train_on(
         &mut self,
-        input: Vec<f64>,
-    ) -> Vec<f64> {
-        self.forward(input.as_slice())
-    }
+        train_inputs: &[Vec<f64>],
+        train_targets: &[Vec<f64>],
+        validation_inputs: &[Vec<f64>],
+        validation_targets: &[Vec<f64>],
+        learning_rate: f64,
+        epochs: usize,
+        tolerance: f64,
+        use_adam: bool,
+        mut optimizer: Option<Box<dyn Optimizer>>,
+        sample_match_percentage: f64,
+        mut on_epoch_end: Option<&mut dyn FnMut(EpochMetrics)>,
+    ) -> f64 {
+        let mut accuracy = 0.0;
+        self.history = TrainingHistory::new();
+        self.diagnostics = None;
+        self.warnings.clear();
+        if let Some(last_layer) = self.shape.layers.last() {
+            let activation = last_layer.activation.activation_type();
+            let targets =
+                train_targets.iter().chain(validation_targets).cloned().collect::<Vec<_>>();
+            if let Some(warning) = TrainingWarning::check_targets(activation, &targets) {
+                self.warnings.push(warning);
+            }
+        }
+        let mut best_validation_loss = f64::INFINITY;
+        let mut epochs_without_improvement = 0;
+        // a checkpoint which failed to save once is not kept up to date anymore
+        let mut checkpoint_directory = self.options.checkpoint_best.clone();
+        self.swa_average = WeightAverage::new();
+        let swa_first_epoch = self.options.swa.map(|swa| swa.first_epoch(epochs));
 
-    fn shape(&self) -> NeuralNetworkShape {
-        self.shape.clone()
-    }
+        // in silent mode the shared progress bars are not touched at all
+        let multi_progress = (self.options.verbosity == Verbosity::ProgressBar)
+            .then(|| self.utils.get_multi_progress());
 
-    fn save(
-        &mut self,
-        user_model_directory: String,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        // Check if we're in test mode - if so, force save to Internal directory
-        if self.utils.is_test_mode() {
-            let workspace = self.utils.get_workspace();
-            let internal_path = if workspace.is_empty() {
-                user_model_directory
-            } else {
-                format!("{workspace}/{user_model_directory}")
-            };
-            // Don't add current directory to past_internal when in test mode to avoid cleanup
-            // since we want to keep the saved model for testing
-            self.model_directory = Directory::Internal(internal_path);
-            let model_directory = self.model_directory.path();
-            return self.save_internal(&model_directory);
-        }
-
-        if self.model_directory.path() != user_model_directory {
-            self.past_internal_model_directory.push(self.model_directory.path());
-        }
-        self.model_directory = Directory::User(user_model_directory);
-        let model_directory = self.model_directory.path();
-        self.save_internal(&model_directory)
-    }
-
-    fn get_model_directory(&self) -> Directory {
-        self.model_directory.clone()
-    }
-
-    /// Allocates the layers of the neural network.
-    fn allocate(&mut self) {
-        for layer in &self.layers {
-            self.utils.allocate_trainable(layer);
-        }
-    }
-
-    /// Deallocates the layers of the neural network.
-    fn deallocate(&mut self) {
-        for layer in &self.layers {
-            self.utils.deallocate_trainable(layer);
-        }
-    }
-
-    fn set_internal(&mut self) {
-        // set the model directory to internal
-        self.model_directory = Directory::Internal(self.model_directory.path());
-    }
-
-    fn duplicate(&self) -> WrappedNeuralNetwork {
-        unimplemented!()
-    }
-
-    fn get_utils(&self) -> WrappedUtils {
-        self.utils.clone()
-    }
-}
-
-impl TrainableNeuralNetwork for TrainableClassicNeuralNetwork {
-    /// Trains the neural network using the given inputs, targets, learning rate, and number of epochs.
-    /// Includes validation using a split of the data.
-    #[allow(clippy::too_many_arguments)]
-    #[allow(clippy::too_many_lines)]
-    fn train(
-        &mut self,
-        inputs: &[Vec<f64>],
-        targets: &[Vec<f64>],
-        learning_rate: f64,
-        epochs: usize,
-        tolerance: f64,
-        use_adam: bool,
-        validation_split: f64,
-        sample_match_percentage: f64,
-    ) -> f64 {
-        // in case one does not have enough samples, don't train and return zero accuracy
-        let (transformed_inputs, transformed_targets) = Self::transform(inputs, targets);
-        assert!(
-            (0.0..=1.0).contains(&validation_split),
-            "validation_split must be between 0 and 1"
-        );
-
-        let inputs_len: f64 =
-            NumCast::from(inputs.len()).expect("Failed to convert inputs.len() to f64");
-        let split_index: usize = NumCast::from((inputs_len * validation_split).round())
-            .expect("Failed to convert split index to usize");
-        let (train_inputs, validation_inputs) = transformed_inputs.split_at(split_index);
-        let (train_targets, validation_targets) = transformed_targets.split_at(split_index);
-
-        let mut accuracy = 0.0;
-
-        let multi_progress = self.utils.get_multi_progress();
+        let noise_augmenter = self.noise_augmenter;
+        let mut noise_rng = noise_augmenter.map(|augmenter| augmenter.rng());
+        let grad_noise = self.options.grad_noise;
+        let mut grad_noise_rng = grad_noise.map(|grad_noise| grad_noise.rng());
+        let loss_function = self.loss_function.clone();
+        let training_start = Instant::now();
 
         for epoch in 0..epochs {
+            let epoch_learning_rate = self.options.lr_schedule.learning_rate(learning_rate, epoch);
+            let augmented_inputs;
+            let train_inputs = match (&noise_augmenter, &mut noise_rng) {
+                (Some(augmenter), Some(rng)) => {
+                    augmented_inputs = augmenter.augment(train_inputs, rng);
+                    augmented_inputs.as_slice()
+                },
+                _ => train_inputs,
+            };
             // Initialize progress bar
-            let pb = multi_progress.add(ProgressBar::new(train_inputs.len() as u64));
-            pb.set_draw_target(ProgressDrawTarget::stdout());
-            pb.set_style(
-            ProgressStyle::default_bar()
-                .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} | {msg}")
-                .expect("Invalid template")
-                .progress_chars("#>-"),);
+            let pb = multi_progress.as_ref().map(|multi_progress| {
+                let pb = multi_progress.add(ProgressBar::new(train_inputs.len() as u64));
+                pb.set_draw_target(self.options.progress_target.draw_target());
+                pb.set_style(
+                ProgressStyle::default_bar()
+                    .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} | {msg}")
+                    .expect("Invalid template")
+                    .progress_chars("#>-"),);
+                pb
+            });
 
             let mut loss = 0.0;
             let mut success_count = 0.0;
+            let mut grad_norm_sums = self.record_grad_norms.then(|| vec![0.0; self.layers.len()]);
+            // (sum of the gradient norms, loss of the first half, loss of the second half)
+            let mut diagnosis = (self.diagnose && epoch == 0).then_some((0.0, 0.0, 0.0));
 
             train_inputs.iter().zip(train_targets).enumerate().for_each(|(j, (input, target))| {
                 // Forward pass
-                let output = self.forward(input.as_slice());
+                let (output, sample_loss, grad_output) =
+                    self.forward_loss(input, target, &loss_function, true);
 
                 // Calculate accuracy
                 let correct_outputs = output
@@ -795,36 +1522,46 @@ impl TrainableNeuralNetwork for TrainableClassicNeuralNetwork {
                 if match_percentage >= sample_match_percentage {
                     success_count += 1.0;
                 }
-                // Calculate loss gradient
-                let grad_output: Vec<f64> = output
-                    .iter()
-                    .zip(target)
-                    .map(|(o, t)| {
-                        let error = o - t;
-                        loss += error * error;
-                        2.0 * error
-                    })
-                    .collect();
+                loss += sample_loss;
 
                 // Backward pass
-                self.backward(grad_output);
+                self.backward_loss(grad_output);
+                if let Some((grad_norm_sum, first_half_loss, second_half_loss)) = diagnosis.as_mut()
+                {
+                    *grad_norm_sum += self.grad_norm();
+                    if 2 * j < train_inputs.len() {
+                        *first_half_loss += sample_loss;
+                    } else {
+                        *second_half_loss += sample_loss;
+                    }
+                }
+                if let Some(grad_norm_sums) = grad_norm_sums.as_mut() {
+                    self.accumulate_grad_norms(grad_norm_sums);
+                }
+                if let (Some(grad_noise), Some(rng)) = (&grad_noise, &mut grad_noise_rng) {
+                    self.add_grad_noise(grad_noise, epoch, rng);
+                }
 
                 // Update weights
-                if use_adam {
-                    self.adjust_adam(j + 1, learning_rate, 0.9, 0.999, 1e-8);
+                if let Some(optimizer) = optimizer.as_mut() {
+                    self.optimize(optimizer.as_mut(), epoch_learning_rate);
+                } else if use_adam {
+                    let AdamParams { beta1, beta2, epsilon } = self.options.adam_params;
+                    self.adjust_adam(j + 1, epoch_learning_rate, beta1, beta2, epsilon);
                 } else {
-                    self.layers
-                        .iter_mut()
-                        .for_each(|layer| layer.update_weights(learning_rate, self.utils.clone()));
+                    self.update_weights(epoch_learning_rate);
                 }
+                self.apply_max_weight_norm();
 
                 // Update the progress bar
-                let train_inputs_len: f64 = NumCast::from(train_inputs.len())
-                    .expect("Failed to convert train_inputs.len() to f64");
-                let accuracy = success_count / train_inputs_len * 100.0;
-                let loss_display = loss / train_inputs_len;
-                pb.set_position((j + 1) as u64);
-                pb.set_message(format!("Accuracy: {accuracy:.2} %, Loss: {loss_display:.4}"));
+                if let Some(pb) = &pb {
+                    let train_inputs_len: f64 = NumCast::from(train_inputs.len())
+                        .expect("Failed to convert train_inputs.len() to f64");
+                    let accuracy = success_count / train_inputs_len * 100.0;
+                    let loss_display = loss / train_inputs_len;
+                    pb.set_position((j + 1) as u64);
+                    pb.set_message(format!("Accuracy: {accuracy:.2} %, Loss: {loss_display:.4}"));
+                }
             });
 
             // Validation phase
@@ -832,7 +1569,8 @@ impl TrainableNeuralNetwork for TrainableClassicNeuralNetwork {
             let mut validation_success_count = 0.0;
 
             validation_inputs.iter().zip(validation_targets).for_each(|(input, target)| {
-                let output = self.forward(input.as_slice());
+                let (output, sample_loss, _) =
+                    self.forward_loss(input, target, &loss_function, false);
                 let correct_outputs = output
                     .iter()
                     .zip(target.iter())
@@ -847,14 +1585,7 @@ impl TrainableNeuralNetwork for TrainableClassicNeuralNetwork {
                     validation_success_count += 1.0;
                 }
 
-                validation_loss += output
-                    .iter()
-                    .zip(target)
-                    .map(|(o, t)| {
-                        let error = o - t;
-                        error * error
-                    })
-                    .sum::<f64>();
+                validation_loss += sample_loss;
             });
 
             let validation_inputs_len: f64 = NumCast::from(validation_inputs.len())
@@ -869,185 +1600,2988 @@ impl TrainableNeuralNetwork for TrainableClassicNeuralNetwork {
             let accuracy = success_count / train_inputs_len * 100.0;
             let message = format!(
             "Epoch {epoch} finished | Train Acc: {accuracy:.2} %, Train Loss: {loss:.4} | Val Acc: {validation_accuracy:.2} %, Val Loss: {validation_loss:.4}");
-            pb.finish_with_message(message);
-            multi_progress.remove(&pb);
-        }
-        accuracy
-    }
-
-    /// Trains the neural network doing batch back propagation.
-    fn train_batch(
-        &mut self,
-        inputs: &[Vec<f64>],
-        targets: &[Vec<f64>],
-        learning_rate: f64,
-        epochs: usize,
-        tolerance: f64,
-        batch_size: usize,
-    ) {
-        for i in 0..epochs {
-            println!("Epoch: {i}\r");
-            let mut loss = 0.0;
-            let input_chunks = inputs.chunks(batch_size);
-            let target_chunks = targets.chunks(batch_size);
-            let mut success_count = 0.0;
-            for batch in input_chunks.zip(target_chunks) {
-                let input_chunk_batch = batch.0;
-                let target_chunk_batch = batch.1;
-                for (input, target) in input_chunk_batch.iter().zip(target_chunk_batch) {
-                    let output = self.forward_batch(input.as_slice());
+            match (&multi_progress, pb) {
+                (Some(multi_progress), Some(pb)) => {
+                    pb.finish_with_message(message);
+                    multi_progress.remove(&pb);
+                },
+                _ if self.options.verbosity == Verbosity::PerEpoch => println!("{message}"),
+                _ => {},
+            }
+            if let Some(grad_norm_sums) = grad_norm_sums {
+                self.history
+                    .grad_norms
+                    .push(grad_norm_sums.iter().map(|sum| sum / train_inputs_len).collect());
+            }
+            let metrics = EpochMetrics {
+                epoch,
+                train_loss: loss,
+                train_accuracy: accuracy,
+                validation_loss,
+                validation_accuracy,
+            };
+            self.history.epochs.push(metrics);
+            if let Some((grad_norm_sum, first_half_loss, second_half_loss)) = diagnosis {
+                let first_half_len: f64 = NumCast::from((train_inputs.len() + 1) / 2)
+                    .expect("Failed to convert first half length to f64");
+                let second_half_len: f64 = NumCast::from((train_inputs.len() / 2).max(1))
+                    .expect("Failed to convert second half length to f64");
+                self.diagnostics = Some(TrainingDiagnostics::from_first_epoch(
+                    grad_norm_sum / train_inputs_len,
+                    first_half_loss / first_half_len,
+                    second_half_loss / second_half_len,
+                ));
+            }
+            if let Some(callback) = on_epoch_end.as_mut() {
+                callback(metrics);
+            }
+            if swa_first_epoch.is_some_and(|first_epoch| epoch >= first_epoch) {
+                let weights = self.weights_flat();
+                self.swa_average.add(&weights);
+            }
 
-                    // Check if the output matches the target
-                    let mut nb_correct_outputs = 0;
-                    for (o, t) in output.iter().zip(target.iter()) {
-                        if (o - t).abs() < tolerance {
-                            nb_correct_outputs += 1;
+            // without validation samples there is no loss to monitor
+            if !validation_inputs.is_empty() {
+                if validation_loss < best_validation_loss {
+                    best_validation_loss = validation_loss;
+                    epochs_without_improvement = 0;
+                    if let Some(directory) = checkpoint_directory.take() {
+                        match self.save_internal(&directory, true) {
+                            Ok(()) => checkpoint_directory = Some(directory),
+                            Err(error) => {
+                                self.warnings.push(TrainingWarning::CheckpointNotSaved {
+                                    directory,
+                                    error: error.to_string(),
+                                });
+                            },
                         }
                     }
-                    let nb_correct_outputs_f64: f64 = NumCast::from(nb_correct_outputs)
-                        .expect("Failed to convert nb_correct_outputs to f64");
-                    let target_len_f64: f64 =
-                        NumCast::from(target.len()).expect("Failed to convert target.len() to f64");
-                    success_count += nb_correct_outputs_f64 / target_len_f64;
-                    let mut grad_output = Vec::new();
-                    for j in 0..output.len() {
-                        let error = output[j] - target[j];
-                        grad_output.push(2.0 * error);
-                        loss += error * error;
+                } else {
+                    epochs_without_improvement += 1;
+                    if self
+                        .options
+                        .early_stopping_patience
+                        .is_some_and(|patience| epochs_without_improvement >= patience)
+                    {
+                        break;
                     }
-                    self.backward_batch(grad_output);
-                }
-                for layer in &mut self.layers {
-                    layer.update_weights(learning_rate, self.utils.clone());
                 }
             }
-            let inputs_len: f64 =
-                NumCast::from(inputs.len()).expect("Failed to convert inputs.len() to f64");
-            let accuracy = success_count / inputs_len * 100.0;
-            println!("Epoch {}: Loss {}, Accuracy {}%\r", i, loss / inputs_len, accuracy);
-            if accuracy < 0.01 && i > 10 {
+            if self
+                .options
+                .time_budget
+                .is_some_and(|time_budget| training_start.elapsed() >= time_budget)
+            {
                 break;
             }
         }
+        if self.options.restore_best_checkpoint && best_validation_loss.is_finite() {
+            if let Some(directory) = checkpoint_directory {
+                if let Err(error) = self.load_checkpoint(directory.clone()) {
+                    self.warnings.push(TrainingWarning::CheckpointNotRestored {
+                        directory,
+                        error: error.to_string(),
+                    });
+                }
+            }
+        }
+        if self.options.swa.is_some_and(|swa| swa.replace_final()) {
+            if let Some(weights) = self.swa_weights() {
+                self.load_weights_flat(&weights)
+                    .expect("Failed to replace the weights with their running average");
+            }
+        }
+        accuracy
     }
 
-    /// Returns the input size of the first layer in the network.
-    fn input_size(&self) -> usize {
-        self.shape.layers.first().map_or(0, super::shape::LayerShape::input_size)
+    fn transform(
+        inputs: &[Vec<f64>],
+        targets: &[Vec<f64>],
+    ) -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+        let repeat_n_times = 1000 / inputs.len();
+        // if factor is greater than 1, then repeat the inputs and targets
+        let (transformed_inputs, transformed_targets) = if repeat_n_times > 1 {
+            let mut transformed_inputs = Vec::new();
+            let mut transformed_targets = Vec::new();
+            for (i, input) in inputs.iter().cycle().enumerate() {
+                if i >= repeat_n_times * inputs.len() {
+                    break;
+                }
+                // repeat the input n times
+                transformed_inputs.push(input.clone());
+            }
+            for (i, target) in targets.iter().cycle().enumerate() {
+                if i >= repeat_n_times * targets.len() {
+                    break;
+                }
+                // repeat the target n times
+                transformed_targets.push(target.clone());
+            }
+            (transformed_inputs, transformed_targets)
+        } else {
+            // if factor is 1, then just return the inputs and targets
+            let transformed_inputs = inputs.to_vec();
+            let transformed_targets = targets.to_vec();
+            (transformed_inputs, transformed_targets)
+        };
+
+        // zip the transformed inputs and targets
+        let mut zipped = transformed_inputs
+            .iter()
+            .zip(transformed_targets.iter())
+            .map(|(input, target)| {
+                let new_input = input.clone();
+                let new_target = target.clone();
+                (new_input, new_target)
+            })
+            .collect::<Vec<_>>();
+
+        // shuffle the zipped inputs and targets
+        let mut thread_rng = rand::thread_rng();
+        zipped.shuffle(&mut thread_rng);
+
+        // unzip the zipped inputs and targets
+        let (transformed_inputs, transformed_targets): (Vec<_>, Vec<_>) =
+            zipped.into_iter().unzip();
+        (transformed_inputs, transformed_targets)
     }
+}
 
-    /// Returns the output size of the last layer in the network.
-    fn output_size(&self) -> usize {
-        self.shape.layers.last().map_or(0, super::shape::LayerShape::output_size)
+impl NeuralNetwork for TrainableClassicNeuralNetwork {
+    /// Makes a prediction based on a single input by performing a forward pass.
+    fn predict(
+        &mut self,
+        input: Vec<f64>,
+    ) -> Vec<f64> {
+        self.forward(input.as_slice())
     }
 
-    fn duplicate_trainable(&self) -> WrappedTrainableNeuralNetwork {
-        // create a sibling directory with the postfix _clone appendended to model_direcotory path
-        let model_directory = self.get_first_free_model_directory();
-        // Save the model to the new directory
-        self.save_internal(&model_directory).unwrap();
-        // Clone the neural network by cloning its layers and activations
-        let mut new_layers = Vec::new();
-        for (i, layer) in self.layers.iter().enumerate() {
-            new_layers.push(layer.duplicate(model_directory.clone(), i));
-            layer.cleanup();
+    fn predict_checked(
+        &mut self,
+        input: Vec<f64>,
+    ) -> Result<Vec<f64>, InferenceError> {
+        check_input_size(&self.shape, &input);
+        let mut output = input;
+        for (i, (layer, activation)) in
+            self.layers.iter_mut().zip(&mut self.activations).enumerate()
+        {
+            layer.mark_for_use();
+            self.utils.allocate_trainable(layer);
+            output = layer.forward(&output, self.utils.clone());
+            layer.free_from_use();
+            check_finite(&output, i)?;
+            output = activation.forward(&output);
+            check_finite(&output, i)?;
         }
-        WrappedTrainableNeuralNetwork::new(Box::new(Self {
-            layers: new_layers,
-            activations: self.activations.clone(),
-            shape: self.shape.clone(),
-            model_directory: Directory::Internal(model_directory),
-            past_internal_model_directory: Vec::new(),
-            utils: self.utils.clone(),
-        }))
+        Ok(output)
     }
-}
 
-impl Drop for TrainableClassicNeuralNetwork {
-    fn drop(&mut self) {
-        // Save the model to ensure that everything is on disk if it is a user_model_directory
-        // ensure that the model_directory exists
-        if let Directory::User(_) = &self.model_directory {
-            if std::fs::metadata(self.model_directory.path()).is_err() {
-                std::fs::create_dir_all(self.model_directory.path()).unwrap();
-            }
-            self.save_layout();
-            self.deallocate();
-        }
-        // Interne Verzeichnisse immer entfernen, unabhängig vom Testmodus
-        if let Directory::Internal(dir) = &self.model_directory {
-            if std::fs::metadata(dir).is_ok() {
-                std::fs::remove_dir_all(dir).unwrap();
-            }
-        }
-        for dir in &self.past_internal_model_directory {
-            if dir != &self.model_directory.path() && std::fs::metadata(dir).is_ok() {
-                std::fs::remove_dir_all(dir).unwrap();
-            }
+    /// Runs the layers alternately between `out` and a buffer kept by the network,
+    /// so that no vector is allocated once both buffers have grown to the layer sizes.
+    fn predict_into(
+        &mut self,
+        input: &[f64],
+        out: &mut Vec<f64>,
+    ) {
+        check_input_size(&self.shape, input);
+        let mut layer_input = std::mem::take(&mut self.prediction_buffer);
+        layer_input.clear();
+        layer_input.extend_from_slice(input);
+        for (layer, activation) in self.layers.iter_mut().zip(&mut self.activations) {
+            layer.mark_for_use();
+            self.utils.allocate_trainable(layer);
+            layer.forward_into(&layer_input, out, self.utils.clone());
+            layer.free_from_use();
+            activation.forward_in_place(out);
+            std::mem::swap(&mut layer_input, out);
         }
+        // the output of the last layer is in layer_input
+        std::mem::swap(&mut layer_input, out);
+        self.prediction_buffer = layer_input;
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::{
+    fn shape(&self) -> NeuralNetworkShape {
+        self.shape.clone()
+    }
+
+    fn save(
+        &mut self,
+        user_model_directory: String,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.save_to(user_model_directory, true)
+    }
+
+    fn save_fast(
+        &mut self,
+        user_model_directory: String,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.save_to(user_model_directory, false)
+    }
+
+    fn get_model_directory(&self) -> Directory {
+        self.model_directory.clone()
+    }
+
+    /// Allocates the layers of the neural network.
+    fn allocate(&mut self) {
+        for layer in &self.layers {
+            self.utils.allocate_trainable(layer);
+        }
+    }
+
+    /// Deallocates the layers of the neural network.
+    fn deallocate(&mut self) {
+        for layer in &self.layers {
+            self.utils.deallocate_trainable(layer);
+        }
+    }
+
+    fn set_internal(&mut self) {
+        // set the model directory to internal
+        self.model_directory = Directory::Internal(self.model_directory.path());
+    }
+
+    fn persist_on_drop(&mut self) {
+        self.persist_on_drop = true;
+    }
+
+    fn duplicate(&self) -> WrappedNeuralNetwork {
+        unimplemented!()
+    }
+
+    fn get_utils(&self) -> WrappedUtils {
+        self.utils.clone()
+    }
+
+    fn to_quantized(&mut self) -> Result<QuantizedNeuralNetwork, Box<dyn std::error::Error>> {
+        check_quantizable(&self.shape)?;
+        let layers = (0..self.layers.len())
+            .map(|i| {
+                self.layer_parameters(i)
+                    .map(|(weights, biases)| QuantizedDenseLayer::new(&weights, &biases))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        QuantizedNeuralNetwork::new(self.shape.clone(), layers)
+    }
+}
+
+impl TrainableNeuralNetwork for TrainableClassicNeuralNetwork {
+    /// Trains the neural network using the given inputs, targets, learning rate, and number of epochs.
+    /// Includes validation using a split of the data.
+    #[allow(clippy::too_many_arguments)]
+    fn train(
+        &mut self,
+        inputs: &[Vec<f64>],
+        targets: &[Vec<f64>],
+        learning_rate: f64,
+        epochs: usize,
+        tolerance: f64,
+        use_adam: bool,
+        optimizer: Option<Box<dyn Optimizer>>,
+        validation_split: f64,
+        sample_match_percentage: f64,
+        on_epoch_end: Option<&mut dyn FnMut(EpochMetrics)>,
+    ) -> f64 {
+        assert!(
+            (0.0..=1.0).contains(&validation_split),
+            "validation_split must be between 0 and 1"
+        );
+        let (train_inputs, train_targets, validation_inputs, validation_targets) =
+            self.split_samples(inputs, targets, validation_split);
+        self.train_on(
+            &train_inputs,
+            &train_targets,
+            &validation_inputs,
+            &validation_targets,
+            learning_rate,
+            epochs,
+            tolerance,
+            use_adam,
+            optimizer,
+            sample_match_percentage,
+            on_epoch_end,
+        )
+    }
+
+    /// Trains the neural network on the given training samples and validates it on the given
+    /// validation samples, which are not taken from the training samples.
+    #[allow(clippy::too_many_arguments)]
+    fn train_with_validation(
+        &mut self,
+        train_inputs: &[Vec<f64>],
+        train_targets: &[Vec<f64>],
+        validation_inputs: &[Vec<f64>],
+        validation_targets: &[Vec<f64>],
+        learning_rate: f64,
+        epochs: usize,
+        tolerance: f64,
+        use_adam: bool,
+        optimizer: Option<Box<dyn Optimizer>>,
+        sample_match_percentage: f64,
+        on_epoch_end: Option<&mut dyn FnMut(EpochMetrics)>,
+    ) -> f64 {
+        assert_eq!(train_inputs.len(), train_targets.len(), "every training input needs a target");
+        assert_eq!(
+            validation_inputs.len(),
+            validation_targets.len(),
+            "every validation input needs a target"
+        );
+        let (train_inputs, train_targets) = if train_inputs.is_empty() {
+            (Vec::new(), Vec::new())
+        } else {
+            Self::transform(train_inputs, train_targets)
+        };
+        self.train_on(
+            &train_inputs,
+            &train_targets,
+            validation_inputs,
+            validation_targets,
+            learning_rate,
+            epochs,
+            tolerance,
+            use_adam,
+            optimizer,
+            sample_match_percentage,
+            on_epoch_end,
+        )
+    }
+
+    /// Trains the neural network doing batch back propagation.
+    fn train_batch(
+        &mut self,
+        inputs: &[Vec<f64>],
+        targets: &[Vec<f64>],
+        learning_rate: f64,
+        epochs: usize,
+        tolerance: f64,
+        batch_size: usize,
+        optimizer: Option<Box<dyn Optimizer>>,
+    ) {
+        let loader = DataLoader::new(inputs, targets, batch_size);
+        self.train_loader(&loader, learning_rate, epochs, tolerance, optimizer);
+    }
+
+    fn train_loader(
+        &mut self,
+        loader: &DataLoader,
+        learning_rate: f64,
+        epochs: usize,
+        tolerance: f64,
+        mut optimizer: Option<Box<dyn Optimizer>>,
+    ) {
+        for i in 0..epochs {
+            let mut loss = 0.0;
+            let mut success_count = 0.0;
+            let loss_function = self.loss_function.clone();
+            for batch in loader.batches(i) {
+                let input_chunk_batch = &batch.inputs;
+                let target_chunk_batch = &batch.targets;
+                let (outputs, losses, _) =
+                    self.backward_averaged(input_chunk_batch, target_chunk_batch, &loss_function);
+                loss += losses.iter().sum::<f64>();
+                for (output, target) in outputs.iter().zip(target_chunk_batch) {
+                    // Check if the output matches the target
+                    let mut nb_correct_outputs = 0;
+                    for (o, t) in output.iter().zip(target.iter()) {
+                        if (o - t).abs() < tolerance {
+                            nb_correct_outputs += 1;
+                        }
+                    }
+                    let nb_correct_outputs_f64: f64 = NumCast::from(nb_correct_outputs)
+                        .expect("Failed to convert nb_correct_outputs to f64");
+                    let target_len_f64: f64 =
+                        NumCast::from(target.len()).expect("Failed to convert target.len() to f64");
+                    success_count += nb_correct_outputs_f64 / target_len_f64;
+                }
+                match optimizer.as_mut() {
+                    Some(optimizer) => self.optimize(optimizer.as_mut(), learning_rate),
+                    None => self.update_weights(learning_rate),
+                }
+                self.apply_max_weight_norm();
+            }
+            let inputs_len: f64 = NumCast::from(loader.num_samples_per_epoch())
+                .expect("Failed to convert the number of samples to f64");
+            let accuracy = success_count / inputs_len * 100.0;
+            if self.options.verbosity == Verbosity::PerEpoch {
+                println!("Epoch {}: Loss {}, Accuracy {}%", i, loss / inputs_len, accuracy);
+            }
+            if accuracy < 0.01 && i > 10 {
+                break;
+            }
+        }
+    }
+
+    fn compute_batch_gradients(
+        &mut self,
+        inputs: &[Vec<f64>],
+        targets: &[Vec<f64>],
+        loss_function: &LossFunction,
+    ) -> Result<Vec<LayerGradients>, Box<dyn std::error::Error>> {
+        if inputs.is_empty() || inputs.len() != targets.len() {
+            return Err(format!(
+                "Expected a non empty batch with as many targets as inputs, got {} inputs and {} targets",
+                inputs.len(),
+                targets.len()
+            )
+            .into());
+        }
+        loss_function.validate(self.output_size())?;
+        Ok(self.backward_averaged(inputs, targets, loss_function).2)
+    }
+
+    fn partial_fit(
+        &mut self,
+        input: &[f64],
+        target: &[f64],
+        learning_rate: f64,
+        loss_function: &LossFunction,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        if input.len() != self.input_size() || target.len() != self.output_size() {
+            return Err(format!(
+                "Expected an input of size {} and a target of size {}, got {} and {}",
+                self.input_size(),
+                self.output_size(),
+                input.len(),
+                target.len()
+            )
+            .into());
+        }
+        loss_function.validate(self.output_size())?;
+        let (_, loss, gradient) = self.forward_loss(input, target, loss_function, true);
+        self.backward_loss(gradient);
+        self.partial_fit_steps += 1;
+        let AdamParams { beta1, beta2, epsilon } = self.options.adam_params;
+        self.adjust_adam(self.partial_fit_steps, learning_rate, beta1, beta2, epsilon);
+        self.apply_max_weight_norm();
+        Ok(loss)
+    }
+
+    fn saliency(
+        &mut self,
+        input: &[f64],
+        output_index: usize,
+    ) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        if input.len() != self.input_size() {
+            return Err(format!(
+                "Expected an input of size {}, got {}",
+                self.input_size(),
+                input.len()
+            )
+            .into());
+        }
+        check_parameter_index("Output", output_index, self.output_size())?;
+        self.forward(input);
+        let mut grad_output = vec![0.0; self.output_size()];
+        grad_output[output_index] = 1.0;
+        Ok(self.backward(grad_output).into_iter().map(f64::abs).collect())
+    }
+
+    /// Returns the input size of the first layer in the network.
+    fn input_size(&self) -> usize {
+        self.shape.layers.first().map_or(0, super::shape::LayerShape::input_size)
+    }
+
+    /// Returns the output size of the last layer in the network.
+    fn output_size(&self) -> usize {
+        self.shape.layers.last().map_or(0, super::shape::LayerShape::output_size)
+    }
+
+    fn profile_forward(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<Duration> {
+        check_input_size(&self.shape, input);
+        let mut output = input.to_vec();
+        let mut durations = Vec::with_capacity(self.layers.len());
+        for (layer, activation) in self.layers.iter_mut().zip(&mut self.activations) {
+            layer.mark_for_use();
+            self.utils.allocate_trainable(layer);
+            // loading the layer into memory is not part of its forward pass
+            let start = Instant::now();
+            output = layer.forward(&output, self.utils.clone());
+            output = activation.forward(&output);
+            durations.push(start.elapsed());
+            layer.free_from_use();
+        }
+        durations
+    }
+
+    fn dead_neuron_report(
+        &mut self,
+        inputs: &[Vec<f64>],
+    ) -> Vec<usize> {
+        let relu_layers = self
+            .shape
+            .layers
+            .iter()
+            .enumerate()
+            .filter(|(_, layer_shape)| {
+                layer_shape.activation.activation_type() == ActivationType::ReLU
+            })
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        let mut alive = relu_layers
+            .iter()
+            .map(|&i| vec![false; self.shape.layers[i].output_size()])
+            .collect::<Vec<_>>();
+        for input in inputs {
+            let activations = self.forward_with_activations(input);
+            for (alive_units, &i) in alive.iter_mut().zip(&relu_layers) {
+                for (unit_alive, value) in alive_units.iter_mut().zip(&activations[i]) {
+                    *unit_alive |= *value != 0.0;
+                }
+            }
+        }
+        alive.iter().map(|alive_units| alive_units.iter().filter(|unit| !**unit).count()).collect()
+    }
+
+    fn training_options(&self) -> TrainingOptions {
+        self.options.clone()
+    }
+
+    /// # Panics
+    ///
+    /// This function will panic if the number of learning rate multipliers does not match
+    /// the number of layers.
+    fn set_training_options(
+        &mut self,
+        options: TrainingOptions,
+    ) {
+        if let Some(multipliers) = &options.lr_multipliers {
+            assert_eq!(
+                multipliers.len(),
+                self.layers.len(),
+                "Number of learning rate multipliers must match the number of layers"
+            );
+        }
+        self.options = options;
+    }
+
+    fn set_loss_function(
+        &mut self,
+        loss_function: LossFunction,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        loss_function.validate(self.output_size())?;
+        self.loss_function = loss_function;
+        Ok(())
+    }
+
+    fn set_logits_loss(
+        &mut self,
+        enabled: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let last_activation =
+            self.shape.layers.last().map(|layer| layer.activation.activation_type());
+        if enabled && last_activation != Some(ActivationType::Softmax) {
+            return Err(
+                "Computing the loss on the logits requires a final softmax activation".into()
+            );
+        }
+        self.logits_loss = enabled;
+        Ok(())
+    }
+
+    fn reinitialize(
+        &mut self,
+        seed: u64,
+    ) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        for i in 0..self.layers.len() {
+            let mut layer = self.allocated_layer(i);
+            layer.reinitialize(&mut rng);
+            layer.free_from_use();
+        }
+        self.partial_fit_steps = 0;
+    }
+
+    fn swa_weights(&self) -> Option<Vec<f64>> {
+        self.swa_average.weights().map(<[f64]>::to_vec)
+    }
+
+    fn set_spectral_norm(
+        &mut self,
+        enabled: bool,
+    ) {
+        for i in 0..self.layers.len() {
+            let mut layer = self.allocated_layer(i);
+            layer.set_spectral_norm(enabled);
+            layer.free_from_use();
+        }
+    }
+
+    fn num_parameters(&self) -> usize {
+        self.layer_sizes().iter().map(|(rows, cols, biases)| rows * cols + biases).sum()
+    }
+
+    fn weights_flat(&self) -> Vec<f64> {
+        let mut flat = Vec::new();
+        for i in 0..self.layers.len() {
+            let mut layer = self.allocated_layer(i);
+            let weights = layer.get_weights();
+            for row in 0..weights.rows() {
+                for col in 0..weights.cols() {
+                    flat.push(weights.get_unchecked(row, col));
+                }
+            }
+            flat.extend(layer.get_biases());
+            layer.free_from_use();
+        }
+        flat
+    }
+
+    fn load_weights_flat(
+        &mut self,
+        weights: &[f64],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let layer_sizes = self.layer_sizes();
+        let num_parameters =
+            layer_sizes.iter().map(|(rows, cols, biases)| rows * cols + biases).sum::<usize>();
+        if weights.len() != num_parameters {
+            return Err(format!(
+                "Expected {num_parameters} flat weights for the neural network, found {}",
+                weights.len()
+            )
+            .into());
+        }
+
+        let mut offset = 0;
+        for (i, (rows, cols, num_biases)) in layer_sizes.into_iter().enumerate() {
+            let layer_weights = WrappedMatrix::new(rows, cols);
+            for row in 0..rows {
+                for col in 0..cols {
+                    layer_weights.set_mut_unchecked(row, col, weights[offset]);
+                    offset += 1;
+                }
+            }
+            let biases = &weights[offset..offset + num_biases];
+            offset += num_biases;
+            let mut layer = self.allocated_layer(i);
+            layer.assign_weights_and_biases(&layer_weights, biases);
+            layer.free_from_use();
+        }
+        Ok(())
+    }
+
+    fn get_weight(
+        &self,
+        layer_index: usize,
+        row: usize,
+        col: usize,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        let (weights, _) = self.layer_parameters(layer_index)?;
+        check_parameter_index("Row", row, weights.rows())?;
+        check_parameter_index("Column", col, weights.cols())?;
+        Ok(weights.get_unchecked(row, col))
+    }
+
+    fn set_weight(
+        &mut self,
+        layer_index: usize,
+        row: usize,
+        col: usize,
+        value: f64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.edit_layer_parameters(layer_index, |weights, _| {
+            check_parameter_index("Row", row, weights.rows())?;
+            check_parameter_index("Column", col, weights.cols())?;
+            weights.set_mut_unchecked(row, col, value);
+            Ok(())
+        })
+    }
+
+    fn get_bias(
+        &self,
+        layer_index: usize,
+        row: usize,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        let (_, biases) = self.layer_parameters(layer_index)?;
+        check_parameter_index("Row", row, biases.len())?;
+        Ok(biases[row])
+    }
+
+    fn set_bias(
+        &mut self,
+        layer_index: usize,
+        row: usize,
+        value: f64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.edit_layer_parameters(layer_index, |_, biases| {
+            check_parameter_index("Row", row, biases.len())?;
+            biases[row] = value;
+            Ok(())
+        })
+    }
+
+    fn spectral_norms(
+        &self,
+        iterations: usize,
+    ) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        (0..self.layers.len())
+            .map(|i| Ok(spectral_norm(&self.layer_parameters(i)?.0, iterations)))
+            .collect()
+    }
+
+    fn prune_neurons(
+        &mut self,
+        layer_index: usize,
+        keep_indices: &[usize],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(layer_shape) = self.shape.layers.get(layer_index) else {
+            return Err(format!(
+                "Layer index {layer_index} is out of range for {} layers",
+                self.layers.len()
+            )
+            .into());
+        };
+        if !matches!(layer_shape.layer_type(), LayerType::Dense { .. }) {
+            return Err(format!("Layer {layer_index} is not a dense layer").into());
+        }
+        let output_size = layer_shape.output_size();
+        if keep_indices.is_empty() {
+            return Err("At least one neuron has to be kept".into());
+        }
+        if keep_indices.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err("Indices of the kept neurons have to be strictly increasing".into());
+        }
+        if keep_indices.iter().any(|&index| index >= output_size) {
+            return Err(format!(
+                "Indices of the kept neurons have to be smaller than the output size {output_size}"
+            )
+            .into());
+        }
+
+        // keep the rows of the pruned layer belonging to the kept neurons
+        let mut layer = self.allocated_layer(layer_index);
+        let weights = layer.get_weights();
+        let biases = layer.get_biases();
+        layer.free_from_use();
+        let pruned_weights = WrappedMatrix::new(keep_indices.len(), weights.cols());
+        for (row, &index) in keep_indices.iter().enumerate() {
+            for col in 0..weights.cols() {
+                pruned_weights.set_mut_unchecked(row, col, weights.get_unchecked(index, col));
+            }
+        }
+        let pruned_biases = keep_indices.iter().map(|&index| biases[index]).collect::<Vec<_>>();
+        self.replace_dense_layer(layer_index, &pruned_weights, &pruned_biases);
+        self.shape.layers[layer_index].layer_type =
+            LayerType::Dense { input_size: weights.cols(), output_size: keep_indices.len() };
+
+        // keep the columns of the next layer reading from the kept neurons
+        if layer_index + 1 < self.layers.len() {
+            let mut next_layer = self.allocated_layer(layer_index + 1);
+            let next_weights = next_layer.get_weights();
+            let next_biases = next_layer.get_biases();
+            next_layer.free_from_use();
+            let pruned_next_weights = WrappedMatrix::new(next_weights.rows(), keep_indices.len());
+            for row in 0..next_weights.rows() {
+                for (col, &index) in keep_indices.iter().enumerate() {
+                    pruned_next_weights.set_mut_unchecked(
+                        row,
+                        col,
+                        next_weights.get_unchecked(row, index),
+                    );
+                }
+            }
+            self.replace_dense_layer(layer_index + 1, &pruned_next_weights, &next_biases);
+            self.shape.layers[layer_index + 1].layer_type = LayerType::Dense {
+                input_size: keep_indices.len(),
+                output_size: next_weights.rows(),
+            };
+        }
+        self.save_layout();
+        Ok(())
+    }
+
+    fn add_identity_layer(
+        &mut self,
+        after_index: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(layer_shape) = self.shape.layers.get(after_index) else {
+            return Err(format!(
+                "Layer index {after_index} is out of range for {} layers",
+                self.layers.len()
+            )
+            .into());
+        };
+        let size = layer_shape.output_size();
+        // a ReLU layer passes the non negative outputs of a preceding ReLU layer unchanged
+        let activation_type = if layer_shape.activation.activation_type() == ActivationType::ReLU {
+            ActivationType::ReLU
+        } else {
+            ActivationType::Identity
+        };
+        let identity_shape = LayerShape {
+            layer_type: LayerType::Dense { input_size: size, output_size: size },
+            activation: ActivationData::new(activation_type),
+        };
+        let identity = WrappedMatrix::new(size, size);
+        for i in 0..size {
+            identity.set_mut_unchecked(i, i, 1.0);
+        }
+
+        // the layers behind the new one move to the next position and with it to the next file
+        let mut parameters = ((after_index + 1)..self.layers.len())
+            .map(|i| self.layer_parameters(i))
+            .collect::<Result<Vec<_>, _>>()?;
+        parameters.insert(0, (identity, vec![0.0; size]));
+        for layer in self.layers.drain((after_index + 1)..) {
+            self.utils.clone().deallocate_trainable(&layer);
+        }
+        self.shape.layers.insert(after_index + 1, identity_shape.clone());
+        self.activations.insert(after_index + 1, create_activation(&identity_shape.activation));
+        if let Some(multipliers) = self.options.lr_multipliers.as_mut() {
+            multipliers.insert(after_index + 1, 1.0);
+        }
+        for (i, (weights, biases)) in parameters.iter().enumerate() {
+            let layer_index = after_index + 1 + i;
+            self.layers.push(trainable_layer(
+                &self.shape.layers[layer_index],
+                &self.model_directory,
+                layer_index,
+            ));
+            let mut layer = self.allocated_layer(layer_index);
+            layer.assign_weights_and_biases(weights, biases);
+            layer.free_from_use();
+        }
+        self.save_layout();
+        Ok(())
+    }
+
+    fn push_layer(
+        &mut self,
+        layer_shape: LayerShape,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !layer_shape.is_valid() {
+            return Err(format!("Cannot append the invalid layer {layer_shape:?}").into());
+        }
+        if layer_shape.input_size() != self.output_size() {
+            return Err(format!(
+                "Cannot append a layer with input size {} to a network with output size {}",
+                layer_shape.input_size(),
+                self.output_size()
+            )
+            .into());
+        }
+        let layer_index = self.layers.len();
+        self.layers.push(trainable_layer(&layer_shape, &self.model_directory, layer_index));
+        self.activations.push(create_activation(&layer_shape.activation));
+        self.shape.layers.push(layer_shape);
+        if let Some(multipliers) = self.options.lr_multipliers.as_mut() {
+            multipliers.push(1.0);
+        }
+        // allocating the new layer initializes its weights
+        self.allocated_layer(layer_index).free_from_use();
+        self.save_layout();
+        Ok(())
+    }
+
+    fn widen_layer(
+        &mut self,
+        layer_index: usize,
+        new_width: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(layer_shape) = self.shape.layers.get(layer_index) else {
+            return Err(format!(
+                "Layer index {layer_index} is out of range for {} layers",
+                self.layers.len()
+            )
+            .into());
+        };
+        if layer_index + 1 == self.layers.len() {
+            return Err("The last layer can not be widened without changing the outputs".into());
+        }
+        if !matches!(layer_shape.layer_type(), LayerType::Dense { .. })
+            || !matches!(self.shape.layers[layer_index + 1].layer_type(), LayerType::Dense { .. })
+        {
+            return Err(format!("Layer {layer_index} and its next layer have to be dense").into());
+        }
+        // the duplicated units only compute the same values for element wise activations
+        if matches!(
+            layer_shape.activation.activation_type(),
+            ActivationType::Softmax | ActivationType::Composite
+        ) {
+            return Err(format!("Layer {layer_index} has no element wise activation").into());
+        }
+        let output_size = layer_shape.output_size();
+        if new_width <= output_size {
+            return Err(format!(
+                "The new width {new_width} has to be larger than the output size {output_size}"
+            )
+            .into());
+        }
+
+        // the new units copy the existing units in turn
+        let source = |unit: usize| unit % output_size;
+        let mut copies = vec![0.0; output_size];
+        for unit in 0..new_width {
+            copies[source(unit)] += 1.0;
+        }
+
+        let mut layer = self.allocated_layer(layer_index);
+        let weights = layer.get_weights();
+        let biases = layer.get_biases();
+        layer.free_from_use();
+        let widened_weights = WrappedMatrix::new(new_width, weights.cols());
+        for row in 0..new_width {
+            for col in 0..weights.cols() {
+                widened_weights.set_mut_unchecked(
+                    row,
+                    col,
+                    weights.get_unchecked(source(row), col),
+                );
+            }
+        }
+        let widened_biases = (0..new_width).map(|unit| biases[source(unit)]).collect::<Vec<_>>();
+        self.replace_dense_layer(layer_index, &widened_weights, &widened_biases);
+        self.shape.layers[layer_index].layer_type =
+            LayerType::Dense { input_size: weights.cols(), output_size: new_width };
+
+        // the copies of a unit share its outgoing weights, so the next layer sums to the same value
+        let mut next_layer = self.allocated_layer(layer_index + 1);
+        let next_weights = next_layer.get_weights();
+        let next_biases = next_layer.get_biases();
+        next_layer.free_from_use();
+        let widened_next_weights = WrappedMatrix::new(next_weights.rows(), new_width);
+        for row in 0..next_weights.rows() {
+            for col in 0..new_width {
+                widened_next_weights.set_mut_unchecked(
+                    row,
+                    col,
+                    next_weights.get_unchecked(row, source(col)) / copies[source(col)],
+                );
+            }
+        }
+        self.replace_dense_layer(layer_index + 1, &widened_next_weights, &next_biases);
+        self.shape.layers[layer_index + 1].layer_type =
+            LayerType::Dense { input_size: new_width, output_size: next_weights.rows() };
+        self.save_layout();
+        Ok(())
+    }
+
+    fn to_inference(&self) -> WrappedNeuralNetwork {
+        let model_directory = self.get_first_free_model_directory();
+        // the inference layers read the plain weights and biases lazily from these files
+        for i in 0..self.layers.len() {
+            let mut layer = self.allocated_layer(i);
+            layer
+                .save(format!("{model_directory}/layers/layer_{i}.txt"))
+                .expect("Failed to save layer for inference");
+            layer.free_from_use();
+        }
+        WrappedNeuralNetwork::new(Box::new(ClassicNeuralNetwork::new(
+            self.shape.clone(),
+            model_directory,
+            self.utils.clone(),
+        )))
+    }
+
+    fn duplicate_trainable(&self) -> WrappedTrainableNeuralNetwork {
+        // create a sibling directory with the postfix _clone appendended to model_direcotory path
+        let model_directory = self.get_first_free_model_directory();
+        // Save the model to the new directory
+        self.save_internal(&model_directory, true).unwrap();
+        // Clone the neural network by cloning its layers and activations
+        let mut new_layers = Vec::new();
+        for (i, layer) in self.layers.iter().enumerate() {
+            new_layers.push(layer.duplicate(model_directory.clone(), i));
+        }
+        WrappedTrainableNeuralNetwork::new(Box::new(Self {
+            layers: new_layers,
+            activations: self.activations.clone(),
+            shape: self.shape.clone(),
+            model_directory: Directory::Internal(model_directory),
+            past_internal_model_directory: Vec::new(),
+            persist_on_drop: false,
+            utils: self.utils.clone(),
+            prediction_buffer: Vec::new(),
+            noise_augmenter: self.noise_augmenter,
+            dropout: self.dropout,
+            dropout_rng: self.dropout.map(|dropout| dropout.rng()),
+            dropout_masks: Vec::new(),
+            record_grad_norms: self.record_grad_norms,
+            history: self.history.clone(),
+            diagnose: self.diagnose,
+            diagnostics: self.diagnostics,
+            warnings: self.warnings.clone(),
+            options: self.options.clone(),
+            swa_average: self.swa_average.clone(),
+            loss_function: self.loss_function.clone(),
+            logits_loss: self.logits_loss,
+            partial_fit_steps: 0,
+        }))
+    }
+}
+
+impl Drop for TrainableClassicNeuralNetwork {
+    fn drop(&mut self) {
+        // Save the model to ensure that everything is on disk if it is a user_model_directory
+        // ensure that the model_directory exists
+        if let Directory::User(_) = &self.model_directory {
+            if std::fs::metadata(self.model_directory.path()).is_err() {
+                std::fs::create_dir_all(self.model_directory.path()).unwrap();
+            }
+            self.save_layout();
+            self.deallocate();
+        }
+        if self.persist_on_drop {
+            // write the layers so that the kept directory holds the current weights
+            self.save_layout();
+            self.deallocate();
+            return;
+        }
+        // Interne Verzeichnisse immer entfernen, unabhängig vom Testmodus
+        if let Directory::Internal(dir) = &self.model_directory {
+            if std::fs::metadata(dir).is_ok() {
+                std::fs::remove_dir_all(dir).unwrap();
+            }
+        }
+        for dir in &self.past_internal_model_directory {
+            if dir != &self.model_directory.path() && std::fs::metadata(dir).is_ok() {
+                std::fs::remove_dir_all(dir).unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        layer::dense_layer::DEFAULT_BATCH_CACHE_LIMIT,
+        layer::prelu::DEFAULT_PRELU_SLOPE,
         nn::shape::{ActivationData, ActivationType, LayerShape},
+        training::importance::ImportanceMetric,
+        training::swa::StochasticWeightAveraging,
         utilities::util::Utils,
     };
 
     #[test]
-    fn test_neural_network_train() {
-        use std::path::Path;
-        use std::sync::atomic::{AtomicUsize, Ordering};
+    fn test_neural_network_train() {
+        use std::path::Path;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // Helper to generate unique workspace names for tests
+        static TEST_COUNTER: AtomicUsize = AtomicUsize::new(100); // Start at 100 to avoid conflicts
+
+        let counter = TEST_COUNTER.fetch_add(1, Ordering::SeqCst);
+        let workspace = format!("/tmp/test_workspace_unit_{counter}");
+        let utils =
+            WrappedUtils::new(Utils::new_with_test_mode(1_000_000_000, 4, workspace.clone()));
+
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape {
+                layers: vec![
+                    LayerShape {
+                        layer_type: LayerType::Dense { input_size: 3, output_size: 3 },
+                        activation: ActivationData::new(ActivationType::Sigmoid),
+                    },
+                    LayerShape {
+                        layer_type: LayerType::Dense { input_size: 3, output_size: 3 },
+                        activation: ActivationData::new(ActivationType::ReLU),
+                    },
+                ],
+            },
+            &Directory::Internal("internal_model".to_string()),
+            utils,
+        );
+
+        let input = vec![1.0, 1.0, 1.0];
+        // put input 200 times in inputs
+        let inputs = vec![input; 200];
+        let target = vec![0.0, 0.0, 0.0];
+        let targets = vec![target; 200];
+
+        nn.train(&inputs, &targets, 0.01, 5, 0.1, true, None, 0.7, 1.0, None);
+
+        let prediction = nn.predict(inputs[0].clone());
+        // print targets[0]
+        println!("{:?}", targets[0]);
+        // print prediction
+        println!("{prediction:?}");
+        assert_eq!(prediction.len(), 3);
+        // assert that the prediction is close to the target
+        for (p, t) in prediction.iter().zip(&targets[0]) {
+            assert!((p - t).abs() < 1e-4);
+        }
+
+        // Clean up workspace
+        if !workspace.is_empty() && Path::new(&workspace).exists() {
+            let _ = std::fs::remove_dir_all(&workspace);
+        }
+    }
+
+    fn layer_weights(layer: &WrappedTrainableLayer) -> Vec<f64> {
+        let weights = layer.get_weights();
+        let mut values = Vec::new();
+        for i in 0..weights.rows() {
+            for j in 0..weights.cols() {
+                values.push(weights.get_unchecked(i, j));
+            }
+        }
+        values.extend(layer.get_biases());
+        values
+    }
+
+    #[test]
+    fn test_lr_multipliers_scale_layer_updates() {
+        let model_directory = "test_model_lr_multipliers".to_string();
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape {
+                layers: vec![
+                    LayerShape {
+                        layer_type: LayerType::Dense { input_size: 3, output_size: 3 },
+                        activation: ActivationData::new(ActivationType::Sigmoid),
+                    },
+                    LayerShape {
+                        layer_type: LayerType::Dense { input_size: 3, output_size: 2 },
+                        activation: ActivationData::new(ActivationType::Sigmoid),
+                    },
+                ],
+            },
+            &Directory::Internal(model_directory.clone()),
+            utils,
+        );
+
+        let output = nn.forward(&[1.0, -0.5, 0.25]);
+        nn.backward(output.iter().map(|o| 2.0 * (o - 1.0)).collect());
+
+        // the gradients stay in the layers, so both updates see identical gradients
+        nn.set_training_options(TrainingOptions {
+            lr_multipliers: Some(vec![1.0, 1.0]),
+            ..nn.training_options()
+        });
+        let before = nn.layers.iter().map(layer_weights).collect::<Vec<_>>();
+        nn.update_weights(0.1);
+        let middle = nn.layers.iter().map(layer_weights).collect::<Vec<_>>();
+        nn.set_training_options(TrainingOptions {
+            lr_multipliers: Some(vec![0.5, 1.0]),
+            ..nn.training_options()
+        });
+        nn.update_weights(0.1);
+        let after = nn.layers.iter().map(layer_weights).collect::<Vec<_>>();
+
+        for (layer, multiplier) in [0.5, 1.0].iter().enumerate() {
+            let mut moved = false;
+            for ((b, m), a) in before[layer].iter().zip(&middle[layer]).zip(&after[layer]) {
+                let full_step = m - b;
+                let scaled_step = a - m;
+                assert!((scaled_step - multiplier * full_step).abs() < 1e-12);
+                moved |= full_step.abs() > 0.0;
+            }
+            assert!(moved);
+        }
+
+        drop(nn);
+        assert!(std::fs::metadata(model_directory).is_err());
+    }
+
+    #[test]
+    fn test_dead_neuron_report() {
+        use crate::layer::dense_layer::{save_weight, Bias, Weight};
+        use matrix::mat::WrappedMatrix;
+
+        let model_directory = "test_model_dead_neurons".to_string();
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape {
+                layers: vec![
+                    LayerShape {
+                        layer_type: LayerType::Dense { input_size: 2, output_size: 3 },
+                        activation: ActivationData::new(ActivationType::ReLU),
+                    },
+                    LayerShape {
+                        layer_type: LayerType::Dense { input_size: 3, output_size: 1 },
+                        activation: ActivationData::new(ActivationType::Sigmoid),
+                    },
+                ],
+            },
+            &Directory::Internal(model_directory.clone()),
+            utils,
+        );
+
+        // unit 1 only has negative weights, so it never fires for positive inputs
+        let weights = WrappedMatrix::new(3, 2);
+        for i in 0..3 {
+            let value = if i == 1 { -1.0 } else { 1.0 };
+            for j in 0..2 {
+                weights.set_mut_unchecked(i, j, Weight { value, grad: 0.0, m: 0.0, v: 0.0 });
+            }
+        }
+        let weights_path = format!("{model_directory}_weights/layer_0.txt");
+        save_weight(weights_path.clone(), &weights, &[Bias::default(); 3]).unwrap();
+        nn.layers[0].read_weight(weights_path).unwrap();
+
+        let inputs = vec![vec![1.0, 0.5], vec![0.2, 2.0], vec![3.0, 0.1]];
+        assert_eq!(nn.dead_neuron_report(&inputs), vec![1]);
+
+        drop(nn);
+        std::fs::remove_dir_all(format!("{model_directory}_weights")).unwrap();
+    }
+
+    #[test]
+    fn test_on_epoch_end_callback() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape {
+                layers: vec![LayerShape {
+                    layer_type: LayerType::Dense { input_size: 2, output_size: 1 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                }],
+            },
+            &Directory::Internal("test_model_epoch_callback".to_string()),
+            utils,
+        );
+
+        let inputs = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0], vec![0.0, 0.0]];
+        let targets = vec![vec![1.0], vec![0.0], vec![1.0], vec![0.0]];
+        let mut metrics = Vec::new();
+        let mut on_epoch_end = |epoch_metrics: EpochMetrics| metrics.push(epoch_metrics);
+        nn.train(&inputs, &targets, 0.1, 4, 0.1, false, None, 0.5, 1.0, Some(&mut on_epoch_end));
+
+        assert_eq!(metrics.len(), 4);
+        for (i, epoch_metrics) in metrics.iter().enumerate() {
+            assert_eq!(epoch_metrics.epoch, i);
+            assert!(epoch_metrics.train_loss >= 0.0);
+            assert!(epoch_metrics.validation_loss >= 0.0);
+        }
+    }
+
+    #[test]
+    fn test_early_stopping_patience() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape {
+                layers: vec![LayerShape {
+                    layer_type: LayerType::Dense { input_size: 2, output_size: 1 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                }],
+            },
+            &Directory::Internal("test_model_early_stopping".to_string()),
+            utils,
+        );
+        nn.set_training_options(TrainingOptions {
+            early_stopping_patience: Some(1),
+            ..nn.training_options()
+        });
+
+        let inputs = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0], vec![0.0, 0.0]];
+        let targets = vec![vec![1.0], vec![0.0], vec![1.0], vec![0.0]];
+        let mut nb_epochs = 0;
+        let mut on_epoch_end = |_: EpochMetrics| nb_epochs += 1;
+        // without a learning rate the validation loss never improves after the first epoch
+        nn.train(&inputs, &targets, 0.0, 10, 0.1, false, None, 0.5, 1.0, Some(&mut on_epoch_end));
+
+        assert_eq!(nb_epochs, 2);
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct Linear;
+
+    impl ActivationTrait for Linear {
+        fn forward(
+            &mut self,
+            input: &[f64],
+        ) -> Vec<f64> {
+            input.to_vec()
+        }
+
+        fn backward(
+            &mut self,
+            grad_output: &[f64],
+        ) -> Vec<f64> {
+            grad_output.to_vec()
+        }
+
+        fn get_activation_data(&self) -> ActivationData {
+            ActivationData::new_custom("linear")
+        }
+    }
+
+    #[test]
+    fn test_diagnostics_detect_exploding_gradients() {
+        use crate::activation::registry::register_activation;
+        use crate::training::diagnostics::GradientHealth;
+
+        register_activation("linear", || Box::new(Linear));
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layers = vec![
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 3, output_size: 8 },
+                activation: ActivationData::new_custom("linear"),
+            },
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 8, output_size: 2 },
+                activation: ActivationData::new_custom("linear"),
+            },
+        ];
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers },
+            &Directory::Internal("test_model_diagnostics".to_string()),
+            utils,
+        );
+        nn.set_training_options(TrainingOptions {
+            verbosity: Verbosity::Silent,
+            ..nn.training_options()
+        });
+        nn.set_diagnose(true);
+
+        let inputs: Vec<Vec<f64>> =
+            (0..40_u8).map(|i| vec![1.0, (i % 5).into(), (i % 3).into()]).collect();
+        let targets: Vec<Vec<f64>> =
+            (0..40_u8).map(|i| vec![(i % 2).into(), (i % 4).into()]).collect();
+        // an absurd learning rate makes gradient descent diverge
+        nn.train(&inputs, &targets, 1e3, 1, 0.1, false, None, 0.25, 1.0, None);
+
+        let diagnostics = nn.training_diagnostics().expect("Diagnostics were enabled");
+        assert_eq!(diagnostics.gradient_health, GradientHealth::Exploding);
+        assert!(diagnostics.loss_increased);
+        assert!(!diagnostics.is_healthy());
+    }
+
+    #[test]
+    fn test_warns_about_targets_outside_of_output_range() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layers = vec![LayerShape {
+            layer_type: LayerType::Dense { input_size: 2, output_size: 1 },
+            activation: ActivationData::new(ActivationType::Tanh),
+        }];
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers },
+            &Directory::Internal("test_model_target_range".to_string()),
+            utils,
+        );
+        nn.set_training_options(TrainingOptions {
+            verbosity: Verbosity::Silent,
+            ..nn.training_options()
+        });
+
+        let inputs = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0], vec![0.0, 0.0]];
+        let targets = vec![vec![5.0], vec![0.5], vec![-0.5], vec![0.0]];
+        nn.train(&inputs, &targets, 0.01, 1, 0.1, false, None, 0.25, 1.0, None);
+        assert_eq!(
+            nn.training_warnings(),
+            &[TrainingWarning::TargetsOutOfRange {
+                activation: ActivationType::Tanh,
+                range: (-1.0, 1.0),
+                min_target: -0.5,
+                max_target: 5.0,
+            }]
+        );
+
+        let scaled_targets: Vec<Vec<f64>> =
+            targets.iter().map(|target| vec![target[0] / 5.0]).collect();
+        nn.train(&inputs, &scaled_targets, 0.01, 1, 0.1, false, None, 0.25, 1.0, None);
+        assert!(nn.training_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_adam_params_are_used() {
+        use crate::training::adam::AdamParams;
+
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layers = vec![LayerShape {
+            layer_type: LayerType::Dense { input_size: 2, output_size: 2 },
+            activation: ActivationData::new(ActivationType::Sigmoid),
+        }];
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers },
+            &Directory::Internal("test_model_adam_params".to_string()),
+            utils,
+        );
+        nn.set_training_options(TrainingOptions {
+            verbosity: Verbosity::Silent,
+            ..nn.training_options()
+        });
+        let mut other = nn.copy().unwrap();
+        let mut same = nn.copy().unwrap();
+        nn.set_training_options(TrainingOptions {
+            adam_params: AdamParams::default(),
+            ..nn.training_options()
+        });
+        other.set_training_options(TrainingOptions {
+            adam_params: AdamParams::new(0.9, 0.99, 1e-8),
+            ..other.training_options()
+        });
+
+        // identical samples make the training independent of the shuffling
+        let inputs = vec![vec![1.0, 0.5]; 4];
+        let targets = vec![vec![1.0, 0.0]; 4];
+        for network in [&mut nn, &mut other, &mut same] {
+            network.train(&inputs, &targets, 0.01, 2, 0.1, true, None, 0.75, 1.0, None);
+        }
+
+        let difference: f64 = nn
+            .weights_flat()
+            .iter()
+            .zip(other.weights_flat())
+            .map(|(weight, other_weight)| (weight - other_weight).abs())
+            .sum();
+        assert!(difference > 1e-6);
+        assert_eq!(nn.weights_flat(), same.weights_flat());
+    }
+
+    #[test]
+    fn test_partial_fit_reduces_loss() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layers = vec![LayerShape {
+            layer_type: LayerType::Dense { input_size: 2, output_size: 2 },
+            activation: ActivationData::new(ActivationType::Sigmoid),
+        }];
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers },
+            &Directory::Internal("test_model_partial_fit".to_string()),
+            utils,
+        );
+
+        let input = [1.0, 0.5];
+        let target = [1.0, 0.0];
+        let loss_function = LossFunction::MeanSquaredError;
+        let mut previous_loss = f64::INFINITY;
+        for _ in 0..50 {
+            let loss = nn.partial_fit(&input, &target, 0.001, &loss_function).unwrap();
+            assert!(loss < previous_loss, "loss increased from {previous_loss} to {loss}");
+            previous_loss = loss;
+        }
+        assert_eq!(nn.partial_fit_steps, 50);
+        assert!(nn.partial_fit(&[1.0], &target, 0.001, &loss_function).is_err());
+    }
+
+    #[test]
+    fn test_grad_noise_is_deterministic() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layers = vec![LayerShape {
+            layer_type: LayerType::Dense { input_size: 2, output_size: 2 },
+            activation: ActivationData::new(ActivationType::Sigmoid),
+        }];
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers },
+            &Directory::Internal("test_model_grad_noise".to_string()),
+            utils,
+        );
+        nn.set_training_options(TrainingOptions {
+            verbosity: Verbosity::Silent,
+            ..nn.training_options()
+        });
+        let mut noisy = nn.copy().unwrap();
+        let mut noisy_again = nn.copy().unwrap();
+        noisy.set_training_options(TrainingOptions {
+            grad_noise: Some(GradientNoise::new(0.1, 42)),
+            ..noisy.training_options()
+        });
+        noisy_again.set_training_options(TrainingOptions {
+            grad_noise: Some(GradientNoise::new(0.1, 42)),
+            ..noisy_again.training_options()
+        });
+
+        // identical samples make the training independent of the shuffling
+        let inputs = vec![vec![1.0, 0.5]; 4];
+        let targets = vec![vec![1.0, 0.0]; 4];
+        for network in [&mut nn, &mut noisy, &mut noisy_again] {
+            network.train(&inputs, &targets, 0.1, 2, 0.1, false, None, 0.75, 1.0, None);
+        }
+
+        let difference: f64 = nn
+            .weights_flat()
+            .iter()
+            .zip(noisy.weights_flat())
+            .map(|(weight, noisy_weight)| (weight - noisy_weight).abs())
+            .sum();
+        assert!(difference > 1e-6);
+        assert_eq!(noisy.weights_flat(), noisy_again.weights_flat());
+    }
+
+    #[test]
+    fn test_max_weight_norm_bounds_the_rows() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layers = vec![LayerShape {
+            layer_type: LayerType::Dense { input_size: 3, output_size: 2 },
+            activation: ActivationData::new(ActivationType::ReLU),
+        }];
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers },
+            &Directory::Internal("test_model_max_weight_norm".to_string()),
+            utils,
+        );
+        nn.set_training_options(TrainingOptions {
+            verbosity: Verbosity::Silent,
+            max_weight_norm: Some(0.5),
+            ..nn.training_options()
+        });
+
+        // large targets and a large learning rate let the weights grow quickly
+        let inputs = vec![vec![1.0, 2.0, 3.0]; 4];
+        let targets = vec![vec![100.0, 100.0]; 4];
+        nn.train(&inputs, &targets, 0.1, 3, 0.1, false, None, 0.75, 1.0, None);
+
+        let (weights, _) = nn.layer_parameters(0).unwrap();
+        for i in 0..weights.rows() {
+            let norm = (0..weights.cols())
+                .map(|j| weights.get_unchecked(i, j).powi(2))
+                .sum::<f64>()
+                .sqrt();
+            assert!(norm <= 0.5 + 1e-9, "row {i} has norm {norm}");
+        }
+    }
+
+    #[test]
+    fn test_saliency_of_a_linear_network() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layers = vec![
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 3, output_size: 4 },
+                activation: ActivationData::new(ActivationType::Identity),
+            },
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 4, output_size: 2 },
+                activation: ActivationData::new(ActivationType::Identity),
+            },
+        ];
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers },
+            &Directory::Internal("test_model_saliency".to_string()),
+            utils,
+        );
+        let (first, _) = nn.layer_parameters(0).unwrap();
+        let (second, _) = nn.layer_parameters(1).unwrap();
+
+        // the gradient of a linear network is the product of its weights along all paths
+        for output_index in 0..2 {
+            let saliency = nn.saliency(&[0.5, -1.0, 2.0], output_index).unwrap();
+            for (j, value) in saliency.into_iter().enumerate() {
+                let expected = (0..4)
+                    .map(|h| second.get_unchecked(output_index, h) * first.get_unchecked(h, j))
+                    .sum::<f64>()
+                    .abs();
+                assert!((value - expected).abs() < 1e-12);
+            }
+        }
+        assert!(nn.saliency(&[0.5, -1.0, 2.0], 2).is_err());
+        assert!(nn.saliency(&[0.5], 0).is_err());
+    }
+
+    #[test]
+    fn test_reinitialize_matches_a_fresh_network() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape {
+            layers: vec![
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 4 },
+                    activation: ActivationData::new(ActivationType::ReLU),
+                },
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 4, output_size: 2 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                },
+            ],
+        };
+        let mut trained = TrainableClassicNeuralNetwork::new(
+            shape.clone(),
+            &Directory::Internal("test_model_reinitialize_trained".to_string()),
+            utils.clone(),
+        );
+        trained.set_training_options(TrainingOptions {
+            verbosity: Verbosity::Silent,
+            ..trained.training_options()
+        });
+        let inputs = vec![vec![1.0, 0.5, -1.0]; 4];
+        let targets = vec![vec![1.0, 0.0]; 4];
+        trained.train(&inputs, &targets, 0.1, 2, 0.1, true, None, 0.75, 1.0, None);
+        let mut fresh = TrainableClassicNeuralNetwork::new(
+            shape,
+            &Directory::Internal("test_model_reinitialize_fresh".to_string()),
+            utils,
+        );
+
+        trained.reinitialize(7);
+        fresh.reinitialize(7);
+        assert_eq!(trained.weights_flat(), fresh.weights_flat());
+        assert_eq!(trained.shape(), fresh.shape());
+        // the Adam moments and the gradients of the training are gone
+        for i in 0..trained.layers.len() {
+            let mut layer = trained.allocated_layer(i);
+            let moments = layer.moments();
+            assert!(layer.grad_norm_sq().abs() < f64::EPSILON);
+            layer.free_from_use();
+            for values in [&moments.first, &moments.second] {
+                let weights = &values.weights;
+                assert!((0..weights.rows()).all(|i| (0..weights.cols())
+                    .all(|j| weights.get_unchecked(i, j).abs() < f64::EPSILON)));
+                assert!(values.biases.iter().all(|value| value.abs() < f64::EPSILON));
+            }
+        }
+        fresh.reinitialize(8);
+        assert_ne!(trained.weights_flat(), fresh.weights_flat());
+    }
+
+    #[test]
+    fn test_swa_averages_the_last_epochs() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layers = vec![LayerShape {
+            layer_type: LayerType::Dense { input_size: 2, output_size: 2 },
+            activation: ActivationData::new(ActivationType::Sigmoid),
+        }];
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers },
+            &Directory::Internal("test_model_swa".to_string()),
+            utils,
+        );
+        nn.set_training_options(TrainingOptions {
+            verbosity: Verbosity::Silent,
+            ..nn.training_options()
+        });
+        let mut reference = nn.copy().unwrap();
+        let mut last_epoch = nn.copy().unwrap();
+        nn.set_training_options(TrainingOptions {
+            swa: Some(StochasticWeightAveraging::new(3, true)),
+            ..nn.training_options()
+        });
+        last_epoch.set_training_options(TrainingOptions {
+            swa: Some(StochasticWeightAveraging::new(3, false)),
+            ..last_epoch.training_options()
+        });
+
+        // identical samples make the training independent of the shuffling, so training
+        // the reference one epoch at a time yields the snapshots of every epoch
+        let inputs = vec![vec![1.0, 0.5]; 4];
+        let targets = vec![vec![1.0, 0.0]; 4];
+        let mut snapshots = Vec::new();
+        for _ in 0..5 {
+            reference.train(&inputs, &targets, 0.5, 1, 0.1, false, None, 0.75, 1.0, None);
+            snapshots.push(reference.weights_flat());
+        }
+        nn.train(&inputs, &targets, 0.5, 5, 0.1, false, None, 0.75, 1.0, None);
+        last_epoch.train(&inputs, &targets, 0.5, 5, 0.1, false, None, 0.75, 1.0, None);
+
+        let swa_weights = nn.swa_weights().unwrap();
+        for (i, weight) in swa_weights.iter().enumerate() {
+            let mean = snapshots[2..].iter().map(|snapshot| snapshot[i]).sum::<f64>() / 3.0;
+            assert!((weight - mean).abs() < 1e-12);
+        }
+        assert_eq!(nn.weights_flat(), swa_weights);
+        assert_eq!(last_epoch.swa_weights().unwrap(), swa_weights);
+        assert_eq!(last_epoch.weights_flat(), snapshots[4]);
+        assert_ne!(swa_weights, snapshots[4]);
+    }
+
+    #[test]
+    fn test_predict_checked_detects_exploding_activations() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layers = vec![
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 2, output_size: 3 },
+                activation: ActivationData::new(ActivationType::Sigmoid),
+            },
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 3, output_size: 1 },
+                activation: ActivationData::new(ActivationType::Sigmoid),
+            },
+        ];
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers },
+            &Directory::Internal("test_model_predict_checked".to_string()),
+            utils,
+        );
+        let input = vec![10.0, 10.0];
+        assert_eq!(nn.predict_checked(input.clone()).unwrap(), nn.predict(input.clone()));
+
+        // the huge weights overflow the first layer, although the sigmoid keeps the output finite
+        let huge_weights = vec![1e308; nn.num_parameters()];
+        nn.load_weights_flat(&huge_weights).unwrap();
+        assert!(nn.predict(input.clone()).iter().all(|value| value.is_finite()));
+        let error = nn.predict_checked(input.clone()).unwrap_err();
+        assert_eq!(error.layer(), 0);
+        assert_eq!(error.unit(), 0);
+        assert!(error.value().is_infinite());
+        assert_eq!(nn.to_inference().predict_checked(input).unwrap_err(), error);
+    }
+
+    #[test]
+    fn test_custom_optimizer_updates_weights() {
+        use crate::layer::layer_trait::TrainableLayer;
+        use crate::training::optimizer::{Optimizer, SgdOptimizer};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        #[derive(Debug, Clone)]
+        struct ScaledSgd {
+            scale: f64,
+            steps: Arc<AtomicUsize>,
+        }
+
+        impl Optimizer for ScaledSgd {
+            fn step(
+                &mut self,
+                layer: &mut dyn TrainableLayer,
+                learning_rate: f64,
+                utils: WrappedUtils,
+            ) {
+                self.steps.fetch_add(1, Ordering::SeqCst);
+                layer.update_weights(self.scale * learning_rate, utils);
+            }
+        }
+
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layers = vec![LayerShape {
+            layer_type: LayerType::Dense { input_size: 2, output_size: 2 },
+            activation: ActivationData::new(ActivationType::Sigmoid),
+        }];
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers },
+            &Directory::Internal("test_model_custom_optimizer".to_string()),
+            utils,
+        );
+        nn.set_training_options(TrainingOptions {
+            verbosity: Verbosity::Silent,
+            ..nn.training_options()
+        });
+        let initial_weights = nn.weights_flat();
+        let mut sgd = nn.copy().unwrap();
+        let mut builtin_sgd = nn.copy().unwrap();
+        let steps = Arc::new(AtomicUsize::new(0));
+
+        // identical samples make the training independent of the shuffling
+        let inputs = vec![vec![1.0, 0.5]; 4];
+        let targets = vec![vec![1.0, 0.0]; 4];
+        let scaled_sgd = ScaledSgd { scale: 0.5, steps: steps.clone() };
+        nn.train(&inputs, &targets, 0.2, 2, 0.1, true, Some(Box::new(scaled_sgd)), 0.75, 1.0, None);
+        sgd.train(
+            &inputs,
+            &targets,
+            0.1,
+            2,
+            0.1,
+            true,
+            Some(Box::new(SgdOptimizer)),
+            0.75,
+            1.0,
+            None,
+        );
+        builtin_sgd.train(&inputs, &targets, 0.1, 2, 0.1, false, None, 0.75, 1.0, None);
+
+        // one step for every layer and training sample of both epochs
+        assert_eq!(steps.load(Ordering::SeqCst), 2 * 3);
+        assert_ne!(nn.weights_flat(), initial_weights);
+        assert_eq!(nn.weights_flat(), builtin_sgd.weights_flat());
+        assert_eq!(sgd.weights_flat(), builtin_sgd.weights_flat());
+    }
+
+    #[test]
+    fn test_quantized_predictions_are_close() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layers = vec![
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 3, output_size: 4 },
+                activation: ActivationData::new(ActivationType::ReLU),
+            },
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 4, output_size: 2 },
+                activation: ActivationData::new(ActivationType::Sigmoid),
+            },
+        ];
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers },
+            &Directory::Internal("test_model_quantized".to_string()),
+            utils.clone(),
+        );
+        let mut quantized = nn.to_quantized().unwrap();
+        let mut from_inference = nn.to_inference().to_quantized().unwrap();
+        let mut restored = ClassicNeuralNetwork::from_quantized(
+            &quantized,
+            "test_model_quantized_restored".to_string(),
+            utils,
+        )
+        .unwrap();
+
+        // with weights and inputs of magnitude at most one, half a quantization step of
+        // every weight moves the outputs by far less than the tolerance
+        let tolerance = 1e-2;
+        for input in [vec![0.5, -1.0, 0.25], vec![1.0, 0.0, -0.5], vec![-0.3, 0.8, 0.9]] {
+            let expected = nn.predict(input.clone());
+            let output = quantized.predict(&input);
+            for (value, expected) in output.iter().zip(&expected) {
+                assert!((value - expected).abs() < tolerance);
+            }
+            assert_eq!(from_inference.predict(&input), output);
+            for (value, quantized_value) in restored.predict(input).iter().zip(&output) {
+                assert!((value - quantized_value).abs() < 1e-12);
+            }
+        }
+        assert_eq!(quantized.layers()[0].quantized_weights().len(), 3 * 4);
+    }
+
+    #[test]
+    fn test_permutation_importance_finds_decisive_feature() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layers = vec![LayerShape {
+            layer_type: LayerType::Dense { input_size: 2, output_size: 1 },
+            activation: ActivationData::new(ActivationType::Sigmoid),
+        }];
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers },
+            &Directory::Internal("test_model_permutation_importance".to_string()),
+            utils,
+        );
+        // Only the first feature decides the output.
+        nn.set_weight(0, 0, 0, 10.0).unwrap();
+        nn.set_weight(0, 0, 1, 0.0).unwrap();
+        nn.set_bias(0, 0, -5.0).unwrap();
+
+        let inputs = (0..20u8)
+            .map(|i| vec![(i % 2).into(), <f64 as From<u8>>::from(i) / 20.0])
+            .collect::<Vec<_>>();
+        let targets = inputs.iter().map(|input| vec![input[0]]).collect::<Vec<_>>();
+
+        let metrics = [
+            ImportanceMetric::Loss(LossFunction::MeanSquaredError),
+            ImportanceMetric::Accuracy { tolerance: 0.1, sample_match_percentage: 1.0 },
+        ];
+        for metric in &metrics {
+            let importances = nn.permutation_importance(&inputs, &targets, metric, 42);
+            assert_eq!(importances.len(), 2);
+            assert!(importances[0] > importances[1]);
+            assert!(importances[1].abs() < 1e-12);
+            // The same seed yields the same importances.
+            assert_eq!(importances, nn.permutation_importance(&inputs, &targets, metric, 42));
+        }
+    }
+
+    #[test]
+    fn test_save_with_leftover_backup_directory() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layers = vec![LayerShape {
+            layer_type: LayerType::Dense { input_size: 2, output_size: 1 },
+            activation: ActivationData::new(ActivationType::Sigmoid),
+        }];
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers },
+            &Directory::Internal("test_model_leftover_backup_source".to_string()),
+            utils.clone(),
+        );
+        let model_directory = "test_model_leftover_backup".to_string();
+        let backup_directory = format!("{model_directory}_backup");
+        let _ = std::fs::remove_dir_all(&model_directory);
+        // a backup left behind by a previous crash
+        std::fs::create_dir_all(format!("{backup_directory}/layers")).unwrap();
+        std::fs::write(format!("{backup_directory}/layers/layer_0.txt"), "stale").unwrap();
+
+        nn.set_weight(0, 0, 0, 1.0).unwrap();
+        nn.save(model_directory.clone()).unwrap();
+        std::fs::create_dir_all(&backup_directory).unwrap();
+        nn.set_weight(0, 0, 0, 2.0).unwrap();
+        nn.save(model_directory.clone()).unwrap();
+
+        assert!(!std::path::Path::new(&backup_directory).exists());
+        assert!(!std::path::Path::new(&format!("{model_directory}_tmp")).exists());
+        let reloaded =
+            TrainableClassicNeuralNetwork::from_disk(model_directory.clone(), utils).unwrap();
+        assert!((reloaded.get_weight(0, 0, 0).unwrap() - 2.0).abs() < 1e-12);
+        drop(reloaded);
+        drop(nn);
+        std::fs::remove_dir_all(&model_directory).unwrap();
+    }
+
+    #[test]
+    fn test_profile_forward() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layer = |input_size, output_size| LayerShape {
+            layer_type: LayerType::Dense { input_size, output_size },
+            activation: ActivationData::new(ActivationType::ReLU),
+        };
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers: vec![layer(8, 16), layer(16, 4), layer(4, 2)] },
+            &Directory::Internal("test_model_profile_forward".to_string()),
+            utils,
+        );
+        let input = vec![0.5; 8];
+        let prediction = nn.predict(input.clone());
+        let durations = nn.profile_forward(&input);
+        assert_eq!(durations.len(), 3);
+        assert!(durations.iter().all(|duration| !duration.is_zero()));
+        assert_eq!(nn.predict(input), prediction);
+    }
+
+    #[test]
+    fn test_logits_loss_matches_softmax_then_cross_entropy() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layers = vec![
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 3, output_size: 4 },
+                activation: ActivationData::new(ActivationType::Tanh),
+            },
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 4, output_size: 3 },
+                activation: ActivationData::new_softmax(0.5),
+            },
+        ];
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers },
+            &Directory::Internal("test_model_logits_loss".to_string()),
+            utils,
+        );
+        let inputs = vec![vec![1.0, 0.0, 0.5], vec![0.0, 1.0, -0.5], vec![-1.0, 0.5, 1.0]];
+        let targets = vec![vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0], vec![0.0, 0.0, 1.0]];
+        let loss = LossFunction::WeightedCrossEntropy { class_weights: vec![1.0, 2.0, 1.0] };
+
+        let expected = nn.compute_batch_gradients(&inputs, &targets, &loss).unwrap();
+        nn.set_logits_loss(true).unwrap();
+        let fused = nn.compute_batch_gradients(&inputs, &targets, &loss).unwrap();
+        for (expected, fused) in expected.iter().zip(&fused) {
+            for (e, f) in expected.weights.to_flat().iter().zip(fused.weights.to_flat()) {
+                assert!((e - f).abs() < 1e-12);
+            }
+        }
+
+        // predictions still apply the softmax
+        let prediction = nn.predict(inputs[0].clone());
+        assert!((prediction.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+
+        // the loss on the logits needs a final softmax
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape {
+                layers: vec![LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 3 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                }],
+            },
+            &Directory::Internal("test_model_logits_loss".to_string()),
+            WrappedUtils::new(Utils::new(1_000_000_000, 4)),
+        );
+        assert!(nn.set_logits_loss(true).is_err());
+        assert!(nn.set_logits_loss(false).is_ok());
+    }
+
+    #[test]
+    fn test_compute_batch_gradients_matches_train_batch() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layers = vec![
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 3, output_size: 4 },
+                activation: ActivationData::new(ActivationType::Sigmoid),
+            },
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 4, output_size: 2 },
+                activation: ActivationData::new(ActivationType::Sigmoid),
+            },
+        ];
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers },
+            &Directory::Internal("test_model_batch_gradients".to_string()),
+            utils,
+        );
+        let mut reference = nn.copy().unwrap();
+
+        let inputs = vec![
+            vec![1.0, 0.0, 0.5],
+            vec![0.0, 1.0, -0.5],
+            vec![1.0, 1.0, 0.0],
+            vec![-1.0, 0.5, 1.0],
+        ];
+        let targets = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0], vec![0.0, 0.0]];
+        let learning_rate: f64 = 0.5;
+
+        let gradients =
+            nn.compute_batch_gradients(&inputs, &targets, &LossFunction::MeanSquaredError).unwrap();
+        assert_eq!(gradients.len(), 2);
+        assert!(gradients[0].weights.to_flat().iter().any(|grad| *grad != 0.0));
+        for (i, layer_gradients) in gradients.iter().enumerate() {
+            for row in 0..layer_gradients.weights.rows() {
+                for col in 0..layer_gradients.weights.cols() {
+                    let grad = *layer_gradients.weights.get_unchecked(row, col);
+                    let weight = nn.get_weight(i, row, col).unwrap();
+                    nn.set_weight(i, row, col, learning_rate.mul_add(-grad, weight)).unwrap();
+                }
+            }
+            for (row, grad) in layer_gradients.biases.iter().enumerate() {
+                let bias = nn.get_bias(i, row).unwrap();
+                nn.set_bias(i, row, learning_rate.mul_add(-grad, bias)).unwrap();
+            }
+        }
+
+        reference.train_batch(&inputs, &targets, learning_rate, 1, 0.1, inputs.len(), None);
+        assert_eq!(nn.weights_flat().len(), reference.weights_flat().len());
+        for (manual, trained) in nn.weights_flat().iter().zip(reference.weights_flat()) {
+            assert!((manual - trained).abs() < 1e-12);
+        }
+        assert!(nn.compute_batch_gradients(&[], &[], &LossFunction::MeanSquaredError).is_err());
+    }
+
+    #[test]
+    fn test_train_batch_clears_the_batch_caches() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layers = vec![
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 2, output_size: 4 },
+                activation: ActivationData::new(ActivationType::ReLU),
+            },
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 4, output_size: 1 },
+                activation: ActivationData::new(ActivationType::Sigmoid),
+            },
+        ];
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers },
+            &Directory::Internal("test_model_batch_cache_training".to_string()),
+            utils,
+        );
+        nn.set_training_options(TrainingOptions {
+            verbosity: Verbosity::Silent,
+            ..nn.training_options()
+        });
+
+        let grid = [0.0, 0.125, 0.25, 0.375, 0.5, 0.625, 0.75, 0.875];
+        let inputs: Vec<Vec<f64>> =
+            grid.iter().flat_map(|&x| grid.iter().map(move |&y| vec![x, y])).collect();
+        let targets: Vec<Vec<f64>> =
+            inputs.iter().map(|input| vec![if input[0] > input[1] { 1.0 } else { 0.0 }]).collect();
+
+        for _ in 0..10 {
+            // batch forward passes without a backward pass leave their inputs behind
+            for i in 0..nn.layers.len() {
+                let mut layer = nn.allocated_layer(i);
+                let input = vec![0.5; layer.input_size()];
+                for _ in 0..=DEFAULT_BATCH_CACHE_LIMIT {
+                    layer.forward_batch(&input);
+                }
+                assert_eq!(layer.batch_cache_len(), DEFAULT_BATCH_CACHE_LIMIT);
+                layer.free_from_use();
+            }
+
+            nn.train_batch(&inputs, &targets, 0.1, 1, 0.1, 4, None);
+            for layer in &nn.layers {
+                assert_eq!(layer.batch_cache_len(), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_grad_norms_vanish_in_deep_sigmoid_network() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layers = (0..6)
+            .map(|_| LayerShape {
+                layer_type: LayerType::Dense { input_size: 4, output_size: 4 },
+                activation: ActivationData::new(ActivationType::Sigmoid),
+            })
+            .collect();
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers },
+            &Directory::Internal("test_model_grad_norms".to_string()),
+            utils,
+        );
+        nn.set_training_options(TrainingOptions {
+            verbosity: Verbosity::Silent,
+            ..nn.training_options()
+        });
+        nn.set_record_grad_norms(true);
+
+        let inputs = vec![
+            vec![1.0, 0.0, 0.5, -1.0],
+            vec![0.0, 1.0, -0.5, 1.0],
+            vec![1.0, 1.0, 0.0, 0.0],
+            vec![0.0, 0.0, 1.0, 0.5],
+        ];
+        let targets = vec![
+            vec![1.0, 0.0, 0.0, 1.0],
+            vec![0.0, 1.0, 1.0, 0.0],
+            vec![1.0, 1.0, 0.0, 0.0],
+            vec![0.0, 0.0, 1.0, 1.0],
+        ];
+        nn.train(&inputs, &targets, 0.01, 2, 0.1, false, None, 0.75, 1.0, None);
+
+        let history = nn.training_history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.grad_norms.len(), 2);
+        for grad_norms in &history.grad_norms {
+            assert_eq!(grad_norms.len(), 6);
+            assert!(grad_norms[0] < grad_norms[5]);
+        }
+    }
+
+    #[test]
+    fn test_train_with_validation_uses_the_supplied_samples() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape {
+                layers: vec![LayerShape {
+                    layer_type: LayerType::Dense { input_size: 2, output_size: 1 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                }],
+            },
+            &Directory::Internal("test_model_train_with_validation".to_string()),
+            utils,
+        );
+        nn.set_training_options(TrainingOptions {
+            verbosity: Verbosity::Silent,
+            ..nn.training_options()
+        });
+
+        let train_inputs = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]];
+        let train_targets = vec![vec![1.0], vec![0.0], vec![1.0]];
+        // the validation samples lie far from the training samples
+        let validation_inputs = vec![vec![-3.0, 2.0], vec![4.0, -5.0]];
+        let validation_targets = vec![vec![0.5], vec![0.25]];
+        let mut validation_losses = Vec::new();
+        let mut on_epoch_end =
+            |metrics: EpochMetrics| validation_losses.push(metrics.validation_loss);
+        nn.train_with_validation(
+            &train_inputs,
+            &train_targets,
+            &validation_inputs,
+            &validation_targets,
+            0.01,
+            1,
+            0.1,
+            false,
+            None,
+            1.0,
+            Some(&mut on_epoch_end),
+        );
+
+        let expected = validation_inputs
+            .iter()
+            .zip(&validation_targets)
+            .map(|(input, target)| {
+                LossFunction::MeanSquaredError.value(&nn.predict(input.clone()), target)
+            })
+            .sum::<f64>()
+            / 2.0;
+        assert_eq!(validation_losses.len(), 1);
+        assert!((validation_losses[0] - expected).abs() < 1e-12);
+        let history = nn.training_history();
+        assert!((history.epochs[0].validation_loss - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_silent_training_does_not_use_progress_bars() {
+        use std::sync::Arc;
+
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape {
+                layers: vec![LayerShape {
+                    layer_type: LayerType::Dense { input_size: 2, output_size: 1 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                }],
+            },
+            &Directory::Internal("test_model_silent_training".to_string()),
+            utils.clone(),
+        );
+        nn.set_training_options(TrainingOptions {
+            verbosity: Verbosity::Silent,
+            ..nn.training_options()
+        });
+
+        let inputs = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0], vec![0.0, 0.0]];
+        let targets = vec![vec![1.0], vec![0.0], vec![1.0], vec![0.0]];
+        let mut epochs = 0;
+        // only the utils and the handle fetched here may reference the shared progress instance
+        let mut on_epoch_end = |_: EpochMetrics| {
+            assert_eq!(Arc::strong_count(&utils.get_multi_progress()), 2);
+            epochs += 1;
+        };
+        nn.train(&inputs, &targets, 0.1, 3, 0.1, false, None, 0.5, 1.0, Some(&mut on_epoch_end));
+        assert_eq!(epochs, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Input has 2 values but the neural network expects 3 inputs")]
+    fn test_predict_rejects_wrong_input_size() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape {
+                layers: vec![LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 2 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                }],
+            },
+            &Directory::Internal("test_model_wrong_input_size".to_string()),
+            utils,
+        );
+        nn.predict(vec![1.0, 2.0]);
+    }
 
-        // Helper to generate unique workspace names for tests
-        static TEST_COUNTER: AtomicUsize = AtomicUsize::new(100); // Start at 100 to avoid conflicts
+    #[test]
+    fn test_predict_mc() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape {
+                layers: vec![
+                    LayerShape {
+                        layer_type: LayerType::Dense { input_size: 3, output_size: 8 },
+                        activation: ActivationData::new(ActivationType::ReLU),
+                    },
+                    LayerShape {
+                        layer_type: LayerType::Dense { input_size: 8, output_size: 2 },
+                        activation: ActivationData::new(ActivationType::Sigmoid),
+                    },
+                ],
+            },
+            &Directory::Internal("test_model_predict_mc".to_string()),
+            utils,
+        );
+        let input = vec![0.5, -1.0, 2.0];
+        let prediction = nn.predict(input.clone());
 
-        let counter = TEST_COUNTER.fetch_add(1, Ordering::SeqCst);
-        let workspace = format!("/tmp/test_workspace_unit_{counter}");
-        let utils =
-            WrappedUtils::new(Utils::new_with_test_mode(1_000_000_000, 4, workspace.clone()));
+        nn.set_dropout(Some(Dropout::new(0.0, 42)));
+        let (mean, variance) = nn.predict_mc(&input, 10);
+        assert!(variance.iter().all(|v| v.abs() < 1e-12));
+        for (m, p) in mean.iter().zip(&prediction) {
+            assert!((m - p).abs() < 1e-12);
+        }
+
+        nn.set_dropout(Some(Dropout::new(0.5, 42)));
+        let (mean, variance) = nn.predict_mc(&input, 50);
+        assert!(variance.iter().any(|v| *v > 0.0));
+        // the random number generator advances between the calls
+        let (next_mean, _) = nn.predict_mc(&input, 50);
+        assert_ne!(mean, next_mean);
+        assert_eq!(nn.predict(input.clone()), prediction);
+    }
 
+    #[test]
+    fn test_training_applies_dropout() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
         let mut nn = TrainableClassicNeuralNetwork::new(
             NeuralNetworkShape {
                 layers: vec![
                     LayerShape {
-                        layer_type: LayerType::Dense { input_size: 3, output_size: 3 },
+                        layer_type: LayerType::Dense { input_size: 3, output_size: 8 },
+                        activation: ActivationData::new(ActivationType::Tanh),
+                    },
+                    LayerShape {
+                        layer_type: LayerType::Dense { input_size: 8, output_size: 2 },
+                        activation: ActivationData::new(ActivationType::Sigmoid),
+                    },
+                ],
+            },
+            &Directory::Internal("test_model_training_dropout".to_string()),
+            utils,
+        );
+        nn.set_dropout(Some(Dropout::new(0.5, 42)));
+        let input = vec![0.5, -1.0, 2.0];
+        let target = vec![0.0, 1.0];
+
+        // the validation samples are not dropped
+        let (output, _, _) =
+            nn.forward_loss(&input, &target, &LossFunction::MeanSquaredError, false);
+        assert!(nn.dropout_masks.is_empty());
+        assert_eq!(nn.predict(input.clone()), output);
+
+        // training drops the same hidden units in the forward and the backward pass
+        let (_, _, gradient) =
+            nn.forward_loss(&input, &target, &LossFunction::MeanSquaredError, true);
+        let mask = nn.dropout_masks[0].clone();
+        assert_eq!(mask.len(), 8);
+        assert!(mask.contains(&0.0));
+        nn.backward_loss(gradient);
+        let gradients = nn.layers[0].gradients();
+        for (unit, factor) in mask.iter().enumerate() {
+            let row_is_zero = (0..3).all(|j| *gradients.weights.get_unchecked(unit, j) == 0.0);
+            assert_eq!(row_is_zero, *factor == 0.0);
+        }
+
+        // the next training pass draws a new mask
+        nn.forward_loss(&input, &target, &LossFunction::MeanSquaredError, true);
+        assert_ne!(nn.dropout_masks[0], mask);
+    }
+
+    #[test]
+    fn test_weights_flat_roundtrip() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape {
+            layers: vec![
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 4 },
+                    activation: ActivationData::new(ActivationType::ReLU),
+                },
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 4, output_size: 2 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                },
+            ],
+        };
+        let mut source = TrainableClassicNeuralNetwork::new(
+            shape.clone(),
+            &Directory::Internal("test_model_weights_flat_source".to_string()),
+            utils.clone(),
+        );
+        let mut target = TrainableClassicNeuralNetwork::new(
+            shape,
+            &Directory::Internal("test_model_weights_flat_target".to_string()),
+            utils,
+        );
+
+        let flat = source.weights_flat();
+        assert_eq!(flat.len(), 3 * 4 + 4 + 4 * 2 + 2);
+        assert!(target.load_weights_flat(&flat[1..]).is_err());
+        target.load_weights_flat(&flat).unwrap();
+        assert_eq!(target.weights_flat(), flat);
+
+        let input = vec![0.5, -1.0, 2.0];
+        assert_eq!(source.predict(input.clone()), target.predict(input));
+    }
+
+    #[test]
+    fn test_from_shape_and_weights() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape {
+            layers: vec![
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 4 },
+                    activation: ActivationData::new(ActivationType::ReLU),
+                },
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 4, output_size: 2 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                },
+            ],
+        };
+        let mut source = TrainableClassicNeuralNetwork::new(
+            shape.clone(),
+            &Directory::Internal("test_model_from_weights_source".to_string()),
+            utils.clone(),
+        );
+        let input = vec![0.5, -1.0, 2.0];
+        // predicting allocates the layers so their weights are written on save
+        let prediction = source.predict(input.clone());
+        source.save("test_model_from_weights_saved".to_string()).unwrap();
+        let mut reloaded = TrainableClassicNeuralNetwork::from_disk(
+            "test_model_from_weights_saved".to_string(),
+            utils.clone(),
+        )
+        .unwrap();
+
+        let weights = (0..source.layers.len())
+            .map(|i| {
+                let mut layer = source.allocated_layer(i);
+                let layer_weights = safe_lock(&layer.get_weights().mat).clone();
+                let biases = layer.get_biases();
+                layer.free_from_use();
+                (layer_weights, biases)
+            })
+            .collect::<Vec<_>>();
+        let mut wrong_weights = weights.clone();
+        wrong_weights[1].1.pop();
+        assert!(TrainableClassicNeuralNetwork::from_shape_and_weights(
+            shape.clone(),
+            wrong_weights,
+            &Directory::Internal("test_model_from_weights_wrong".to_string()),
+            utils.clone(),
+        )
+        .is_err());
+
+        let mut built = TrainableClassicNeuralNetwork::from_shape_and_weights(
+            shape,
+            weights,
+            &Directory::Internal("test_model_from_weights_built".to_string()),
+            utils,
+        )
+        .unwrap();
+        assert_eq!(reloaded.predict(input.clone()), prediction);
+        assert_eq!(built.predict(input), prediction);
+
+        drop(reloaded);
+        drop(source);
+        std::fs::remove_dir_all("test_model_from_weights_saved").unwrap();
+    }
+
+    #[test]
+    fn test_prelu_layer_is_trained_saved_and_loaded() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape {
+            layers: vec![
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 2, output_size: 3 },
+                    activation: ActivationData::new(ActivationType::Identity),
+                },
+                LayerShape {
+                    layer_type: LayerType::PReLU { size: 3 },
+                    activation: ActivationData::new(ActivationType::Identity),
+                },
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 1 },
+                    activation: ActivationData::new(ActivationType::Identity),
+                },
+            ],
+        };
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            shape.clone(),
+            &Directory::Internal("test_model_prelu_network_source".to_string()),
+            utils.clone(),
+        );
+        let inputs = vec![vec![1.0, -1.0], vec![-1.0, 1.0], vec![-1.0, -1.0], vec![0.5, 0.5]];
+        let targets = vec![vec![1.0], vec![-0.5], vec![-1.0], vec![0.5]];
+        nn.train(&inputs, &targets, 0.05, 20, 0.1, false, None, 1.0, 1.0, None);
+        let slope = nn.layers[1].get_weights().get_unchecked(0, 0);
+        assert!((slope - DEFAULT_PRELU_SLOPE).abs() > f64::EPSILON);
+
+        let model_directory = "test_model_prelu_network".to_string();
+        nn.save(model_directory.clone()).unwrap();
+        let prediction = nn.predict(inputs[0].clone());
+        drop(nn);
+
+        let mut loaded =
+            TrainableClassicNeuralNetwork::try_from_disk(model_directory.clone(), utils.clone())
+                .unwrap();
+        assert_eq!(loaded.shape(), shape);
+        assert_eq!(loaded.predict(inputs[0].clone()), prediction);
+        assert!((loaded.layers[1].get_weights().get_unchecked(0, 0) - slope).abs() < f64::EPSILON);
+        drop(loaded);
+        let mut classic =
+            ClassicNeuralNetwork::try_from_disk(model_directory.clone(), utils).unwrap();
+        assert_eq!(classic.predict(inputs[0].clone()), prediction);
+
+        drop(classic);
+        std::fs::remove_dir_all(model_directory).unwrap();
+    }
+
+    #[test]
+    fn test_try_from_disk_reports_truncated_layer_file() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape {
+            layers: vec![
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 4 },
+                    activation: ActivationData::new(ActivationType::ReLU),
+                },
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 4, output_size: 2 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                },
+            ],
+        };
+        let model_directory = "test_model_truncated_layer".to_string();
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            shape,
+            &Directory::Internal("test_model_truncated_layer_source".to_string()),
+            utils.clone(),
+        );
+        // predicting allocates the layers so their weights are written on save
+        let _ = nn.predict(vec![0.5, -1.0, 2.0]);
+        nn.save(model_directory.clone()).unwrap();
+        drop(nn);
+        assert!(TrainableClassicNeuralNetwork::try_from_disk(
+            model_directory.clone(),
+            utils.clone()
+        )
+        .is_ok());
+
+        // cut the layer file after its first weight row
+        let layer_path = format!("{model_directory}/layers/layer_1.txt");
+        let content = std::fs::read_to_string(&layer_path).unwrap();
+        let truncated = content.lines().take(3).collect::<Vec<_>>().join("\n");
+        std::fs::write(&layer_path, truncated).unwrap();
+
+        let error =
+            TrainableClassicNeuralNetwork::try_from_disk(model_directory.clone(), utils.clone())
+                .unwrap_err();
+        assert_eq!(error.path(), layer_path);
+        assert!(error.to_string().contains("missing weight row 1 of 2"));
+        assert!(ClassicNeuralNetwork::from_disk(model_directory.clone(), utils.clone()).is_none());
+
+        let error = ClassicNeuralNetwork::try_from_disk("test_model_not_there".to_string(), utils)
+            .unwrap_err();
+        assert_eq!(error.path(), "test_model_not_there/shape.yaml");
+
+        std::fs::remove_dir_all(model_directory).unwrap();
+    }
+
+    #[test]
+    fn test_from_disk_defaults_a_missing_softmax_temperature() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape {
+            layers: vec![LayerShape {
+                layer_type: LayerType::Dense { input_size: 3, output_size: 3 },
+                activation: ActivationData::new(ActivationType::Softmax),
+            }],
+        };
+        let model_directory = "test_model_softmax_without_temperature".to_string();
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            shape,
+            &Directory::Internal("test_model_softmax_without_temperature_source".to_string()),
+            utils.clone(),
+        );
+        let (weights, biases) = nn.layer_parameters(0).unwrap();
+        nn.save(model_directory.clone()).unwrap();
+        drop(nn);
+
+        // remove the temperature field like in an older or hand-edited shape file
+        let shape_path = format!("{model_directory}/shape.yaml");
+        let yaml = std::fs::read_to_string(&shape_path).unwrap();
+        let yaml = yaml
+            .lines()
+            .filter(|line| !line.contains("temperature"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        std::fs::write(&shape_path, yaml).unwrap();
+
+        let mut loaded =
+            ClassicNeuralNetwork::try_from_disk(model_directory.clone(), utils).unwrap();
+        assert_eq!(loaded.shape().layers[0].activation.temperature(), None);
+        let input = [0.5, -1.0, 2.0];
+        let logits = (0..3)
+            .map(|i| {
+                (0..3).map(|j| weights.get_unchecked(i, j) * input[j]).sum::<f64>() + biases[i]
+            })
+            .collect::<Vec<_>>();
+        let expected = Softmax::new(1.0).forward(&logits);
+        for (output, expected) in loaded.predict(input.to_vec()).into_iter().zip(expected) {
+            assert!((output - expected).abs() < 1e-12);
+        }
+
+        drop(loaded);
+        std::fs::remove_dir_all(model_directory).unwrap();
+    }
+
+    #[test]
+    fn test_prune_neurons() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape {
+            layers: vec![
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 2, output_size: 3 },
+                    activation: ActivationData::new(ActivationType::ReLU),
+                },
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 2 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                },
+            ],
+        };
+        // the second neuron of the first layer does not contribute to the output
+        let weights = vec![
+            (
+                Matrix::from_rows(vec![vec![0.5, -0.3], vec![0.8, 0.1], vec![-0.2, 0.7]]).unwrap(),
+                vec![0.1, 0.2, 0.3],
+            ),
+            (
+                Matrix::from_rows(vec![vec![0.4, 0.0, -0.6], vec![-0.9, 0.0, 0.2]]).unwrap(),
+                vec![0.05, -0.05],
+            ),
+        ];
+        let mut nn = TrainableClassicNeuralNetwork::from_shape_and_weights(
+            shape,
+            weights,
+            &Directory::Internal("test_model_prune_neurons".to_string()),
+            utils,
+        )
+        .unwrap();
+        let input = vec![1.0, 2.0];
+        let prediction = nn.predict(input.clone());
+
+        assert!(nn.prune_neurons(0, &[2, 0]).is_err());
+        assert!(nn.prune_neurons(0, &[0, 3]).is_err());
+        nn.prune_neurons(0, &[0, 2]).unwrap();
+
+        assert_eq!(nn.shape().layers[0].output_size(), 2);
+        assert_eq!(nn.shape().layers[1].input_size(), 2);
+        assert!(nn.shape().is_valid());
+        for (p, q) in nn.predict(input).iter().zip(&prediction) {
+            assert!((p - q).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_widen_layer_preserves_predictions() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape {
+            layers: vec![
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 2, output_size: 3 },
+                    activation: ActivationData::new(ActivationType::Tanh),
+                },
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 2 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                },
+            ],
+        };
+        let weights = vec![
+            (
+                Matrix::from_rows(vec![vec![0.5, -0.3], vec![0.8, 0.1], vec![-0.2, 0.7]]).unwrap(),
+                vec![0.1, 0.2, 0.3],
+            ),
+            (
+                Matrix::from_rows(vec![vec![0.4, 0.3, -0.6], vec![-0.9, 0.5, 0.2]]).unwrap(),
+                vec![0.05, -0.05],
+            ),
+        ];
+        let mut nn = TrainableClassicNeuralNetwork::from_shape_and_weights(
+            shape,
+            weights,
+            &Directory::Internal("test_model_widen_layer".to_string()),
+            utils,
+        )
+        .unwrap();
+        let inputs = [vec![1.0, 2.0], vec![-0.5, 0.3], vec![0.0, -1.5]];
+        let predictions = inputs.iter().map(|input| nn.predict(input.clone())).collect::<Vec<_>>();
+
+        assert!(nn.widen_layer(0, 3).is_err());
+        assert!(nn.widen_layer(1, 4).is_err());
+        // more than twice the width duplicates some units more than once
+        nn.widen_layer(0, 7).unwrap();
+
+        assert_eq!(nn.shape().layers[0].output_size(), 7);
+        assert_eq!(nn.shape().layers[1].input_size(), 7);
+        assert!(nn.shape().is_valid());
+        for (input, prediction) in inputs.iter().zip(&predictions) {
+            for (p, q) in nn.predict(input.clone()).iter().zip(prediction) {
+                assert!((p - q).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_add_identity_layer_preserves_predictions() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layer = |input_size, output_size, activation| LayerShape {
+            layer_type: LayerType::Dense { input_size, output_size },
+            activation: ActivationData::new(activation),
+        };
+        let shape = NeuralNetworkShape {
+            layers: vec![
+                layer(2, 4, ActivationType::ReLU),
+                layer(4, 3, ActivationType::Tanh),
+                layer(3, 1, ActivationType::Sigmoid),
+            ],
+        };
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            shape,
+            &Directory::Internal("test_model_add_identity_layer".to_string()),
+            utils,
+        );
+        nn.set_training_options(TrainingOptions {
+            verbosity: Verbosity::Silent,
+            ..nn.training_options()
+        });
+        let inputs = vec![vec![1.0, 2.0], vec![-0.5, 0.3], vec![0.0, -1.5], vec![0.7, 0.7]];
+        let targets = vec![vec![0.9], vec![0.1], vec![0.8], vec![0.2]];
+        let predictions = inputs.iter().map(|input| nn.predict(input.clone())).collect::<Vec<_>>();
+
+        assert!(nn.add_identity_layer(3).is_err());
+        nn.add_identity_layer(0).unwrap();
+        nn.add_identity_layer(2).unwrap();
+
+        let shape = nn.shape();
+        assert_eq!(shape.layers.len(), 5);
+        assert!(shape.is_valid());
+        assert_eq!(shape.layers[1].activation.activation_type(), ActivationType::ReLU);
+        assert_eq!(shape.layers[3].activation.activation_type(), ActivationType::Identity);
+        for (input, prediction) in inputs.iter().zip(&predictions) {
+            for (p, q) in nn.predict(input.clone()).iter().zip(prediction) {
+                assert!((p - q).abs() < 1e-12);
+            }
+        }
+
+        nn.train_batch(&inputs, &targets, 0.05, 20, 0.1, 4, None);
+        let changed = inputs
+            .iter()
+            .zip(&predictions)
+            .any(|(input, prediction)| (nn.predict(input.clone())[0] - prediction[0]).abs() > 1e-6);
+        assert!(changed);
+    }
+
+    #[test]
+    fn test_push_layer_appends_compatible_layers() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layer = |input_size, output_size| LayerShape {
+            layer_type: LayerType::Dense { input_size, output_size },
+            activation: ActivationData::new(ActivationType::Sigmoid),
+        };
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape { layers: vec![layer(3, 2)] },
+            &Directory::Internal("test_model_push_layer".to_string()),
+            utils,
+        );
+        nn.set_training_options(TrainingOptions {
+            verbosity: Verbosity::Silent,
+            ..nn.training_options()
+        });
+
+        nn.push_layer(layer(2, 3)).unwrap();
+        assert_eq!(nn.shape().layers.len(), 2);
+        assert_eq!(nn.output_size(), 3);
+        assert_eq!(nn.predict(vec![0.1, 0.2, 0.3]).len(), 3);
+
+        assert!(nn.push_layer(layer(2, 1)).is_err());
+        assert_eq!(nn.shape().layers.len(), 2);
+        assert_eq!(nn.output_size(), 3);
+    }
+
+    #[test]
+    fn test_num_parameters_follows_pruning() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let layer = |input_size, output_size| LayerShape {
+            layer_type: LayerType::Dense { input_size, output_size },
+            activation: ActivationData::new(ActivationType::ReLU),
+        };
+        let shape = NeuralNetworkShape { layers: vec![layer(4, 5), layer(5, 3), layer(3, 2)] };
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            shape.clone(),
+            &Directory::Internal("test_model_num_parameters".to_string()),
+            utils,
+        );
+        assert_eq!(nn.num_parameters(), (4 + 1) * 5 + (5 + 1) * 3 + (3 + 1) * 2);
+        assert_eq!(nn.num_parameters(), shape.num_parameters());
+        assert_eq!(nn.num_parameters(), nn.weights_flat().len());
+
+        nn.prune_neurons(0, &[0, 2, 4]).unwrap();
+        assert_eq!(nn.num_parameters(), (4 + 1) * 3 + (3 + 1) * 3 + (3 + 1) * 2);
+        assert_eq!(nn.num_parameters(), nn.shape().num_parameters());
+    }
+
+    #[test]
+    fn test_to_inference() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape {
+                layers: vec![
+                    LayerShape {
+                        layer_type: LayerType::Dense { input_size: 3, output_size: 4 },
+                        activation: ActivationData::new(ActivationType::ReLU),
+                    },
+                    LayerShape {
+                        layer_type: LayerType::Dense { input_size: 4, output_size: 2 },
                         activation: ActivationData::new(ActivationType::Sigmoid),
                     },
+                ],
+            },
+            &Directory::Internal("test_model_to_inference".to_string()),
+            utils,
+        );
+        let inputs = vec![vec![0.5, -1.0, 2.0], vec![1.0, 0.0, -0.5]];
+        let targets = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+        nn.train(&inputs, &targets, 0.1, 2, 0.1, true, None, 0.5, 1.0, None);
+
+        let mut inference = nn.to_inference();
+        assert_eq!(inference.shape(), nn.shape());
+        for input in &inputs {
+            assert_eq!(inference.predict(input.clone()), nn.forward(input));
+        }
+    }
+
+    #[test]
+    fn test_get_and_set_weight() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape {
+                layers: vec![LayerShape {
+                    layer_type: LayerType::Dense { input_size: 2, output_size: 1 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                }],
+            },
+            &Directory::Internal("test_model_single_weight".to_string()),
+            utils,
+        );
+        let input = vec![1.0, 0.0];
+        let output = nn.predict(input.clone())[0];
+
+        let weight = nn.get_weight(0, 0, 0).unwrap();
+        nn.set_weight(0, 0, 0, weight + 1.0).unwrap();
+        assert!((nn.get_weight(0, 0, 0).unwrap() - (weight + 1.0)).abs() < 1e-12);
+        let increased = nn.predict(input.clone())[0];
+        assert!(increased > output);
+
+        // the second input is zero, so its weight does not matter
+        nn.set_weight(0, 0, 1, 5.0).unwrap();
+        assert!((nn.predict(input.clone())[0] - increased).abs() < 1e-12);
+
+        let bias = nn.get_bias(0, 0).unwrap();
+        nn.set_bias(0, 0, bias - 2.0).unwrap();
+        assert!(nn.predict(input)[0] < increased);
+
+        assert!(nn.get_weight(1, 0, 0).is_err());
+        assert!(nn.get_weight(0, 1, 0).is_err());
+        assert!(nn.set_weight(0, 0, 2, 1.0).is_err());
+        assert!(nn.get_bias(0, 1).is_err());
+        assert!(nn.set_bias(1, 0, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_spectral_norms() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape {
+                layers: vec![
                     LayerShape {
-                        layer_type: LayerType::Dense { input_size: 3, output_size: 3 },
+                        layer_type: LayerType::Dense { input_size: 2, output_size: 2 },
                         activation: ActivationData::new(ActivationType::ReLU),
                     },
+                    LayerShape {
+                        layer_type: LayerType::Dense { input_size: 2, output_size: 1 },
+                        activation: ActivationData::new(ActivationType::Sigmoid),
+                    },
                 ],
             },
-            &Directory::Internal("internal_model".to_string()),
+            &Directory::Internal("test_model_spectral_norms".to_string()),
             utils,
         );
+        // diag(-4, 1) and the row (3, 4) without biases
+        nn.load_weights_flat(&[-4.0, 0.0, 0.0, 1.0, 0.0, 0.0, 3.0, 4.0, 0.0]).unwrap();
+        let norms = nn.spectral_norms(100).unwrap();
+        assert_eq!(norms.len(), 2);
+        assert!((norms[0] - 4.0).abs() < 1e-9);
+        assert!((norms[1] - 5.0).abs() < 1e-9);
 
-        let input = vec![1.0, 1.0, 1.0];
-        // put input 200 times in inputs
-        let inputs = vec![input; 200];
-        let target = vec![0.0, 0.0, 0.0];
-        let targets = vec![target; 200];
+        nn.set_weight(0, 1, 1, 6.0).unwrap();
+        assert!((nn.spectral_norms(100).unwrap()[0] - 6.0).abs() < 1e-6);
+    }
 
-        nn.train(&inputs, &targets, 0.01, 5, 0.1, true, 0.7, 1.0);
+    /// Returns the weight matrix the single identity layer of the network effectively applies,
+    /// read off the outputs for the unit vectors.
+    fn effective_weights(
+        nn: &mut TrainableClassicNeuralNetwork,
+        size: usize,
+    ) -> WrappedMatrix<f64> {
+        let offset = nn.predict(vec![0.0; size]);
+        let weights = WrappedMatrix::new(size, size);
+        for col in 0..size {
+            let mut input = vec![0.0; size];
+            input[col] = 1.0;
+            for (row, (output, offset)) in nn.predict(input).iter().zip(&offset).enumerate() {
+                weights.set_mut_unchecked(row, col, output - offset);
+            }
+        }
+        weights
+    }
 
-        let prediction = nn.predict(inputs[0].clone());
-        // print targets[0]
-        println!("{:?}", targets[0]);
-        // print prediction
-        println!("{prediction:?}");
-        assert_eq!(prediction.len(), 3);
-        // assert that the prediction is close to the target
-        for (p, t) in prediction.iter().zip(&targets[0]) {
-            assert!((p - t).abs() < 1e-4);
+    #[test]
+    fn test_spectral_normalization_keeps_norm_near_one() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut nn = TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape {
+                layers: vec![LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 3 },
+                    activation: ActivationData::new(ActivationType::Identity),
+                }],
+            },
+            &Directory::Internal("test_model_spectral_normalization".to_string()),
+            utils,
+        );
+        nn.set_training_options(TrainingOptions {
+            verbosity: Verbosity::Silent,
+            ..nn.training_options()
+        });
+        nn.load_weights_flat(&[4.0, 1.0, 0.0, -2.0, 3.0, 1.0, 0.5, 0.0, -6.0, 0.0, 0.0, 0.0])
+            .unwrap();
+        assert!(nn.spectral_norms(100).unwrap()[0] > 6.0);
+        nn.set_spectral_norm(true);
+
+        let inputs = vec![vec![1.0, 0.0, 0.5], vec![0.0, -1.0, 1.0], vec![0.5, 0.5, -1.0]];
+        let targets = vec![vec![3.0, -1.0, 0.0], vec![0.0, 2.0, -3.0], vec![1.0, 1.0, 4.0]];
+        for _ in 0..5 {
+            nn.train_batch(&inputs, &targets, 0.01, 10, 0.1, 1, None);
+            let norm = spectral_norm(&effective_weights(&mut nn, 3), 100);
+            assert!((norm - 1.0).abs() < 1e-3, "effective spectral norm {norm}");
         }
+        // the raw weights are not normalized
+        assert!(nn.spectral_norms(100).unwrap()[0] > 2.0);
 
-        // Clean up workspace
-        if !workspace.is_empty() && Path::new(&workspace).exists() {
-            let _ = std::fs::remove_dir_all(&workspace);
+        // disabling folds the normalization into the weights
+        let outputs = inputs.iter().map(|input| nn.predict(input.clone())).collect::<Vec<_>>();
+        nn.set_spectral_norm(false);
+        assert!((nn.spectral_norms(100).unwrap()[0] - 1.0).abs() < 1e-3);
+        for (input, output) in inputs.iter().zip(outputs) {
+            for (folded, normalized) in nn.predict(input.clone()).iter().zip(output) {
+                assert!((folded - normalized).abs() < 1e-6);
+            }
         }
     }
+
+    #[test]
+    fn test_checkpoint_best_restores_best_epoch() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let checkpoint_directory = "test_model_checkpoint_best_saved".to_string();
+        let mut nn = TrainableClassicNeuralNetwork::from_shape_and_weights(
+            NeuralNetworkShape {
+                layers: vec![LayerShape {
+                    layer_type: LayerType::Dense { input_size: 1, output_size: 1 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                }],
+            },
+            vec![(Matrix::from_rows(vec![vec![-3.0]]).unwrap(), vec![0.0])],
+            &Directory::Internal("test_model_checkpoint_best".to_string()),
+            utils.clone(),
+        )
+        .unwrap();
+        nn.set_training_options(TrainingOptions {
+            split_mode: SplitMode::Positional,
+            verbosity: Verbosity::Silent,
+            checkpoint_best: Some(checkpoint_directory.clone()),
+            restore_best_checkpoint: true,
+            ..nn.training_options()
+        });
+
+        // training pulls the output towards 1.0 and passes the validation target 0.5 on the way
+        let inputs = vec![vec![1.0]; 4];
+        let targets = vec![vec![1.0], vec![1.0], vec![0.5], vec![0.5]];
+        let mut validation_losses = Vec::new();
+        let mut on_epoch_end =
+            |epoch_metrics: EpochMetrics| validation_losses.push(epoch_metrics.validation_loss);
+        nn.train(&inputs, &targets, 0.001, 30, 0.1, false, None, 0.5, 1.0, Some(&mut on_epoch_end));
+
+        let (best_epoch, best_loss) = validation_losses
+            .iter()
+            .copied()
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+        assert!(best_epoch > 0 && best_epoch < validation_losses.len() - 1);
+        assert!(validation_losses.last().unwrap() > &best_loss);
+
+        let checkpoint =
+            TrainableClassicNeuralNetwork::from_disk(checkpoint_directory.clone(), utils).unwrap();
+        assert_eq!(nn.weights_flat(), checkpoint.weights_flat());
+        let output = nn.predict(vec![1.0])[0];
+        assert!((output - 0.5).mul_add(output - 0.5, -best_loss).abs() < 1e-12);
+
+        drop(checkpoint);
+        std::fs::remove_dir_all(checkpoint_directory).unwrap();
+    }
+
+    #[test]
+    fn test_checkpoint_best_unwritable_keeps_training() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        // a directory cannot be created below a regular file
+        let blocking_file = "test_model_checkpoint_blocking_file".to_string();
+        std::fs::write(&blocking_file, "").unwrap();
+        let checkpoint_directory = format!("{blocking_file}/checkpoint");
+        let mut nn = TrainableClassicNeuralNetwork::from_shape_and_weights(
+            NeuralNetworkShape {
+                layers: vec![LayerShape {
+                    layer_type: LayerType::Dense { input_size: 1, output_size: 1 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                }],
+            },
+            vec![(Matrix::from_rows(vec![vec![-3.0]]).unwrap(), vec![0.0])],
+            &Directory::Internal("test_model_checkpoint_unwritable".to_string()),
+            utils,
+        )
+        .unwrap();
+        nn.set_training_options(TrainingOptions {
+            split_mode: SplitMode::Positional,
+            verbosity: Verbosity::Silent,
+            checkpoint_best: Some(checkpoint_directory.clone()),
+            restore_best_checkpoint: true,
+            ..nn.training_options()
+        });
+
+        let inputs = vec![vec![1.0]; 4];
+        let targets = vec![vec![1.0], vec![1.0], vec![0.5], vec![0.5]];
+        let mut epochs = 0;
+        let mut on_epoch_end = |_: EpochMetrics| epochs += 1;
+        nn.train(&inputs, &targets, 0.001, 30, 0.1, false, None, 0.5, 1.0, Some(&mut on_epoch_end));
+        assert_eq!(epochs, 30);
+
+        // the failure is reported once and the last weights are kept
+        let warnings = nn.training_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(
+            &warnings[0],
+            TrainingWarning::CheckpointNotSaved { directory, .. } if *directory == checkpoint_directory
+        ));
+        assert!(nn.get_weight(0, 0, 0).unwrap() > -3.0);
+
+        std::fs::remove_file(blocking_file).unwrap();
+    }
 }
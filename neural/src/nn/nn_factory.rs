@@ -5,9 +5,10 @@ use crate::utilities::util::WrappedUtils;
 use super::{
     directory::Directory,
     either_nn::{EitherNeuralNetwork, TrainableEitherNeuralNetwork},
+    in_memory::InMemoryNeuralNetwork,
     neuralnet::{ClassicNeuralNetwork, TrainableClassicNeuralNetwork},
     nn_trait::{WrappedNeuralNetwork, WrappedTrainableNeuralNetwork},
-    retry_nn::{RetryNeuralNetwork, TrainableRetryNeuralNetwork},
+    retry_nn::{RetryNeuralNetwork, TrainableRetryNeuralNetwork, DEFAULT_MIN_TRAINING_SAMPLES},
     shape::NeuralNetworkShape,
 };
 
@@ -17,6 +18,8 @@ pub struct NeuralNetworkCreationArguments {
     pre_shape: Option<NeuralNetworkShape>,
     model_directory: String,
     utils: WrappedUtils,
+    min_training_samples: usize,
+    insufficient_samples_accuracy: f64,
 }
 
 impl NeuralNetworkCreationArguments {
@@ -28,7 +31,28 @@ impl NeuralNetworkCreationArguments {
         model_directory: String,
         utils: WrappedUtils,
     ) -> Self {
-        Self { shape, levels, pre_shape, model_directory, utils }
+        Self {
+            shape,
+            levels,
+            pre_shape,
+            model_directory,
+            utils,
+            min_training_samples: DEFAULT_MIN_TRAINING_SAMPLES,
+            insufficient_samples_accuracy: 0.0,
+        }
+    }
+
+    /// Sets the number of samples below which a created retry network does not train and
+    /// returns `insufficient_samples_accuracy` instead.
+    #[must_use]
+    pub const fn with_min_training_samples(
+        mut self,
+        min_training_samples: usize,
+        insufficient_samples_accuracy: f64,
+    ) -> Self {
+        self.min_training_samples = min_training_samples;
+        self.insufficient_samples_accuracy = insufficient_samples_accuracy;
+        self
     }
 }
 
@@ -40,6 +64,8 @@ pub fn new_neural_network(
         Some(levels) => WrappedNeuralNetwork::new(Box::new(RetryNeuralNetwork::new(
             neural_network_creation_arguments.shape,
             levels,
+            None,
+            1,
             neural_network_creation_arguments.model_directory,
             neural_network_creation_arguments.utils,
         ))),
@@ -56,14 +82,20 @@ pub fn new_trainable_neural_network(
     neural_network_creation_arguments: NeuralNetworkCreationArguments
 ) -> WrappedTrainableNeuralNetwork {
     match (neural_network_creation_arguments.pre_shape, neural_network_creation_arguments.levels) {
-        (None, Some(levels)) => {
-            WrappedTrainableNeuralNetwork::new(Box::new(TrainableRetryNeuralNetwork::new(
+        (None, Some(levels)) => WrappedTrainableNeuralNetwork::new(Box::new(
+            TrainableRetryNeuralNetwork::new(
                 neural_network_creation_arguments.shape,
                 levels,
+                None,
+                1,
                 neural_network_creation_arguments.model_directory,
                 neural_network_creation_arguments.utils,
-            )))
-        },
+            )
+            .with_min_training_samples(
+                neural_network_creation_arguments.min_training_samples,
+                neural_network_creation_arguments.insufficient_samples_accuracy,
+            ),
+        )),
         (Some(pre_shape), Some(levels)) => {
             WrappedTrainableNeuralNetwork::new(Box::new(TrainableEitherNeuralNetwork::new(
                 neural_network_creation_arguments.shape,
@@ -99,7 +131,8 @@ pub fn neural_network_from_disk(
         return EitherNeuralNetwork::from_disk(model_directory, utils);
     }
     WrappedNeuralNetwork::new(Box::new(
-        ClassicNeuralNetwork::from_disk(model_directory, utils).unwrap(),
+        ClassicNeuralNetwork::try_from_disk(model_directory, utils)
+            .unwrap_or_else(|e| panic!("{e}")),
     ))
 }
 
@@ -117,34 +150,215 @@ pub fn trainable_neural_network_from_disk(
         return TrainableRetryNeuralNetwork::from_disk(model_directory, utils);
     }
     WrappedTrainableNeuralNetwork::new(Box::new(
-        TrainableClassicNeuralNetwork::from_disk(model_directory, utils).unwrap(),
+        TrainableClassicNeuralNetwork::try_from_disk(model_directory, utils)
+            .unwrap_or_else(|e| panic!("{e}")),
     ))
 }
 
+/// Builds an inference-only neural network from weights and biases in the order produced by
+/// `weights_flat`, entirely in memory without creating a model directory.
+///
+/// # Errors
+/// Returns an error if the shape has a layer which is not dense or if the number of weights
+/// does not match the shape.
+pub fn neural_network_from_weights(
+    shape: NeuralNetworkShape,
+    weights: &[f64],
+    utils: WrappedUtils,
+) -> Result<WrappedNeuralNetwork, Box<dyn std::error::Error>> {
+    Ok(WrappedNeuralNetwork::new(Box::new(InMemoryNeuralNetwork::new(shape, weights, utils)?)))
+}
+
+/// The default pattern of internal model directory names, e.g. `model_1`.
+pub const DEFAULT_MODEL_DIRECTORY_PATTERN: &str = "{name}_{index}";
+
+/// How the internal model directories are named that networks create when they are
+/// constructed, duplicated or converted.
+///
+/// In the pattern `{name}` is replaced with the name of the original model directory and
+/// `{index}` with the first index starting at 1 whose directory does not exist yet. Names which
+/// already follow the pattern are reduced to their original name first, so copies of copies do
+/// not pile up indices. With a base path the directories are created under it, otherwise next
+/// to the original model directory. The directories are removed when their network is dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelDirectoryNaming {
+    base_path: Option<String>,
+    pattern: String,
+}
+
+impl Default for ModelDirectoryNaming {
+    fn default() -> Self {
+        Self { base_path: None, pattern: DEFAULT_MODEL_DIRECTORY_PATTERN.to_string() }
+    }
+}
+
+impl ModelDirectoryNaming {
+    /// Creates a new `ModelDirectoryNaming` with an optional base path and a pattern.
+    ///
+    /// # Panics
+    /// Panics if the pattern does not contain `{name}` followed by `{index}`.
+    #[must_use]
+    pub fn new(
+        base_path: Option<String>,
+        pattern: &str,
+    ) -> Self {
+        let valid = pattern
+            .split_once("{name}")
+            .is_some_and(|(_, after_name)| after_name.contains("{index}"));
+        assert!(valid, "The pattern has to contain {{name}} followed by {{index}}");
+        Self { base_path, pattern: pattern.to_string() }
+    }
+
+    #[must_use]
+    pub fn base_path(&self) -> Option<&str> {
+        self.base_path.as_deref()
+    }
+
+    #[must_use]
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Returns the original name of a directory name following the pattern,
+    /// or the directory name itself if it does not follow the pattern.
+    fn original_name<'a>(
+        &self,
+        directory_name: &'a str,
+    ) -> &'a str {
+        let Some((before_name, after_name)) = self.pattern.split_once("{name}") else {
+            return directory_name;
+        };
+        let Some((between, after_index)) = after_name.split_once("{index}") else {
+            return directory_name;
+        };
+        let Some(name_and_index) = directory_name
+            .strip_prefix(before_name)
+            .and_then(|name| name.strip_suffix(after_index))
+        else {
+            return directory_name;
+        };
+        let name_and_between = name_and_index.trim_end_matches(|c: char| c.is_ascii_digit());
+        if name_and_between.len() == name_and_index.len() {
+            return directory_name;
+        }
+        name_and_between.strip_suffix(between).unwrap_or(directory_name)
+    }
+
+    /// Creates and returns the first free internal directory for the given model directory.
+    ///
+    /// # Panics
+    /// Panics if creating the directory fails.
+    #[must_use]
+    pub fn first_free_model_directory(
+        &self,
+        model_directory: &Directory,
+    ) -> String {
+        let model_directory = model_directory.path();
+        let (parent, directory_name) =
+            model_directory.rfind('/').map_or(("", model_directory.as_str()), |pos| {
+                (&model_directory[..=pos], &model_directory[pos + 1..])
+            });
+        let parent =
+            self.base_path.as_ref().map_or_else(|| parent.to_string(), |base| format!("{base}/"));
+        if !parent.is_empty() {
+            fs::create_dir_all(&parent).unwrap();
+        }
+        let original_name = self.original_name(directory_name);
+        // create the directory to block the name, create_dir fails if another thread was faster
+        let mut i = 1;
+        loop {
+            let candidate = format!(
+                "{parent}{}",
+                self.pattern.replace("{name}", original_name).replace("{index}", &i.to_string())
+            );
+            match fs::create_dir(&candidate) {
+                Ok(()) => return candidate,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => i += 1,
+                Err(e) => panic!("Failed to create model directory {candidate}: {e}"),
+            }
+        }
+    }
+}
+
 /// Returns the first free model directory name by appending an integer suffix.
 ///
 /// # Panics
 /// Panics if creating the directory fails.
 #[must_use]
 pub fn get_first_free_model_directory(model_directory: &Directory) -> String {
-    let model_directory_orig = model_directory.path();
-    // truncate _{integer} from the end of the model_directory
-    let mut model_directory = model_directory_orig;
-    if let Some(pos) = model_directory.rfind('_') {
-        // check that the remainder is an integer
-        let remainder = &model_directory[pos + 1..];
-        if remainder.parse::<usize>().is_ok() {
-            model_directory = model_directory[..pos].to_string();
+    ModelDirectoryNaming::default().first_free_model_directory(model_directory)
+}
+
+/// Saves a model directory atomically with the given `write` function.
+///
+/// The current model is first copied into `{model_directory}_tmp`, where `write` stores the new
+/// model. The temporary directory then replaces `model_directory`, while the previous model is
+/// kept in `{model_directory}_backup` until the swap has completed. A crash at any point therefore
+/// leaves either the previous or the new model on disk. Leftovers of an interrupted save,
+/// including a stale backup directory, are cleaned up or restored before saving.
+///
+/// # Errors
+/// Returns an error if `write` fails or if any file system operation fails.
+pub fn save_atomically<F>(
+    model_directory: &str,
+    write: F,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: FnOnce(&str) -> Result<(), Box<dyn std::error::Error>>,
+{
+    let model_path = Path::new(model_directory);
+    let backup_directory = format!("{model_directory}_backup");
+    let backup_path = Path::new(&backup_directory);
+    let temp_directory = format!("{model_directory}_tmp");
+    let temp_path = Path::new(&temp_directory);
+
+    // recover from an interrupted save
+    if temp_path.exists() {
+        fs::remove_dir_all(temp_path)?;
+    }
+    if backup_path.exists() {
+        if model_path.exists() {
+            // the swap completed, only the cleanup of the backup is missing
+            fs::remove_dir_all(backup_path)?;
+        } else {
+            // the crash happened between moving the model away and moving the new one in
+            fs::rename(backup_path, model_path)?;
         }
     }
-    let mut i = 1;
-    while std::fs::metadata(format!("{model_directory}_{i}")).is_ok() {
-        i += 1;
+
+    if model_path.exists() {
+        copy_dir_recursive(model_path, temp_path)?;
+    } else {
+        fs::create_dir_all(temp_path)?;
+    }
+    write(&temp_directory)?;
+
+    if model_path.exists() {
+        fs::rename(model_path, backup_path)?;
     }
-    model_directory = format!("{model_directory}_{i}");
-    // create the directory to block the name
-    std::fs::create_dir_all(&model_directory).unwrap();
-    model_directory
+    fs::rename(temp_path, model_path)?;
+    if backup_path.exists() {
+        fs::remove_dir_all(backup_path)?;
+    }
+    Ok(())
+}
+
+/// Saves a model directory in place with the given `write` function.
+///
+/// This skips the temporary and backup directories of `save_atomically` and is therefore faster,
+/// but a crash while writing can leave a partially written model on disk.
+///
+/// # Errors
+/// Returns an error if `write` fails or if the directory cannot be created.
+pub fn save_in_place<F>(
+    model_directory: &str,
+    write: F,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: FnOnce(&str) -> Result<(), Box<dyn std::error::Error>>,
+{
+    fs::create_dir_all(model_directory)?;
+    write(model_directory)
 }
 
 /// Recursively copies a directory from `src` to `dst`.
@@ -173,3 +387,114 @@ pub fn copy_dir_recursive(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nn::shape::{ActivationData, ActivationType, LayerShape, LayerType};
+    use crate::utilities::util::Utils;
+
+    #[test]
+    fn test_model_directory_naming() {
+        let base_path = "test_model_naming_base";
+        let naming = ModelDirectoryNaming::new(Some(base_path.to_string()), "{name}-v{index}");
+        let utils = WrappedUtils::new(
+            Utils::new(1_000_000_000, 4).with_model_directory_naming(naming.clone()),
+        );
+        let shape = NeuralNetworkShape::new(vec![LayerShape {
+            layer_type: LayerType::Dense { input_size: 2, output_size: 1 },
+            activation: ActivationData::new(ActivationType::Sigmoid),
+        }]);
+        let arguments = NeuralNetworkCreationArguments::new(
+            shape,
+            None,
+            None,
+            "models/test_model_naming".to_string(),
+            utils,
+        );
+
+        let nn = new_trainable_neural_network(arguments);
+        let model_directory = nn.get_model_directory().path();
+        assert_eq!(model_directory, format!("{base_path}/test_model_naming-v1"));
+        assert!(Path::new(&model_directory).is_dir());
+        // the copy keeps the original name instead of appending another index
+        let copy = nn.duplicate_trainable();
+        let copy_directory = copy.get_model_directory().path();
+        assert_eq!(copy_directory, format!("{base_path}/test_model_naming-v2"));
+        assert!(!Path::new("models").exists());
+
+        drop(nn);
+        drop(copy);
+        assert!(!Path::new(&model_directory).exists());
+        assert!(!Path::new(&copy_directory).exists());
+        std::fs::remove_dir_all(base_path).unwrap();
+
+        assert_eq!(naming.original_name("model-v12"), "model");
+        assert_eq!(naming.original_name("model-v"), "model-v");
+        assert_eq!(ModelDirectoryNaming::default().original_name("model_3"), "model");
+    }
+
+    #[test]
+    fn test_retry_min_training_samples_from_arguments() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape::new(vec![LayerShape {
+            layer_type: LayerType::Dense { input_size: 3, output_size: 2 },
+            activation: ActivationData::new(ActivationType::Sigmoid),
+        }]);
+        let arguments = NeuralNetworkCreationArguments::new(
+            shape,
+            Some(0),
+            None,
+            "internal_model_factory_min_samples".to_string(),
+            utils,
+        )
+        .with_min_training_samples(10, -1.0);
+        let mut nn = new_trainable_neural_network(arguments);
+
+        let inputs = vec![vec![1.0, 0.0, 1.0]; 5];
+        let targets = vec![vec![1.0, 0.0]; 5];
+        let accuracy = nn.train(&inputs, &targets, 0.1, 2, 0.1, false, None, 0.7, 1.0, None);
+        assert!((accuracy + 1.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_neural_network_from_weights_predicts_like_from_disk() {
+        let model_directory = "test_model_from_weights";
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape::new(vec![
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 3, output_size: 4 },
+                activation: ActivationData::new(ActivationType::ReLU),
+            },
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 4, output_size: 2 },
+                activation: ActivationData::new(ActivationType::Sigmoid),
+            },
+        ]);
+        let mut trainable = new_trainable_neural_network(NeuralNetworkCreationArguments::new(
+            shape.clone(),
+            None,
+            None,
+            "test_model_from_weights_source".to_string(),
+            utils.clone(),
+        ));
+        // reading the weights allocates the layers, so that they are saved
+        let weights = trainable.weights_flat();
+        trainable.save(model_directory.to_string()).unwrap();
+        drop(trainable);
+
+        let mut from_disk = neural_network_from_disk(model_directory.to_string(), utils.clone());
+        let mut in_memory = neural_network_from_weights(shape, &weights, utils.clone()).unwrap();
+        for input in [vec![0.5, -1.0, 2.0], vec![1.0, 1.0, 1.0], vec![-3.0, 0.25, 0.0]] {
+            assert_eq!(in_memory.predict(input.clone()), from_disk.predict(input));
+        }
+        let wrong_shape = NeuralNetworkShape::new(vec![LayerShape {
+            layer_type: LayerType::Dense { input_size: 3, output_size: 4 },
+            activation: ActivationData::new(ActivationType::ReLU),
+        }]);
+        assert!(neural_network_from_weights(wrong_shape, &weights, utils).is_err());
+
+        drop(from_disk);
+        std::fs::remove_dir_all(model_directory).unwrap();
+    }
+}
@@ -1,6 +1,18 @@
-use crate::nn::shape::NeuralNetworkShape;
+use crate::activation::activate::ActivationTrait;
+use crate::activation::softmax::{Softmax, DEFAULT_TEMPERATURE};
+use crate::data::loader::DataLoader;
+use crate::layer::layer_trait::LayerGradients;
+use crate::nn::inference_error::{check_finite, InferenceError};
+use crate::nn::quantized::QuantizedNeuralNetwork;
+use crate::nn::shape::{LayerShape, NeuralNetworkShape};
+use crate::training::importance::{self, ImportanceMetric};
+use crate::training::loss::LossFunction;
+use crate::training::metrics::EpochMetrics;
+use crate::training::optimizer::Optimizer;
+use crate::training::training_options::TrainingOptions;
 use crate::{nn::directory::Directory, utilities::util::WrappedUtils};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use utils::safer::safe_lock;
 
 pub trait NeuralNetwork: std::fmt::Debug {
@@ -8,6 +20,42 @@ pub trait NeuralNetwork: std::fmt::Debug {
         &mut self,
         input: Vec<f64>,
     ) -> Vec<f64>;
+    /// Predicts the output for the input into the given buffer, which is cleared first.
+    /// Classic networks reuse the buffer and buffers of their own, so that repeated calls do
+    /// not allocate in hot loops. By default the output of `predict` is copied into the buffer.
+    fn predict_into(
+        &mut self,
+        input: &[f64],
+        out: &mut Vec<f64>,
+    ) {
+        let output = self.predict(input.to_vec());
+        out.clear();
+        out.extend_from_slice(&output);
+    }
+    /// Predicts the output for the input and normalizes it into a probability distribution
+    /// with a softmax of temperature `DEFAULT_TEMPERATURE`. The softmax is applied post hoc
+    /// regardless of the final activation, so the values are only probability-like unless
+    /// the network was trained for them. A softmax output layer is normalized a second time.
+    fn predict_proba(
+        &mut self,
+        input: Vec<f64>,
+    ) -> Vec<f64> {
+        Softmax::new(DEFAULT_TEMPERATURE).forward(&self.predict(input))
+    }
+    /// Predicts the output for the input and returns an error if an activation is NaN or
+    /// infinite. The default implementation only checks the final output, networks made of
+    /// layers also check the output of every layer before and after its activation.
+    ///
+    /// # Errors
+    /// Returns an `InferenceError` naming the layer and unit of the first non finite value.
+    fn predict_checked(
+        &mut self,
+        input: Vec<f64>,
+    ) -> Result<Vec<f64>, InferenceError> {
+        let output = self.predict(input);
+        check_finite(&output, self.shape().layers.len().saturating_sub(1))?;
+        Ok(output)
+    }
     fn shape(&self) -> NeuralNetworkShape;
     /// Saves the neural network to the specified user model directory.
     ///
@@ -17,12 +65,82 @@ pub trait NeuralNetwork: std::fmt::Debug {
         &mut self,
         user_model_directory: String,
     ) -> Result<(), Box<dyn std::error::Error>>;
+    /// Saves the neural network to the specified user model directory by overwriting it in place.
+    /// This is faster than `save` but not crash safe. Falls back to `save` unless overridden.
+    ///
+    /// # Errors
+    /// Returns an error if saving the model fails due to IO issues or serialization errors.
+    fn save_fast(
+        &mut self,
+        user_model_directory: String,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.save(user_model_directory)
+    }
     fn get_model_directory(&self) -> Directory;
     fn allocate(&mut self);
     fn deallocate(&mut self);
     fn set_internal(&mut self);
+    /// Keeps the internal model directories of the network and its sub networks on disk
+    /// when it is dropped, e.g. to inspect them while debugging.
+    fn persist_on_drop(&mut self);
     fn duplicate(&self) -> WrappedNeuralNetwork;
     fn get_utils(&self) -> WrappedUtils;
+    /// Returns a copy of the network with the weights of its dense layers quantized to int8.
+    ///
+    /// # Errors
+    /// Returns an error if the network has a layer which is not dense or does not support
+    /// quantization.
+    fn to_quantized(&mut self) -> Result<QuantizedNeuralNetwork, Box<dyn std::error::Error>> {
+        Err("Quantization is only supported for classic neural networks".into())
+    }
+    /// Returns the permutation importance of every input feature: the drop of the metric
+    /// when that feature column is shuffled across the samples with a seeded random number generator.
+    ///
+    /// # Panics
+    /// Panics if the number of inputs and targets differ.
+    fn permutation_importance(
+        &mut self,
+        inputs: &[Vec<f64>],
+        targets: &[Vec<f64>],
+        metric: &ImportanceMetric,
+        seed: u64,
+    ) -> Vec<f64> {
+        importance::permutation_importance(
+            |input| self.predict(input),
+            inputs,
+            targets,
+            metric,
+            seed,
+        )
+    }
+
+    /// Returns the mean and the population variance of every output unit over the
+    /// predictions for the inputs, computed in a single pass with Welford's algorithm.
+    /// Without inputs every mean and variance is zero.
+    fn activation_stats(
+        &mut self,
+        inputs: &[Vec<f64>],
+    ) -> Vec<(f64, f64)> {
+        let output_size = self.shape().layers.last().map_or(0, LayerShape::output_size);
+        // running mean and sum of squared deviations of every unit
+        let mut stats = vec![(0.0, 0.0); output_size];
+        let mut count = 0.0;
+        for input in inputs {
+            let output = self.predict(input.clone());
+            count += 1.0;
+            for ((mean, deviations), value) in stats.iter_mut().zip(output) {
+                let delta = value - *mean;
+                *mean += delta / count;
+                *deviations += delta * (value - *mean);
+            }
+        }
+        if count > 0.0 {
+            for (_, deviations) in &mut stats {
+                *deviations /= count;
+            }
+        }
+        stats
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -43,6 +161,32 @@ impl WrappedNeuralNetwork {
         safe_lock(&self.nn).predict(input)
     }
 
+    pub fn predict_into(
+        &mut self,
+        input: &[f64],
+        out: &mut Vec<f64>,
+    ) {
+        safe_lock(&self.nn).predict_into(input, out);
+    }
+
+    pub fn predict_proba(
+        &mut self,
+        input: Vec<f64>,
+    ) -> Vec<f64> {
+        safe_lock(&self.nn).predict_proba(input)
+    }
+
+    /// Predicts the output for the input and checks that every activation is finite.
+    ///
+    /// # Errors
+    /// Returns an `InferenceError` naming the layer and unit of the first non finite value.
+    pub fn predict_checked(
+        &mut self,
+        input: Vec<f64>,
+    ) -> Result<Vec<f64>, InferenceError> {
+        safe_lock(&self.nn).predict_checked(input)
+    }
+
     #[must_use]
     pub fn shape(&self) -> NeuralNetworkShape {
         safe_lock(&self.nn).shape()
@@ -59,6 +203,22 @@ impl WrappedNeuralNetwork {
         safe_lock(&self.nn).save(user_model_directory)
     }
 
+    /// Saves the neural network to the specified user model directory in place, without crash safety.
+    ///
+    /// # Errors
+    /// Returns an error if saving the model fails due to IO issues or serialization errors.
+    pub fn save_fast(
+        &mut self,
+        user_model_directory: String,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).save_fast(user_model_directory)
+    }
+
+    #[must_use]
+    pub fn get_model_directory(&self) -> Directory {
+        safe_lock(&self.nn).get_model_directory()
+    }
+
     pub fn allocate(&self) {
         safe_lock(&self.nn).allocate();
     }
@@ -71,6 +231,10 @@ impl WrappedNeuralNetwork {
         safe_lock(&self.nn).set_internal();
     }
 
+    pub fn persist_on_drop(&mut self) {
+        safe_lock(&self.nn).persist_on_drop();
+    }
+
     #[must_use]
     pub fn duplicate(&self) -> Self {
         safe_lock(&self.nn).duplicate()
@@ -80,11 +244,43 @@ impl WrappedNeuralNetwork {
     pub fn get_utils(&self) -> WrappedUtils {
         safe_lock(&self.nn).get_utils()
     }
+
+    /// Returns a copy of the network with the weights of its dense layers quantized to int8.
+    ///
+    /// # Errors
+    /// Returns an error if the network does not support quantization.
+    pub fn to_quantized(&self) -> Result<QuantizedNeuralNetwork, Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).to_quantized()
+    }
+
+    /// Returns the permutation importance of every input feature.
+    ///
+    /// # Panics
+    /// Panics if the number of inputs and targets differ.
+    pub fn permutation_importance(
+        &mut self,
+        inputs: &[Vec<f64>],
+        targets: &[Vec<f64>],
+        metric: &ImportanceMetric,
+        seed: u64,
+    ) -> Vec<f64> {
+        safe_lock(&self.nn).permutation_importance(inputs, targets, metric, seed)
+    }
+
+    /// Returns the mean and the variance of every output unit over the inputs.
+    pub fn activation_stats(
+        &mut self,
+        inputs: &[Vec<f64>],
+    ) -> Vec<(f64, f64)> {
+        safe_lock(&self.nn).activation_stats(inputs)
+    }
 }
 
 pub trait TrainableNeuralNetwork: NeuralNetwork {
     /// Trains the neural network using the given inputs, targets, learning rate, and number of epochs.
     /// Includes validation using a split of the data.
+    /// If given, `optimizer` updates the weights instead of the built-in update chosen by
+    /// `use_adam`, and `on_epoch_end` is invoked with the metrics of every finished epoch.
     #[allow(clippy::too_many_arguments)]
     fn train(
         &mut self,
@@ -94,11 +290,33 @@ pub trait TrainableNeuralNetwork: NeuralNetwork {
         epochs: usize,
         tolerance: f64,
         use_adam: bool,
+        optimizer: Option<Box<dyn Optimizer>>,
         validation_split: f64,
         sample_match_percentage: f64,
+        on_epoch_end: Option<&mut dyn FnMut(EpochMetrics)>,
+    ) -> f64;
+
+    /// Trains the neural network like `train`, but validates it on the given validation
+    /// samples instead of a split of the training samples.
+    #[allow(clippy::too_many_arguments)]
+    fn train_with_validation(
+        &mut self,
+        train_inputs: &[Vec<f64>],
+        train_targets: &[Vec<f64>],
+        validation_inputs: &[Vec<f64>],
+        validation_targets: &[Vec<f64>],
+        learning_rate: f64,
+        epochs: usize,
+        tolerance: f64,
+        use_adam: bool,
+        optimizer: Option<Box<dyn Optimizer>>,
+        sample_match_percentage: f64,
+        on_epoch_end: Option<&mut dyn FnMut(EpochMetrics)>,
     ) -> f64;
 
     /// Trains the neural network doing batch back propagation.
+    /// If given, `optimizer` updates the weights instead of plain gradient descent.
+    #[allow(clippy::too_many_arguments)]
     fn train_batch(
         &mut self,
         inputs: &[Vec<f64>],
@@ -107,15 +325,322 @@ pub trait TrainableNeuralNetwork: NeuralNetwork {
         epochs: usize,
         tolerance: f64,
         batch_size: usize,
+        optimizer: Option<Box<dyn Optimizer>>,
+    );
+
+    /// Trains the neural network doing batch back propagation on the batches of the loader.
+    /// If given, `optimizer` updates the weights instead of plain gradient descent.
+    fn train_loader(
+        &mut self,
+        loader: &DataLoader,
+        learning_rate: f64,
+        epochs: usize,
+        tolerance: f64,
+        optimizer: Option<Box<dyn Optimizer>>,
     );
 
+    /// Runs the forward and backward pass over a batch and returns the gradients of every
+    /// layer averaged over the batch, without applying them. The averaged gradients are
+    /// also left in the layers, so a following weight update applies them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the batch is empty, if the number of inputs and targets differ
+    /// or if the loss does not fit the network.
+    fn compute_batch_gradients(
+        &mut self,
+        inputs: &[Vec<f64>],
+        targets: &[Vec<f64>],
+        loss_function: &LossFunction,
+    ) -> Result<Vec<LayerGradients>, Box<dyn std::error::Error>>;
+
+    /// Trains on a single sample with one forward pass, backward pass and Adam update and
+    /// returns the loss of the sample before the update. The Adam state is kept across calls,
+    /// so feeding one labeled example after another trains the network online.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the sizes of the input or the target or the loss do not fit the network.
+    fn partial_fit(
+        &mut self,
+        input: &[f64],
+        target: &[f64],
+        learning_rate: f64,
+        loss_function: &LossFunction,
+    ) -> Result<f64, Box<dyn std::error::Error>>;
+
+    /// Returns the saliency map of the input for the output with the given index: the absolute
+    /// gradient of that output with respect to every input value. The backward pass overwrites
+    /// the gradients of the layers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input size does not fit the network or the output index is out
+    /// of range.
+    fn saliency(
+        &mut self,
+        input: &[f64],
+        output_index: usize,
+    ) -> Result<Vec<f64>, Box<dyn std::error::Error>>;
+
     /// Returns the input size of the first layer in the network.
     fn input_size(&self) -> usize;
 
     /// Returns the output size of the last layer in the network.
     fn output_size(&self) -> usize;
 
+    /// Runs the input through the network like `predict` and returns the time every layer
+    /// took for its forward pass, including its activation.
+    fn profile_forward(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<Duration>;
+
+    /// Runs the given inputs through the network and returns, for every layer with a `ReLU`
+    /// activation, the number of output units which were zero for all inputs.
+    fn dead_neuron_report(
+        &mut self,
+        inputs: &[Vec<f64>],
+    ) -> Vec<usize>;
+
+    /// Returns the options the network is trained with.
+    fn training_options(&self) -> TrainingOptions;
+
+    /// Sets the options the network is trained with.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the learning rate multipliers do not match the layers.
+    fn set_training_options(
+        &mut self,
+        options: TrainingOptions,
+    );
+
+    /// Sets the loss minimized during training.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the loss does not fit the output size of the network.
+    fn set_loss_function(
+        &mut self,
+        loss_function: LossFunction,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Computes the loss on the logits of the final softmax activation while training,
+    /// fusing the softmax into the loss. Predictions still apply the softmax.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the network does not end with a softmax activation.
+    fn set_logits_loss(
+        &mut self,
+        enabled: bool,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Returns the weights averaged during the last `train` in the order of `weights_flat`,
+    /// or `None` if no epoch was averaged.
+    fn swa_weights(&self) -> Option<Vec<f64>>;
+
+    /// Re-runs the weight initialization of every layer with a random number generator seeded
+    /// with `seed` and zeros the gradients, keeping the shape.
+    fn reinitialize(
+        &mut self,
+        seed: u64,
+    );
+
+    /// Enables or disables spectral normalization of the dense layers, which divides their
+    /// weights by a power iteration estimate of the largest singular value in every forward
+    /// pass. Disabling it folds the normalization into the weights, so disable it before
+    /// saving the network or converting it to an inference network.
+    fn set_spectral_norm(
+        &mut self,
+        enabled: bool,
+    );
+
+    /// Returns the weight connecting input `col` to output unit `row` of the given layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the layer, row or column index is out of range.
+    fn get_weight(
+        &self,
+        layer_index: usize,
+        row: usize,
+        col: usize,
+    ) -> Result<f64, Box<dyn std::error::Error>>;
+
+    /// Sets the weight connecting input `col` to output unit `row` of the given layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the layer, row or column index is out of range.
+    fn set_weight(
+        &mut self,
+        layer_index: usize,
+        row: usize,
+        col: usize,
+        value: f64,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Returns the bias of output unit `row` of the given layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the layer or row index is out of range.
+    fn get_bias(
+        &self,
+        layer_index: usize,
+        row: usize,
+    ) -> Result<f64, Box<dyn std::error::Error>>;
+
+    /// Sets the bias of output unit `row` of the given layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the layer or row index is out of range.
+    fn set_bias(
+        &mut self,
+        layer_index: usize,
+        row: usize,
+        value: f64,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Returns the largest singular value of the weight matrix of every layer,
+    /// estimated with `iterations` steps of power iteration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the network does not support accessing its layers.
+    fn spectral_norms(
+        &self,
+        iterations: usize,
+    ) -> Result<Vec<f64>, Box<dyn std::error::Error>>;
+
+    /// Removes all output units of the layer at `layer_index` except the ones in
+    /// `keep_indices`, together with the corresponding input units of the next layer.
+    /// The weights of the remaining units are preserved.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the layer can not be pruned or if `keep_indices` is empty,
+    /// not strictly increasing or out of range.
+    fn prune_neurons(
+        &mut self,
+        layer_index: usize,
+        keep_indices: &[usize],
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Inserts a dense layer behind the layer at `after_index` that initially maps its input
+    /// to itself, so the outputs of the network stay the same. The new layer uses `ReLU`
+    /// behind a `ReLU` layer and the identity activation otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the network does not support inserting layers or if `after_index`
+    /// is out of range.
+    fn add_identity_layer(
+        &mut self,
+        after_index: usize,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Appends a newly initialized layer of the given shape behind the last layer,
+    /// e.g. to build a network incrementally.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the network does not support appending layers, if the layer shape
+    /// is not valid or if its input size differs from the output size of the network.
+    fn push_layer(
+        &mut self,
+        layer_shape: LayerShape,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Widens the layer at `layer_index` to `new_width` output units while preserving the
+    /// function of the network (`Net2Net`). The new units duplicate existing ones and the
+    /// outgoing weights of every duplicated unit are divided among its copies.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the layer can not be widened or if `new_width` is not larger
+    /// than the current output size.
+    fn widen_layer(
+        &mut self,
+        layer_index: usize,
+        new_width: usize,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
     fn duplicate_trainable(&self) -> WrappedTrainableNeuralNetwork;
+
+    /// Returns a forward only copy of the network holding only its weights and activations,
+    /// without gradients or optimizer state.
+    fn to_inference(&self) -> WrappedNeuralNetwork;
+
+    /// Returns all weights and biases as one flat vector.
+    /// For every layer in order, the weights are written row by row (one row per output unit),
+    /// followed by the biases of that layer.
+    fn weights_flat(&self) -> Vec<f64>;
+
+    /// Loads weights and biases in the order produced by `weights_flat`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the number of values does not match the number of parameters.
+    fn load_weights_flat(
+        &mut self,
+        weights: &[f64],
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Returns the number of weights and biases of the instantiated layers,
+    /// which follows the layers after pruning.
+    fn num_parameters(&self) -> usize;
+
+    /// Returns the indices and scores of the `k` highest outputs for the given input,
+    /// sorted by descending score. Ties are broken by the lower index.
+    /// If `k` exceeds the output size, all outputs are returned.
+    fn predict_top_k(
+        &mut self,
+        input: Vec<f64>,
+        k: usize,
+    ) -> Vec<(usize, f64)> {
+        top_k(&self.predict(input), k)
+    }
+}
+
+/// Loads flat weights into several networks, each taking as many values as its
+/// `weights_flat` returns, in the given order.
+pub(crate) fn load_weights_flat_sequentially(
+    networks: Vec<&mut WrappedTrainableNeuralNetwork>,
+    weights: &[f64],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let sizes = networks.iter().map(|nn| nn.weights_flat().len()).collect::<Vec<_>>();
+    let num_parameters = sizes.iter().sum::<usize>();
+    if weights.len() != num_parameters {
+        return Err(format!(
+            "Expected {num_parameters} flat weights for the neural network, found {}",
+            weights.len()
+        )
+        .into());
+    }
+    let mut offset = 0;
+    for (nn, size) in networks.into_iter().zip(sizes) {
+        nn.load_weights_flat(&weights[offset..offset + size])?;
+        offset += size;
+    }
+    Ok(())
+}
+
+/// Returns the indices and values of the `k` highest values sorted by descending value.
+/// Ties are broken by the lower index.
+#[must_use]
+pub fn top_k(
+    output: &[f64],
+    k: usize,
+) -> Vec<(usize, f64)> {
+    let mut indexed = output.iter().copied().enumerate().collect::<Vec<_>>();
+    // the sort is stable, so equal scores keep their ascending index order
+    indexed.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    indexed.truncate(k);
+    indexed
 }
 
 #[derive(Debug, Clone)]
@@ -136,6 +661,32 @@ impl WrappedTrainableNeuralNetwork {
         safe_lock(&self.nn).predict(input)
     }
 
+    pub fn predict_into(
+        &mut self,
+        input: &[f64],
+        out: &mut Vec<f64>,
+    ) {
+        safe_lock(&self.nn).predict_into(input, out);
+    }
+
+    pub fn predict_proba(
+        &mut self,
+        input: Vec<f64>,
+    ) -> Vec<f64> {
+        safe_lock(&self.nn).predict_proba(input)
+    }
+
+    /// Predicts the output for the input and checks that every activation is finite.
+    ///
+    /// # Errors
+    /// Returns an `InferenceError` naming the layer and unit of the first non finite value.
+    pub fn predict_checked(
+        &mut self,
+        input: Vec<f64>,
+    ) -> Result<Vec<f64>, InferenceError> {
+        safe_lock(&self.nn).predict_checked(input)
+    }
+
     #[must_use]
     pub fn shape(&self) -> NeuralNetworkShape {
         safe_lock(&self.nn).shape()
@@ -152,6 +703,17 @@ impl WrappedTrainableNeuralNetwork {
         safe_lock(&self.nn).save(user_model_directory)
     }
 
+    /// Saves the trainable neural network to the specified user model directory in place, without crash safety.
+    ///
+    /// # Errors
+    /// Returns an error if saving the model fails due to IO issues or serialization errors.
+    pub fn save_fast(
+        &mut self,
+        user_model_directory: String,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).save_fast(user_model_directory)
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn train(
         &mut self,
@@ -161,8 +723,10 @@ impl WrappedTrainableNeuralNetwork {
         epochs: usize,
         tolerance: f64,
         use_adam: bool,
+        optimizer: Option<Box<dyn Optimizer>>,
         validation_split: f64,
         sample_match_percentage: f64,
+        on_epoch_end: Option<&mut dyn FnMut(EpochMetrics)>,
     ) -> f64 {
         safe_lock(&self.nn).train(
             inputs,
@@ -171,8 +735,42 @@ impl WrappedTrainableNeuralNetwork {
             epochs,
             tolerance,
             use_adam,
+            optimizer,
             validation_split,
             sample_match_percentage,
+            on_epoch_end,
+        )
+    }
+
+    /// Trains the neural network on the training samples and validates it on the given
+    /// validation samples, ignoring any validation split.
+    #[allow(clippy::too_many_arguments)]
+    pub fn train_with_validation(
+        &mut self,
+        train_inputs: &[Vec<f64>],
+        train_targets: &[Vec<f64>],
+        validation_inputs: &[Vec<f64>],
+        validation_targets: &[Vec<f64>],
+        learning_rate: f64,
+        epochs: usize,
+        tolerance: f64,
+        use_adam: bool,
+        optimizer: Option<Box<dyn Optimizer>>,
+        sample_match_percentage: f64,
+        on_epoch_end: Option<&mut dyn FnMut(EpochMetrics)>,
+    ) -> f64 {
+        safe_lock(&self.nn).train_with_validation(
+            train_inputs,
+            train_targets,
+            validation_inputs,
+            validation_targets,
+            learning_rate,
+            epochs,
+            tolerance,
+            use_adam,
+            optimizer,
+            sample_match_percentage,
+            on_epoch_end,
         )
     }
 
@@ -185,6 +783,7 @@ impl WrappedTrainableNeuralNetwork {
         epochs: usize,
         tolerance: f64,
         batch_size: usize,
+        optimizer: Option<Box<dyn Optimizer>>,
     ) {
         safe_lock(&self.nn).train_batch(
             inputs,
@@ -193,9 +792,64 @@ impl WrappedTrainableNeuralNetwork {
             epochs,
             tolerance,
             batch_size,
+            optimizer,
         );
     }
 
+    /// Trains the neural network doing batch back propagation on the batches of the loader.
+    pub fn train_loader(
+        &mut self,
+        loader: &DataLoader,
+        learning_rate: f64,
+        epochs: usize,
+        tolerance: f64,
+        optimizer: Option<Box<dyn Optimizer>>,
+    ) {
+        safe_lock(&self.nn).train_loader(loader, learning_rate, epochs, tolerance, optimizer);
+    }
+
+    /// Returns the gradients of every layer averaged over the batch, without applying them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the batch or the loss does not fit the network.
+    pub fn compute_batch_gradients(
+        &mut self,
+        inputs: &[Vec<f64>],
+        targets: &[Vec<f64>],
+        loss_function: &LossFunction,
+    ) -> Result<Vec<LayerGradients>, Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).compute_batch_gradients(inputs, targets, loss_function)
+    }
+
+    /// Trains on a single sample and returns its loss before the update.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the sample or the loss does not fit the network.
+    pub fn partial_fit(
+        &mut self,
+        input: &[f64],
+        target: &[f64],
+        learning_rate: f64,
+        loss_function: &LossFunction,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).partial_fit(input, target, learning_rate, loss_function)
+    }
+
+    /// Returns the absolute gradient of the chosen output with respect to every input value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input or the output index does not fit the network.
+    pub fn saliency(
+        &mut self,
+        input: &[f64],
+        output_index: usize,
+    ) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).saliency(input, output_index)
+    }
+
     #[must_use]
     pub fn input_size(&self) -> usize {
         safe_lock(&self.nn).input_size()
@@ -223,6 +877,261 @@ impl WrappedTrainableNeuralNetwork {
         safe_lock(&self.nn).set_internal();
     }
 
+    pub fn persist_on_drop(&mut self) {
+        safe_lock(&self.nn).persist_on_drop();
+    }
+
+    /// Returns the time every layer took for the forward pass of the input.
+    #[must_use]
+    pub fn profile_forward(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<Duration> {
+        safe_lock(&self.nn).profile_forward(input)
+    }
+
+    pub fn dead_neuron_report(
+        &mut self,
+        inputs: &[Vec<f64>],
+    ) -> Vec<usize> {
+        safe_lock(&self.nn).dead_neuron_report(inputs)
+    }
+
+    #[must_use]
+    pub fn training_options(&self) -> TrainingOptions {
+        safe_lock(&self.nn).training_options()
+    }
+
+    pub fn set_training_options(
+        &mut self,
+        options: TrainingOptions,
+    ) {
+        safe_lock(&self.nn).set_training_options(options);
+    }
+
+    /// Sets the loss minimized during training.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the loss does not fit the output size of the network.
+    pub fn set_loss_function(
+        &mut self,
+        loss_function: LossFunction,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).set_loss_function(loss_function)
+    }
+
+    /// Computes the loss on the logits of the final softmax activation while training.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the network does not end with a softmax activation.
+    pub fn set_logits_loss(
+        &mut self,
+        enabled: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).set_logits_loss(enabled)
+    }
+
+    #[must_use]
+    pub fn swa_weights(&self) -> Option<Vec<f64>> {
+        safe_lock(&self.nn).swa_weights()
+    }
+
+    pub fn reinitialize(
+        &mut self,
+        seed: u64,
+    ) {
+        safe_lock(&self.nn).reinitialize(seed);
+    }
+
+    pub fn set_spectral_norm(
+        &mut self,
+        enabled: bool,
+    ) {
+        safe_lock(&self.nn).set_spectral_norm(enabled);
+    }
+
+    pub fn predict_top_k(
+        &mut self,
+        input: Vec<f64>,
+        k: usize,
+    ) -> Vec<(usize, f64)> {
+        safe_lock(&self.nn).predict_top_k(input, k)
+    }
+
+    #[must_use]
+    pub fn weights_flat(&self) -> Vec<f64> {
+        safe_lock(&self.nn).weights_flat()
+    }
+
+    /// Loads weights and biases in the order produced by `weights_flat`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the number of values does not match the number of parameters.
+    pub fn load_weights_flat(
+        &mut self,
+        weights: &[f64],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).load_weights_flat(weights)
+    }
+
+    /// Returns a duplicate of the network whose weights and biases are
+    /// `alpha * self + (1 - alpha) * other`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the shapes of the networks differ or the blended weights cannot be loaded.
+    pub fn interpolate(
+        &self,
+        other: &Self,
+        alpha: f64,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        if self.shape() != other.shape() {
+            return Err("Interpolated networks must have the same shape".into());
+        }
+        let other_weights = other.weights_flat();
+        let weights = self
+            .weights_flat()
+            .iter()
+            .zip(&other_weights)
+            .map(|(weight, other_weight)| alpha.mul_add(*weight, (1.0 - alpha) * other_weight))
+            .collect::<Vec<f64>>();
+        let mut interpolated = self.duplicate_trainable();
+        interpolated.load_weights_flat(&weights)?;
+        Ok(interpolated)
+    }
+
+    /// Returns the weight connecting input `col` to output unit `row` of the given layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the layer, row or column index is out of range.
+    pub fn get_weight(
+        &self,
+        layer_index: usize,
+        row: usize,
+        col: usize,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).get_weight(layer_index, row, col)
+    }
+
+    /// Sets the weight connecting input `col` to output unit `row` of the given layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the layer, row or column index is out of range.
+    pub fn set_weight(
+        &mut self,
+        layer_index: usize,
+        row: usize,
+        col: usize,
+        value: f64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).set_weight(layer_index, row, col, value)
+    }
+
+    /// Returns the bias of output unit `row` of the given layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the layer or row index is out of range.
+    pub fn get_bias(
+        &self,
+        layer_index: usize,
+        row: usize,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).get_bias(layer_index, row)
+    }
+
+    /// Sets the bias of output unit `row` of the given layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the layer or row index is out of range.
+    pub fn set_bias(
+        &mut self,
+        layer_index: usize,
+        row: usize,
+        value: f64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).set_bias(layer_index, row, value)
+    }
+
+    /// Returns the largest singular value of the weight matrix of every layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the network does not support accessing its layers.
+    pub fn spectral_norms(
+        &self,
+        iterations: usize,
+    ) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).spectral_norms(iterations)
+    }
+
+    /// Removes all output units of a layer except the ones in `keep_indices`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the layer can not be pruned or if `keep_indices` is invalid.
+    pub fn prune_neurons(
+        &mut self,
+        layer_index: usize,
+        keep_indices: &[usize],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).prune_neurons(layer_index, keep_indices)
+    }
+
+    /// Inserts a layer behind the layer at `after_index` that initially maps its input to itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if inserting layers is not supported or `after_index` is out of range.
+    pub fn add_identity_layer(
+        &mut self,
+        after_index: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).add_identity_layer(after_index)
+    }
+
+    /// Appends a newly initialized layer of the given shape behind the last layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if appending layers is not supported or the layer does not fit.
+    pub fn push_layer(
+        &mut self,
+        layer_shape: LayerShape,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).push_layer(layer_shape)
+    }
+
+    /// Widens a layer to `new_width` output units while preserving the function of the network.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the layer can not be widened or if `new_width` is too small.
+    pub fn widen_layer(
+        &mut self,
+        layer_index: usize,
+        new_width: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).widen_layer(layer_index, new_width)
+    }
+
+    /// Returns the number of weights and biases of the instantiated layers.
+    #[must_use]
+    pub fn num_parameters(&self) -> usize {
+        safe_lock(&self.nn).num_parameters()
+    }
+
+    #[must_use]
+    pub fn to_inference(&self) -> WrappedNeuralNetwork {
+        safe_lock(&self.nn).to_inference()
+    }
+
     #[must_use]
     pub fn duplicate_trainable(&self) -> Self {
         safe_lock(&self.nn).duplicate_trainable()
@@ -232,4 +1141,247 @@ impl WrappedTrainableNeuralNetwork {
     pub fn get_utils(&self) -> WrappedUtils {
         safe_lock(&self.nn).get_utils()
     }
+
+    /// Returns a copy of the network with the weights of its dense layers quantized to int8.
+    ///
+    /// # Errors
+    /// Returns an error if the network does not support quantization.
+    pub fn to_quantized(&self) -> Result<QuantizedNeuralNetwork, Box<dyn std::error::Error>> {
+        safe_lock(&self.nn).to_quantized()
+    }
+
+    /// Returns the permutation importance of every input feature.
+    ///
+    /// # Panics
+    /// Panics if the number of inputs and targets differ.
+    pub fn permutation_importance(
+        &mut self,
+        inputs: &[Vec<f64>],
+        targets: &[Vec<f64>],
+        metric: &ImportanceMetric,
+        seed: u64,
+    ) -> Vec<f64> {
+        safe_lock(&self.nn).permutation_importance(inputs, targets, metric, seed)
+    }
+
+    /// Returns the mean and the variance of every output unit over the inputs.
+    pub fn activation_stats(
+        &mut self,
+        inputs: &[Vec<f64>],
+    ) -> Vec<(f64, f64)> {
+        safe_lock(&self.nn).activation_stats(inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nn::nn_factory::{new_trainable_neural_network, NeuralNetworkCreationArguments};
+    use crate::nn::shape::{ActivationData, ActivationType, LayerShape, LayerType};
+    use crate::utilities::util::Utils;
+
+    /// Counts the allocations of the current thread, so that tests running in parallel
+    /// do not disturb each other.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(
+            &self,
+            layout: std::alloc::Layout,
+        ) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+            std::alloc::System.alloc(layout)
+        }
+
+        unsafe fn dealloc(
+            &self,
+            ptr: *mut u8,
+            layout: std::alloc::Layout,
+        ) {
+            std::alloc::System.dealloc(ptr, layout);
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingAllocator = CountingAllocator;
+
+    fn allocations() -> usize {
+        ALLOCATIONS.with(std::cell::Cell::get)
+    }
+
+    #[test]
+    fn test_predict_into_does_not_allocate() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let dense = |input_size, output_size, activation_type| LayerShape {
+            layer_type: LayerType::Dense { input_size, output_size },
+            activation: ActivationData::new(activation_type),
+        };
+        let mut nn = new_trainable_neural_network(NeuralNetworkCreationArguments::new(
+            NeuralNetworkShape {
+                layers: vec![
+                    dense(3, 8, ActivationType::ReLU),
+                    dense(8, 2, ActivationType::Tanh),
+                    dense(2, 4, ActivationType::Sigmoid),
+                ],
+            },
+            None,
+            None,
+            "test_model_predict_into".to_string(),
+            utils,
+        ));
+
+        let before = allocations();
+        drop(std::hint::black_box(Vec::<f64>::with_capacity(4)));
+        assert!(allocations() > before, "allocations are not counted");
+
+        let inputs = [[0.5, -1.0, 2.0], [1.0, 0.0, -0.5], [-2.0, 0.25, 1.0]];
+        let mut out = Vec::new();
+        // the first call allocates the layers and grows the buffers to the largest layer
+        nn.predict_into(&inputs[0], &mut out);
+        assert_eq!(out, nn.predict(inputs[0].to_vec()));
+        let (buffer, capacity) = (out.as_ptr(), out.capacity());
+        for input in inputs {
+            let before = allocations();
+            nn.predict_into(&input, &mut out);
+            assert_eq!(allocations(), before, "predict_into allocated");
+            assert_eq!((out.as_ptr(), out.capacity()), (buffer, capacity));
+            assert_eq!(out, nn.predict(input.to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_predict_proba_sums_to_one() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut nn = new_trainable_neural_network(NeuralNetworkCreationArguments::new(
+            NeuralNetworkShape {
+                layers: vec![LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 4 },
+                    activation: ActivationData::new(ActivationType::ReLU),
+                }],
+            },
+            None,
+            None,
+            "test_model_predict_proba".to_string(),
+            utils,
+        ));
+
+        for input in [vec![0.5, -1.0, 2.0], vec![10.0, 0.0, -5.0]] {
+            let output = nn.predict(input.clone());
+            let probabilities = nn.predict_proba(input);
+            assert_eq!(probabilities.len(), output.len());
+            assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+            assert!(probabilities.iter().all(|p| (0.0..=1.0).contains(p)));
+            // the softmax keeps the order of the raw outputs
+            for i in 0..output.len() {
+                for j in 0..output.len() {
+                    if output[i] > output[j] {
+                        assert!(probabilities[i] > probabilities[j]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_activation_stats_of_constant_inputs() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut nn = new_trainable_neural_network(NeuralNetworkCreationArguments::new(
+            NeuralNetworkShape {
+                layers: vec![LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 4 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                }],
+            },
+            None,
+            None,
+            "test_model_activation_stats".to_string(),
+            utils,
+        ));
+
+        let input = vec![0.5, -1.0, 2.0];
+        let output = nn.predict(input.clone());
+        let stats = nn.activation_stats(&vec![input; 10]);
+        assert_eq!(stats.len(), 4);
+        for ((mean, variance), expected) in stats.into_iter().zip(output) {
+            assert!((mean - expected).abs() < 1e-12);
+            assert!(variance.abs() < 1e-12);
+        }
+        assert_eq!(nn.activation_stats(&[]), vec![(0.0, 0.0); 4]);
+    }
+
+    #[test]
+    fn test_top_k() {
+        let output = vec![0.1, 0.7, 0.3, 0.7, 0.5];
+        assert_eq!(top_k(&output, 3), vec![(1, 0.7), (3, 0.7), (4, 0.5)]);
+        assert_eq!(top_k(&output, 10), vec![(1, 0.7), (3, 0.7), (4, 0.5), (2, 0.3), (0, 0.1)]);
+        assert!(top_k(&output, 0).is_empty());
+
+        // exactly tied outputs always rank the lower index first
+        for _ in 0..100 {
+            assert_eq!(top_k(&[0.4, 0.4, 0.4], 2), vec![(0, 0.4), (1, 0.4)]);
+        }
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape {
+            layers: vec![
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 4 },
+                    activation: ActivationData::new(ActivationType::ReLU),
+                },
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 4, output_size: 2 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                },
+            ],
+        };
+        let nn = new_trainable_neural_network(NeuralNetworkCreationArguments::new(
+            shape.clone(),
+            None,
+            None,
+            "test_model_interpolate".to_string(),
+            utils.clone(),
+        ));
+        let other = new_trainable_neural_network(NeuralNetworkCreationArguments::new(
+            shape,
+            None,
+            None,
+            "test_model_interpolate_other".to_string(),
+            utils.clone(),
+        ));
+
+        let weights = nn.weights_flat();
+        let with_itself = nn.interpolate(&nn, 0.3).unwrap();
+        for (weight, expected) in with_itself.weights_flat().iter().zip(&weights) {
+            assert!((weight - expected).abs() < 1e-12);
+        }
+        // interpolating leaves the original network untouched
+        assert_eq!(nn.weights_flat(), weights);
+        assert_eq!(nn.interpolate(&other, 0.0).unwrap().weights_flat(), other.weights_flat());
+
+        let halfway = nn.interpolate(&other, 0.5).unwrap().weights_flat();
+        for ((weight, a), b) in halfway.iter().zip(nn.weights_flat()).zip(other.weights_flat()) {
+            assert!((weight - 0.5 * (a + b)).abs() < 1e-12);
+        }
+
+        let different = new_trainable_neural_network(NeuralNetworkCreationArguments::new(
+            NeuralNetworkShape {
+                layers: vec![LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 2 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                }],
+            },
+            None,
+            None,
+            "test_model_interpolate_different".to_string(),
+            utils,
+        ));
+        assert!(nn.interpolate(&different, 0.5).is_err());
+    }
 }
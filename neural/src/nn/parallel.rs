@@ -0,0 +1,867 @@
+use std::time::Duration;
+
+use crate::data::loader::DataLoader;
+use crate::layer::layer_trait::LayerGradients;
+use crate::nn::directory::Directory;
+use crate::nn::neuralnet::TrainableClassicNeuralNetwork;
+use crate::nn::nn_trait::{
+    load_weights_flat_sequentially, NeuralNetwork, TrainableNeuralNetwork, WrappedNeuralNetwork,
+    WrappedTrainableNeuralNetwork,
+};
+use crate::nn::shape::{
+    ActivationData, ActivationSegment, ActivationType, LayerShape, LayerType, NeuralNetworkShape,
+};
+use crate::training::loss::LossFunction;
+use crate::training::metrics::EpochMetrics;
+use crate::training::optimizer::Optimizer;
+use crate::training::training_options::TrainingOptions;
+use crate::utilities::util::WrappedUtils;
+
+/// Two neural networks running side by side on the same input.
+///
+/// The output is the output of the left branch followed by the output of the right branch.
+/// The branches do not share any weights, so training splits every target at the output size
+/// of the left branch and trains each branch on its own part with the regular training of
+/// the branch. The left branch is stored in `{model_directory}/left` and the right branch
+/// in `{model_directory}/right`.
+#[derive(Debug)]
+pub struct ParallelNetwork {
+    left: WrappedTrainableNeuralNetwork,
+    right: WrappedTrainableNeuralNetwork,
+    model_directory: Directory,
+    past_internal_model_directories: Vec<String>,
+    persist_on_drop: bool,
+    utils: WrappedUtils,
+    options: TrainingOptions,
+}
+
+impl ParallelNetwork {
+    /// Creates a new `ParallelNetwork` from its two branches.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input sizes of the branches differ.
+    pub fn new(
+        left: TrainableClassicNeuralNetwork,
+        right: TrainableClassicNeuralNetwork,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        if left.input_size() != right.input_size() {
+            return Err(format!(
+                "Parallel branches need equal input sizes, found {} and {}",
+                left.input_size(),
+                right.input_size()
+            )
+            .into());
+        }
+        let utils = left.get_utils();
+        let model_directory = utils.first_free_model_directory(&left.get_model_directory());
+        Ok(Self::from_branches(
+            WrappedTrainableNeuralNetwork::new(Box::new(left)),
+            WrappedTrainableNeuralNetwork::new(Box::new(right)),
+            Directory::Internal(model_directory),
+            utils,
+            TrainingOptions::default(),
+        ))
+    }
+
+    const fn from_branches(
+        left: WrappedTrainableNeuralNetwork,
+        right: WrappedTrainableNeuralNetwork,
+        model_directory: Directory,
+        utils: WrappedUtils,
+        options: TrainingOptions,
+    ) -> Self {
+        Self {
+            left,
+            right,
+            model_directory,
+            past_internal_model_directories: Vec::new(),
+            persist_on_drop: false,
+            utils,
+            options,
+        }
+    }
+
+    /// Returns the shapes of the left and the right branch.
+    #[must_use]
+    pub fn branch_shapes(&self) -> (NeuralNetworkShape, NeuralNetworkShape) {
+        (self.left.shape(), self.right.shape())
+    }
+
+    /// Splits every target at the output size of the left branch.
+    fn split_targets(
+        &self,
+        targets: &[Vec<f64>],
+    ) -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+        let left_output_size = self.left.output_size();
+        targets
+            .iter()
+            .map(|target| {
+                assert_eq!(target.len(), self.output_size(), "Target size must match output size");
+                let (left_target, right_target) = target.split_at(left_output_size);
+                (left_target.to_vec(), right_target.to_vec())
+            })
+            .unzip()
+    }
+}
+
+/// Returns the block diagonal shape of two branches running side by side.
+///
+/// The layer at every depth maps the inputs of both branch layers to their outputs, so its
+/// sizes are the sums of the sizes of the branch layers, except for the first layer which
+/// reads the shared input. A branch with fewer layers passes its output on unchanged. The
+/// activation is a composite of the activations of both branch layers unless they apply
+/// the same element wise activation.
+fn parallel_shape(
+    left: &NeuralNetworkShape,
+    right: &NeuralNetworkShape,
+) -> NeuralNetworkShape {
+    let input_size = left.layers.first().map_or(0, LayerShape::input_size);
+    let depth = left.layers.len().max(right.layers.len());
+    let branch_layer = |shape: &NeuralNetworkShape, i: usize| {
+        shape.layers.get(i).cloned().unwrap_or_else(|| {
+            let size = shape.layers.last().map_or(0, LayerShape::output_size);
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: size, output_size: size },
+                activation: ActivationData::new(ActivationType::Identity),
+            }
+        })
+    };
+    let layers = (0..depth)
+        .map(|i| {
+            let (left_layer, right_layer) = (branch_layer(left, i), branch_layer(right, i));
+            let layer_input_size = if i == 0 {
+                input_size
+            } else {
+                left_layer.input_size() + right_layer.input_size()
+            };
+            let output_size = left_layer.output_size() + right_layer.output_size();
+            let element_wise = matches!(
+                left_layer.activation.activation_type(),
+                ActivationType::ReLU
+                    | ActivationType::Sigmoid
+                    | ActivationType::Tanh
+                    | ActivationType::Identity
+            );
+            let activation = if element_wise && left_layer.activation == right_layer.activation {
+                left_layer.activation
+            } else {
+                let mut segments = Vec::new();
+                for (offset, layer) in [(0, &left_layer), (left_layer.output_size(), &right_layer)]
+                {
+                    if layer.activation.activation_type() == ActivationType::Composite {
+                        segments.extend(layer.activation.segments().iter().map(|segment| {
+                            ActivationSegment::new(
+                                segment.start + offset,
+                                segment.end + offset,
+                                segment.activation.clone(),
+                            )
+                        }));
+                    } else {
+                        segments.push(ActivationSegment::new(
+                            offset,
+                            offset + layer.output_size(),
+                            layer.activation.clone(),
+                        ));
+                    }
+                }
+                ActivationData::new_composite(segments)
+            };
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: layer_input_size, output_size },
+                activation,
+            }
+        })
+        .collect();
+    NeuralNetworkShape { layers }
+}
+
+fn append_dir(
+    model_directory: &str,
+    subdir: &str,
+) -> String {
+    format!("{model_directory}/{subdir}")
+}
+
+impl NeuralNetwork for ParallelNetwork {
+    fn predict(
+        &mut self,
+        input: Vec<f64>,
+    ) -> Vec<f64> {
+        let mut output = self.left.predict(input.clone());
+        output.extend(self.right.predict(input));
+        output
+    }
+
+    fn shape(&self) -> NeuralNetworkShape {
+        parallel_shape(&self.left.shape(), &self.right.shape())
+    }
+
+    fn save(
+        &mut self,
+        user_model_directory: String,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Directory::Internal(_) = self.model_directory {
+            self.past_internal_model_directories.push(self.model_directory.path());
+        }
+        self.model_directory = Directory::User(user_model_directory.clone());
+        self.left.save(append_dir(&user_model_directory, "left"))?;
+        self.right.save(append_dir(&user_model_directory, "right"))
+    }
+
+    fn get_model_directory(&self) -> Directory {
+        self.model_directory.clone()
+    }
+
+    fn allocate(&mut self) {
+        self.left.allocate();
+        self.right.allocate();
+    }
+
+    fn deallocate(&mut self) {
+        self.left.deallocate();
+        self.right.deallocate();
+    }
+
+    fn set_internal(&mut self) {
+        self.model_directory = Directory::Internal(self.model_directory.path());
+        self.left.set_internal();
+        self.right.set_internal();
+    }
+
+    fn persist_on_drop(&mut self) {
+        self.persist_on_drop = true;
+        self.left.persist_on_drop();
+        self.right.persist_on_drop();
+    }
+
+    fn duplicate(&self) -> WrappedNeuralNetwork {
+        unimplemented!()
+    }
+
+    fn get_utils(&self) -> WrappedUtils {
+        self.utils.clone()
+    }
+}
+
+impl TrainableNeuralNetwork for ParallelNetwork {
+    /// Trains the left branch on the first part of the targets and the right branch on the
+    /// rest. Every branch is updated by its own copy of `optimizer`, and `on_epoch_end`
+    /// receives the epochs of the left branch followed by those of the right branch.
+    /// Returns the mean of the accuracies of both branches.
+    fn train(
+        &mut self,
+        inputs: &[Vec<f64>],
+        targets: &[Vec<f64>],
+        learning_rate: f64,
+        epochs: usize,
+        tolerance: f64,
+        use_adam: bool,
+        optimizer: Option<Box<dyn Optimizer>>,
+        validation_split: f64,
+        sample_match_percentage: f64,
+        mut on_epoch_end: Option<&mut dyn FnMut(EpochMetrics)>,
+    ) -> f64 {
+        let (left_targets, right_targets) = self.split_targets(targets);
+        let left_accuracy = self.left.train(
+            inputs,
+            &left_targets,
+            learning_rate,
+            epochs,
+            tolerance,
+            use_adam,
+            optimizer.clone(),
+            validation_split,
+            sample_match_percentage,
+            on_epoch_end.as_mut().map(|f| &mut **f as &mut dyn FnMut(EpochMetrics)),
+        );
+        let right_accuracy = self.right.train(
+            inputs,
+            &right_targets,
+            learning_rate,
+            epochs,
+            tolerance,
+            use_adam,
+            optimizer,
+            validation_split,
+            sample_match_percentage,
+            on_epoch_end,
+        );
+        (left_accuracy + right_accuracy) / 2.0
+    }
+
+    /// Trains both branches like `train` on the split targets of the given samples.
+    #[allow(clippy::too_many_arguments)]
+    fn train_with_validation(
+        &mut self,
+        train_inputs: &[Vec<f64>],
+        train_targets: &[Vec<f64>],
+        validation_inputs: &[Vec<f64>],
+        validation_targets: &[Vec<f64>],
+        learning_rate: f64,
+        epochs: usize,
+        tolerance: f64,
+        use_adam: bool,
+        optimizer: Option<Box<dyn Optimizer>>,
+        sample_match_percentage: f64,
+        mut on_epoch_end: Option<&mut dyn FnMut(EpochMetrics)>,
+    ) -> f64 {
+        let (left_targets, right_targets) = self.split_targets(train_targets);
+        let (left_validation_targets, right_validation_targets) =
+            self.split_targets(validation_targets);
+        let left_accuracy = self.left.train_with_validation(
+            train_inputs,
+            &left_targets,
+            validation_inputs,
+            &left_validation_targets,
+            learning_rate,
+            epochs,
+            tolerance,
+            use_adam,
+            optimizer.clone(),
+            sample_match_percentage,
+            on_epoch_end.as_mut().map(|f| &mut **f as &mut dyn FnMut(EpochMetrics)),
+        );
+        let right_accuracy = self.right.train_with_validation(
+            train_inputs,
+            &right_targets,
+            validation_inputs,
+            &right_validation_targets,
+            learning_rate,
+            epochs,
+            tolerance,
+            use_adam,
+            optimizer,
+            sample_match_percentage,
+            on_epoch_end,
+        );
+        (left_accuracy + right_accuracy) / 2.0
+    }
+
+    fn train_batch(
+        &mut self,
+        inputs: &[Vec<f64>],
+        targets: &[Vec<f64>],
+        learning_rate: f64,
+        epochs: usize,
+        tolerance: f64,
+        batch_size: usize,
+        optimizer: Option<Box<dyn Optimizer>>,
+    ) {
+        let (left_targets, right_targets) = self.split_targets(targets);
+        self.left.train_batch(
+            inputs,
+            &left_targets,
+            learning_rate,
+            epochs,
+            tolerance,
+            batch_size,
+            optimizer.clone(),
+        );
+        self.right.train_batch(
+            inputs,
+            &right_targets,
+            learning_rate,
+            epochs,
+            tolerance,
+            batch_size,
+            optimizer,
+        );
+    }
+
+    fn train_loader(
+        &mut self,
+        loader: &DataLoader,
+        learning_rate: f64,
+        epochs: usize,
+        tolerance: f64,
+        optimizer: Option<Box<dyn Optimizer>>,
+    ) {
+        let (left_targets, right_targets) = self.split_targets(loader.targets());
+        self.left.train_loader(
+            &loader.with_targets(&left_targets),
+            learning_rate,
+            epochs,
+            tolerance,
+            optimizer.clone(),
+        );
+        self.right.train_loader(
+            &loader.with_targets(&right_targets),
+            learning_rate,
+            epochs,
+            tolerance,
+            optimizer,
+        );
+    }
+
+    fn compute_batch_gradients(
+        &mut self,
+        _inputs: &[Vec<f64>],
+        _targets: &[Vec<f64>],
+        _loss_function: &LossFunction,
+    ) -> Result<Vec<LayerGradients>, Box<dyn std::error::Error>> {
+        Err("Batch gradients are only supported for classic neural networks".into())
+    }
+
+    fn partial_fit(
+        &mut self,
+        _input: &[f64],
+        _target: &[f64],
+        _learning_rate: f64,
+        _loss_function: &LossFunction,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        Err("Online training is only supported for classic neural networks".into())
+    }
+
+    /// Every output only depends on the branch producing it, so its saliency is the saliency
+    /// of that branch.
+    fn saliency(
+        &mut self,
+        input: &[f64],
+        output_index: usize,
+    ) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        let left_output_size = self.left.output_size();
+        if output_index < left_output_size {
+            self.left.saliency(input, output_index)
+        } else {
+            self.right.saliency(input, output_index - left_output_size)
+        }
+    }
+
+    fn input_size(&self) -> usize {
+        self.left.input_size()
+    }
+
+    fn output_size(&self) -> usize {
+        self.left.output_size() + self.right.output_size()
+    }
+
+    /// Returns the durations of the layers of the left branch followed by those of the right.
+    fn profile_forward(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<Duration> {
+        let mut durations = self.left.profile_forward(input);
+        durations.extend(self.right.profile_forward(input));
+        durations
+    }
+
+    /// Returns the report of the left branch followed by the report of the right branch.
+    fn dead_neuron_report(
+        &mut self,
+        inputs: &[Vec<f64>],
+    ) -> Vec<usize> {
+        let mut report = self.left.dead_neuron_report(inputs);
+        report.extend(self.right.dead_neuron_report(inputs));
+        report
+    }
+
+    /// The loss is applied to the output of every branch on its own.
+    fn training_options(&self) -> TrainingOptions {
+        self.options.clone()
+    }
+
+    /// The multiplier of layer `i` applies to layer `i` of both branches.
+    fn set_training_options(
+        &mut self,
+        options: TrainingOptions,
+    ) {
+        let branch_options = |subdir: &str, num_layers: usize| TrainingOptions {
+            lr_multipliers: options
+                .lr_multipliers
+                .as_ref()
+                .map(|multipliers| multipliers.iter().copied().take(num_layers).collect()),
+            ..options.for_subdirectory(subdir)
+        };
+        self.left.set_training_options(branch_options("left", self.left.shape().layers.len()));
+        self.right.set_training_options(branch_options("right", self.right.shape().layers.len()));
+        self.options = options;
+    }
+
+    fn set_loss_function(
+        &mut self,
+        loss_function: LossFunction,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.left.set_loss_function(loss_function.clone())?;
+        self.right.set_loss_function(loss_function)
+    }
+
+    fn set_logits_loss(
+        &mut self,
+        enabled: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.left.set_logits_loss(enabled)?;
+        self.right.set_logits_loss(enabled)
+    }
+
+    fn swa_weights(&self) -> Option<Vec<f64>> {
+        let mut flat = self.left.swa_weights()?;
+        flat.extend(self.right.swa_weights()?);
+        Some(flat)
+    }
+
+    fn reinitialize(
+        &mut self,
+        seed: u64,
+    ) {
+        self.left.reinitialize(seed);
+        self.right.reinitialize(seed.wrapping_add(1));
+    }
+
+    fn set_spectral_norm(
+        &mut self,
+        enabled: bool,
+    ) {
+        self.left.set_spectral_norm(enabled);
+        self.right.set_spectral_norm(enabled);
+    }
+
+    fn get_weight(
+        &self,
+        _layer_index: usize,
+        _row: usize,
+        _col: usize,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        Err("Accessing single weights is only supported for classic neural networks".into())
+    }
+
+    fn set_weight(
+        &mut self,
+        _layer_index: usize,
+        _row: usize,
+        _col: usize,
+        _value: f64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Err("Accessing single weights is only supported for classic neural networks".into())
+    }
+
+    fn get_bias(
+        &self,
+        _layer_index: usize,
+        _row: usize,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        Err("Accessing single biases is only supported for classic neural networks".into())
+    }
+
+    fn set_bias(
+        &mut self,
+        _layer_index: usize,
+        _row: usize,
+        _value: f64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Err("Accessing single biases is only supported for classic neural networks".into())
+    }
+
+    fn spectral_norms(
+        &self,
+        _iterations: usize,
+    ) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        Err("Spectral norms are only supported for classic neural networks".into())
+    }
+
+    fn prune_neurons(
+        &mut self,
+        _layer_index: usize,
+        _keep_indices: &[usize],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // the layers of the exposed shape are spread over both branches
+        Err("Pruning neurons is only supported for classic neural networks".into())
+    }
+
+    fn add_identity_layer(
+        &mut self,
+        _after_index: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // the layers of the exposed shape are spread over both branches
+        Err("Inserting layers is only supported for classic neural networks".into())
+    }
+
+    fn push_layer(
+        &mut self,
+        _layer_shape: LayerShape,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // the layers of the exposed shape are spread over both branches
+        Err("Appending layers is only supported for classic neural networks".into())
+    }
+
+    fn widen_layer(
+        &mut self,
+        _layer_index: usize,
+        _new_width: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // the layers of the exposed shape are spread over both branches
+        Err("Widening layers is only supported for classic neural networks".into())
+    }
+
+    fn duplicate_trainable(&self) -> WrappedTrainableNeuralNetwork {
+        let model_directory = self.utils.first_free_model_directory(&self.model_directory);
+        WrappedTrainableNeuralNetwork::new(Box::new(Self::from_branches(
+            self.left.duplicate_trainable(),
+            self.right.duplicate_trainable(),
+            Directory::Internal(model_directory),
+            self.utils.clone(),
+            self.options.clone(),
+        )))
+    }
+
+    fn to_inference(&self) -> WrappedNeuralNetwork {
+        WrappedNeuralNetwork::new(Box::new(InferenceParallelNetwork {
+            left: self.left.to_inference(),
+            right: self.right.to_inference(),
+            model_directory: Directory::Internal(
+                self.utils.first_free_model_directory(&self.model_directory),
+            ),
+            utils: self.utils.clone(),
+        }))
+    }
+
+    /// Returns the flat weights of the left branch followed by those of the right branch.
+    fn weights_flat(&self) -> Vec<f64> {
+        let mut flat = self.left.weights_flat();
+        flat.extend(self.right.weights_flat());
+        flat
+    }
+
+    fn load_weights_flat(
+        &mut self,
+        weights: &[f64],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        load_weights_flat_sequentially(vec![&mut self.left, &mut self.right], weights)
+    }
+
+    fn num_parameters(&self) -> usize {
+        self.left.num_parameters() + self.right.num_parameters()
+    }
+}
+
+impl Drop for ParallelNetwork {
+    fn drop(&mut self) {
+        if self.persist_on_drop {
+            return;
+        }
+        // the branches remove their own directories, only the parallel directories are left
+        if let Directory::Internal(dir) = &self.model_directory {
+            if std::fs::metadata(dir).is_ok() {
+                std::fs::remove_dir_all(dir).unwrap();
+            }
+        }
+        for dir in &self.past_internal_model_directories {
+            if dir != &self.model_directory.path() && std::fs::metadata(dir).is_ok() {
+                std::fs::remove_dir_all(dir).unwrap();
+            }
+        }
+    }
+}
+
+/// The forward only counterpart of `ParallelNetwork` returned by its `to_inference`.
+#[derive(Debug)]
+struct InferenceParallelNetwork {
+    left: WrappedNeuralNetwork,
+    right: WrappedNeuralNetwork,
+    model_directory: Directory,
+    utils: WrappedUtils,
+}
+
+impl NeuralNetwork for InferenceParallelNetwork {
+    fn predict(
+        &mut self,
+        input: Vec<f64>,
+    ) -> Vec<f64> {
+        let mut output = self.left.predict(input.clone());
+        output.extend(self.right.predict(input));
+        output
+    }
+
+    fn shape(&self) -> NeuralNetworkShape {
+        parallel_shape(&self.left.shape(), &self.right.shape())
+    }
+
+    fn save(
+        &mut self,
+        user_model_directory: String,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.model_directory = Directory::User(user_model_directory.clone());
+        self.left.save(append_dir(&user_model_directory, "left"))?;
+        self.right.save(append_dir(&user_model_directory, "right"))
+    }
+
+    fn get_model_directory(&self) -> Directory {
+        self.model_directory.clone()
+    }
+
+    fn allocate(&mut self) {
+        self.left.allocate();
+        self.right.allocate();
+    }
+
+    fn deallocate(&mut self) {
+        self.left.deallocate();
+        self.right.deallocate();
+    }
+
+    fn set_internal(&mut self) {
+        self.model_directory = Directory::Internal(self.model_directory.path());
+        self.left.set_internal();
+        self.right.set_internal();
+    }
+
+    fn persist_on_drop(&mut self) {
+        self.left.persist_on_drop();
+        self.right.persist_on_drop();
+    }
+
+    fn duplicate(&self) -> WrappedNeuralNetwork {
+        WrappedNeuralNetwork::new(Box::new(Self {
+            left: self.left.duplicate(),
+            right: self.right.duplicate(),
+            model_directory: Directory::Internal(
+                self.utils.first_free_model_directory(&self.model_directory),
+            ),
+            utils: self.utils.clone(),
+        }))
+    }
+
+    fn get_utils(&self) -> WrappedUtils {
+        self.utils.clone()
+    }
+}
+
+impl Drop for InferenceParallelNetwork {
+    fn drop(&mut self) {
+        if let Directory::Internal(dir) = &self.model_directory {
+            if std::fs::metadata(dir).is_ok() {
+                std::fs::remove_dir_all(dir).unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::training::adam::AdamParams;
+    use crate::utilities::util::Utils;
+
+    fn dense_network(
+        input_size: usize,
+        output_size: usize,
+        model_directory: &str,
+        utils: WrappedUtils,
+    ) -> TrainableClassicNeuralNetwork {
+        TrainableClassicNeuralNetwork::new(
+            NeuralNetworkShape {
+                layers: vec![LayerShape {
+                    layer_type: LayerType::Dense { input_size, output_size },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                }],
+            },
+            &Directory::Internal(model_directory.to_string()),
+            utils,
+        )
+    }
+
+    fn squared_errors(
+        nn: &mut ParallelNetwork,
+        inputs: &[Vec<f64>],
+        targets: &[Vec<f64>],
+        split: usize,
+    ) -> (f64, f64) {
+        let mut errors = (0.0, 0.0);
+        for (input, target) in inputs.iter().zip(targets) {
+            for (i, (o, t)) in nn.predict(input.clone()).iter().zip(target).enumerate() {
+                if i < split {
+                    errors.0 += (o - t) * (o - t);
+                } else {
+                    errors.1 += (o - t) * (o - t);
+                }
+            }
+        }
+        errors
+    }
+
+    #[test]
+    fn test_parallel_network_train() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let left = dense_network(3, 2, "test_model_parallel_left", utils.clone());
+        let right = dense_network(3, 3, "test_model_parallel_right", utils.clone());
+        let mut nn = left.parallel(&right).unwrap();
+        assert_eq!(nn.input_size(), 3);
+        assert_eq!(nn.output_size(), 5);
+        assert_eq!(nn.branch_shapes(), (left.shape(), right.shape()));
+        // both branches use the same activation, so the combined layer does as well
+        let shape = nn.shape();
+        assert!(shape.is_valid());
+        assert_eq!(shape.layers.len(), 1);
+        assert_eq!(shape.layers[0].input_size(), 3);
+        assert_eq!(shape.layers[0].output_size(), 5);
+        assert_eq!(nn.num_parameters(), shape.num_parameters());
+
+        let mismatched = dense_network(4, 1, "test_model_parallel_mismatched", utils);
+        assert!(left.parallel(&mismatched).is_err());
+
+        let inputs = vec![vec![1.0, 0.5, -0.5], vec![-1.0, 0.2, 0.7]];
+        let targets = vec![vec![0.9, 0.1, 0.2, 0.8, 0.5], vec![0.1, 0.9, 0.8, 0.2, 0.5]];
+        assert_eq!(nn.predict(inputs[0].clone()).len(), 5);
+
+        // the branches are trained with their regular training, e.g. with Adam and its params
+        nn.set_training_options(TrainingOptions {
+            adam_params: AdamParams::new(0.9, 0.99, 1e-8),
+            ..nn.training_options()
+        });
+        let before = squared_errors(&mut nn, &inputs, &targets, 2);
+        let mut epochs = 0;
+        let mut on_epoch_end = |_: EpochMetrics| epochs += 1;
+        nn.train(&inputs, &targets, 0.05, 100, 0.1, true, None, 1.0, 1.0, Some(&mut on_epoch_end));
+        assert_eq!(epochs, 200);
+        let after = squared_errors(&mut nn, &inputs, &targets, 2);
+        assert!(after.0 < before.0);
+        assert!(after.1 < before.1);
+
+        // the outputs of the right branch only depend on the right branch
+        let saliency = nn.saliency(&inputs[0], 3).unwrap();
+        assert_eq!(saliency.len(), 3);
+
+        let weights = nn.weights_flat();
+        let mut duplicate = nn.duplicate_trainable();
+        assert_eq!(duplicate.weights_flat(), weights);
+        assert_eq!(duplicate.predict(inputs[1].clone()), nn.predict(inputs[1].clone()));
+        let mut inference = nn.to_inference();
+        assert_eq!(inference.predict(inputs[1].clone()), nn.predict(inputs[1].clone()));
+    }
+
+    #[test]
+    fn test_parallel_shape_of_branches_with_different_depths() {
+        let sigmoid = ActivationData::new(ActivationType::Sigmoid);
+        let left = NeuralNetworkShape {
+            layers: vec![
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 4 },
+                    activation: ActivationData::new(ActivationType::ReLU),
+                },
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 4, output_size: 2 },
+                    activation: sigmoid.clone(),
+                },
+            ],
+        };
+        let right = NeuralNetworkShape {
+            layers: vec![LayerShape {
+                layer_type: LayerType::Dense { input_size: 3, output_size: 1 },
+                activation: sigmoid.clone(),
+            }],
+        };
+        let shape = parallel_shape(&left, &right);
+        assert!(shape.is_valid());
+        assert_eq!(shape.layers[0].layer_type, LayerType::Dense { input_size: 3, output_size: 5 });
+        assert_eq!(shape.layers[0].activation.activation_type(), ActivationType::Composite);
+        // the right branch passes its output through the second layer unchanged
+        assert_eq!(shape.layers[1].layer_type, LayerType::Dense { input_size: 5, output_size: 3 });
+        assert_eq!(
+            shape.layers[1].activation.segments(),
+            &[
+                ActivationSegment::new(0, 2, sigmoid),
+                ActivationSegment::new(2, 3, ActivationData::new(ActivationType::Identity)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use crate::activation::{activate::ActivationTrait, registry::create_activation};
+use crate::layer::quantized::QuantizedDenseLayer;
+use crate::nn::shape::{LayerType, NeuralNetworkShape};
+
+use std::error::Error;
+
+/// A neural network of int8 quantized dense layers for deployment, created by `to_quantized`.
+/// It is held in memory and dequantizes the weights on the fly in every prediction.
+#[derive(Debug, Clone)]
+pub struct QuantizedNeuralNetwork {
+    layers: Vec<QuantizedDenseLayer>,
+    activations: Vec<Box<dyn ActivationTrait + Send>>,
+    shape: NeuralNetworkShape,
+}
+
+impl QuantizedNeuralNetwork {
+    /// Creates a new `QuantizedNeuralNetwork` with one quantized layer for every layer of the shape.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the shape has a layer which is not dense or if the sizes of the
+    /// quantized layers do not match the shape.
+    pub fn new(
+        shape: NeuralNetworkShape,
+        layers: Vec<QuantizedDenseLayer>,
+    ) -> Result<Self, Box<dyn Error>> {
+        if layers.len() != shape.layers.len() {
+            return Err(format!(
+                "Expected {} quantized layers, found {}",
+                shape.layers.len(),
+                layers.len()
+            )
+            .into());
+        }
+        check_quantizable(&shape)?;
+        for (i, (layer_shape, layer)) in shape.layers.iter().zip(&layers).enumerate() {
+            if layer.input_size() != layer_shape.input_size()
+                || layer.output_size() != layer_shape.output_size()
+            {
+                return Err(format!("Quantized layer {i} does not match the shape").into());
+            }
+        }
+        let activations = shape
+            .layers
+            .iter()
+            .map(|layer_shape| create_activation(&layer_shape.activation))
+            .collect();
+        Ok(Self { layers, activations, shape })
+    }
+
+    /// Predicts the output for the input with the dequantized weights.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the input size does not match the input size of the network.
+    pub fn predict(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<f64> {
+        let mut output = input.to_vec();
+        for (layer, activation) in self.layers.iter().zip(&mut self.activations) {
+            output = activation.forward(&layer.forward(&output));
+        }
+        output
+    }
+
+    #[must_use]
+    pub const fn shape(&self) -> &NeuralNetworkShape {
+        &self.shape
+    }
+
+    #[must_use]
+    pub fn layers(&self) -> &[QuantizedDenseLayer] {
+        &self.layers
+    }
+}
+
+/// Returns an error naming the first layer of the shape which is not dense.
+pub(crate) fn check_quantizable(shape: &NeuralNetworkShape) -> Result<(), Box<dyn Error>> {
+    shape
+        .layers
+        .iter()
+        .position(|layer_shape| !matches!(layer_shape.layer_type(), LayerType::Dense { .. }))
+        .map_or(Ok(()), |i| Err(format!("Layer {i} is not dense and cannot be quantized").into()))
+}
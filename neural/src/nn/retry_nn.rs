@@ -1,23 +1,31 @@
 use std::path::Path;
+use std::time::Duration;
 
 use num_traits::NumCast;
 
 use super::nn_factory::copy_dir_recursive;
 use super::nn_factory::neural_network_from_disk;
 use super::nn_factory::trainable_neural_network_from_disk;
+use super::nn_trait::load_weights_flat_sequentially;
 use super::nn_trait::WrappedNeuralNetwork;
 use super::nn_trait::WrappedTrainableNeuralNetwork;
 use super::shape::NeuralNetworkShape;
 
+use crate::data::loader::DataLoader;
+use crate::layer::layer_trait::LayerGradients;
 use crate::nn::directory::Directory;
 use crate::nn::neuralnet::ClassicNeuralNetwork;
 use crate::nn::neuralnet::TrainableClassicNeuralNetwork;
-use crate::nn::nn_factory::get_first_free_model_directory;
 use crate::nn::nn_trait::NeuralNetwork;
 use crate::nn::nn_trait::TrainableNeuralNetwork;
+use crate::nn::shape::ActivationData;
 use crate::nn::shape::AnnotatedNeuralNetworkShape;
 use crate::nn::shape::LayerShape;
 use crate::nn::shape::LayerType;
+use crate::training::loss::LossFunction;
+use crate::training::metrics::EpochMetrics;
+use crate::training::optimizer::Optimizer;
+use crate::training::training_options::TrainingOptions;
 use crate::utilities::util::WrappedUtils;
 
 #[derive(Debug)]
@@ -28,24 +36,33 @@ pub struct RetryNeuralNetwork {
     shape: NeuralNetworkShape,
     model_directory: Directory,
     past_internal_model_directories: Vec<String>,
+    persist_on_drop: bool,
     utils: WrappedUtils,
+    internal_dims: usize,
 }
 
 impl RetryNeuralNetwork {
     /// Creates a new `RetryNeuralNetwork` with the given shape, levels, and model directory.
     ///
+    /// The `internal_dims` internal units appended to every layer use `internal_activation`
+    /// if given, otherwise they share the activation of their layer.
+    ///
     /// # Panics
     ///
-    /// This function will panic if the internal model directory is invalid or if the
-    /// neural networks cannot be created.
+    /// This function will panic if `internal_dims` is zero, if the internal model directory
+    /// is invalid or if the neural networks cannot be created.
     #[must_use]
     pub fn new(
         shape: NeuralNetworkShape,
         levels: i32,
+        internal_activation: Option<ActivationData>,
+        internal_dims: usize,
         internal_model_directory: String,
         utils: WrappedUtils,
     ) -> Self {
-        let actual_shape = add_internal_dimensions(&shape);
+        assert!(internal_dims > 0, "A retry network needs at least one internal dimension");
+        let actual_shape =
+            add_internal_dimensions(&shape, internal_activation.as_ref(), internal_dims);
         let primary_nn = WrappedNeuralNetwork::new(Box::new(ClassicNeuralNetwork::new(
             actual_shape,
             append_dir(internal_model_directory.clone(), "primary"),
@@ -55,6 +72,8 @@ impl RetryNeuralNetwork {
             1..=i32::MAX => WrappedNeuralNetwork::new(Box::new(Self::new(
                 shape.clone(),
                 levels - 1,
+                internal_activation,
+                internal_dims,
                 append_dir(internal_model_directory.clone(), "backup"),
                 utils.clone(),
             ))),
@@ -71,7 +90,9 @@ impl RetryNeuralNetwork {
             shape,
             model_directory: Directory::Internal(internal_model_directory),
             past_internal_model_directories: vec![],
+            persist_on_drop: false,
             utils,
+            internal_dims,
         }
     }
 
@@ -94,13 +115,16 @@ impl RetryNeuralNetwork {
             ));
             let backup_nn = Self::from_disk(backup_model_directory, utils.clone());
             let shape = backup_nn.shape();
+            let internal_dims = internal_dims_of(&primary_nn.shape(), &shape);
             WrappedNeuralNetwork::new(Box::new(Self {
                 primary_nn,
                 backup_nn,
                 shape,
                 model_directory: Directory::User(model_directory),
                 past_internal_model_directories: vec![],
+                persist_on_drop: false,
                 utils,
+                internal_dims,
             }))
         } else {
             WrappedNeuralNetwork::new(Box::new(
@@ -114,29 +138,91 @@ impl RetryNeuralNetwork {
         input: Vec<f64>,
     ) -> Vec<f64> {
         let primary_output = self.primary_nn.predict(input.clone());
-        // if the last value in primary output is as close to zero as some tolerance, then we need to use the backup neural network
-        if (primary_output[primary_output.len() - 1] - 1.0).abs() < 0.2 {
+        // if the largest internal value in primary output is closer to one than to zero, then we need to use the backup neural network
+        let num_outputs = primary_output.len() - self.internal_dims;
+        let internal_value = max_internal_value(&primary_output[num_outputs..]);
+        if signals_retry(internal_value) {
             self.backup_nn.predict(input)
         } else {
-            // return the primary output despite the last internal value
-            primary_output[0..primary_output.len() - 1].to_vec()
+            // return the primary output despite the internal values
+            primary_output[0..num_outputs].to_vec()
         }
     }
 }
 
-fn add_internal_dimensions(shape: &NeuralNetworkShape) -> NeuralNetworkShape {
+/// The number of samples below which a retry network does not train by default.
+pub const DEFAULT_MIN_TRAINING_SAMPLES: usize = 100;
+
+/// Returns the number of internal units of a primary network whose retry network exposes `shape`.
+fn internal_dims_of(
+    primary_shape: &NeuralNetworkShape,
+    shape: &NeuralNetworkShape,
+) -> usize {
+    primary_shape.layers.last().map_or(0, LayerShape::output_size)
+        - shape.layers.last().map_or(0, LayerShape::output_size)
+}
+
+/// Returns the largest of the internal values, which decides whether to retry.
+fn max_internal_value(internal_values: &[f64]) -> f64 {
+    internal_values.iter().copied().fold(f64::NEG_INFINITY, f64::max)
+}
+
+/// The internal value above which the backup network takes over.
+///
+/// The internal units are trained towards zero for the samples the primary network gets right
+/// and towards one for those the backup network has to take over, so whatever their activation,
+/// the midpoint of the two labels separates the two cases.
+const RETRY_THRESHOLD: f64 = 0.5;
+
+/// Returns whether the largest internal value signals that the backup network is needed.
+fn signals_retry(internal_value: f64) -> bool {
+    internal_value > RETRY_THRESHOLD
+}
+
+/// Returns the activation of a layer extended by `internal_dims` internal units.
+fn with_internal_units(
+    activation: &ActivationData,
+    internal_activation: Option<&ActivationData>,
+    internal_dims: usize,
+) -> ActivationData {
+    internal_activation.map_or_else(
+        || activation.clone(),
+        |internal_activation| {
+            activation
+                .clone()
+                .with_internal_activation(internal_activation.clone())
+                .with_internal_units(internal_dims)
+        },
+    )
+}
+
+fn add_internal_dimensions(
+    shape: &NeuralNetworkShape,
+    internal_activation: Option<&ActivationData>,
+    internal_dims: usize,
+) -> NeuralNetworkShape {
     // Add internal dimensions to the shape
     let mut annotated_shape = AnnotatedNeuralNetworkShape::new(shape);
     let first_layer = shape.layers.first().unwrap();
 
     // Add internal dimensions to the first layer
     let internal_layer = first_layer.clone();
-    let new_dense_layer_type = LayerShape {
-        layer_type: LayerType::Dense {
+    let layer_type = match internal_layer.layer_type() {
+        LayerType::Embedding { num_embeddings, dim } => {
+            LayerType::Embedding { num_embeddings, dim: dim + internal_dims }
+        },
+        LayerType::Dense { .. } | LayerType::PReLU { .. } => LayerType::Dense {
             input_size: internal_layer.input_size(),
-            output_size: internal_layer.output_size() + 1,
+            output_size: internal_layer.output_size() + internal_dims,
         },
-        activation: internal_layer.activation,
+    };
+    let new_dense_layer_type = LayerShape {
+        layer_type,
+        activation: with_internal_units(
+            &internal_layer.activation,
+            internal_activation,
+            internal_dims,
+        ),
     };
     annotated_shape.change_layer(0, new_dense_layer_type);
 
@@ -144,18 +230,42 @@ fn add_internal_dimensions(shape: &NeuralNetworkShape) -> NeuralNetworkShape {
     for (i, layer) in shape.layers.iter().skip(1).enumerate() {
         // Add internal dimensions to the layer
         let internal_layer = layer.clone();
-        let new_dense_layer_type = LayerShape {
-            layer_type: LayerType::Dense {
-                input_size: internal_layer.input_size() + 1,
-                output_size: internal_layer.output_size() + 1,
+        let layer_type = match internal_layer.layer_type() {
+            LayerType::PReLU { size } => LayerType::PReLU { size: size + internal_dims },
+            LayerType::Dense { .. } | LayerType::Embedding { .. } => LayerType::Dense {
+                input_size: internal_layer.input_size() + internal_dims,
+                output_size: internal_layer.output_size() + internal_dims,
             },
-            activation: internal_layer.activation.clone(),
+        };
+        let new_dense_layer_type = LayerShape {
+            layer_type,
+            activation: with_internal_units(
+                &internal_layer.activation,
+                internal_activation,
+                internal_dims,
+            ),
         };
         annotated_shape.change_layer(i + 1, new_dense_layer_type);
     }
     annotated_shape.to_neural_network_shape()
 }
 
+/// Returns whether enough outputs of a prediction lie within `tolerance` of the target.
+fn sample_matches(
+    prediction: &[f64],
+    target: &[f64],
+    tolerance: f64,
+    sample_match_percentage: f64,
+) -> bool {
+    let nb_correct_outputs =
+        prediction.iter().zip(target.iter()).filter(|(o, t)| (*o - *t).abs() < tolerance).count();
+    let nb_correct_f64: f64 =
+        NumCast::from(nb_correct_outputs).expect("Failed to convert nb_correct_outputs to f64");
+    let target_len_f64: f64 =
+        NumCast::from(target.len()).expect("Failed to convert target.len() to f64");
+    nb_correct_f64 / target_len_f64 >= sample_match_percentage
+}
+
 fn append_dir(
     model_directory: String,
     subdir: &str,
@@ -213,8 +323,14 @@ impl NeuralNetwork for RetryNeuralNetwork {
         self.backup_nn.set_internal();
     }
 
+    fn persist_on_drop(&mut self) {
+        self.persist_on_drop = true;
+        self.primary_nn.persist_on_drop();
+        self.backup_nn.persist_on_drop();
+    }
+
     fn duplicate(&self) -> WrappedNeuralNetwork {
-        let new_model_directory = get_first_free_model_directory(&self.model_directory);
+        let new_model_directory = self.utils.first_free_model_directory(&self.model_directory);
         copy_dir_recursive(
             Path::new(&self.model_directory.path()),
             Path::new(&new_model_directory),
@@ -240,6 +356,9 @@ impl Drop for RetryNeuralNetwork {
             }
             self.deallocate();
         }
+        if self.persist_on_drop {
+            return;
+        }
         // Remove the internal model directory from disk
         if let Directory::Internal(dir) = &self.model_directory {
             if std::fs::metadata(dir).is_ok() {
@@ -263,24 +382,37 @@ pub struct TrainableRetryNeuralNetwork {
     shape: NeuralNetworkShape,
     model_directory: Directory,
     past_internal_model_directories: Vec<String>,
+    persist_on_drop: bool,
     utils: WrappedUtils,
+    internal_dims: usize,
+    options: TrainingOptions,
+    min_training_samples: usize,
+    insufficient_samples_accuracy: f64,
+    backup_usage: Option<f64>,
 }
 
 impl TrainableRetryNeuralNetwork {
     /// Creates a new `TrainableRetryNeuralNetwork` with the given shape, levels, and model directory.
     ///
+    /// The `internal_dims` internal units appended to every layer use `internal_activation`
+    /// if given, otherwise they share the activation of their layer.
+    ///
     /// # Panics
     ///
-    /// This function will panic if the internal model directory is invalid or if the
-    /// neural networks cannot be created.
+    /// This function will panic if `internal_dims` is zero, if the internal model directory
+    /// is invalid or if the neural networks cannot be created.
     #[must_use]
     pub fn new(
         shape: NeuralNetworkShape,
         levels: i32,
+        internal_activation: Option<ActivationData>,
+        internal_dims: usize,
         internal_model_directory: String,
         utils: WrappedUtils,
     ) -> Self {
-        let actual_shape = add_internal_dimensions(&shape);
+        assert!(internal_dims > 0, "A retry network needs at least one internal dimension");
+        let actual_shape =
+            add_internal_dimensions(&shape, internal_activation.as_ref(), internal_dims);
         let primary_nn =
             WrappedTrainableNeuralNetwork::new(Box::new(TrainableClassicNeuralNetwork::new(
                 actual_shape,
@@ -291,6 +423,8 @@ impl TrainableRetryNeuralNetwork {
             1..=i32::MAX => WrappedTrainableNeuralNetwork::new(Box::new(Self::new(
                 shape.clone(),
                 levels - 1,
+                internal_activation,
+                internal_dims,
                 append_dir(internal_model_directory.clone(), "backup"),
                 utils.clone(),
             ))),
@@ -307,10 +441,37 @@ impl TrainableRetryNeuralNetwork {
             shape,
             model_directory: Directory::Internal(internal_model_directory),
             past_internal_model_directories: vec![],
+            persist_on_drop: false,
             utils,
+            internal_dims,
+            options: TrainingOptions::default(),
+            min_training_samples: DEFAULT_MIN_TRAINING_SAMPLES,
+            insufficient_samples_accuracy: 0.0,
+            backup_usage: None,
         }
     }
 
+    /// Sets the number of samples below which `train` does not train at all and returns
+    /// `insufficient_samples_accuracy` instead.
+    #[must_use]
+    pub const fn with_min_training_samples(
+        mut self,
+        min_training_samples: usize,
+        insufficient_samples_accuracy: f64,
+    ) -> Self {
+        self.min_training_samples = min_training_samples;
+        self.insufficient_samples_accuracy = insufficient_samples_accuracy;
+        self
+    }
+
+    /// Returns the fraction of the held out samples of the last `train` call which were
+    /// routed to the backup network, or `None` if the last call did not train.
+    /// A value near zero means the primary network handles almost all samples by itself.
+    #[must_use]
+    pub const fn backup_usage(&self) -> Option<f64> {
+        self.backup_usage
+    }
+
     /// Creates a new `TrainableRetryNeuralNetwork` from the given model directory.
     ///
     /// # Panics
@@ -331,13 +492,20 @@ impl TrainableRetryNeuralNetwork {
             ));
             let backup_nn = Self::from_disk(backup_model_directory, utils.clone());
             let shape = backup_nn.shape();
+            let internal_dims = internal_dims_of(&primary_nn.shape(), &shape);
             WrappedTrainableNeuralNetwork::new(Box::new(Self {
                 primary_nn,
                 backup_nn,
                 shape,
                 model_directory: Directory::User(model_directory),
                 past_internal_model_directories: vec![],
+                persist_on_drop: false,
                 utils,
+                internal_dims,
+                options: TrainingOptions::default(),
+                min_training_samples: DEFAULT_MIN_TRAINING_SAMPLES,
+                insufficient_samples_accuracy: 0.0,
+                backup_usage: None,
             }))
         } else {
             WrappedTrainableNeuralNetwork::new(Box::new(
@@ -350,13 +518,23 @@ impl TrainableRetryNeuralNetwork {
         &mut self,
         input: Vec<f64>,
     ) -> Vec<f64> {
+        self.route(input).0
+    }
+
+    /// Predicts the output for the input and returns whether the backup network was used.
+    fn route(
+        &mut self,
+        input: Vec<f64>,
+    ) -> (Vec<f64>, bool) {
         let primary_output = self.primary_nn.predict(input.clone());
-        // if the last value in primary output is as close to zero as some tolerance, then we need to use the backup neural network
-        if primary_output[primary_output.len() - 1].abs() < 0.05 {
-            self.backup_nn.predict(input)
+        // if the largest internal value in primary output is closer to one than to zero, then we need to use the backup neural network
+        let num_outputs = primary_output.len() - self.internal_dims;
+        let internal_value = max_internal_value(&primary_output[num_outputs..]);
+        if signals_retry(internal_value) {
+            (self.backup_nn.predict(input), true)
         } else {
-            // return the primary output despite the last internal value
-            primary_output[0..primary_output.len() - 1].to_vec()
+            // return the primary output despite the internal values
+            (primary_output[0..num_outputs].to_vec(), false)
         }
     }
 }
@@ -408,6 +586,12 @@ impl NeuralNetwork for TrainableRetryNeuralNetwork {
         self.backup_nn.set_internal();
     }
 
+    fn persist_on_drop(&mut self) {
+        self.persist_on_drop = true;
+        self.primary_nn.persist_on_drop();
+        self.backup_nn.persist_on_drop();
+    }
+
     fn duplicate(&self) -> WrappedNeuralNetwork {
         unimplemented!()
     }
@@ -418,6 +602,7 @@ impl NeuralNetwork for TrainableRetryNeuralNetwork {
 }
 
 impl TrainableNeuralNetwork for TrainableRetryNeuralNetwork {
+    #[allow(clippy::too_many_lines)]
     fn train(
         &mut self,
         inputs: &[Vec<f64>],
@@ -426,112 +611,156 @@ impl TrainableNeuralNetwork for TrainableRetryNeuralNetwork {
         epochs: usize,
         tolerance: f64,
         use_adam: bool,
+        optimizer: Option<Box<dyn Optimizer>>,
         validation_split: f64,
         sample_match_percentage: f64,
+        on_epoch_end: Option<&mut dyn FnMut(EpochMetrics)>,
     ) -> f64 {
-        // in case one does not have enough samples, don't train and return zero accuracy
-        if inputs.len() < 100 {
-            return 0.0;
+        self.backup_usage = None;
+        // in case one does not have enough samples, don't train and return the sentinel
+        if inputs.is_empty() || inputs.len() < self.min_training_samples {
+            return self.insufficient_samples_accuracy;
         }
+        // all networks are trained on the same split, the held out samples are only evaluated
+        let (train_indices, validation_indices) = self.options.split_mode.split_indices(
+            targets,
+            validation_split,
+            &mut rand::thread_rng(),
+        );
+        let gather = |samples: &[Vec<f64>], indices: &[usize]| {
+            indices.iter().map(|i| samples[*i].clone()).collect::<Vec<_>>()
+        };
+        let (train_inputs, train_targets) =
+            (gather(inputs, &train_indices), gather(targets, &train_indices));
+        let (validation_inputs, validation_targets) =
+            (gather(inputs, &validation_indices), gather(targets, &validation_indices));
+
         let mut temp_neural_network = TrainableClassicNeuralNetwork::new(
             self.shape.clone(),
             &Directory::Internal(append_dir(self.model_directory.path(), "temp_primary")),
             self.utils.clone(),
         );
-        let _ = temp_neural_network.train(
-            inputs,
-            targets,
+        // the temporary network only labels the samples, so it keeps no checkpoint
+        temp_neural_network.set_training_options(TrainingOptions {
+            checkpoint_best: None,
+            ..self.options.clone()
+        });
+        let _ = temp_neural_network.train_with_validation(
+            &train_inputs,
+            &train_targets,
+            &validation_inputs,
+            &validation_targets,
             learning_rate,
             epochs,
             tolerance,
             use_adam,
-            validation_split,
+            optimizer.clone(),
             sample_match_percentage,
+            None,
         );
 
-        let (primary_inputs, primary_targets): (Vec<Vec<f64>>, Vec<Vec<f64>>) = inputs
-            .iter()
-            .zip(targets.iter())
-            .map(|(input, target)| {
-                let prediction = temp_neural_network.predict(input.clone());
-                (input, target, prediction)
-            })
-            .map(|(input, target, prediction)| {
-                // Check if the output matches the target
-                let mut nb_correct_outputs = 0;
-                for (o, t) in prediction.iter().zip(target.iter()) {
-                    if (o - t).abs() < tolerance {
-                        nb_correct_outputs += 1;
-                    }
-                }
-                let mut t = target.clone();
-                let nb_correct_f64: f64 = NumCast::from(nb_correct_outputs)
-                    .expect("Failed to convert nb_correct_outputs to f64");
-                let target_len_f64: f64 =
-                    NumCast::from(target.len()).expect("Failed to convert target.len() to f64");
-                let match_percentage = nb_correct_f64 / target_len_f64;
-                if match_percentage >= sample_match_percentage {
-                    t.push(0.0);
-                } else {
-                    t.push(1.0);
-                }
-                (input.clone(), t)
-            })
-            .unzip();
+        // the internal target is zero for the samples the temporary network gets right
+        // and one for those the backup network has to take over
+        let mut label = |samples: &[Vec<f64>], sample_targets: &[Vec<f64>]| {
+            sample_targets
+                .iter()
+                .zip(samples)
+                .map(|(target, input)| {
+                    let prediction = temp_neural_network.predict(input.clone());
+                    let internal_target = if sample_matches(
+                        &prediction,
+                        target,
+                        tolerance,
+                        sample_match_percentage,
+                    ) {
+                        0.0
+                    } else {
+                        1.0
+                    };
+                    let mut t = target.clone();
+                    t.resize(t.len() + self.internal_dims, internal_target);
+                    t
+                })
+                .collect::<Vec<_>>()
+        };
+        let primary_train_targets = label(&train_inputs, &train_targets);
+        let primary_validation_targets = label(&validation_inputs, &validation_targets);
 
-        // train the primary neural network with the modified outputs
-        let primary_accuracy = self.primary_nn.train(
-            &primary_inputs,
-            &primary_targets,
+        // train the primary neural network with the modified outputs, it reports the epoch metrics
+        let _ = self.primary_nn.train_with_validation(
+            &train_inputs,
+            &primary_train_targets,
+            &validation_inputs,
+            &primary_validation_targets,
             learning_rate,
             epochs,
             tolerance,
             use_adam,
-            validation_split,
+            optimizer.clone(),
             sample_match_percentage,
+            on_epoch_end,
         );
 
-        let (backup_inputs, backup_targets): (Vec<Vec<f64>>, Vec<Vec<f64>>) = primary_inputs
+        // the backup network learns the training samples whose outputs and internal target
+        // the primary network predicts correctly
+        let (backup_inputs, backup_targets): (Vec<Vec<f64>>, Vec<Vec<f64>>) = train_inputs
             .iter()
-            .zip(primary_targets.iter())
+            .zip(primary_train_targets.iter())
             .map(|(input, target)| {
                 let prediction = self.primary_nn.predict(input.clone());
                 (input, target, prediction)
             })
             .filter(|(_, target, prediction)| {
-                // Check if the output matches the target
-                let mut nb_correct_outputs = 0;
-                for (o, t) in prediction.iter().zip(target.iter()) {
-                    if (o - t).abs() < tolerance {
-                        nb_correct_outputs += 1;
-                    }
-                }
-                let nb_correct_f64: f64 = NumCast::from(nb_correct_outputs)
-                    .expect("Failed to convert nb_correct_outputs to f64");
-                let target_len_f64: f64 =
-                    NumCast::from(target.len()).expect("Failed to convert target.len() to f64");
-                let match_percentage = nb_correct_f64 / target_len_f64;
-                match_percentage >= sample_match_percentage
+                sample_matches(prediction, target, tolerance, sample_match_percentage)
             })
             .map(|(input, target, _)| {
                 let mut t = target.clone();
-                t.remove(t.len() - 1);
+                t.truncate(t.len() - self.internal_dims);
                 (input.clone(), t)
             })
             .unzip();
 
-        let backup_accuracy = self.backup_nn.train(
-            &backup_inputs,
-            &backup_targets,
-            learning_rate,
-            epochs,
-            tolerance,
-            use_adam,
-            validation_split,
-            sample_match_percentage,
-        );
+        if !backup_inputs.is_empty() {
+            let _ = self.backup_nn.train(
+                &backup_inputs,
+                &backup_targets,
+                learning_rate,
+                epochs,
+                tolerance,
+                use_adam,
+                optimizer,
+                validation_split,
+                sample_match_percentage,
+                None,
+            );
+        }
 
-        primary_accuracy + backup_accuracy
+        // the combined accuracy is measured on the held out samples, routed like in predict,
+        // or on all samples if none are held out
+        let (evaluated_inputs, evaluated_targets) = if validation_inputs.is_empty() {
+            (inputs, targets)
+        } else {
+            (validation_inputs.as_slice(), validation_targets.as_slice())
+        };
+        let mut nb_matching_samples = 0;
+        let mut nb_backup_samples = 0;
+        for (input, target) in evaluated_inputs.iter().zip(evaluated_targets.iter()) {
+            let (prediction, used_backup) = self.route(input.clone());
+            if sample_matches(&prediction, target, tolerance, sample_match_percentage) {
+                nb_matching_samples += 1;
+            }
+            if used_backup {
+                nb_backup_samples += 1;
+            }
+        }
+        let nb_matching_f64: f64 = NumCast::from(nb_matching_samples)
+            .expect("Failed to convert nb_matching_samples to f64");
+        let nb_backup_f64: f64 =
+            NumCast::from(nb_backup_samples).expect("Failed to convert nb_backup_samples to f64");
+        let nb_evaluated_f64: f64 = NumCast::from(evaluated_inputs.len())
+            .expect("Failed to convert number of evaluated samples to f64");
+        self.backup_usage = Some(nb_backup_f64 / nb_evaluated_f64);
+        nb_matching_f64 / nb_evaluated_f64
     }
 
     fn train_batch(
@@ -542,8 +771,87 @@ impl TrainableNeuralNetwork for TrainableRetryNeuralNetwork {
         epochs: usize,
         tolerance: f64,
         batch_size: usize,
+        optimizer: Option<Box<dyn Optimizer>>,
     ) {
-        self.primary_nn.train_batch(inputs, targets, learning_rate, epochs, tolerance, batch_size);
+        self.primary_nn.train_batch(
+            inputs,
+            targets,
+            learning_rate,
+            epochs,
+            tolerance,
+            batch_size,
+            optimizer,
+        );
+    }
+
+    /// Only the primary network is trained, as the inner networks are trained on splits
+    /// of the training samples.
+    #[allow(clippy::too_many_arguments)]
+    fn train_with_validation(
+        &mut self,
+        train_inputs: &[Vec<f64>],
+        train_targets: &[Vec<f64>],
+        validation_inputs: &[Vec<f64>],
+        validation_targets: &[Vec<f64>],
+        learning_rate: f64,
+        epochs: usize,
+        tolerance: f64,
+        use_adam: bool,
+        optimizer: Option<Box<dyn Optimizer>>,
+        sample_match_percentage: f64,
+        on_epoch_end: Option<&mut dyn FnMut(EpochMetrics)>,
+    ) -> f64 {
+        self.primary_nn.train_with_validation(
+            train_inputs,
+            train_targets,
+            validation_inputs,
+            validation_targets,
+            learning_rate,
+            epochs,
+            tolerance,
+            use_adam,
+            optimizer,
+            sample_match_percentage,
+            on_epoch_end,
+        )
+    }
+
+    fn train_loader(
+        &mut self,
+        loader: &DataLoader,
+        learning_rate: f64,
+        epochs: usize,
+        tolerance: f64,
+        optimizer: Option<Box<dyn Optimizer>>,
+    ) {
+        self.primary_nn.train_loader(loader, learning_rate, epochs, tolerance, optimizer);
+    }
+
+    fn compute_batch_gradients(
+        &mut self,
+        _inputs: &[Vec<f64>],
+        _targets: &[Vec<f64>],
+        _loss_function: &LossFunction,
+    ) -> Result<Vec<LayerGradients>, Box<dyn std::error::Error>> {
+        Err("Batch gradients are only supported for classic neural networks".into())
+    }
+
+    fn partial_fit(
+        &mut self,
+        _input: &[f64],
+        _target: &[f64],
+        _learning_rate: f64,
+        _loss_function: &LossFunction,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        Err("Online training is only supported for classic neural networks".into())
+    }
+
+    fn saliency(
+        &mut self,
+        _input: &[f64],
+        _output_index: usize,
+    ) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        Err("Saliency maps are only supported for classic neural networks".into())
     }
 
     fn input_size(&self) -> usize {
@@ -554,8 +862,196 @@ impl TrainableNeuralNetwork for TrainableRetryNeuralNetwork {
         self.shape.layers[self.shape.layers.len() - 1].output_size()
     }
 
+    fn profile_forward(
+        &mut self,
+        input: &[f64],
+    ) -> Vec<Duration> {
+        // every input passes through the primary network, so its layers are the ones profiled
+        self.primary_nn.profile_forward(input)
+    }
+
+    fn dead_neuron_report(
+        &mut self,
+        inputs: &[Vec<f64>],
+    ) -> Vec<usize> {
+        // every input passes through the primary network, so its units are the ones reported
+        self.primary_nn.dead_neuron_report(inputs)
+    }
+
+    fn training_options(&self) -> TrainingOptions {
+        self.options.clone()
+    }
+
+    /// The internal dimensions only widen the layers, so the layer multipliers apply to the
+    /// primary and the backup network alike.
+    fn set_training_options(
+        &mut self,
+        options: TrainingOptions,
+    ) {
+        self.primary_nn.set_training_options(options.for_subdirectory("primary"));
+        self.backup_nn.set_training_options(options.for_subdirectory("backup"));
+        self.options = options;
+    }
+
+    fn set_loss_function(
+        &mut self,
+        loss_function: LossFunction,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // the inner networks are trained on intermediate targets
+        if loss_function == LossFunction::MeanSquaredError {
+            Ok(())
+        } else {
+            Err("Custom loss functions are only supported for classic neural networks".into())
+        }
+    }
+
+    fn set_logits_loss(
+        &mut self,
+        enabled: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // the inner networks are trained on intermediate targets
+        if enabled {
+            Err("Computing the loss on the logits is only supported for classic neural networks"
+                .into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn reinitialize(
+        &mut self,
+        seed: u64,
+    ) {
+        self.primary_nn.reinitialize(seed);
+        self.backup_nn.reinitialize(seed.wrapping_add(1));
+    }
+
+    fn swa_weights(&self) -> Option<Vec<f64>> {
+        let mut flat = self.primary_nn.swa_weights()?;
+        flat.extend(self.backup_nn.swa_weights()?);
+        Some(flat)
+    }
+
+    fn set_spectral_norm(
+        &mut self,
+        enabled: bool,
+    ) {
+        self.primary_nn.set_spectral_norm(enabled);
+        self.backup_nn.set_spectral_norm(enabled);
+    }
+
+    /// Returns the flat weights of the primary network followed by those of the backup network.
+    fn weights_flat(&self) -> Vec<f64> {
+        let mut flat = self.primary_nn.weights_flat();
+        flat.extend(self.backup_nn.weights_flat());
+        flat
+    }
+
+    fn load_weights_flat(
+        &mut self,
+        weights: &[f64],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        load_weights_flat_sequentially(vec![&mut self.primary_nn, &mut self.backup_nn], weights)
+    }
+
+    fn num_parameters(&self) -> usize {
+        self.primary_nn.num_parameters() + self.backup_nn.num_parameters()
+    }
+
+    fn get_weight(
+        &self,
+        _layer_index: usize,
+        _row: usize,
+        _col: usize,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        Err("Accessing single weights is only supported for classic neural networks".into())
+    }
+
+    fn set_weight(
+        &mut self,
+        _layer_index: usize,
+        _row: usize,
+        _col: usize,
+        _value: f64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Err("Accessing single weights is only supported for classic neural networks".into())
+    }
+
+    fn get_bias(
+        &self,
+        _layer_index: usize,
+        _row: usize,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        Err("Accessing single biases is only supported for classic neural networks".into())
+    }
+
+    fn set_bias(
+        &mut self,
+        _layer_index: usize,
+        _row: usize,
+        _value: f64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Err("Accessing single biases is only supported for classic neural networks".into())
+    }
+
+    fn spectral_norms(
+        &self,
+        _iterations: usize,
+    ) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        Err("Spectral norms are only supported for classic neural networks".into())
+    }
+
+    fn prune_neurons(
+        &mut self,
+        _layer_index: usize,
+        _keep_indices: &[usize],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // the layers of the exposed shape are spread over the inner networks
+        Err("Pruning neurons is only supported for classic neural networks".into())
+    }
+
+    fn add_identity_layer(
+        &mut self,
+        _after_index: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // the layers of the exposed shape are spread over the inner networks
+        Err("Inserting layers is only supported for classic neural networks".into())
+    }
+
+    fn push_layer(
+        &mut self,
+        _layer_shape: LayerShape,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // the layers of the exposed shape are spread over the inner networks
+        Err("Appending layers is only supported for classic neural networks".into())
+    }
+
+    fn widen_layer(
+        &mut self,
+        _layer_index: usize,
+        _new_width: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // the layers of the exposed shape are spread over the inner networks
+        Err("Widening layers is only supported for classic neural networks".into())
+    }
+
+    fn to_inference(&self) -> WrappedNeuralNetwork {
+        WrappedNeuralNetwork::new(Box::new(RetryNeuralNetwork {
+            primary_nn: self.primary_nn.to_inference(),
+            backup_nn: self.backup_nn.to_inference(),
+            shape: self.shape.clone(),
+            model_directory: Directory::Internal(
+                self.utils.first_free_model_directory(&self.model_directory),
+            ),
+            past_internal_model_directories: vec![],
+            persist_on_drop: false,
+            utils: self.utils.clone(),
+            internal_dims: self.internal_dims,
+        }))
+    }
+
     fn duplicate_trainable(&self) -> WrappedTrainableNeuralNetwork {
-        let new_model_directory = get_first_free_model_directory(&self.model_directory);
+        let new_model_directory = self.utils.first_free_model_directory(&self.model_directory);
         copy_dir_recursive(
             Path::new(&self.model_directory.path()),
             Path::new(&new_model_directory),
@@ -578,6 +1074,9 @@ impl Drop for TrainableRetryNeuralNetwork {
             }
             self.deallocate();
         }
+        if self.persist_on_drop {
+            return;
+        }
         // Remove the internal model directory from disk
         if let Directory::Internal(dir) = &self.model_directory {
             if std::fs::metadata(dir).is_ok() {
@@ -598,6 +1097,7 @@ mod tests {
     use super::*;
     use crate::{
         nn::shape::{ActivationData, ActivationType, LayerShape},
+        training::verbosity::Verbosity,
         utilities::util::Utils,
     };
 
@@ -618,6 +1118,8 @@ mod tests {
                 ],
             },
             1,
+            None,
+            1,
             "internal_model".to_string(),
             utils,
         );
@@ -628,7 +1130,7 @@ mod tests {
         let target = vec![0.0, 0.0, 0.0];
         let targets = vec![target; 500];
 
-        nn.train(&inputs, &targets, 0.01, 5, 0.1, true, 0.7, 1.0);
+        nn.train(&inputs, &targets, 0.01, 5, 0.1, true, None, 0.7, 1.0, None);
 
         let prediction = nn.predict(inputs[0].clone());
         // print targets[0]
@@ -641,4 +1143,258 @@ mod tests {
             assert!((p - t).abs() < 1e-4);
         }
     }
+
+    /// Trains a single layer retry network with a linear primary network on a grid of inputs
+    /// labelled by `target` and returns its accuracy and backup usage.
+    fn train_linear_retry_network(
+        internal_model_directory: &str,
+        target: impl Fn(f64, f64) -> f64,
+    ) -> (f64, f64) {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape {
+            layers: vec![LayerShape {
+                layer_type: LayerType::Dense { input_size: 2, output_size: 1 },
+                activation: ActivationData::new(ActivationType::Identity),
+            }],
+        };
+        let mut nn = TrainableRetryNeuralNetwork::new(
+            shape,
+            0,
+            Some(ActivationData::new(ActivationType::Sigmoid)),
+            1,
+            internal_model_directory.to_string(),
+            utils,
+        );
+        nn.set_training_options(TrainingOptions {
+            verbosity: Verbosity::Silent,
+            ..nn.training_options()
+        });
+        assert!(nn.backup_usage().is_none());
+
+        let grid = (0..15_u8).map(|i| <f64 as From<u8>>::from(i) / 14.0).collect::<Vec<_>>();
+        let inputs =
+            grid.iter().flat_map(|x| grid.iter().map(move |y| vec![*x, *y])).collect::<Vec<_>>();
+        let targets =
+            inputs.iter().map(|input| vec![target(input[0], input[1])]).collect::<Vec<_>>();
+        let accuracy = nn.train(&inputs, &targets, 0.05, 30, 0.1, false, None, 0.7, 1.0, None);
+        (accuracy, nn.backup_usage().unwrap())
+    }
+
+    #[test]
+    fn test_retry_backup_usage_on_easy_data() {
+        // a linear mapping the primary network learns exactly
+        let (accuracy, backup_usage) =
+            train_linear_retry_network("internal_model_retry_backup_usage_easy", |x, y| {
+                0.5f64.mul_add(x, -0.25 * y) + 0.1
+            });
+        assert!(accuracy > 0.95, "accuracy {accuracy}");
+        assert!(backup_usage < 0.05, "backup usage {backup_usage}");
+    }
+
+    #[test]
+    fn test_retry_backup_usage_on_unfittable_data() {
+        // the exclusive or of the two halves is not linearly separable, so the primary network
+        // gets almost no sample right and hands them to the backup network
+        let (_, backup_usage) =
+            train_linear_retry_network("internal_model_retry_backup_usage_hard", |x, y| {
+                if (x > 0.5) == (y > 0.5) {
+                    0.0
+                } else {
+                    1.0
+                }
+            });
+        assert!(backup_usage > 0.5, "backup usage {backup_usage}");
+    }
+
+    #[test]
+    fn test_retry_min_training_samples() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape {
+            layers: vec![LayerShape {
+                layer_type: LayerType::Dense { input_size: 3, output_size: 2 },
+                activation: ActivationData::new(ActivationType::Sigmoid),
+            }],
+        };
+        let mut nn = TrainableRetryNeuralNetwork::new(
+            shape,
+            0,
+            None,
+            1,
+            "internal_model_retry_min_samples".to_string(),
+            utils,
+        )
+        .with_min_training_samples(10, -1.0);
+
+        let inputs = vec![vec![1.0, 0.0, 1.0]; 5];
+        let targets = vec![vec![1.0, 0.0]; 5];
+        let accuracy = nn.train(&inputs, &targets, 0.1, 2, 0.1, false, None, 0.7, 1.0, None);
+        assert!((accuracy + 1.0).abs() < f64::EPSILON);
+
+        let inputs = vec![vec![1.0, 0.0, 1.0]; 20];
+        let targets = vec![vec![1.0, 0.0]; 20];
+        let accuracy = nn.train(&inputs, &targets, 0.1, 2, 0.1, false, None, 0.7, 1.0, None);
+        assert!((0.0..=1.0).contains(&accuracy));
+    }
+
+    #[test]
+    fn test_retry_internal_activation() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape {
+            layers: vec![
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 4 },
+                    activation: ActivationData::new(ActivationType::ReLU),
+                },
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 4, output_size: 2 },
+                    activation: ActivationData::new(ActivationType::ReLU),
+                },
+            ],
+        };
+        let sigmoid = ActivationData::new(ActivationType::Sigmoid);
+        let mut nn = TrainableRetryNeuralNetwork::new(
+            shape.clone(),
+            0,
+            Some(sigmoid.clone()),
+            1,
+            "internal_model_retry_activation".to_string(),
+            utils,
+        );
+        assert_eq!(nn.shape(), shape);
+
+        let primary_shape = nn.primary_nn.shape();
+        assert!(primary_shape.is_valid());
+        for layer in &primary_shape.layers {
+            assert_eq!(layer.activation.activation_type(), ActivationType::ReLU);
+            assert_eq!(layer.activation.internal_activation(), Some(&sigmoid));
+        }
+
+        // the sigmoid squashes the internal unit into (0, 1)
+        let primary_output = nn.primary_nn.predict(vec![1.0, -2.0, 0.5]);
+        assert_eq!(primary_output.len(), 3);
+        assert!(primary_output[2] > 0.0 && primary_output[2] < 1.0);
+        assert_eq!(nn.predict(vec![1.0, -2.0, 0.5]).len(), 2);
+    }
+
+    #[test]
+    fn test_retry_internal_dims() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape {
+            layers: vec![
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size: 4 },
+                    activation: ActivationData::new(ActivationType::ReLU),
+                },
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 4, output_size: 2 },
+                    activation: ActivationData::new(ActivationType::ReLU),
+                },
+            ],
+        };
+        let sigmoid = ActivationData::new(ActivationType::Sigmoid);
+        let mut nn = TrainableRetryNeuralNetwork::new(
+            shape.clone(),
+            0,
+            Some(sigmoid),
+            2,
+            "internal_model_retry_internal_dims".to_string(),
+            utils,
+        );
+        assert_eq!(nn.shape(), shape);
+
+        let primary_shape = nn.primary_nn.shape();
+        assert!(primary_shape.is_valid());
+        for (primary_layer, layer) in primary_shape.layers.iter().zip(&shape.layers) {
+            assert_eq!(primary_layer.output_size(), layer.output_size() + 2);
+            assert_eq!(primary_layer.activation.internal_units(), 2);
+        }
+        assert_eq!(primary_shape.layers[1].input_size(), shape.layers[1].input_size() + 2);
+
+        // both internal units are squashed by the sigmoid
+        let primary_output = nn.primary_nn.predict(vec![1.0, -2.0, 0.5]);
+        assert_eq!(primary_output.len(), 4);
+        assert!(primary_output[2..].iter().all(|value| *value > 0.0 && *value < 1.0));
+        assert_eq!(nn.predict(vec![1.0, -2.0, 0.5]).len(), 2);
+    }
+
+    #[test]
+    fn test_retry_routing_follows_the_internal_labels() {
+        let shape = NeuralNetworkShape {
+            layers: vec![LayerShape {
+                layer_type: LayerType::Dense { input_size: 2, output_size: 1 },
+                activation: ActivationData::new(ActivationType::Identity),
+            }],
+        };
+        for activation_type in
+            [ActivationType::Sigmoid, ActivationType::Tanh, ActivationType::Identity]
+        {
+            let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+            let mut nn = TrainableRetryNeuralNetwork::new(
+                shape.clone(),
+                0,
+                Some(ActivationData::new(activation_type)),
+                1,
+                "internal_model_retry_routing".to_string(),
+                utils,
+            );
+            nn.primary_nn.set_weight(0, 1, 0, 0.0).unwrap();
+            nn.primary_nn.set_weight(0, 1, 1, 0.0).unwrap();
+            let input = vec![0.5, -1.0];
+            let primary_output = nn.primary_nn.predict(input.clone())[0..1].to_vec();
+            let backup_output = nn.backup_nn.predict(input.clone());
+
+            // an internal unit trained towards the label one hands the sample to the backup
+            nn.primary_nn.set_bias(0, 1, 1.0).unwrap();
+            assert_eq!(nn.route(input.clone()), (backup_output.clone(), true));
+            assert_eq!(nn.to_inference().predict(input.clone()), backup_output);
+
+            // an internal unit trained towards the label zero keeps the primary output
+            nn.primary_nn.set_bias(0, 1, 0.0).unwrap();
+            assert_eq!(nn.route(input.clone()), (primary_output.clone(), false));
+            assert_eq!(nn.to_inference().predict(input), primary_output);
+        }
+    }
+
+    #[test]
+    fn test_retry_persist_on_drop() {
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let shape = NeuralNetworkShape {
+            layers: vec![LayerShape {
+                layer_type: LayerType::Dense { input_size: 3, output_size: 2 },
+                activation: ActivationData::new(ActivationType::ReLU),
+            }],
+        };
+        let model_directory = "internal_model_retry_persist_on_drop";
+        let mut nn = RetryNeuralNetwork::new(
+            shape.clone(),
+            1,
+            None,
+            1,
+            model_directory.to_string(),
+            utils.clone(),
+        );
+        nn.persist_on_drop();
+        assert_eq!(nn.predict(vec![1.0, -2.0, 0.5]).len(), 2);
+        drop(nn);
+        assert!(Path::new(model_directory).is_dir());
+        assert!(Path::new(&format!("{model_directory}/primary/shape.yaml")).exists());
+        std::fs::remove_dir_all(model_directory).unwrap();
+
+        let trainable_model_directory = "internal_model_trainable_retry_persist_on_drop";
+        let mut nn = TrainableRetryNeuralNetwork::new(
+            shape,
+            1,
+            None,
+            1,
+            trainable_model_directory.to_string(),
+            utils,
+        );
+        nn.persist_on_drop();
+        let primary_directory = nn.primary_nn.get_model_directory().path();
+        assert_eq!(nn.predict(vec![1.0, -2.0, 0.5]).len(), 2);
+        drop(nn);
+        assert!(Path::new(trainable_model_directory).is_dir());
+        assert!(Path::new(&format!("{primary_directory}/shape.yaml")).exists());
+        std::fs::remove_dir_all(trainable_model_directory).unwrap();
+    }
 }
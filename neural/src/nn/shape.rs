@@ -1,17 +1,22 @@
 use serde::{Deserialize, Serialize};
 use std::fs::File;
+use std::hash::{Hash, Hasher};
 use std::io::Write;
 
 /// Enum representing the type of layer in a neural network.
 /// Each variant includes the input size and output size of the layer.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum LayerType {
     /// A fully connected (dense) layer with specified input and output sizes.
     Dense { input_size: usize, output_size: usize },
+    /// An embedding lookup table mapping a single integer index to a vector of size `dim`.
+    Embedding { num_embeddings: usize, dim: usize },
+    /// A parametric `ReLU` of `size` values with a single learned negative slope.
+    PReLU { size: usize },
 }
 
 /// Enum representing the type of activation function used in a layer.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ActivationType {
     /// `ReLU` (Rectified Linear Unit) activation function.
     ReLU,
@@ -21,23 +26,179 @@ pub enum ActivationType {
     Tanh,
     /// Softmax activation function.
     Softmax,
+    /// A user defined activation function looked up by name in the `ActivationRegistry`.
+    Custom,
+    /// Identity (linear) activation function, e.g. for the output of regression networks.
+    Identity,
+    /// Applies different activation functions to consecutive ranges of the output units.
+    Composite,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+impl ActivationType {
+    /// Returns the closed range of the values the activation can produce,
+    /// or `None` if the activation is unbounded or user defined.
+    #[must_use]
+    pub const fn output_range(&self) -> Option<(f64, f64)> {
+        match self {
+            Self::Sigmoid | Self::Softmax => Some((0.0, 1.0)),
+            Self::Tanh => Some((-1.0, 1.0)),
+            Self::ReLU | Self::Custom | Self::Identity | Self::Composite => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ActivationData {
     activation_type: ActivationType,
+    // older or hand-edited shape files may leave out the temperature, a softmax then uses 1.0
+    #[serde(default)]
     temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    internal_activation: Option<Box<Self>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    internal_units: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    segments: Option<Vec<ActivationSegment>>,
+}
+
+/// The activation of the output units `start..end` of a composite activation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ActivationSegment {
+    pub start: usize,
+    pub end: usize,
+    pub activation: ActivationData,
+}
+
+impl ActivationSegment {
+    #[must_use]
+    pub const fn new(
+        start: usize,
+        end: usize,
+        activation: ActivationData,
+    ) -> Self {
+        Self { start, end, activation }
+    }
+}
+
+impl ActivationData {
+    /// Returns the bits of the temperature with signed zeros and NaNs unified,
+    /// so that equal temperatures compare and hash equally.
+    fn temperature_bits(&self) -> Option<u64> {
+        self.temperature.map(|temperature| {
+            if temperature.is_nan() {
+                f64::NAN.to_bits()
+            } else {
+                (temperature + 0.0).to_bits()
+            }
+        })
+    }
+}
+
+impl PartialEq for ActivationData {
+    fn eq(
+        &self,
+        other: &Self,
+    ) -> bool {
+        self.activation_type == other.activation_type
+            && self.temperature_bits() == other.temperature_bits()
+            && self.name == other.name
+            && self.internal_activation == other.internal_activation
+            && self.internal_units == other.internal_units
+            && self.segments == other.segments
+    }
+}
+
+impl Eq for ActivationData {}
+
+impl Hash for ActivationData {
+    fn hash<H: Hasher>(
+        &self,
+        state: &mut H,
+    ) {
+        self.activation_type.hash(state);
+        self.temperature_bits().hash(state);
+        self.name.hash(state);
+        self.internal_activation.hash(state);
+        self.internal_units.hash(state);
+        self.segments.hash(state);
+    }
 }
 
 impl ActivationData {
     #[must_use]
     pub const fn new(activation_type: ActivationType) -> Self {
-        Self { activation_type, temperature: None }
+        Self {
+            activation_type,
+            temperature: None,
+            name: None,
+            internal_activation: None,
+            internal_units: None,
+            segments: None,
+        }
     }
 
     #[must_use]
     pub const fn new_softmax(temperature: f64) -> Self {
-        Self { activation_type: ActivationType::Softmax, temperature: Some(temperature) }
+        Self {
+            activation_type: ActivationType::Softmax,
+            temperature: Some(temperature),
+            name: None,
+            internal_activation: None,
+            internal_units: None,
+            segments: None,
+        }
+    }
+
+    /// Creates the activation data of a user defined activation registered under `name`.
+    #[must_use]
+    pub fn new_custom(name: &str) -> Self {
+        Self {
+            activation_type: ActivationType::Custom,
+            temperature: None,
+            name: Some(name.to_string()),
+            internal_activation: None,
+            internal_units: None,
+            segments: None,
+        }
+    }
+
+    /// Creates the activation data of a composite activation applying the activation of every
+    /// segment to its range of output units. The segments have to cover the output units
+    /// in order without gaps, starting at the first unit.
+    #[must_use]
+    pub const fn new_composite(segments: Vec<ActivationSegment>) -> Self {
+        Self {
+            activation_type: ActivationType::Composite,
+            temperature: None,
+            name: None,
+            internal_activation: None,
+            internal_units: None,
+            segments: Some(segments),
+        }
+    }
+
+    /// Applies `internal_activation` to the last output unit instead of this activation.
+    /// The retry network uses this for its internal "should I retry" unit.
+    #[must_use]
+    pub fn with_internal_activation(
+        mut self,
+        internal_activation: Self,
+    ) -> Self {
+        self.internal_activation = Some(Box::new(internal_activation));
+        self
+    }
+
+    /// Applies the internal activation to the last `internal_units` output units instead of
+    /// only the last one.
+    #[must_use]
+    pub const fn with_internal_units(
+        mut self,
+        internal_units: usize,
+    ) -> Self {
+        self.internal_units = if internal_units == 1 { None } else { Some(internal_units) };
+        self
     }
 
     /// Checks if the activation data is valid.
@@ -52,11 +213,41 @@ impl ActivationData {
     /// This function will panic if `activation_type` is `Softmax` and `temperature` is `None`.
     #[must_use]
     pub fn is_valid(&self) -> bool {
+        if let Some(internal_activation) = &self.internal_activation {
+            if internal_activation.internal_activation.is_some() || !internal_activation.is_valid()
+            {
+                return false;
+            }
+        } else if self.internal_units.is_some() {
+            return false;
+        }
+        if self.internal_units == Some(0) {
+            return false;
+        }
+        if self.activation_type != ActivationType::Composite && self.segments.is_some() {
+            return false;
+        }
         match self.activation_type {
+            ActivationType::Composite => {
+                self.name.is_none() && self.temperature.is_none() && {
+                    let segments = self.segments();
+                    let mut end = 0;
+                    !segments.is_empty()
+                        && segments.iter().all(|segment| {
+                            let contiguous = segment.start == end && segment.end > segment.start;
+                            end = segment.end;
+                            // nested composite activations are not supported
+                            contiguous
+                                && segment.activation.activation_type() != ActivationType::Composite
+                                && segment.activation.is_valid()
+                        })
+                }
+            },
             ActivationType::Softmax => {
-                self.temperature.is_some() && self.temperature.unwrap() > 0.0
+                self.name.is_none() && self.temperature.is_some() && self.temperature.unwrap() > 0.0
             },
-            _ => self.temperature.is_none(),
+            ActivationType::Custom => self.name.is_some() && self.temperature.is_none(),
+            _ => self.name.is_none() && self.temperature.is_none(),
         }
     }
 
@@ -69,10 +260,40 @@ impl ActivationData {
     pub const fn temperature(&self) -> Option<f64> {
         self.temperature
     }
+
+    /// Returns the registry name of a user defined activation.
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Removes a separate activation of the last output units.
+    pub fn clear_internal_activation(&mut self) {
+        self.internal_activation = None;
+        self.internal_units = None;
+    }
+
+    /// Returns the activation of the last output unit if it differs from this activation.
+    #[must_use]
+    pub fn internal_activation(&self) -> Option<&Self> {
+        self.internal_activation.as_deref()
+    }
+
+    /// Returns the number of last output units using the internal activation.
+    #[must_use]
+    pub fn internal_units(&self) -> usize {
+        self.internal_units.unwrap_or(1)
+    }
+
+    /// Returns the segments of a composite activation, or an empty slice for other activations.
+    #[must_use]
+    pub fn segments(&self) -> &[ActivationSegment] {
+        self.segments.as_deref().unwrap_or_default()
+    }
 }
 
 /// Struct representing the shape and configuration of a neural network layer.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct LayerShape {
     /// The type of the layer (e.g., Dense) with input and output sizes.
     pub layer_type: LayerType,
@@ -86,6 +307,8 @@ impl LayerShape {
     pub const fn input_size(&self) -> usize {
         match self.layer_type {
             LayerType::Dense { input_size, .. } => input_size,
+            LayerType::Embedding { .. } => 1,
+            LayerType::PReLU { size } => size,
         }
     }
 
@@ -94,6 +317,20 @@ impl LayerShape {
     pub const fn output_size(&self) -> usize {
         match self.layer_type {
             LayerType::Dense { output_size, .. } => output_size,
+            LayerType::Embedding { dim, .. } => dim,
+            LayerType::PReLU { size } => size,
+        }
+    }
+
+    /// Returns the number of trainable parameters of the layer.
+    /// A dense layer has a weight per input and output pair plus a bias per output,
+    /// an embedding layer has one value per table entry and a `PReLU` layer only its slope.
+    #[must_use]
+    pub const fn num_parameters(&self) -> usize {
+        match self.layer_type {
+            LayerType::Dense { input_size, output_size } => (input_size + 1) * output_size,
+            LayerType::Embedding { num_embeddings, dim } => num_embeddings * dim,
+            LayerType::PReLU { .. } => 1,
         }
     }
 
@@ -107,43 +344,150 @@ impl LayerShape {
     ///
     /// # Returns
     ///
-    /// * `true` if both input size and output size are greater than zero.
+    /// * `true` if both input size and output size are greater than zero,
+    ///   an embedding table has at least one row
+    ///   and the segments of a composite activation cover all output units.
     /// * `false` otherwise.
     #[must_use]
     pub fn is_valid(&self) -> bool {
-        self.input_size() > 0 && self.output_size() > 0 && self.activation.is_valid()
+        let has_rows = match self.layer_type {
+            LayerType::Dense { .. } | LayerType::PReLU { .. } => true,
+            LayerType::Embedding { num_embeddings, .. } => num_embeddings > 0,
+        };
+        has_rows
+            && self.input_size() > 0
+            && self.output_size() > 0
+            && self.activation.is_valid()
+            && self.covers_output_units()
+    }
+
+    /// Checks that a composite activation, possibly followed by the internal units of the
+    /// retry network, applies to exactly the output units of the layer.
+    fn covers_output_units(&self) -> bool {
+        let activation = &self.activation;
+        if activation.activation_type() != ActivationType::Composite {
+            return true;
+        }
+        let internal_units = if activation.internal_activation().is_some() {
+            activation.internal_units()
+        } else {
+            0
+        };
+        activation.segments().last().map_or(0, |segment| segment.end) + internal_units
+            == self.output_size()
+    }
+}
+
+/// Dimensions of the data flowing between layers as `(channels, height, width)`.
+pub type Dims = (usize, usize, usize);
+
+/// Version of the shape format written by `to_yaml`.
+/// Files of version 1 were written without a version field.
+pub const SHAPE_FORMAT_VERSION: u32 = 2;
+
+const fn default_shape_format_version() -> u32 {
+    1
+}
+
+/// The content of a `shape.yaml` file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ShapeFile {
+    #[serde(default = "default_shape_format_version")]
+    version: u32,
+    layers: Vec<LayerShape>,
+}
+
+/// A 64 bit FNV-1a hasher, used for hashes which have to be stable across runs and platforms.
+struct StructuralHasher(u64);
+
+impl StructuralHasher {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+}
+
+impl Hasher for StructuralHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(
+        &mut self,
+        bytes: &[u8],
+    ) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn write_u32(
+        &mut self,
+        i: u32,
+    ) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(
+        &mut self,
+        i: u64,
+    ) {
+        self.write(&i.to_le_bytes());
+    }
+
+    // sizes and discriminants are hashed as 64 bit so the hash does not depend on the pointer width
+    fn write_usize(
+        &mut self,
+        i: usize,
+    ) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_isize(
+        &mut self,
+        i: isize,
+    ) {
+        self.write(&(i as i64).to_le_bytes());
     }
 }
 
 /// Struct representing the shape and configuration of an entire neural network.
-#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct NeuralNetworkShape {
     /// A vector of `LayerShape` structs, representing each layer in the neural network.
     pub layers: Vec<LayerShape>,
 }
 
 impl NeuralNetworkShape {
+    /// Returns a hash of the layer types, sizes and activations (including the softmax
+    /// temperature) of the shape, which is stable across runs and platforms.
+    /// Structurally equal shapes have equal hashes.
+    #[must_use]
+    pub fn structural_hash(&self) -> u64 {
+        let mut hasher = StructuralHasher(StructuralHasher::OFFSET_BASIS);
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
     /// Creates a new `NeuralNetworkShape` with the given layers.
     #[must_use]
     pub const fn new(layers: Vec<LayerShape>) -> Self {
         Self { layers }
     }
 
-    /// Creates a new `NeuralNetworkShape` with the given layers from disk.
+    /// Reads the shape stored in the given model directory.
+    /// Returns `None` if the directory holds no shape file.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// This function will panic if the model directory is invalid or if the YAML
-    /// deserialization fails.
-    #[must_use]
-    pub fn from_disk(model_directory: &str) -> Option<Self> {
+    /// Returns an error naming the shape file if it cannot be read, is malformed or was
+    /// written in an unknown version of the shape format.
+    pub fn from_disk(model_directory: &str) -> Result<Option<Self>, Box<dyn std::error::Error>> {
         let path = format!("{model_directory}/shape.yaml");
         if !std::path::Path::new(&path).exists() {
-            return None;
+            return Ok(None);
         }
-        let file = File::open(path).unwrap();
-        let shape: Self = serde_yaml::from_reader(file).unwrap();
-        Some(shape)
+        let yaml =
+            std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {path}: {e}"))?;
+        Self::from_yaml(&yaml).map(Some).map_err(|e| format!("Failed to load {path}: {e}").into())
     }
 
     /// Creates a new `NeuralNetworkShape` with the given layers from file.
@@ -154,9 +498,27 @@ impl NeuralNetworkShape {
     /// deserialization fails.
     #[must_use]
     pub fn from_file(file_name: String) -> Self {
-        let file = File::open(file_name).unwrap();
-        let shape: Self = serde_yaml::from_reader(file).unwrap();
-        shape
+        let yaml = std::fs::read_to_string(file_name).unwrap();
+        Self::from_yaml(&yaml).unwrap_or_else(|e| panic!("Failed to load the shape file: {e}"))
+    }
+
+    /// Parses a shape written in any known version of the shape format.
+    /// Files without a version field are read as version 1.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the YAML is malformed or was written in an unknown version.
+    pub fn from_yaml(yaml: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let shape_file: ShapeFile = serde_yaml::from_str(yaml)?;
+        match shape_file.version {
+            // version 1 has the same layout, it only lacks the version field
+            1 | SHAPE_FORMAT_VERSION => Ok(Self::new(shape_file.layers)),
+            version => Err(format!(
+                "Unsupported shape format version {version}, \
+                 the newest supported version is {SHAPE_FORMAT_VERSION}"
+            )
+            .into()),
+        }
     }
 
     /// Checks if the neural network shape is valid.
@@ -190,6 +552,40 @@ impl NeuralNetworkShape {
         true
     }
 
+    /// Propagates the `(channels, height, width)` input dimensions through the layers and
+    /// returns the output dimensions of every layer. Dense and embedding layers flatten
+    /// their input, so their output dimensions are `(1, 1, output_size)`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a dimension becomes zero or if the flattened dimensions do not
+    /// match the input size of a layer.
+    pub fn output_shapes(
+        &self,
+        input_dims: Dims,
+    ) -> Result<Vec<Dims>, Box<dyn std::error::Error>> {
+        let mut dims = input_dims;
+        let mut output_shapes = Vec::with_capacity(self.layers.len());
+        for (i, layer) in self.layers.iter().enumerate() {
+            let (channels, height, width) = dims;
+            if channels == 0 || height == 0 || width == 0 {
+                return Err(
+                    format!("Layer {i} receives the non-positive dimensions {dims:?}").into()
+                );
+            }
+            if channels * height * width != layer.input_size() {
+                return Err(format!(
+                    "Layer {i} expects {} inputs but receives the dimensions {dims:?}",
+                    layer.input_size()
+                )
+                .into());
+            }
+            dims = (1, 1, layer.output_size());
+            output_shapes.push(dims);
+        }
+        Ok(output_shapes)
+    }
+
     /// Converts the neural network shape to YAML format.
     ///
     /// # Panics
@@ -206,7 +602,8 @@ impl NeuralNetworkShape {
         }
         let path = format!("{model_directory}/shape.yaml");
         let mut file = File::create(path).unwrap();
-        let yaml = serde_yaml::to_string(self).unwrap();
+        let shape_file = ShapeFile { version: SHAPE_FORMAT_VERSION, layers: self.layers.clone() };
+        let yaml = serde_yaml::to_string(&shape_file).unwrap();
         file.write_all(yaml.as_bytes()).unwrap();
     }
 
@@ -219,6 +616,71 @@ impl NeuralNetworkShape {
         self.layers[index].clone()
     }
 
+    /// Returns the number of trainable parameters of all layers.
+    #[must_use]
+    pub fn num_parameters(&self) -> usize {
+        self.layers.iter().map(LayerShape::num_parameters).sum()
+    }
+
+    /// Returns a table listing the index, type, input and output size, activation and
+    /// number of parameters of every layer, followed by the total number of parameters.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut rows = vec![[
+            "Layer".to_string(),
+            "Type".to_string(),
+            "Input".to_string(),
+            "Output".to_string(),
+            "Activation".to_string(),
+            "Params".to_string(),
+        ]];
+        for (i, layer) in self.layers.iter().enumerate() {
+            let layer_type = match layer.layer_type {
+                LayerType::Dense { .. } => "Dense",
+                LayerType::Embedding { .. } => "Embedding",
+                LayerType::PReLU { .. } => "PReLU",
+            };
+            let activation = match (layer.activation.name(), layer.activation.temperature()) {
+                (Some(name), _) => name.to_string(),
+                (None, Some(temperature)) => {
+                    format!("{:?}(t={temperature})", layer.activation.activation_type())
+                },
+                (None, None) => format!("{:?}", layer.activation.activation_type()),
+            };
+            rows.push([
+                i.to_string(),
+                layer_type.to_string(),
+                layer.input_size().to_string(),
+                layer.output_size().to_string(),
+                activation,
+                layer.num_parameters().to_string(),
+            ]);
+        }
+        let widths: Vec<usize> = (0..6)
+            .map(|column| rows.iter().map(|row| row[column].len()).max().unwrap_or(0))
+            .collect();
+        let separator =
+            widths.iter().map(|width| "-".repeat(*width)).collect::<Vec<_>>().join("-+-");
+        let mut summary = String::new();
+        for (i, row) in rows.iter().enumerate() {
+            let line = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect::<Vec<_>>()
+                .join(" | ");
+            summary.push_str(line.trim_end());
+            summary.push('\n');
+            if i == 0 {
+                summary.push_str(&separator);
+                summary.push('\n');
+            }
+        }
+        summary.push_str(&separator);
+        summary.push('\n');
+        format!("{summary}Total params: {}\n", self.num_parameters())
+    }
+
     /// Returns the number of layers in the neural network shape.
     #[must_use]
     pub fn num_layers(&self) -> usize {
@@ -431,4 +893,121 @@ mod tests {
         let invalid_network = NeuralNetworkShape { layers: invalid_layers };
         assert!(!invalid_network.is_valid());
     }
+
+    #[test]
+    fn test_output_shapes() {
+        let network = NeuralNetworkShape {
+            layers: vec![
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 12, output_size: 5 },
+                    activation: ActivationData::new(ActivationType::ReLU),
+                },
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 5, output_size: 3 },
+                    activation: ActivationData::new(ActivationType::Sigmoid),
+                },
+            ],
+        };
+        assert_eq!(network.output_shapes((3, 2, 2)).unwrap(), vec![(1, 1, 5), (1, 1, 3)]);
+        assert!(network.output_shapes((3, 2, 3)).is_err());
+        assert!(network.output_shapes((0, 2, 2)).is_err());
+    }
+
+    #[test]
+    fn test_shape_format_versions() {
+        let v1_yaml = "layers:
+- layer_type: !Dense
+    input_size: 4
+    output_size: 2
+  activation:
+    activation_type: ReLU
+    temperature: null
+";
+        let shape = NeuralNetworkShape::from_yaml(v1_yaml).unwrap();
+        assert_eq!(
+            shape,
+            NeuralNetworkShape::new(vec![LayerShape {
+                layer_type: LayerType::Dense { input_size: 4, output_size: 2 },
+                activation: ActivationData::new(ActivationType::ReLU),
+            }])
+        );
+
+        let model_directory = "test_shape_format_versions";
+        shape.to_yaml(model_directory);
+        let yaml = std::fs::read_to_string(format!("{model_directory}/shape.yaml")).unwrap();
+        assert!(yaml.starts_with(&format!("version: {SHAPE_FORMAT_VERSION}")));
+        assert_eq!(NeuralNetworkShape::from_disk(model_directory).unwrap(), Some(shape));
+
+        let future_yaml = format!("version: {}\n{v1_yaml}", SHAPE_FORMAT_VERSION + 1);
+        let error = NeuralNetworkShape::from_yaml(&future_yaml).unwrap_err();
+        assert!(error.to_string().contains("Unsupported shape format version"));
+        std::fs::write(format!("{model_directory}/shape.yaml"), future_yaml).unwrap();
+        let error = NeuralNetworkShape::from_disk(model_directory).unwrap_err();
+        assert!(error.to_string().contains("Unsupported shape format version"));
+        std::fs::remove_dir_all(model_directory).unwrap();
+        assert!(NeuralNetworkShape::from_disk(model_directory).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_structural_hash() {
+        let build = |output_size: usize, temperature: f64| NeuralNetworkShape {
+            layers: vec![
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: 3, output_size },
+                    activation: ActivationData::new(ActivationType::ReLU),
+                },
+                LayerShape {
+                    layer_type: LayerType::Dense { input_size: output_size, output_size: 2 },
+                    activation: ActivationData::new_softmax(temperature),
+                },
+            ],
+        };
+        let shape = build(4, 1.0);
+        assert_eq!(shape, build(4, 1.0));
+        assert_eq!(shape.structural_hash(), build(4, 1.0).structural_hash());
+        assert_ne!(shape, build(5, 1.0));
+        assert_ne!(shape.structural_hash(), build(5, 1.0).structural_hash());
+        assert_ne!(shape.structural_hash(), build(4, 0.5).structural_hash());
+        assert_eq!(build(4, 0.0).structural_hash(), build(4, -0.0).structural_hash());
+
+        let mut shapes = std::collections::HashSet::new();
+        shapes.insert(shape);
+        assert!(shapes.contains(&build(4, 1.0)));
+    }
+
+    #[test]
+    fn test_summary() {
+        let shape = NeuralNetworkShape::new(vec![
+            LayerShape {
+                layer_type: LayerType::Embedding { num_embeddings: 10, dim: 4 },
+                activation: ActivationData::new(ActivationType::ReLU),
+            },
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 4, output_size: 3 },
+                activation: ActivationData::new(ActivationType::Tanh),
+            },
+            LayerShape {
+                layer_type: LayerType::PReLU { size: 3 },
+                activation: ActivationData::new(ActivationType::Identity),
+            },
+            LayerShape {
+                layer_type: LayerType::Dense { input_size: 3, output_size: 2 },
+                activation: ActivationData::new_softmax(1.0),
+            },
+        ]);
+        // 10 * 4 embedding values, (4 + 1) * 3 and (3 + 1) * 2 dense parameters and a slope
+        assert_eq!(shape.num_parameters(), 64);
+        assert!(shape.is_valid());
+
+        let summary = shape.summary();
+        let lines: Vec<&str> = summary.lines().collect();
+        // header, separator, one line per layer, separator and total
+        assert_eq!(lines.len(), 3 + 4 + 1);
+        assert!(lines[0].starts_with("Layer"));
+        assert!(lines[2].starts_with("0 ") && lines[2].contains("Embedding"));
+        assert!(lines[3].contains("Tanh") && lines[3].ends_with("15"));
+        assert!(lines[4].contains("PReLU") && lines[4].ends_with('1'));
+        assert!(lines[5].contains("Softmax(t=1)") && lines[5].ends_with('8'));
+        assert_eq!(lines[7], "Total params: 64");
+    }
 }
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+/// Hyperparameters of the Adam optimizer.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AdamParams {
+    /// Decay rate of the first moment estimates.
+    pub beta1: f64,
+    /// Decay rate of the second moment estimates.
+    pub beta2: f64,
+    /// Term added to the denominator for numerical stability.
+    pub epsilon: f64,
+}
+
+impl AdamParams {
+    #[must_use]
+    pub const fn new(
+        beta1: f64,
+        beta2: f64,
+        epsilon: f64,
+    ) -> Self {
+        Self { beta1, beta2, epsilon }
+    }
+}
+
+impl Default for AdamParams {
+    fn default() -> Self {
+        Self::new(0.9, 0.999, 1e-8)
+    }
+}
@@ -0,0 +1,128 @@
+use crate::nn::shape::ActivationType;
+
+use std::fmt;
+
+/// Mean gradient norm below which the network is considered dead.
+pub const VANISHING_GRADIENT_NORM: f64 = 1e-7;
+/// Mean gradient norm above which the gradients are considered to explode.
+pub const EXPLODING_GRADIENT_NORM: f64 = 1e3;
+
+/// Classification of the gradient signal seen during the first epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GradientHealth {
+    /// The gradients are neither near zero nor exploding.
+    Healthy,
+    /// The gradients are near zero, the network is possibly dead.
+    Vanishing,
+    /// The gradients are huge or not finite, the learning rate is likely too high.
+    Exploding,
+}
+
+/// Health checks of the gradient signal and the loss after the first training epoch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrainingDiagnostics {
+    /// The mean L2 norm of the gradients of all layers per training sample.
+    pub mean_grad_norm: f64,
+    /// The classification of `mean_grad_norm`.
+    pub gradient_health: GradientHealth,
+    /// The average loss over the first half of the training samples.
+    pub first_half_loss: f64,
+    /// The average loss over the second half of the training samples.
+    pub second_half_loss: f64,
+    /// Whether the loss increased from the first to the second half of the epoch
+    /// or stopped being finite.
+    pub loss_increased: bool,
+}
+
+impl TrainingDiagnostics {
+    /// Derives the diagnostics from the measurements of the first epoch.
+    #[must_use]
+    pub fn from_first_epoch(
+        mean_grad_norm: f64,
+        first_half_loss: f64,
+        second_half_loss: f64,
+    ) -> Self {
+        let gradient_health =
+            if !mean_grad_norm.is_finite() || mean_grad_norm > EXPLODING_GRADIENT_NORM {
+                GradientHealth::Exploding
+            } else if mean_grad_norm < VANISHING_GRADIENT_NORM {
+                GradientHealth::Vanishing
+            } else {
+                GradientHealth::Healthy
+            };
+        let loss_increased = !second_half_loss.is_finite() || second_half_loss > first_half_loss;
+        Self { mean_grad_norm, gradient_health, first_half_loss, second_half_loss, loss_increased }
+    }
+
+    /// Returns whether no warning was raised.
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        self.gradient_health == GradientHealth::Healthy && !self.loss_increased
+    }
+}
+
+/// A problem with the training setup or with the checkpoint of the best epoch
+/// which did not stop the training.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrainingWarning {
+    /// Some targets lie outside the range the bounded output activation can produce,
+    /// so the loss can never vanish. The targets should be scaled into `range`.
+    TargetsOutOfRange {
+        activation: ActivationType,
+        range: (f64, f64),
+        min_target: f64,
+        max_target: f64,
+    },
+    /// The checkpoint of the best epoch could not be saved into `directory`,
+    /// so the training went on without checkpointing.
+    CheckpointNotSaved { directory: String, error: String },
+    /// The checkpoint of the best epoch could not be restored from `directory`,
+    /// so the network keeps the weights of its last epoch.
+    CheckpointNotRestored { directory: String, error: String },
+}
+
+impl TrainingWarning {
+    /// Checks whether the targets are reachable by the given output activation.
+    #[must_use]
+    pub fn check_targets(
+        activation: ActivationType,
+        targets: &[Vec<f64>],
+    ) -> Option<Self> {
+        let range = activation.output_range()?;
+        let values = targets.iter().flatten().copied();
+        let min_target = values.clone().fold(f64::INFINITY, f64::min);
+        let max_target = values.fold(f64::NEG_INFINITY, f64::max);
+        (min_target < range.0 || max_target > range.1).then_some(Self::TargetsOutOfRange {
+            activation,
+            range,
+            min_target,
+            max_target,
+        })
+    }
+}
+
+impl fmt::Display for TrainingWarning {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::TargetsOutOfRange { activation, range, min_target, max_target } => write!(
+                f,
+                "The targets lie in [{min_target}, {max_target}] but the {activation:?} output \
+                 only reaches [{}, {}], consider scaling the targets into that range",
+                range.0, range.1
+            ),
+            Self::CheckpointNotSaved { directory, error } => write!(
+                f,
+                "The best checkpoint could not be saved into {directory} ({error}), \
+                 the training went on without it"
+            ),
+            Self::CheckpointNotRestored { directory, error } => write!(
+                f,
+                "The best checkpoint could not be restored from {directory} ({error}), \
+                 the network keeps the weights of its last epoch"
+            ),
+        }
+    }
+}
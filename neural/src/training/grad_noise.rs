@@ -0,0 +1,109 @@
+use crate::layer::layer_trait::LayerGradients;
+
+use num_traits::NumCast;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
+
+/// Exponent of the decay of the noise, the standard deviation at epoch `t` is
+/// `stddev / (1 + t)^GRADIENT_NOISE_DECAY`.
+pub const GRADIENT_NOISE_DECAY: f64 = 0.55;
+
+/// Adds annealed zero-mean Gaussian noise to the gradients before every weight update.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GradientNoise {
+    stddev: f64,
+    seed: u64,
+}
+
+impl GradientNoise {
+    /// Creates a new `GradientNoise` with the standard deviation of the noise in the first epoch.
+    /// The seed is used for the random number generator returned by `rng`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the standard deviation is negative or not finite.
+    #[must_use]
+    pub fn new(
+        stddev: f64,
+        seed: u64,
+    ) -> Self {
+        assert!(stddev.is_finite() && stddev >= 0.0, "stddev must be a non negative number");
+        Self { stddev, seed }
+    }
+
+    #[must_use]
+    pub const fn stddev(&self) -> f64 {
+        self.stddev
+    }
+
+    #[must_use]
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns a random number generator seeded with the seed of the noise.
+    #[must_use]
+    pub fn rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.seed)
+    }
+
+    /// Returns the standard deviation of the noise in the given epoch.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the epoch cannot be converted to `f64`.
+    #[must_use]
+    pub fn stddev_at(
+        &self,
+        epoch: usize,
+    ) -> f64 {
+        let epoch: f64 = NumCast::from(epoch).expect("Failed to convert epoch to f64");
+        self.stddev / (1.0 + epoch).powf(GRADIENT_NOISE_DECAY)
+    }
+
+    /// Adds noise of the standard deviation of the given epoch to every weight and bias gradient.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the normal distribution cannot be created.
+    pub fn apply<R: Rng>(
+        &self,
+        gradients: &mut LayerGradients,
+        epoch: usize,
+        rng: &mut R,
+    ) {
+        let normal =
+            Normal::new(0.0, self.stddev_at(epoch)).expect("Failed to create normal distribution");
+        for i in 0..gradients.weights.rows() {
+            for j in 0..gradients.weights.cols() {
+                *gradients.weights.get_mut_unchecked(i, j) += normal.sample(rng);
+            }
+        }
+        for bias in &mut gradients.biases {
+            *bias += normal.sample(rng);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gradient_noise_decays() {
+        let noise = GradientNoise::new(1.0, 3);
+        assert!((noise.stddev_at(0) - 1.0).abs() < f64::EPSILON);
+        assert!(noise.stddev_at(1) < noise.stddev_at(0));
+        assert!(noise.stddev_at(10) < noise.stddev_at(1));
+
+        let mut gradients = LayerGradients::zeros(2, 3, 2);
+        noise.apply(&mut gradients, 0, &mut noise.rng());
+        let mut gradients_again = LayerGradients::zeros(2, 3, 2);
+        noise.apply(&mut gradients_again, 0, &mut noise.rng());
+        assert!(gradients.biases.iter().all(|bias| *bias != 0.0));
+        assert_eq!(gradients.biases, gradients_again.biases);
+    }
+}
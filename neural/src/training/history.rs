@@ -0,0 +1,136 @@
+use super::metrics::EpochMetrics;
+
+use std::error::Error;
+
+/// The metrics recorded while a neural network was trained, one entry per epoch.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrainingHistory {
+    /// The metrics of every epoch.
+    pub epochs: Vec<EpochMetrics>,
+    /// The mean L2 norm of the gradients of every layer per epoch.
+    /// Only filled when the recording of gradient norms is enabled.
+    pub grad_norms: Vec<Vec<f64>>,
+}
+
+impl TrainingHistory {
+    /// Creates an empty `TrainingHistory`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of recorded epochs.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.epochs.len()
+    }
+
+    /// Returns whether no epoch has been recorded.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.epochs.is_empty()
+    }
+
+    /// Writes the history to a CSV file with one row per epoch and the columns
+    /// `epoch,train_loss,train_acc,val_loss,val_acc`, followed by one `grad_norm_<layer>`
+    /// column per layer if the gradient norms were recorded.
+    /// The validation columns are empty for epochs trained without validation samples.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn to_csv(
+        &self,
+        path: String,
+    ) -> Result<(), Box<dyn Error>> {
+        let num_layers = self.grad_norms.first().map_or(0, Vec::len);
+        let mut writer = csv::Writer::from_path(path)?;
+        let mut header = ["epoch", "train_loss", "train_acc", "val_loss", "val_acc"]
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        header.extend((0..num_layers).map(|layer| format!("grad_norm_{layer}")));
+        writer.write_record(&header)?;
+        // without validation samples the validation metrics are NaN
+        let optional = |value: f64| if value.is_nan() { String::new() } else { value.to_string() };
+        for (i, metrics) in self.epochs.iter().enumerate() {
+            let mut record = vec![
+                metrics.epoch.to_string(),
+                metrics.train_loss.to_string(),
+                metrics.train_accuracy.to_string(),
+                optional(metrics.validation_loss),
+                optional(metrics.validation_accuracy),
+            ];
+            let grad_norms = self.grad_norms.get(i).map_or(&[][..], Vec::as_slice);
+            record.extend(
+                (0..num_layers).map(|layer| {
+                    grad_norms.get(layer).map_or_else(String::new, ToString::to_string)
+                }),
+            );
+            writer.write_record(&record)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_csv_writes_one_row_per_epoch() {
+        let path = "test_history.csv";
+        let history = TrainingHistory {
+            epochs: vec![
+                EpochMetrics {
+                    epoch: 0,
+                    train_loss: 0.5,
+                    train_accuracy: 25.0,
+                    validation_loss: 0.75,
+                    validation_accuracy: 12.5,
+                },
+                EpochMetrics {
+                    epoch: 1,
+                    train_loss: 0.125,
+                    train_accuracy: 50.0,
+                    validation_loss: f64::NAN,
+                    validation_accuracy: f64::NAN,
+                },
+            ],
+            grad_norms: vec![vec![1.5, 0.25], vec![0.5, 0.0625]],
+        };
+        history.to_csv(path.to_string()).unwrap();
+
+        let content = std::fs::read_to_string(path).unwrap();
+        assert_eq!(content.lines().count(), history.len() + 1);
+        let mut reader = csv::Reader::from_path(path).unwrap();
+        assert_eq!(
+            reader.headers().unwrap(),
+            vec![
+                "epoch",
+                "train_loss",
+                "train_acc",
+                "val_loss",
+                "val_acc",
+                "grad_norm_0",
+                "grad_norm_1"
+            ]
+        );
+        let rows = reader.records().map(Result::unwrap).collect::<Vec<_>>();
+        for (row, (metrics, grad_norms)) in
+            rows.iter().zip(history.epochs.iter().zip(&history.grad_norms))
+        {
+            let value = |i: usize| row[i].parse::<f64>().ok();
+            assert_eq!(row[0].parse::<usize>().unwrap(), metrics.epoch);
+            assert_eq!(value(1), Some(metrics.train_loss));
+            assert_eq!(value(2), Some(metrics.train_accuracy));
+            assert_eq!(value(5), Some(grad_norms[0]));
+            assert_eq!(value(6), Some(grad_norms[1]));
+        }
+        assert_eq!(rows[0][3].parse::<f64>().ok(), Some(0.75));
+        assert_eq!(rows[0][4].parse::<f64>().ok(), Some(12.5));
+        assert!(rows[1][3].is_empty() && rows[1][4].is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+use crate::training::loss::LossFunction;
+use num_traits::NumCast;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+/// The metric used to measure how much a model degrades when a feature is permuted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportanceMetric {
+    /// The fraction of samples whose outputs match the targets.
+    /// A sample matches if at least `sample_match_percentage` of its outputs
+    /// are within `tolerance` of the target.
+    Accuracy { tolerance: f64, sample_match_percentage: f64 },
+    /// The average loss over all samples.
+    Loss(LossFunction),
+}
+
+impl ImportanceMetric {
+    /// Returns the score of the outputs, where a higher score is better.
+    fn score(
+        &self,
+        outputs: &[Vec<f64>],
+        targets: &[Vec<f64>],
+    ) -> f64 {
+        let num_samples: f64 =
+            NumCast::from(outputs.len().max(1)).expect("Failed to convert outputs.len() to f64");
+        match self {
+            Self::Accuracy { tolerance, sample_match_percentage } => {
+                let matches = outputs
+                    .iter()
+                    .zip(targets)
+                    .filter(|(output, target)| {
+                        let correct = output
+                            .iter()
+                            .zip(target.iter())
+                            .filter(|(&o, &t)| (o - t).abs() < *tolerance)
+                            .count();
+                        let correct: f64 =
+                            NumCast::from(correct).expect("Failed to convert correct to f64");
+                        let target_len: f64 = NumCast::from(target.len().max(1))
+                            .expect("Failed to convert target.len() to f64");
+                        correct / target_len >= *sample_match_percentage
+                    })
+                    .count();
+                let matches: f64 =
+                    NumCast::from(matches).expect("Failed to convert matches to f64");
+                matches / num_samples
+            },
+            Self::Loss(loss_function) => {
+                let loss = outputs
+                    .iter()
+                    .zip(targets)
+                    .map(|(output, target)| loss_function.value(output, target))
+                    .sum::<f64>();
+                -loss / num_samples
+            },
+        }
+    }
+}
+
+/// Computes the permutation feature importance of a model given by its `predict` function.
+///
+/// For each input feature the column is shuffled across the samples with a random number
+/// generator seeded by `seed`, and the importance is the drop of the score compared to the
+/// unshuffled inputs: the drop in accuracy or the increase in average loss.
+///
+/// # Panics
+///
+/// This function will panic if the number of inputs and targets differ.
+pub fn permutation_importance<F>(
+    mut predict: F,
+    inputs: &[Vec<f64>],
+    targets: &[Vec<f64>],
+    metric: &ImportanceMetric,
+    seed: u64,
+) -> Vec<f64>
+where
+    F: FnMut(Vec<f64>) -> Vec<f64>,
+{
+    assert_eq!(inputs.len(), targets.len(), "Number of inputs and targets must match");
+    let mut score_inputs = |inputs: &[Vec<f64>]| {
+        let outputs = inputs.iter().map(|input| predict(input.clone())).collect::<Vec<_>>();
+        metric.score(&outputs, targets)
+    };
+    let baseline = score_inputs(inputs);
+    let num_features = inputs.first().map_or(0, Vec::len);
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..num_features)
+        .map(|feature| {
+            let mut column = inputs.iter().map(|input| input[feature]).collect::<Vec<_>>();
+            column.shuffle(&mut rng);
+            let permuted = inputs
+                .iter()
+                .zip(column)
+                .map(|(input, value)| {
+                    let mut input = input.clone();
+                    input[feature] = value;
+                    input
+                })
+                .collect::<Vec<_>>();
+            baseline - score_inputs(&permuted)
+        })
+        .collect()
+}
@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+
+/// Smallest output used inside logarithms and divisions of the cross-entropy.
+const CROSS_ENTROPY_EPSILON: f64 = 1e-12;
+
+/// Loss minimized during training.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum LossFunction {
+    /// Sum of the squared differences between the outputs and the targets.
+    #[default]
+    MeanSquaredError,
+    /// Cross-entropy in which the contribution of every class is scaled by its weight.
+    /// Useful for imbalanced classification, the outputs are expected to be probabilities.
+    WeightedCrossEntropy { class_weights: Vec<f64> },
+}
+
+impl LossFunction {
+    /// Checks that the loss fits a network with the given number of outputs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the number of class weights differs from `output_size`.
+    pub fn validate(
+        &self,
+        output_size: usize,
+    ) -> Result<(), Box<dyn Error>> {
+        match self {
+            Self::MeanSquaredError => Ok(()),
+            Self::WeightedCrossEntropy { class_weights } => {
+                if class_weights.len() == output_size {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "Expected {output_size} class weights, got {}",
+                        class_weights.len()
+                    )
+                    .into())
+                }
+            },
+        }
+    }
+
+    /// Returns the loss of a single sample.
+    #[must_use]
+    pub fn value(
+        &self,
+        output: &[f64],
+        target: &[f64],
+    ) -> f64 {
+        match self {
+            Self::MeanSquaredError => {
+                output.iter().zip(target).map(|(o, t)| (o - t) * (o - t)).sum()
+            },
+            Self::WeightedCrossEntropy { class_weights } => output
+                .iter()
+                .zip(target)
+                .zip(class_weights)
+                .map(|((o, t), w)| -w * t * o.max(CROSS_ENTROPY_EPSILON).ln())
+                .sum(),
+        }
+    }
+
+    /// Returns the gradient of the loss of a single sample with respect to the outputs.
+    #[must_use]
+    pub fn gradient(
+        &self,
+        output: &[f64],
+        target: &[f64],
+    ) -> Vec<f64> {
+        match self {
+            Self::MeanSquaredError => {
+                output.iter().zip(target).map(|(o, t)| 2.0 * (o - t)).collect()
+            },
+            Self::WeightedCrossEntropy { class_weights } => output
+                .iter()
+                .zip(target)
+                .zip(class_weights)
+                .map(|((o, t), w)| -w * t / o.max(CROSS_ENTROPY_EPSILON))
+                .collect(),
+        }
+    }
+
+    /// Returns the loss of a single sample whose outputs are the softmax of the given logits.
+    /// The cross-entropy is computed from the log-softmax of the logits, so that tiny
+    /// probabilities are not clamped.
+    #[must_use]
+    pub fn value_from_logits(
+        &self,
+        logits: &[f64],
+        target: &[f64],
+    ) -> f64 {
+        match self {
+            Self::MeanSquaredError => self.value(&softmax(logits), target),
+            Self::WeightedCrossEntropy { class_weights } => {
+                let log_sum_exp = log_sum_exp(logits);
+                logits
+                    .iter()
+                    .zip(target)
+                    .zip(class_weights)
+                    .map(|((z, t), w)| -w * t * (z - log_sum_exp))
+                    .sum()
+            },
+        }
+    }
+
+    /// Returns the gradient of the loss of a single sample with respect to the logits whose
+    /// softmax are the outputs.
+    #[must_use]
+    pub fn gradient_from_logits(
+        &self,
+        logits: &[f64],
+        target: &[f64],
+    ) -> Vec<f64> {
+        let probabilities = softmax(logits);
+        match self {
+            Self::MeanSquaredError => {
+                // chain rule through the jacobian of the softmax
+                let gradient = self.gradient(&probabilities, target);
+                let weighted_sum =
+                    probabilities.iter().zip(&gradient).map(|(p, g)| p * g).sum::<f64>();
+                probabilities.iter().zip(&gradient).map(|(p, g)| p * (g - weighted_sum)).collect()
+            },
+            Self::WeightedCrossEntropy { class_weights } => {
+                let target_weight =
+                    target.iter().zip(class_weights).map(|(t, w)| w * t).sum::<f64>();
+                probabilities
+                    .iter()
+                    .zip(target)
+                    .zip(class_weights)
+                    .map(|((p, t), w)| p.mul_add(target_weight, -w * t))
+                    .collect()
+            },
+        }
+    }
+}
+
+/// Returns the logarithm of the sum of the exponentials of the logits, shifted by the
+/// maximum logit so that the exponentials cannot overflow.
+fn log_sum_exp(logits: &[f64]) -> f64 {
+    let max_logit = logits.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    max_logit + logits.iter().map(|z| (z - max_logit).exp()).sum::<f64>().ln()
+}
+
+/// Returns the softmax of the logits.
+fn softmax(logits: &[f64]) -> Vec<f64> {
+    let log_sum_exp = log_sum_exp(logits);
+    logits.iter().map(|z| (z - log_sum_exp).exp()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activation::activate::ActivationTrait;
+    use crate::activation::softmax::{Softmax, DEFAULT_TEMPERATURE};
+
+    #[test]
+    fn test_weighted_cross_entropy_scales_class_contribution() {
+        let output = [0.2, 0.5, 0.3];
+        let target = [0.0, 1.0, 1.0];
+        let loss = LossFunction::WeightedCrossEntropy { class_weights: vec![1.0, 1.0, 1.0] };
+        let weighted = LossFunction::WeightedCrossEntropy { class_weights: vec![1.0, 2.0, 1.0] };
+
+        let gradient = loss.gradient(&output, &target);
+        let weighted_gradient = weighted.gradient(&output, &target);
+        assert!((weighted_gradient[1] / gradient[1] - 2.0).abs() < 1e-12);
+        assert!((weighted_gradient[2] - gradient[2]).abs() < 1e-12);
+        assert!(weighted_gradient[0].abs() < 1e-12);
+
+        // the loss grows by exactly the contribution of the reweighted class
+        let contribution = -(0.5_f64.ln());
+        let difference = weighted.value(&output, &target) - loss.value(&output, &target);
+        assert!((difference - contribution).abs() < 1e-12);
+
+        assert!(weighted.validate(3).is_ok());
+        assert!(weighted.validate(2).is_err());
+        assert!(LossFunction::MeanSquaredError.validate(2).is_ok());
+    }
+
+    #[test]
+    fn test_logits_gradient_matches_softmax_then_loss() {
+        let logits = [0.5, -1.0, 2.0];
+        let target = [0.0, 0.0, 1.0];
+        let losses = [
+            LossFunction::MeanSquaredError,
+            LossFunction::WeightedCrossEntropy { class_weights: vec![1.0, 2.0, 0.5] },
+        ];
+        for loss in losses {
+            let mut softmax = Softmax::new(DEFAULT_TEMPERATURE);
+            let probabilities = softmax.forward(&logits);
+            let expected = softmax.backward(&loss.gradient(&probabilities, &target));
+            for (fused, expected) in
+                loss.gradient_from_logits(&logits, &target).iter().zip(expected)
+            {
+                assert!((fused - expected).abs() < 1e-12);
+            }
+            let difference =
+                loss.value_from_logits(&logits, &target) - loss.value(&probabilities, &target);
+            assert!(difference.abs() < 1e-12);
+        }
+
+        // the probability of the target class is far below the clamp of the cross-entropy
+        let logits = [0.0, 50.0];
+        let target = [1.0, 0.0];
+        let loss = LossFunction::WeightedCrossEntropy { class_weights: vec![1.0, 1.0] };
+        let mut softmax = Softmax::new(DEFAULT_TEMPERATURE);
+        let probabilities = softmax.forward(&logits);
+        let clamped_gradient = softmax.backward(&loss.gradient(&probabilities, &target));
+        assert!(clamped_gradient[0].abs() < 1e-6);
+        assert!((loss.value(&probabilities, &target) + CROSS_ENTROPY_EPSILON.ln()).abs() < 1e-9);
+
+        let gradient = loss.gradient_from_logits(&logits, &target);
+        assert!((gradient[0] + 1.0).abs() < 1e-12);
+        assert!((gradient[1] - 1.0).abs() < 1e-12);
+        assert!((loss.value_from_logits(&logits, &target) - 50.0).abs() < 1e-12);
+    }
+}
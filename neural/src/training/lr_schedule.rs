@@ -0,0 +1,104 @@
+use num_traits::NumCast;
+use serde::{Deserialize, Serialize};
+
+/// Schedule deriving the learning rate of an epoch from the base learning rate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum LrSchedule {
+    /// The base learning rate is used in every epoch.
+    #[default]
+    Constant,
+    /// Cosine annealing (SGDR) from the base rate down to `eta_min` at epoch `t_max`.
+    ///
+    /// With `warm_restarts` the rate jumps back to the base rate after reaching `eta_min`,
+    /// so every cycle spans `t_max + 1` epochs. Without restarts it stays at `eta_min`.
+    CosineAnnealing { t_max: usize, eta_min: f64, warm_restarts: bool },
+    /// Cyclical learning rate rising linearly from the base rate to `max_lr` over `step_size`
+    /// epochs and falling back to the base rate over the next `step_size` epochs.
+    Triangular { step_size: usize, max_lr: f64 },
+}
+
+impl LrSchedule {
+    /// Returns the learning rate of the given zero based epoch.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the epoch cannot be converted to `f64`.
+    #[must_use]
+    pub fn learning_rate(
+        &self,
+        base_learning_rate: f64,
+        epoch: usize,
+    ) -> f64 {
+        match *self {
+            Self::Constant => base_learning_rate,
+            Self::CosineAnnealing { t_max, eta_min, warm_restarts } => {
+                if t_max == 0 {
+                    return eta_min;
+                }
+                let t_cur = if warm_restarts { epoch % (t_max + 1) } else { epoch.min(t_max) };
+                let t_cur: f64 = NumCast::from(t_cur).expect("Failed to convert t_cur to f64");
+                let t_max: f64 = NumCast::from(t_max).expect("Failed to convert t_max to f64");
+                let cosine = 1.0 + (std::f64::consts::PI * t_cur / t_max).cos();
+                (0.5 * (base_learning_rate - eta_min)).mul_add(cosine, eta_min)
+            },
+            Self::Triangular { step_size, max_lr } => {
+                if step_size == 0 {
+                    return base_learning_rate;
+                }
+                let t_cur = epoch % (2 * step_size);
+                let t_cur = if t_cur > step_size { 2 * step_size - t_cur } else { t_cur };
+                let t_cur: f64 = NumCast::from(t_cur).expect("Failed to convert t_cur to f64");
+                let step_size: f64 =
+                    NumCast::from(step_size).expect("Failed to convert step_size to f64");
+                (max_lr - base_learning_rate).mul_add(t_cur / step_size, base_learning_rate)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_annealing() {
+        let schedule =
+            LrSchedule::CosineAnnealing { t_max: 4, eta_min: 0.001, warm_restarts: true };
+        let base = 0.1;
+        assert!((schedule.learning_rate(base, 0) - base).abs() < 1e-12);
+        // half way through the cycle the rate is the mean of the base rate and eta_min
+        assert!((schedule.learning_rate(base, 2) - (base + 0.001) / 2.0).abs() < 1e-12);
+        assert!((schedule.learning_rate(base, 4) - 0.001).abs() < 1e-12);
+        for epoch in 0..4 {
+            assert!(schedule.learning_rate(base, epoch + 1) < schedule.learning_rate(base, epoch));
+        }
+        // warm restart back to the base rate
+        assert!((schedule.learning_rate(base, 5) - base).abs() < 1e-12);
+        assert!((schedule.learning_rate(base, 9) - 0.001).abs() < 1e-12);
+
+        let no_restarts =
+            LrSchedule::CosineAnnealing { t_max: 4, eta_min: 0.001, warm_restarts: false };
+        assert!((no_restarts.learning_rate(base, 7) - 0.001).abs() < 1e-12);
+        assert!((LrSchedule::Constant.learning_rate(base, 7) - base).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_triangular() {
+        let schedule = LrSchedule::Triangular { step_size: 4, max_lr: 0.5 };
+        let base = 0.1;
+        assert!((schedule.learning_rate(base, 0) - base).abs() < 1e-12);
+        assert!((schedule.learning_rate(base, 2) - 0.3).abs() < 1e-12);
+        // the rate peaks at the half cycle and is back at the base rate after the full cycle
+        assert!((schedule.learning_rate(base, 4) - 0.5).abs() < 1e-12);
+        assert!((schedule.learning_rate(base, 6) - 0.3).abs() < 1e-12);
+        assert!((schedule.learning_rate(base, 8) - base).abs() < 1e-12);
+        for epoch in 0..4 {
+            assert!(schedule.learning_rate(base, epoch + 1) > schedule.learning_rate(base, epoch));
+            assert!(
+                schedule.learning_rate(base, epoch + 5) < schedule.learning_rate(base, epoch + 4)
+            );
+        }
+        // the next cycle repeats the first one
+        assert!((schedule.learning_rate(base, 12) - 0.5).abs() < 1e-12);
+    }
+}
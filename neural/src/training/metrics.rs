@@ -0,0 +1,139 @@
+use num_traits::NumCast;
+
+/// Metrics of a single training epoch, passed to the `on_epoch_end` callback of `train`.
+///
+/// Accuracies are given in percent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EpochMetrics {
+    /// The zero based index of the epoch.
+    pub epoch: usize,
+    /// The average loss on the training samples.
+    pub train_loss: f64,
+    /// The accuracy on the training samples.
+    pub train_accuracy: f64,
+    /// The average loss on the validation samples.
+    pub validation_loss: f64,
+    /// The accuracy on the validation samples.
+    pub validation_accuracy: f64,
+}
+
+/// How the accuracy of the outputs of a network is measured against the targets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccuracyMetric {
+    /// An output is correct if it is within `tolerance` of its target, a sample matches
+    /// if at least `sample_match_percentage` of its outputs are correct.
+    Tolerance { tolerance: f64, sample_match_percentage: f64 },
+    /// Multi-label classification with sigmoid outputs: an output predicts its label as active
+    /// if it is at least `threshold`, a target marks its label as active if it is at least 0.5.
+    /// A sample matches if all of its labels are predicted correctly.
+    MultiLabel { threshold: f64 },
+}
+
+/// The accuracies measured by an `AccuracyMetric`, given in percent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AccuracyScores {
+    /// The share of samples matching their targets.
+    pub exact_match: f64,
+    /// The share of single outputs predicted correctly (Hamming accuracy).
+    pub hamming: f64,
+}
+
+impl AccuracyMetric {
+    /// Returns whether a single output is correct for its target.
+    fn is_correct(
+        &self,
+        output: f64,
+        target: f64,
+    ) -> bool {
+        match *self {
+            Self::Tolerance { tolerance, .. } => (output - target).abs() < tolerance,
+            Self::MultiLabel { threshold } => (output >= threshold) == (target >= 0.5),
+        }
+    }
+
+    /// Measures the accuracy of the outputs against the targets.
+    /// Empty outputs yield zero accuracies.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the number of outputs and targets differ.
+    #[must_use]
+    pub fn evaluate(
+        &self,
+        outputs: &[Vec<f64>],
+        targets: &[Vec<f64>],
+    ) -> AccuracyScores {
+        assert_eq!(outputs.len(), targets.len(), "Number of outputs and targets must match");
+        let mut matching_samples = 0;
+        let mut correct_outputs = 0;
+        let mut num_outputs = 0;
+        for (output, target) in outputs.iter().zip(targets) {
+            let correct =
+                output.iter().zip(target).filter(|(&o, &t)| self.is_correct(o, t)).count();
+            let sample_matches = match *self {
+                Self::Tolerance { sample_match_percentage, .. } => {
+                    let correct: f64 =
+                        NumCast::from(correct).expect("Failed to convert correct to f64");
+                    let target_len: f64 = NumCast::from(target.len().max(1))
+                        .expect("Failed to convert target.len() to f64");
+                    correct / target_len >= sample_match_percentage
+                },
+                Self::MultiLabel { .. } => correct == target.len(),
+            };
+            if sample_matches {
+                matching_samples += 1;
+            }
+            correct_outputs += correct;
+            num_outputs += target.len();
+        }
+        AccuracyScores {
+            exact_match: percentage(matching_samples, outputs.len()),
+            hamming: percentage(correct_outputs, num_outputs),
+        }
+    }
+}
+
+/// Returns `count` as a percentage of `total`, or zero if `total` is zero.
+fn percentage(
+    count: usize,
+    total: usize,
+) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    let count: f64 = NumCast::from(count).expect("Failed to convert count to f64");
+    let total: f64 = NumCast::from(total).expect("Failed to convert total to f64");
+    count / total * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_label_accuracy() {
+        let metric = AccuracyMetric::MultiLabel { threshold: 0.5 };
+        let outputs = vec![
+            vec![0.9, 0.2, 0.7, 0.1],
+            vec![0.6, 0.8, 0.3, 0.4],
+            vec![0.1, 0.4, 0.9, 0.6],
+            vec![0.2, 0.3, 0.1, 0.2],
+        ];
+        let targets = vec![
+            vec![1.0, 0.0, 1.0, 0.0],
+            vec![1.0, 1.0, 0.0, 0.0],
+            vec![0.0, 1.0, 1.0, 0.0],
+            vec![0.0, 0.0, 0.0, 1.0],
+        ];
+        // the first two samples match exactly, the last two miss two and one label,
+        // so 13 of the 16 labels are right
+        let scores = metric.evaluate(&outputs, &targets);
+        assert!((scores.exact_match - 50.0).abs() < 1e-12);
+        assert!((scores.hamming - 81.25).abs() < 1e-12);
+
+        // the element tolerance accuracy treats the confident outputs as wrong
+        let tolerance = AccuracyMetric::Tolerance { tolerance: 0.1, sample_match_percentage: 1.0 };
+        let scores = tolerance.evaluate(&outputs, &targets);
+        assert!(scores.exact_match.abs() < 1e-12);
+    }
+}
@@ -1,3 +1,15 @@
+pub mod adam;
 pub mod data_importer;
+pub mod diagnostics;
+pub mod grad_noise;
+pub mod history;
+pub mod importance;
+pub mod loss;
+pub mod lr_schedule;
+pub mod metrics;
+pub mod optimizer;
+pub mod swa;
+pub mod training_options;
 pub mod training_params;
 pub mod training_session;
+pub mod verbosity;
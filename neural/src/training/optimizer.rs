@@ -0,0 +1,99 @@
+use crate::layer::layer_trait::TrainableLayer;
+use crate::training::adam::AdamParams;
+use crate::utilities::util::WrappedUtils;
+
+use dyn_clone::DynClone;
+
+/// A trait for optimizers updating the weights of the layers from their current gradients.
+/// Passing an optimizer to the training of a network replaces the built-in update chosen by
+/// `use_adam`.
+pub trait Optimizer: std::fmt::Debug + DynClone {
+    /// Called once before the layers are updated with the gradients of a sample or batch,
+    /// e.g. to advance the time step of the optimizer.
+    fn begin_step(&mut self) {}
+
+    /// Forgets the state collected over the previous updates, e.g. when the weights of the
+    /// network are reinitialized.
+    fn reset(&mut self) {}
+
+    /// Updates the weights and biases of the layer from its current gradients.
+    ///
+    /// # Arguments
+    ///
+    /// * `layer` - The layer to update, whose gradients were computed by the backward pass.
+    /// * `learning_rate` - The learning rate of the layer, including its multiplier.
+    fn step(
+        &mut self,
+        layer: &mut dyn TrainableLayer,
+        learning_rate: f64,
+        utils: WrappedUtils,
+    );
+}
+
+dyn_clone::clone_trait_object!(Optimizer);
+
+/// Plain stochastic gradient descent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SgdOptimizer;
+
+impl Optimizer for SgdOptimizer {
+    fn step(
+        &mut self,
+        layer: &mut dyn TrainableLayer,
+        learning_rate: f64,
+        utils: WrappedUtils,
+    ) {
+        layer.update_weights(learning_rate, utils);
+    }
+}
+
+/// The Adam optimizer, counting its time step over all updates since its creation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdamOptimizer {
+    params: AdamParams,
+    t: usize,
+}
+
+impl AdamOptimizer {
+    #[must_use]
+    pub const fn new(params: AdamParams) -> Self {
+        Self { params, t: 0 }
+    }
+
+    #[must_use]
+    pub const fn params(&self) -> AdamParams {
+        self.params
+    }
+
+    /// Returns the number of updates made so far.
+    #[must_use]
+    pub const fn time_step(&self) -> usize {
+        self.t
+    }
+}
+
+impl Default for AdamOptimizer {
+    fn default() -> Self {
+        Self::new(AdamParams::default())
+    }
+}
+
+impl Optimizer for AdamOptimizer {
+    fn begin_step(&mut self) {
+        self.t += 1;
+    }
+
+    fn reset(&mut self) {
+        self.t = 0;
+    }
+
+    fn step(
+        &mut self,
+        layer: &mut dyn TrainableLayer,
+        learning_rate: f64,
+        utils: WrappedUtils,
+    ) {
+        let AdamParams { beta1, beta2, epsilon } = self.params;
+        layer.adjust_adam(self.t.max(1), learning_rate, beta1, beta2, epsilon, utils);
+    }
+}
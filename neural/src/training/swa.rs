@@ -0,0 +1,115 @@
+use num_traits::NumCast;
+use serde::{Deserialize, Serialize};
+
+/// Stochastic weight averaging: keeps a running average of the weights at the end of
+/// the last `epochs` epochs of `train`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StochasticWeightAveraging {
+    epochs: usize,
+    replace_final: bool,
+}
+
+impl StochasticWeightAveraging {
+    /// Creates a new `StochasticWeightAveraging` over the last `epochs` epochs.
+    /// If `replace_final` is set, the trained network is replaced with the averaged weights.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `epochs` is zero.
+    #[must_use]
+    pub fn new(
+        epochs: usize,
+        replace_final: bool,
+    ) -> Self {
+        assert!(epochs > 0, "epochs must be positive");
+        Self { epochs, replace_final }
+    }
+
+    #[must_use]
+    pub const fn epochs(&self) -> usize {
+        self.epochs
+    }
+
+    #[must_use]
+    pub const fn replace_final(&self) -> bool {
+        self.replace_final
+    }
+
+    /// Returns the first epoch whose weights are collected when training for `total_epochs`.
+    /// If training stops early, the average covers the collected epochs only.
+    #[must_use]
+    pub const fn first_epoch(
+        &self,
+        total_epochs: usize,
+    ) -> usize {
+        total_epochs.saturating_sub(self.epochs)
+    }
+}
+
+/// Running average of flat weight snapshots.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WeightAverage {
+    weights: Vec<f64>,
+    count: usize,
+}
+
+impl WeightAverage {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { weights: Vec::new(), count: 0 }
+    }
+
+    /// Adds a snapshot by interpolating between the current average and the snapshot.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the snapshot size differs from the previous snapshots.
+    pub fn add(
+        &mut self,
+        snapshot: &[f64],
+    ) {
+        self.count += 1;
+        if self.count == 1 {
+            self.weights = snapshot.to_vec();
+            return;
+        }
+        assert_eq!(self.weights.len(), snapshot.len(), "snapshots must have the same size");
+        let count: f64 = NumCast::from(self.count).expect("Failed to convert count to f64");
+        let alpha = (count - 1.0) / count;
+        for (average, weight) in self.weights.iter_mut().zip(snapshot) {
+            *average = alpha.mul_add(*average, (1.0 - alpha) * weight);
+        }
+    }
+
+    /// Returns the number of collected snapshots.
+    #[must_use]
+    pub const fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the averaged weights, or `None` if no snapshot was collected.
+    #[must_use]
+    pub fn weights(&self) -> Option<&[f64]> {
+        (self.count > 0).then_some(self.weights.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weight_average_is_the_mean() {
+        let mut average = WeightAverage::new();
+        assert!(average.weights().is_none());
+        for snapshot in [[1.0, -2.0], [2.0, 0.0], [6.0, 5.0]] {
+            average.add(&snapshot);
+        }
+        assert_eq!(average.count(), 3);
+        let weights = average.weights().unwrap();
+        assert!((weights[0] - 3.0).abs() < 1e-12);
+        assert!((weights[1] - 1.0).abs() < 1e-12);
+        assert_eq!(StochasticWeightAveraging::new(3, false).first_epoch(10), 7);
+        assert_eq!(StochasticWeightAveraging::new(30, false).first_epoch(10), 0);
+    }
+}
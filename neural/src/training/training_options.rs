@@ -0,0 +1,81 @@
+use super::adam::AdamParams;
+use super::grad_noise::GradientNoise;
+use super::lr_schedule::LrSchedule;
+use super::swa::StochasticWeightAveraging;
+use super::verbosity::{ProgressTarget, Verbosity};
+use crate::data::split::SplitMode;
+
+use std::time::Duration;
+
+/// The options controlling how a neural network is trained.
+///
+/// A network made of other networks hands its options on to them, see `for_subdirectory`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrainingOptions {
+    /// Per layer learning rate multipliers. The learning rate of layer `i` is multiplied by
+    /// `lr_multipliers[i]`, a multiplier of zero freezes the layer. `None` trains all layers
+    /// with the plain learning rate.
+    pub lr_multipliers: Option<Vec<f64>>,
+    /// How much output is printed while the network is trained.
+    pub verbosity: Verbosity,
+    /// The stream the progress bars are drawn to while the network is trained.
+    pub progress_target: ProgressTarget,
+    /// The schedule deriving the learning rate of each epoch from the base learning rate.
+    pub lr_schedule: LrSchedule,
+    /// The hyperparameters of the Adam optimizer.
+    pub adam_params: AdamParams,
+    /// How the training samples are split into a training and a validation subset.
+    pub split_mode: SplitMode,
+    /// Stops training once the validation loss has not improved for this many epochs.
+    pub early_stopping_patience: Option<usize>,
+    /// Stops training at the first epoch boundary after this time has elapsed.
+    pub time_budget: Option<Duration>,
+    /// Adds annealed Gaussian noise to the gradients of every layer before each update.
+    pub grad_noise: Option<GradientNoise>,
+    /// Rescales the incoming weights of every neuron after each update of the training,
+    /// so that their L2 norm does not exceed this value.
+    pub max_weight_norm: Option<f64>,
+    /// Keeps a running average of the weights at the end of the last epochs of `train`
+    /// and, if requested, replaces the trained weights with the average.
+    pub swa: Option<StochasticWeightAveraging>,
+    /// Saves the network to this directory whenever its validation loss improves.
+    pub checkpoint_best: Option<String>,
+    /// Reloads the checkpoint of `checkpoint_best` once training has finished.
+    pub restore_best_checkpoint: bool,
+}
+
+impl TrainingOptions {
+    /// Returns the options of a network stored in the sub directory `subdir` of the network
+    /// these options belong to. Its best checkpoint is kept in the same sub directory of
+    /// `checkpoint_best`.
+    #[must_use]
+    pub fn for_subdirectory(
+        &self,
+        subdir: &str,
+    ) -> Self {
+        Self {
+            checkpoint_best: self.checkpoint_best.as_ref().map(|dir| format!("{dir}/{subdir}")),
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_subdirectory() {
+        let options = TrainingOptions {
+            verbosity: Verbosity::Silent,
+            checkpoint_best: Some("best".to_string()),
+            restore_best_checkpoint: true,
+            ..TrainingOptions::default()
+        };
+        let sub_options = options.for_subdirectory("left");
+        assert_eq!(sub_options.checkpoint_best.as_deref(), Some("best/left"));
+        assert_eq!(sub_options.verbosity, Verbosity::Silent);
+        assert!(sub_options.restore_best_checkpoint);
+        assert_eq!(TrainingOptions::default().for_subdirectory("left").checkpoint_best, None);
+    }
+}
@@ -1,6 +1,24 @@
+use super::adam::AdamParams;
+use super::grad_noise::GradientNoise;
+use super::loss::LossFunction;
+use super::lr_schedule::LrSchedule;
+use super::swa::StochasticWeightAveraging;
+use super::training_options::TrainingOptions;
+use super::verbosity::{ProgressTarget, Verbosity};
+use crate::data::split::SplitMode;
+use crate::nn::retry_nn::DEFAULT_MIN_TRAINING_SAMPLES;
 use crate::nn::shape::NeuralNetworkShape;
 
-#[derive(Clone)]
+use serde::{Deserialize, Serialize};
+
+use std::time::Duration;
+
+const fn default_min_training_samples() -> usize {
+    DEFAULT_MIN_TRAINING_SAMPLES
+}
+
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TrainingParams {
     shape: NeuralNetworkShape,
     levels: Option<i32>,
@@ -12,6 +30,36 @@ pub struct TrainingParams {
     batch_size: usize,
     use_adam: bool,
     sample_match_percentage: f64,
+    lr_multipliers: Option<Vec<f64>>,
+    verbosity: Verbosity,
+    #[serde(default)]
+    progress_target: ProgressTarget,
+    lr_schedule: LrSchedule,
+    split_mode: SplitMode,
+    early_stopping_patience: Option<usize>,
+    #[serde(default)]
+    time_budget: Option<Duration>,
+    #[serde(default)]
+    grad_noise: Option<GradientNoise>,
+    #[serde(default)]
+    max_weight_norm: Option<f64>,
+    #[serde(default)]
+    swa: Option<StochasticWeightAveraging>,
+    checkpoint_best: Option<String>,
+    #[serde(default)]
+    restore_best_checkpoint: bool,
+    #[serde(default)]
+    loss_function: LossFunction,
+    #[serde(default)]
+    adam_params: AdamParams,
+    #[serde(default)]
+    spectral_norm: bool,
+    #[serde(default)]
+    logits_loss: bool,
+    #[serde(default = "default_min_training_samples")]
+    min_training_samples: usize,
+    #[serde(default)]
+    insufficient_samples_accuracy: f64,
 }
 
 impl TrainingParams {
@@ -40,9 +88,38 @@ impl TrainingParams {
             batch_size,
             use_adam,
             sample_match_percentage,
+            lr_multipliers: None,
+            verbosity: Verbosity::ProgressBar,
+            progress_target: ProgressTarget::Stdout,
+            lr_schedule: LrSchedule::Constant,
+            split_mode: SplitMode::Random,
+            early_stopping_patience: None,
+            time_budget: None,
+            grad_noise: None,
+            max_weight_norm: None,
+            swa: None,
+            checkpoint_best: None,
+            restore_best_checkpoint: false,
+            loss_function: LossFunction::MeanSquaredError,
+            adam_params: AdamParams::new(0.9, 0.999, 1e-8),
+            spectral_norm: false,
+            logits_loss: false,
+            min_training_samples: DEFAULT_MIN_TRAINING_SAMPLES,
+            insufficient_samples_accuracy: 0.0,
         }
     }
 
+    /// Sets per layer learning rate multipliers, one for every layer of the shape.
+    /// A multiplier of zero freezes the corresponding layer.
+    #[must_use]
+    pub fn with_lr_multipliers(
+        mut self,
+        lr_multipliers: Option<Vec<f64>>,
+    ) -> Self {
+        self.lr_multipliers = lr_multipliers;
+        self
+    }
+
     #[must_use]
     pub const fn shape(&self) -> &NeuralNetworkShape {
         &self.shape
@@ -93,10 +170,290 @@ impl TrainingParams {
         self.sample_match_percentage
     }
 
+    /// Sets how much output is printed during training.
+    #[must_use]
+    pub const fn with_verbosity(
+        mut self,
+        verbosity: Verbosity,
+    ) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    #[must_use]
+    pub const fn verbosity(&self) -> Verbosity {
+        self.verbosity
+    }
+
+    /// Sets the stream the progress bars are drawn to during training.
+    #[must_use]
+    pub const fn with_progress_target(
+        mut self,
+        progress_target: ProgressTarget,
+    ) -> Self {
+        self.progress_target = progress_target;
+        self
+    }
+
+    #[must_use]
+    pub const fn progress_target(&self) -> ProgressTarget {
+        self.progress_target
+    }
+
+    /// Sets the schedule deriving the learning rate of each epoch from the base learning rate.
+    #[must_use]
+    pub const fn with_lr_schedule(
+        mut self,
+        lr_schedule: LrSchedule,
+    ) -> Self {
+        self.lr_schedule = lr_schedule;
+        self
+    }
+
+    #[must_use]
+    pub const fn lr_schedule(&self) -> LrSchedule {
+        self.lr_schedule
+    }
+
+    /// Sets the hyperparameters of the Adam optimizer used when `use_adam` is set.
+    #[must_use]
+    pub const fn with_adam_params(
+        mut self,
+        adam_params: AdamParams,
+    ) -> Self {
+        self.adam_params = adam_params;
+        self
+    }
+
+    #[must_use]
+    pub const fn adam_params(&self) -> AdamParams {
+        self.adam_params
+    }
+
+    /// Divides the weights of the dense layers by their largest singular value in every
+    /// forward pass during training, which stabilizes e.g. GAN-style training.
+    #[must_use]
+    pub const fn with_spectral_norm(
+        mut self,
+        spectral_norm: bool,
+    ) -> Self {
+        self.spectral_norm = spectral_norm;
+        self
+    }
+
+    #[must_use]
+    pub const fn spectral_norm(&self) -> bool {
+        self.spectral_norm
+    }
+
+    /// Sets the loss minimized during training.
+    #[must_use]
+    pub fn with_loss_function(
+        mut self,
+        loss_function: LossFunction,
+    ) -> Self {
+        self.loss_function = loss_function;
+        self
+    }
+
+    #[must_use]
+    pub const fn loss_function(&self) -> &LossFunction {
+        &self.loss_function
+    }
+
+    /// Computes the loss on the logits of the final softmax activation while training,
+    /// fusing the softmax into the loss. `predict` still applies the softmax.
+    #[must_use]
+    pub const fn with_logits_loss(
+        mut self,
+        logits_loss: bool,
+    ) -> Self {
+        self.logits_loss = logits_loss;
+        self
+    }
+
+    #[must_use]
+    pub const fn logits_loss(&self) -> bool {
+        self.logits_loss
+    }
+
+    /// Sets how the samples are split into a training and a validation subset.
+    #[must_use]
+    pub const fn with_split_mode(
+        mut self,
+        split_mode: SplitMode,
+    ) -> Self {
+        self.split_mode = split_mode;
+        self
+    }
+
+    #[must_use]
+    pub const fn split_mode(&self) -> SplitMode {
+        self.split_mode
+    }
+
+    /// Stops training once the validation loss has not improved for `patience` epochs.
+    /// Passing `None` always trains for all epochs.
+    #[must_use]
+    pub const fn with_early_stopping_patience(
+        mut self,
+        early_stopping_patience: Option<usize>,
+    ) -> Self {
+        self.early_stopping_patience = early_stopping_patience;
+        self
+    }
+
+    #[must_use]
+    pub const fn early_stopping_patience(&self) -> Option<usize> {
+        self.early_stopping_patience
+    }
+
+    /// Stops training at the first epoch boundary after the given wall clock time has elapsed.
+    /// Passing `None` always trains for all epochs.
+    #[must_use]
+    pub const fn with_time_budget(
+        mut self,
+        time_budget: Option<Duration>,
+    ) -> Self {
+        self.time_budget = time_budget;
+        self
+    }
+
+    #[must_use]
+    pub const fn time_budget(&self) -> Option<Duration> {
+        self.time_budget
+    }
+
+    /// Adds annealed Gaussian noise to the gradients before every weight update.
+    /// Passing `None` trains with the plain gradients.
+    #[must_use]
+    pub const fn with_grad_noise(
+        mut self,
+        grad_noise: Option<GradientNoise>,
+    ) -> Self {
+        self.grad_noise = grad_noise;
+        self
+    }
+
+    #[must_use]
+    pub const fn grad_noise(&self) -> Option<GradientNoise> {
+        self.grad_noise
+    }
+
+    /// Rescales the incoming weights of every neuron after each update, so that their
+    /// L2 norm does not exceed the given limit. Passing `None` leaves the weights unconstrained.
+    #[must_use]
+    pub const fn with_max_weight_norm(
+        mut self,
+        max_weight_norm: Option<f64>,
+    ) -> Self {
+        self.max_weight_norm = max_weight_norm;
+        self
+    }
+
+    #[must_use]
+    pub const fn max_weight_norm(&self) -> Option<f64> {
+        self.max_weight_norm
+    }
+
+    /// Averages the weights of the last epochs of training (stochastic weight averaging).
+    /// Passing `None` keeps the weights of the last epoch.
+    #[must_use]
+    pub const fn with_swa(
+        mut self,
+        swa: Option<StochasticWeightAveraging>,
+    ) -> Self {
+        self.swa = swa;
+        self
+    }
+
+    #[must_use]
+    pub const fn swa(&self) -> Option<StochasticWeightAveraging> {
+        self.swa
+    }
+
+    /// Saves the network to the given directory whenever its validation loss improves.
+    #[must_use]
+    pub fn with_checkpoint_best(
+        mut self,
+        checkpoint_best: Option<String>,
+    ) -> Self {
+        self.checkpoint_best = checkpoint_best;
+        self
+    }
+
+    #[must_use]
+    pub const fn checkpoint_best(&self) -> Option<&String> {
+        self.checkpoint_best.as_ref()
+    }
+
+    /// Reloads the best checkpoint into the network once training has finished.
+    #[must_use]
+    pub const fn with_restore_best_checkpoint(
+        mut self,
+        restore_best_checkpoint: bool,
+    ) -> Self {
+        self.restore_best_checkpoint = restore_best_checkpoint;
+        self
+    }
+
+    #[must_use]
+    pub const fn restore_best_checkpoint(&self) -> bool {
+        self.restore_best_checkpoint
+    }
+
+    /// Sets the number of samples below which a retry network does not train and returns
+    /// `insufficient_samples_accuracy` instead.
+    #[must_use]
+    pub const fn with_min_training_samples(
+        mut self,
+        min_training_samples: usize,
+        insufficient_samples_accuracy: f64,
+    ) -> Self {
+        self.min_training_samples = min_training_samples;
+        self.insufficient_samples_accuracy = insufficient_samples_accuracy;
+        self
+    }
+
+    #[must_use]
+    pub const fn min_training_samples(&self) -> usize {
+        self.min_training_samples
+    }
+
+    #[must_use]
+    pub const fn insufficient_samples_accuracy(&self) -> f64 {
+        self.insufficient_samples_accuracy
+    }
+
+    #[must_use]
+    pub fn lr_multipliers(&self) -> Option<Vec<f64>> {
+        self.lr_multipliers.clone()
+    }
+
     pub fn set_shape(
         &mut self,
         shape: NeuralNetworkShape,
     ) {
         self.shape = shape;
     }
+
+    /// Returns the options a network is trained with according to these parameters.
+    #[must_use]
+    pub fn training_options(&self) -> TrainingOptions {
+        TrainingOptions {
+            lr_multipliers: self.lr_multipliers.clone(),
+            verbosity: self.verbosity,
+            progress_target: self.progress_target,
+            lr_schedule: self.lr_schedule,
+            adam_params: self.adam_params,
+            split_mode: self.split_mode,
+            early_stopping_patience: self.early_stopping_patience,
+            time_budget: self.time_budget,
+            grad_noise: self.grad_noise,
+            max_weight_norm: self.max_weight_norm,
+            swa: self.swa,
+            checkpoint_best: self.checkpoint_best.clone(),
+            restore_best_checkpoint: self.restore_best_checkpoint,
+        }
+    }
 }
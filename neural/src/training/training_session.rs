@@ -1,5 +1,6 @@
 use super::data_importer::DataImporter;
 use super::training_params::TrainingParams;
+use super::verbosity::Verbosity;
 use crate::nn::directory::Directory;
 use crate::nn::nn_factory::new_trainable_neural_network;
 use crate::nn::nn_factory::trainable_neural_network_from_disk;
@@ -15,6 +16,7 @@ pub struct TrainingSession {
     params: TrainingParams,
     neural_network: WrappedTrainableNeuralNetwork,
     data_importer: Box<dyn DataImporter>,
+    epochs_completed: usize,
 }
 
 impl TrainingSession {
@@ -33,16 +35,22 @@ impl TrainingSession {
         let shape = params.shape().clone();
         let pre_shape = params.pre_shape();
         let levels = params.levels();
+        let arguments = NeuralNetworkCreationArguments::new(
+            shape,
+            levels,
+            pre_shape,
+            model_directory.path(),
+            utils,
+        )
+        .with_min_training_samples(
+            params.min_training_samples(),
+            params.insufficient_samples_accuracy(),
+        );
         Ok(Self {
             params,
-            neural_network: new_trainable_neural_network(NeuralNetworkCreationArguments::new(
-                shape,
-                levels,
-                pre_shape,
-                model_directory.path(),
-                utils,
-            )),
+            neural_network: new_trainable_neural_network(arguments),
             data_importer,
+            epochs_completed: 0,
         })
     }
 
@@ -59,7 +67,7 @@ impl TrainingSession {
         let mut changed_params = params;
         changed_params.set_shape(nn.shape());
         validate_params(&changed_params)?;
-        Ok(Self { params: changed_params, neural_network: nn, data_importer })
+        Ok(Self { params: changed_params, neural_network: nn, data_importer, epochs_completed: 0 })
     }
 
     /// Loads a model from disk and creates a training session.
@@ -78,7 +86,7 @@ impl TrainingSession {
             return Err("Model directory does not exist".into());
         }
         let nn = trainable_neural_network_from_disk(model_directory, utils);
-        Ok(Self { params, neural_network: nn, data_importer })
+        Ok(Self { params, neural_network: nn, data_importer, epochs_completed: 0 })
     }
 
     /// Train method
@@ -97,8 +105,11 @@ impl TrainingSession {
         let targets = data.labels;
 
         let input_size = inputs[0].len();
-        println!("Inputs: {} x {}", inputs.len(), inputs[0].len());
-        println!("Targets: {} x {}", targets.len(), targets[0].len());
+        let silent = self.params.verbosity() == Verbosity::Silent;
+        if !silent {
+            println!("Inputs: {} x {}", inputs.len(), inputs[0].len());
+            println!("Targets: {} x {}", targets.len(), targets[0].len());
+        }
 
         // Prepare and validate the neural network
         let nn = &mut self.neural_network;
@@ -109,8 +120,23 @@ impl TrainingSession {
             return Err("Output size mismatch with neural network".into());
         }
 
-        println!("Training neural network with shape: {:?}", nn.shape());
-        // Train the neural network
+        if let Some(lr_multipliers) = self.params.lr_multipliers() {
+            if lr_multipliers.len() != nn.shape().layers.len() {
+                return Err("Learning rate multipliers mismatch with neural network layers".into());
+            }
+        }
+        nn.set_training_options(self.params.training_options());
+        nn.set_loss_function(self.params.loss_function().clone())?;
+        nn.set_logits_loss(self.params.logits_loss())?;
+        if self.params.spectral_norm() {
+            nn.set_spectral_norm(true);
+        }
+
+        if !silent {
+            println!("Training neural network with shape: {:?}", nn.shape());
+        }
+        // Train the neural network, counting the epochs as training may stop early
+        let mut epochs_completed = 0;
         nn.train(
             &inputs,
             &targets,
@@ -118,9 +144,16 @@ impl TrainingSession {
             self.params.epochs(),
             self.params.tolerance(),
             self.params.use_adam(),
+            None,
             self.params.validation_split(),
             self.params.sample_match_percentage(),
+            Some(&mut |_| epochs_completed += 1),
         );
+        self.epochs_completed = epochs_completed;
+        if self.params.spectral_norm() {
+            // fold the spectral normalization into the weights, so that a saved model matches
+            nn.set_spectral_norm(false);
+        }
 
         // Validation phase
         let mut success_count = 0.0;
@@ -175,6 +208,13 @@ impl TrainingSession {
         self.neural_network.save(model_directory)
     }
 
+    /// Returns how many epochs the last call to `train` completed.
+    /// This is less than the configured epochs if early stopping or the time budget ended training.
+    #[must_use]
+    pub const fn epochs_completed(&self) -> usize {
+        self.epochs_completed
+    }
+
     /// get the resulting neural network
     #[must_use]
     pub fn get_nn(&self) -> WrappedTrainableNeuralNetwork {
@@ -195,6 +235,19 @@ fn validate_params(params: &TrainingParams) -> Result<(), Box<dyn Error>> {
     if params.epochs() == 0 {
         return Err("Number of epochs must be positive".into());
     }
+    if let Some(lr_multipliers) = params.lr_multipliers() {
+        if lr_multipliers.len() != params.shape().layers.len() {
+            return Err(format!(
+                "Expected {} learning rate multipliers, one per layer, found {}",
+                params.shape().layers.len(),
+                lr_multipliers.len()
+            )
+            .into());
+        }
+        if lr_multipliers.iter().any(|multiplier| *multiplier < 0.0) {
+            return Err("Learning rate multipliers must not be negative".into());
+        }
+    }
     if !params.shape().is_valid() {
         // put the shape in the error message
         return Err(format!("Invalid neural network shape: {:?}", params.shape()).into());
@@ -281,4 +334,53 @@ mod tests {
         let success_rate = training_session.train().expect("Training failed");
         assert!(success_rate >= 0.9, "Expected success rate >= 0.9, got {success_rate}");
     }
+
+    #[test]
+    fn test_lr_multipliers_length_is_validated() {
+        let nn_shape = NeuralNetworkShape {
+            layers: vec![LayerShape {
+                layer_type: LayerType::Dense { input_size: 4, output_size: 2 },
+                activation: ActivationData::new(ActivationType::Sigmoid),
+            }],
+        };
+        let training_params =
+            TrainingParams::new(nn_shape.clone(), None, None, 0.7, 0.01, 1, 0.1, 32, false, 1.0)
+                .with_lr_multipliers(Some(vec![1.0, 0.5]));
+
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let result = TrainingSession::new(
+            training_params,
+            Box::new(MockDataImporter::new(nn_shape)),
+            &Directory::Internal("test_session_lr_multipliers".to_string()),
+            utils,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_time_budget_stops_training_early() {
+        let nn_shape = NeuralNetworkShape {
+            layers: vec![LayerShape {
+                layer_type: LayerType::Dense { input_size: 4, output_size: 2 },
+                activation: ActivationData::new(ActivationType::Sigmoid),
+            }],
+        };
+        let training_params =
+            TrainingParams::new(nn_shape.clone(), None, None, 0.7, 0.01, 50, 0.1, 32, false, 1.0)
+                .with_verbosity(Verbosity::Silent)
+                .with_time_budget(Some(std::time::Duration::ZERO));
+
+        let utils = WrappedUtils::new(Utils::new(1_000_000_000, 4));
+        let mut training_session = TrainingSession::new(
+            training_params,
+            Box::new(MockDataImporter::new(nn_shape)),
+            &Directory::Internal("test_session_time_budget".to_string()),
+            utils,
+        )
+        .expect("Failed to create TrainingSession");
+
+        training_session.train().expect("Training failed");
+        // the budget is checked at the epoch boundary, so exactly one epoch completes
+        assert_eq!(training_session.epochs_completed(), 1);
+    }
 }
@@ -0,0 +1,47 @@
+use indicatif::ProgressDrawTarget;
+use serde::{Deserialize, Serialize};
+
+/// Controls how much output is printed while a neural network is trained.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Verbosity {
+    /// Prints nothing and never touches the shared progress bars.
+    Silent,
+    /// Shows a progress bar for every epoch.
+    #[default]
+    ProgressBar,
+    /// Prints a single summary line at the end of every epoch.
+    PerEpoch,
+}
+
+/// The stream progress bars are drawn to while a neural network is trained.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProgressTarget {
+    #[default]
+    Stdout,
+    Stderr,
+    /// Progress bars are not drawn at all.
+    Hidden,
+}
+
+impl ProgressTarget {
+    /// Returns the indicatif draw target of this progress target.
+    #[must_use]
+    pub fn draw_target(self) -> ProgressDrawTarget {
+        match self {
+            Self::Stdout => ProgressDrawTarget::stdout(),
+            Self::Stderr => ProgressDrawTarget::stderr(),
+            Self::Hidden => ProgressDrawTarget::hidden(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hidden_progress_target_does_not_draw() {
+        assert!(ProgressTarget::Hidden.draw_target().is_hidden());
+        assert_eq!(ProgressTarget::default(), ProgressTarget::Stdout);
+    }
+}
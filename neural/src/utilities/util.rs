@@ -3,6 +3,8 @@ use utils::safer::safe_lock;
 use std::sync::{Arc, Mutex};
 
 use crate::layer::layer_trait::{WrappedLayer, WrappedTrainableLayer};
+use crate::nn::directory::Directory;
+use crate::nn::nn_factory::ModelDirectoryNaming;
 use alloc::alloc_manager::{AllocManager, WrappedAllocManager};
 
 use indicatif::MultiProgress;
@@ -34,6 +36,12 @@ impl WrappedThreadPool {
     {
         safe_lock(&self.thread_pool).install(f)
     }
+
+    /// Returns the number of threads of the thread pool.
+    #[must_use]
+    pub fn num_threads(&self) -> usize {
+        safe_lock(&self.thread_pool).current_num_threads()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -44,6 +52,7 @@ pub struct Utils {
     thread_pool: WrappedThreadPool,
     test_mode: bool,
     workspace: String,
+    model_directory_naming: ModelDirectoryNaming,
 }
 
 impl Utils {
@@ -65,6 +74,7 @@ impl Utils {
             thread_pool: WrappedThreadPool::new(num_threads),
             test_mode: false,
             workspace: String::new(),
+            model_directory_naming: ModelDirectoryNaming::default(),
         }
     }
 
@@ -87,9 +97,25 @@ impl Utils {
             thread_pool: WrappedThreadPool::new(num_threads),
             test_mode: true,
             workspace,
+            model_directory_naming: ModelDirectoryNaming::default(),
         }
     }
 
+    /// Sets how the networks using these utils name their internal model directories.
+    #[must_use]
+    pub fn with_model_directory_naming(
+        mut self,
+        model_directory_naming: ModelDirectoryNaming,
+    ) -> Self {
+        self.model_directory_naming = model_directory_naming;
+        self
+    }
+
+    #[must_use]
+    pub const fn model_directory_naming(&self) -> &ModelDirectoryNaming {
+        &self.model_directory_naming
+    }
+
     pub fn allocate(
         &mut self,
         allocatable: &WrappedLayer,
@@ -139,6 +165,11 @@ impl Utils {
         self.thread_pool.execute(f)
     }
 
+    #[must_use]
+    pub fn get_num_threads(&self) -> usize {
+        self.thread_pool.num_threads()
+    }
+
     #[must_use]
     pub const fn is_test_mode(&self) -> bool {
         self.test_mode
@@ -210,6 +241,11 @@ impl WrappedUtils {
         safe_lock(&self.utils).execute(f)
     }
 
+    #[must_use]
+    pub fn get_num_threads(&self) -> usize {
+        safe_lock(&self.utils).get_num_threads()
+    }
+
     #[must_use]
     pub fn is_test_mode(&self) -> bool {
         safe_lock(&self.utils).is_test_mode()
@@ -219,4 +255,15 @@ impl WrappedUtils {
     pub fn get_workspace(&self) -> String {
         safe_lock(&self.utils).get_workspace().to_string()
     }
+
+    /// Creates and returns the first free internal directory for the given model directory
+    /// according to the configured `ModelDirectoryNaming`.
+    #[must_use]
+    pub fn first_free_model_directory(
+        &self,
+        model_directory: &Directory,
+    ) -> String {
+        let model_directory_naming = safe_lock(&self.utils).model_directory_naming().clone();
+        model_directory_naming.first_free_model_directory(model_directory)
+    }
 }
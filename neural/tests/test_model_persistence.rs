@@ -228,3 +228,39 @@ fn trained_model_is_convertible_to_ordinary_model_and_back() {
     // Clean up workspace
     cleanup_workspace(&utils);
 }
+
+#[test]
+fn fast_saved_model_is_loadable() {
+    let model_directory = "tests/test_model_persistence_fast".to_string();
+    let new_model_directory = "tests/test_model_persistence_fast_new".to_string();
+    let utils = create_test_utils();
+
+    // Arrange
+    let _training_session = train_model(
+        model_directory.clone(),
+        "tests/test_model_persistence_fast_internal".to_string(),
+        utils.clone(),
+    );
+    let workspace = utils.get_workspace();
+    let mut model =
+        ClassicNeuralNetwork::from_disk(format!("{workspace}/{model_directory}"), utils.clone())
+            .unwrap();
+    model.allocate();
+    let input = vec![0.5; model.shape().layers[0].input_size()];
+    let expected_output = model.predict(input.clone());
+
+    // Act
+    model.save_fast(new_model_directory.clone()).expect("Failed to save model");
+
+    // Assert
+    let new_model_path = format!("{workspace}/{new_model_directory}");
+    let backup_path = format!("{new_model_path}_backup");
+    assert!(!std::path::Path::new(&backup_path).exists());
+    let mut loaded_model = ClassicNeuralNetwork::from_disk(new_model_path, utils.clone())
+        .expect("Failed to load fast saved model");
+    loaded_model.allocate();
+    assert_eq!(loaded_model.predict(input), expected_output);
+
+    // Clean up workspace
+    cleanup_workspace(&utils);
+}